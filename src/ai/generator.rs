@@ -2,6 +2,7 @@ use crate::executor::pipeline::OptionGenerator;
 use crate::executor::ast::CommandAst;
 use crate::ai::router::SmartRouter;
 use crate::context::SystemContext;
+use crate::safety::flags;
 use async_trait::async_trait;
 
 pub struct AiOptionGenerator;
@@ -30,8 +31,9 @@ impl OptionGenerator for AiOptionGenerator {
             Ok(res) => {
                 // Attempt to parse result as JSON array
                 let res_trimmed = res.trim();
+                let mut proposed = Vec::new();
                 if let Ok(options) = serde_json::from_str::<Vec<String>>(res_trimmed) {
-                    ast.options.extend(options);
+                    proposed.extend(options);
                 } else {
                     // Fallback: try to find something that looks like an array or just split whitespace
                     let cleaned = res_trimmed
@@ -41,10 +43,15 @@ impl OptionGenerator for AiOptionGenerator {
                         .replace(",", " ");
                     for flag in cleaned.split_whitespace() {
                         if flag.starts_with('-') {
-                            ast.options.push(flag.to_string());
+                            proposed.push(flag.to_string());
                         }
                     }
                 }
+
+                // Only flags the tool itself documents reach the command
+                let report = flags::validate(ast, &proposed);
+                report.print();
+                ast.options.extend(report.accepted);
                 Ok(())
             },
             Err(e) => anyhow::bail!("AI Option Generation failed: {}", e),
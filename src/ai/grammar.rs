@@ -0,0 +1,390 @@
+use crate::executor::pipeline::Intent;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Shipped with the binary; see the header of `data/intents.toml` for the format.
+const BUNDLED: &str = include_str!("../../data/intents.toml");
+
+const KEYWORD_SCORE: f32 = 0.5;
+const CONTEXT_SCORE: f32 = 0.2;
+const PATTERN_SCORE: f32 = 0.3;
+const VOCABULARY_SCORE: f32 = 0.1;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IntentDef {
+    pub tool: String,
+    pub operation: String,
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub context: Vec<String>,
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub slots: Vec<String>,
+    #[serde(default)]
+    pub required: Vec<String>,
+}
+
+/// Intent definitions with multilingual synonyms, loaded from `data/intents.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IntentGrammar {
+    #[serde(default)]
+    pub version: u32,
+    /// Confidence at which a request is answered locally (default 0.7).
+    #[serde(default)]
+    pub threshold: Option<f32>,
+    #[serde(default)]
+    pub fragments: BTreeMap<String, String>,
+    #[serde(default)]
+    pub intents: BTreeMap<String, IntentDef>,
+}
+
+const DEFAULT_THRESHOLD: f32 = 0.7;
+
+impl IntentGrammar {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| format!("Invalid intent grammar: {}", e))
+    }
+
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED).expect("bundled data/intents.toml is valid")
+    }
+
+    /// `~/.config/vega/intents.toml`, merged over the bundled grammar.
+    pub fn override_path() -> PathBuf {
+        crate::init::get_config_path().with_file_name("intents.toml")
+    }
+
+    /// Intents and fragments in `other` replace ours of the same name.
+    pub fn merge(&mut self, other: IntentGrammar) {
+        self.fragments.extend(other.fragments);
+        self.intents.extend(other.intents);
+        if other.threshold.is_some() {
+            self.threshold = other.threshold;
+        }
+    }
+}
+
+/// Storage aliases and hosts the user has configured; requests name them directly.
+#[derive(Debug, Clone, Default)]
+pub struct Vocabulary {
+    pub remotes: Vec<String>,
+    pub hosts: Vec<String>,
+}
+
+impl Vocabulary {
+    pub fn load() -> Self {
+        Vocabulary {
+            remotes: crate::system::storage::SmartStorage::new().aliases.into_keys().collect(),
+            hosts: crate::knowledge::KnowledgeBase::load().targets.into_keys().collect(),
+        }
+    }
+}
+
+/// An intent recognised in a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntentMatch {
+    pub name: String,
+    pub tool: String,
+    pub operation: String,
+    pub slots: BTreeMap<String, String>,
+    /// The first declared slot that was filled.
+    pub target: Option<String>,
+    pub confidence: f32,
+}
+
+impl IntentMatch {
+    pub fn slot(&self, name: &str) -> Option<&str> {
+        self.slots.get(name).map(String::as_str)
+    }
+
+    pub fn to_intent(&self) -> Intent {
+        Intent {
+            tool: self.tool.clone(),
+            operation: self.operation.clone(),
+            target: self.target.clone(),
+            slots: self.slots.clone(),
+        }
+    }
+}
+
+struct CompiledIntent {
+    name: String,
+    def: IntentDef,
+    keywords: Regex,
+    context: Option<Regex>,
+    patterns: Vec<Regex>,
+}
+
+/// The grammar with its regexes compiled; one instance serves every local lookup.
+pub struct IntentMatcher {
+    pub threshold: f32,
+    intents: Vec<CompiledIntent>,
+}
+
+impl IntentMatcher {
+    pub fn new(grammar: &IntentGrammar) -> Result<Self, String> {
+        let mut intents = Vec::new();
+        for (name, def) in &grammar.intents {
+            let keywords = word_regex(&def.keywords)
+                .ok_or_else(|| format!("Intent '{}' has no keywords", name))?
+                .map_err(|e| format!("Intent '{}': {}", name, e))?;
+            let context = word_regex(&def.context)
+                .transpose()
+                .map_err(|e| format!("Intent '{}': {}", name, e))?;
+            let mut patterns = Vec::new();
+            for pattern in &def.patterns {
+                let mut expanded = pattern.clone();
+                for (fragment, body) in &grammar.fragments {
+                    expanded = expanded.replace(&format!("%{}%", fragment), body);
+                }
+                // Patterns run on the request as typed, so slot values keep their case
+                patterns.push(
+                    Regex::new(&format!("(?i){}", expanded)).map_err(|e| format!("Intent '{}': {}", name, e))?,
+                );
+            }
+            intents.push(CompiledIntent {
+                name: name.clone(),
+                def: def.clone(),
+                keywords,
+                context,
+                patterns,
+            });
+        }
+        Ok(IntentMatcher {
+            threshold: grammar.threshold.unwrap_or(DEFAULT_THRESHOLD),
+            intents,
+        })
+    }
+
+    /// Bundled grammar plus the local override, compiled once per process.
+    pub fn global() -> &'static IntentMatcher {
+        static MATCHER: OnceLock<IntentMatcher> = OnceLock::new();
+        MATCHER.get_or_init(|| {
+            let bundled = IntentGrammar::bundled();
+            if let Ok(content) = std::fs::read_to_string(IntentGrammar::override_path()) {
+                let mut merged = bundled.clone();
+                match IntentGrammar::parse(&content).map(|local| merged.merge(local)) {
+                    Ok(()) => match Self::new(&merged) {
+                        Ok(matcher) => return matcher,
+                        Err(e) => eprintln!("⚠️  Ignoring {}: {}", IntentGrammar::override_path().display(), e),
+                    },
+                    Err(e) => eprintln!("⚠️  Ignoring {}: {}", IntentGrammar::override_path().display(), e),
+                }
+            }
+            Self::new(&bundled).expect("bundled data/intents.toml compiles")
+        })
+    }
+
+    /// Every intent whose keywords appear in `input`, most confident first.
+    pub fn matches(&self, input: &str, vocab: &Vocabulary) -> Vec<IntentMatch> {
+        let input = input.trim();
+        let text = input.to_lowercase();
+        let mut found: Vec<IntentMatch> = self
+            .intents
+            .iter()
+            .filter_map(|intent| intent.score(input, &text, vocab))
+            .collect();
+        found.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        found
+    }
+
+    /// The best intent, when it is confident enough to skip the AI.
+    pub fn resolve(&self, input: &str, vocab: &Vocabulary) -> Option<IntentMatch> {
+        self.matches(input, vocab).into_iter().next().filter(|m| self.accepts(m))
+    }
+
+    pub fn accepts(&self, found: &IntentMatch) -> bool {
+        // The scores are sums of tenths; keep 0.5 + 0.2 from landing just under 0.7
+        found.confidence + 1e-4 >= self.threshold
+    }
+}
+
+impl CompiledIntent {
+    /// `text` is `input` lowercased, for keywords and vocabulary; slots come from `input`.
+    fn score(&self, input: &str, text: &str, vocab: &Vocabulary) -> Option<IntentMatch> {
+        if !self.keywords.is_match(text) {
+            return None;
+        }
+        let mut confidence = KEYWORD_SCORE;
+        if self.context.as_ref().is_some_and(|c| c.is_match(text)) {
+            confidence += CONTEXT_SCORE;
+        }
+
+        let mut slots = BTreeMap::new();
+        if let Some(caps) = self.patterns.iter().find_map(|p| p.captures(input)) {
+            confidence += PATTERN_SCORE;
+            for slot in &self.def.slots {
+                if let Some(value) = caps.name(slot).map(|m| m.as_str().trim()).filter(|v| !v.is_empty()) {
+                    slots.insert(slot.clone(), value.to_string());
+                }
+            }
+        }
+
+        // A configured alias or host named anywhere in the request wins over the
+        // pattern's guess ("구드에 백업" -> remote 구드)
+        let mut from_vocabulary = false;
+        for (slot, words) in [("remote", &vocab.remotes), ("target", &vocab.hosts)] {
+            if !self.def.slots.iter().any(|s| s == slot) {
+                continue;
+            }
+            let named = words
+                .iter()
+                .filter(|w| contains_word(text, &w.to_lowercase()))
+                .max_by_key(|w| w.len());
+            if let Some(word) = named {
+                slots.insert(slot.to_string(), word.clone());
+                from_vocabulary = true;
+            }
+        }
+        if from_vocabulary {
+            confidence += VOCABULARY_SCORE;
+        }
+
+        if self.def.required.iter().any(|r| !slots.contains_key(r)) {
+            confidence /= 2.0;
+        }
+        let target = self.def.slots.iter().find_map(|s| slots.get(s).cloned());
+        Some(IntentMatch {
+            name: self.name.clone(),
+            tool: self.def.tool.clone(),
+            operation: self.def.operation.clone(),
+            slots,
+            target,
+            confidence: confidence.min(1.0),
+        })
+    }
+}
+
+/// One alternation over `words`: ASCII words match whole words, others (Korean, Japanese,
+/// ...) as substrings so attached particles do not hide them.
+fn word_regex(words: &[String]) -> Option<Result<Regex, regex::Error>> {
+    if words.is_empty() {
+        return None;
+    }
+    let alternatives: Vec<String> = words
+        .iter()
+        .map(|w| {
+            let escaped = regex::escape(&w.to_lowercase());
+            if w.is_ascii() {
+                format!(r"\b{}\b", escaped)
+            } else {
+                escaped
+            }
+        })
+        .collect();
+    Some(Regex::new(&format!("(?:{})", alternatives.join("|"))))
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    if word.is_empty() {
+        return false;
+    }
+    if !word.is_ascii() {
+        return text.contains(word);
+    }
+    text.match_indices(word).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        !before.is_some_and(|c| c.is_ascii_alphanumeric()) && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher() -> IntentMatcher {
+        IntentMatcher::new(&IntentGrammar::bundled()).unwrap()
+    }
+
+    fn resolve(input: &str, vocab: &Vocabulary) -> Option<(String, Option<String>)> {
+        matcher().resolve(input, vocab).map(|m| (m.name, m.target))
+    }
+
+    #[test]
+    fn resolves_english_and_korean_requests() {
+        let none = Vocabulary::default();
+        let r = |name: &str, target: Option<&str>| Some((name.to_string(), target.map(str::to_string)));
+
+        assert_eq!(resolve("install htop", &none), r("install", Some("htop")));
+        assert_eq!(resolve("htop 설치해줘", &none), r("install", Some("htop")));
+        assert_eq!(resolve("htop을 설치", &none), r("install", Some("htop")));
+        assert_eq!(resolve("vega ssh to web1", &none), r("connect", Some("web1")));
+        assert_eq!(resolve("web1에 접속해줘", &none), r("connect", Some("web1")));
+        assert_eq!(resolve("update the system", &none), r("update", None));
+        assert_eq!(resolve("시스템 업데이트 해줘", &none), r("update", None));
+        assert_eq!(resolve("show recent logs", &none), r("show_log", None));
+        assert_eq!(resolve("where is my thesis pdf", &none), r("locate", Some("my thesis pdf")));
+        assert_eq!(resolve("내 논문 pdf 어디있어", &none), r("locate", Some("내 논문 pdf")));
+
+        // Keyword alone is not enough to skip the AI
+        assert_eq!(resolve("why does apt update fail with a gpg error", &none), None);
+        assert_eq!(resolve("ssh web1 and check the disk usage", &none), None);
+        assert_eq!(resolve("list the ssh keys", &none), None);
+    }
+
+    #[test]
+    fn slots_come_from_aliases_and_hosts() {
+        let vocab = Vocabulary {
+            remotes: vec!["구드".to_string()],
+            hosts: vec!["nas".to_string()],
+        };
+        let m = matcher().resolve("프로젝트를 구드에 백업해줘", &vocab).unwrap();
+        assert_eq!(m.name, "backup");
+        assert_eq!(m.slot("remote"), Some("구드"));
+        assert_eq!(m.slot("path"), Some("프로젝트"));
+        assert!(m.confidence > 0.85);
+        assert_eq!(m.to_intent().target.as_deref(), Some("구드"));
+
+        let m = matcher().matches("please connect me to the nas box", &vocab);
+        assert_eq!(m[0].slot("target"), Some("nas"));
+        assert!(m[0].confidence < 0.7);
+
+        // No remote at all: the intent is recognised but not confidently
+        let m = &matcher().matches("백업", &Vocabulary::default())[0];
+        assert_eq!(m.name, "backup");
+        assert!(m.confidence < 0.5);
+    }
+
+    #[test]
+    fn slot_values_keep_their_case() {
+        let m = matcher().resolve("Backup ~/Documents to MyRemote", &Vocabulary::default()).unwrap();
+        assert_eq!(m.name, "backup");
+        assert_eq!(m.slot("path"), Some("~/Documents"));
+        assert_eq!(m.slot("remote"), Some("MyRemote"));
+
+        let m = matcher().resolve("INSTALL NetworkManager", &Vocabulary::default()).unwrap();
+        assert_eq!(m.slot("package"), Some("NetworkManager"));
+
+        // Configured names keep the spelling they were configured with
+        let vocab = Vocabulary {
+            remotes: vec!["GDrive".to_string()],
+            hosts: Vec::new(),
+        };
+        let m = matcher().resolve("backup Notes to gdrive", &vocab).unwrap();
+        assert_eq!(m.slot("remote"), Some("GDrive"));
+        assert_eq!(m.slot("path"), Some("Notes"));
+    }
+
+    #[test]
+    fn local_grammar_overrides_bundled_intents() {
+        let mut grammar = IntentGrammar::bundled();
+        grammar.merge(
+            IntentGrammar::parse(
+                "[intents.install]\ntool = \"pkg\"\noperation = \"install\"\nkeywords = [\"깔아\"]\npatterns = ['^(?P<package>\\S+)\\s*깔아%polite%$']\nslots = [\"package\"]\n",
+            )
+            .unwrap(),
+        );
+        let matcher = IntentMatcher::new(&grammar).unwrap();
+        let m = matcher.resolve("ripgrep 깔아줘", &Vocabulary::default()).unwrap();
+        assert_eq!(m.target.as_deref(), Some("ripgrep"));
+        assert!(matcher.resolve("install htop", &Vocabulary::default()).is_none());
+
+        let broken = IntentGrammar::parse("[intents.x]\ntool = \"a\"\noperation = \"b\"\nkeywords = [\"x\"]\npatterns = ['(']\n").unwrap();
+        assert!(IntentMatcher::new(&broken).is_err());
+    }
+}
@@ -1,57 +1,19 @@
 use crate::executor::pipeline::{Intent, IntentResolver};
 use async_trait::async_trait;
-use regex::Regex;
+use crate::ai::grammar::{IntentMatcher, Vocabulary};
+use crate::executor::template;
 
 pub struct LocalIntentResolver;
 
+/// Answers from the intent grammar (`data/intents.toml`) when it is confident enough.
 #[async_trait]
 impl IntentResolver for LocalIntentResolver {
     async fn resolve(&self, input: &str) -> anyhow::Result<Intent> {
-        let input_lower = input.to_lowercase();
-
-        // Backup
-        if input_lower.contains("backup") {
-            let re = Regex::new(r"backup (?:of |server )?(\S+)").unwrap();
-            let target = re.captures(&input_lower).map(|c| c[1].to_string());
-            return Ok(Intent {
-                tool: "rclone".to_string(),
-                operation: "sync".to_string(),
-                target,
-            });
-        }
-
-        // SSH / Connect
-        if input_lower.contains("connect") || input_lower.contains("ssh") {
-            let re = Regex::new(r"(?:connect to |ssh to |ssh )(\S+)").unwrap();
-            let target = re.captures(&input_lower).map(|c| c[1].to_string());
-            return Ok(Intent {
-                tool: "ssh".to_string(),
-                operation: "connect".to_string(),
-                target,
-            });
-        }
-
-        // Install
-        if input_lower.contains("install") {
-            let re = Regex::new(r"install (\S+)").unwrap();
-            let target = re.captures(&input_lower).map(|c| c[1].to_string());
-            return Ok(Intent {
-                tool: "pkg".to_string(),
-                operation: "install".to_string(),
-                target,
-            });
-        }
-
-        // Update
-        if input_lower.contains("update") || input_lower.contains("upgrade") {
-             return Ok(Intent {
-                tool: "pkg".to_string(),
-                operation: "update".to_string(),
-                target: None,
-            });
+        match IntentMatcher::global().resolve(input, &Vocabulary::load()) {
+            // Only intents the template registry can compile; the rest go to the AI
+            Some(found) if template::supports(&found.tool, &found.operation) => Ok(found.to_intent()),
+            _ => anyhow::bail!("Unknown intent"),
         }
-
-        anyhow::bail!("Unknown intent")
     }
 }
 
@@ -81,10 +43,13 @@ impl IntentResolver for AiIntentResolver {
              INPUT: \"{}\"
              
              RULES:
-             1. Return ONLY a JSON object with fields: tool, operation, target.
-             2. Do not explain.
-             Example: {{\"tool\": \"rclone\", \"operation\": \"sync\", \"target\": \"server_a\"}}",
-            input
+             1. Return ONLY a JSON object with fields: tool, operation, target, and optionally
+                slots (an object with path, source, destination, remote, package, unit or container).
+             2. tool and operation must be one of: {}.
+             3. Do not explain.
+             Example: {{\"tool\": \"rclone\", \"operation\": \"sync\", \"target\": \"server_a\", \"slots\": {{\"path\": \"docs\"}}}}",
+            input,
+            template::known().join(", ")
         );
 
         match crate::ai::router::SmartRouter::generate_with_fallback(&ctx, &query, None).await {
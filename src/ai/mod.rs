@@ -74,3 +74,4 @@ pub mod providers;
 pub mod router;
 pub mod intent;
 pub mod generator;
+pub mod grammar;
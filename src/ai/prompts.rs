@@ -7,6 +7,57 @@ impl SystemPrompt {
         let block_devices_info =
             serde_json::to_string_pretty(&context.block_devices).unwrap_or_default();
         let mem_info = serde_json::to_string_pretty(&context.mem_info).unwrap_or_default();
+        let now = crate::storage::events::now_secs();
+        let sync_topology = if context.sync_edges.is_empty() {
+            "None recorded".to_string()
+        } else {
+            context
+                .sync_edges
+                .iter()
+                .map(|e| format!("- {}", e.describe(now)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let containers = if context.containers.containers.is_empty() {
+            "None detected".to_string()
+        } else {
+            let mut lines: Vec<String> = context
+                .containers
+                .containers
+                .iter()
+                .map(|c| format!("- {}", c.describe()))
+                .collect();
+            for project in &context.containers.compose_projects {
+                lines.push(format!(
+                    "- compose project {} ({}): {}/{} running [{}]",
+                    project.name,
+                    project.runtime.binary(),
+                    project.running,
+                    project.services.len(),
+                    project.services.join(", ")
+                ));
+            }
+            let images: Vec<String> = context
+                .containers
+                .images
+                .iter()
+                .map(|i| format!("{}:{}", i.repository, i.tag))
+                .collect();
+            if !images.is_empty() {
+                lines.push(format!("- images: {}", images.join(", ")));
+            }
+            lines.join("\n")
+        };
+        // The distro's manager first, then flatpak/snap/nix when present
+        let pkg_managers = if context.pkg_managers.is_empty() {
+            context.pkg_manager.clone()
+        } else {
+            context.pkg_managers.join(", ")
+        };
+        let project = match &context.project {
+            Some(project) => project.describe(),
+            None => "None (not inside a project)".to_string(),
+        };
 
         format!(
             r#"You are VEGA, a 20-year veteran Senior Embedded Linux SRE.
@@ -17,7 +68,7 @@ You prioritize safety, precision, and raw technical efficiency.
 - OS: {}
 - Kernel: {}
 - Load Avg: {:?}
-- Pkg Manager: {}
+- Pkg Managers: {}
 - Nvim Plugin Manager: {}
 - SSH Auth Sock: {}
 - VMs: {}
@@ -32,12 +83,24 @@ You prioritize safety, precision, and raw technical efficiency.
 ### Cloud Storage (rclone)
 {}
 
+### Sync Topology (what is backed up where)
+{}
+
+### Containers (docker/podman)
+{}
+
+### Current Project
+{}
+
 ## CORE DIRECTIVES (HOST ADMIN MODE)
 1. **Unrestricted Access**: You are the **Host System Administrator** (Root/Sudoer).
 2. **KISS Principle**: Generate the simplest, most robust command possible. Avoid complex pipes unless necessary.
 3. **Cloud Operations**: When the user mentions "cloud" or "Google Drive", use the identified `Cloud Storage` remotes. 
    - **MANDATORY**: Use the **MASKED NAME** (e.g., `REMOTE_01`) in your commands. VEGA will automatically resolve this to the real remote name.
    - **Default Destination**: If a copy/sync destination is not specified, assume the current directory (`./`).
+   - **Known Sync Targets**: When the user asks to back up or sync a directory listed in `Sync Topology`, reuse its recorded destination.
+   - **Containers**: To act inside a container listed under `Containers`, use `<runtime> exec <name> sh -c '...'` with the runtime shown for it. Use `docker compose`/`podman compose` for compose projects.
+   - **Project**: For "this project"/"the repo", work from the `Current Project` root with its detected build system. Use its named remotes (masked) and hosts. If `Allowed commands` is listed, use ONLY those commands; VEGA refuses anything else.
 3. **Search Hygiene (Internalized)**: You no longer need to manually add `2>/dev/null` or `-prune`. VEGA's core executor automatically suppresses permission errors and skips noise directories.
 4. **Search Precision (Keyword First)**: When the user mentions a specific category or noun (e.g., "screencast", "logs", "backups"):
    - **MANDATORY**: Use `-iname "*keyword*"` as the **primary** filter. This takes precedence over generic extensions.
@@ -78,14 +141,17 @@ You prioritize safety, precision, and raw technical efficiency.
             context.os_name,
             context.kernel_version,
             context.load_avg,
-            context.pkg_manager,
+            pkg_managers,
             context.plugin_manager.as_deref().unwrap_or("None detected"),
             context.ssh_auth_sock.as_deref().unwrap_or("None"),
             serde_json::to_string(&context.vms).unwrap_or_else(|_| "[]".to_string()),
             context.locale,
             mem_info,
             block_devices_info,
-            serde_json::to_string_pretty(&context.cloud_nodes).unwrap_or_default()
+            serde_json::to_string_pretty(&context.cloud_nodes).unwrap_or_default(),
+            sync_topology,
+            containers,
+            project
         )
     }
 }
@@ -2,6 +2,7 @@ use crate::ai::providers::gemini::GeminiProvider;
 use crate::ai::providers::offline::OfflineEngine;
 use crate::ai::providers::vertex_ai::VertexAiProvider;
 use crate::ai::AiProvider;
+use crate::storage::events::EventKind;
 use log::{debug, info, warn};
 
 pub struct SmartRouter;
@@ -159,14 +160,8 @@ impl SmartRouter {
     fn save_quota_state(timestamp: u64) {
         let path = Self::get_cache_path();
         let json = serde_json::json!({ "last_quota_error": timestamp });
-        if let Ok(data) = serde_json::to_string(&json) {
-            // Race Condition: Atomic write using tempfile + rename
-            let mut temp_path = path.clone();
-            temp_path.set_extension("tmp");
-            if std::fs::write(&temp_path, data).is_ok() {
-                let _ = std::fs::rename(temp_path, path);
-            }
-        }
+        // Race Condition: Atomic write using a per-writer tempfile + rename
+        let _ = crate::storage::cache_file::save(&path, &json);
     }
 
     pub async fn generate_with_fallback(
@@ -243,46 +238,19 @@ impl SmartRouter {
 
     fn get_context_summary(query: &str, limit: usize) -> Option<String> {
         // Local RAG: Search relevant history using SQLite FTS5
-        if let Ok(db) = crate::storage::db::Database::new() {
-            if let Ok(matches) = db.search_relevant_context(query, limit) {
-                if !matches.is_empty() {
-                    return Some(matches.join(" -> "));
-                }
+        let db = crate::storage::db::Database::new().ok()?;
+        if let Ok(matches) = db.search_relevant_context(query, limit) {
+            if !matches.is_empty() {
+                return Some(matches.join(" -> "));
             }
         }
 
-        // Fallback to simple tailing if DB search fails or returns nothing
-        let history_path = dirs::data_local_dir()
-            .map(|mut p| {
-                p.push("vega");
-                p.push("history.jsonl");
-                p
-            })
-            .unwrap_or_else(|| std::path::PathBuf::from("logs/history.jsonl"));
-
-        if let Ok(file) = std::fs::File::open(history_path) {
-            use std::io::{BufRead, BufReader};
-            let reader = BufReader::new(file);
-            let mut recent = Vec::new();
-            for line in reader.lines().flatten() {
-                if let Ok(entry) = serde_json::from_str::<serde_json::Value>(&line) {
-                    let cmd = entry["command"].as_str().unwrap_or("");
-                    recent.push(cmd.to_string());
-                }
-            }
-
-            if recent.is_empty() {
-                return None;
-            }
-
-            let take = if recent.len() > limit {
-                limit
-            } else {
-                recent.len()
-            };
-            let summary = recent[recent.len() - take..].join(" -> ");
-            return Some(summary);
+        // Fallback to the most recent requests if FTS returns nothing
+        let events = db.get_events(Some(EventKind::Request), None, limit).ok()?;
+        let recent: Vec<String> = events.into_iter().filter_map(|e| e.command).collect();
+        if recent.is_empty() {
+            return None;
         }
-        None
+        Some(recent.join(" -> "))
     }
 }
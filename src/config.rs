@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
@@ -7,6 +8,13 @@ pub struct VegaConfig {
     pub execution: ExecutionConfig,
     pub optimization: Option<OptimizationConfig>,
     pub ai: Option<AiConfig>,
+    pub storage: Option<StorageConfig>,
+    pub backup: Option<BackupConfig>,
+    pub aliases: Option<HashMap<String, String>>, // e.g. "구드" = "gdrive:"
+    pub rclone: Option<RcloneConfig>,
+    pub sync: Option<SyncConfig>,
+    pub index: Option<IndexConfig>,
+    pub virt: Option<VirtConfig>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
@@ -50,6 +58,54 @@ pub struct OptimizationConfig {
     pub shell_snapshot_path: Option<String>,
     pub auto_sync: Option<bool>,
     pub primary_remote: Option<String>,
+    pub session_timeout_minutes: Option<i64>,
+}
+
+/// History retention and redaction for `vega.db`.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct StorageConfig {
+    pub retention_days: Option<i64>,   // Default: 180, 0 keeps everything
+    pub max_db_size_mb: Option<u64>,   // History (events + search index) cap, default: 256, 0 disables it
+    pub max_output_bytes: Option<usize>, // Per stdout/stderr field, default: 16384
+    pub redact_outputs: Option<bool>,  // Default: true
+}
+
+/// Version retention for `vega backup` on the remote.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct BackupConfig {
+    pub keep_daily: Option<usize>,  // Default: 7
+    pub keep_weekly: Option<usize>, // Default: 4
+}
+
+/// How VEGA talks to rclone. Without `rc_url` a private `rclone rcd` is started per run.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct RcloneConfig {
+    pub use_rc: Option<bool>,     // Default: true, false forces the CLI
+    pub rc_url: Option<String>,   // Attach to a running `rclone rcd`, e.g. http://127.0.0.1:5572
+    pub rc_user: Option<String>,
+    pub rc_pass: Option<String>,
+}
+
+/// Freshness SLA for the sync topology shown by `vega status`.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct SyncConfig {
+    pub freshness_hours: Option<i64>, // Default: 24; older successful syncs are flagged stale
+}
+
+/// Offline file index of remotes and hosts (`vega index`, `vega find`).
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct IndexConfig {
+    pub background: Option<bool>,      // Default: false, crawl in the background once older than refresh_hours
+    pub refresh_hours: Option<i64>,    // Default: 24, 0 disables the background crawl
+    pub full_crawl_days: Option<i64>,  // Default: 7, re-list unchanged remote folders at least this often
+    pub hosts: Option<bool>,           // Default: true, also crawl Knowledge Base hosts over SSH
+}
+
+/// libvirt connection used by `vega vm` and VM discovery.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct VirtConfig {
+    pub uri: Option<String>,                // Default: $LIBVIRT_DEFAULT_URI, then qemu:///session
+    pub shutdown_timeout_secs: Option<u64>, // Default: 60, then the guest is destroyed
 }
 
 impl VegaConfig {
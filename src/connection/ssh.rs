@@ -1,3 +1,5 @@
+use crate::executor::template::SlotKind;
+use crate::system::probe::Probe;
 use std::process::Command;
 
 pub struct SshConnection;
@@ -14,23 +16,31 @@ impl std::fmt::Display for DiagnosticResult {
 }
 
 impl SshConnection {
-    pub fn check_connection(ip: &str, user: Option<&str>) -> Result<(), (Option<i32>, String)> {
-        let target = if let Some(u) = user {
-            format!("{}@{}", u, ip)
-        } else {
-            ip.to_string()
+    /// `[user@]host` as one argv entry. Checked like a `host` slot, so a Knowledge Base or
+    /// `.vega.toml` entry can never be read by ssh as an option (`-oProxyCommand=...`).
+    pub fn destination(ip: &str, user: Option<&str>) -> Result<String, String> {
+        let target = match user {
+            Some(u) => format!("{}@{}", u, ip),
+            None => ip.to_string(),
         };
+        SlotKind::Host.check(&target)?;
+        Ok(target)
+    }
+
+    pub fn check_connection(ip: &str, user: Option<&str>) -> Result<(), (Option<i32>, String)> {
+        let target = Self::destination(ip, user).map_err(|e| (None, e))?;
 
         println!("🔌 Testing connection to {}...", target);
 
-        let output = Command::new("ssh")
-            .args(&[
+        let output = Probe::new("ssh")
+            .args([
                 "-o",
                 "BatchMode=yes",
                 "-o",
                 "ConnectTimeout=3",
                 "-o",
-                "StrictHostKeyChecking=no",
+                "StrictHostKeyChecking=accept-new",
+                "--",
                 &target,
                 "echo 'ok'",
             ])
@@ -46,26 +56,21 @@ impl SshConnection {
     }
 
     pub fn connect(ip: &str, user: Option<&str>) {
-        let target = if let Some(u) = user {
-            format!("{}@{}", u, ip)
-        } else {
-            ip.to_string()
-        };
-
-        let _ = Command::new("ssh").arg(&target).status();
+        match Self::destination(ip, user) {
+            Ok(target) => {
+                let _ = Command::new("ssh").args(["--", &target]).status();
+            }
+            Err(e) => println!("❌ {}", e),
+        }
     }
 
     pub fn detect_os(ip: &str, user: Option<&str>) -> Option<String> {
-        let target = if let Some(u) = user {
-            format!("{}@{}", u, ip)
-        } else {
-            ip.to_string()
-        };
+        let target = Self::destination(ip, user).ok()?;
 
-        let common_args = &["-o", "BatchMode=yes", "-o", "ConnectTimeout=5", &target];
+        let common_args = &["-o", "BatchMode=yes", "-o", "ConnectTimeout=5", "--", &target];
 
         // 1. Try getting ID from os-release (Standard Linux)
-        if let Ok(output) = Command::new("ssh")
+        if let Ok(output) = Probe::new("ssh")
             .args(common_args)
             .arg("source /etc/os-release && echo $ID")
             .output()
@@ -79,7 +84,7 @@ impl SshConnection {
         }
 
         // 2. Fallback: uname -s (BSD/Busybox/Legacy)
-        if let Ok(output) = Command::new("ssh")
+        if let Ok(output) = Probe::new("ssh")
             .args(common_args)
             .arg("uname -s")
             .output()
@@ -139,18 +144,20 @@ impl SshConnection {
 
     #[allow(dead_code)]
     pub async fn execute_remote_async(ip: &str, cmd: &str) -> Result<String, String> {
+        SlotKind::Host.check(ip)?;
+        // Runs an arbitrary remote command, so it is never recorded or replayed
         let output = tokio::process::Command::new("ssh")
-            .args(&[
+            .args([
                 "-o",
                 "BatchMode=yes",
                 "-o",
                 "ConnectTimeout=10",
                 "-o",
-                "StrictHostKeyChecking=no",
+                "StrictHostKeyChecking=accept-new",
+                "--",
                 ip,
                 cmd,
             ])
-            .stderr(std::process::Stdio::null()) // Sovereign SRE: Suppress remote noise
             .output()
             .await
             .map_err(|e| e.to_string())?;
@@ -162,3 +169,38 @@ impl SshConnection {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::probe::{with_bundle, Bundle};
+
+    #[test]
+    fn detect_os_falls_back_to_uname() {
+        let mut bundle = Bundle::new().with_command(
+            "ssh",
+            &["-o", "BatchMode=yes", "-o", "ConnectTimeout=5", "--", "root@10.0.0.9", "uname -s"],
+            "FreeBSD\n",
+        );
+        // Busybox/BSD: no /etc/os-release, so the first probe prints nothing useful
+        bundle = bundle.with_command(
+            "ssh",
+            &["-o", "BatchMode=yes", "-o", "ConnectTimeout=5", "--", "root@10.0.0.9", "source /etc/os-release && echo $ID"],
+            "",
+        );
+        let os = with_bundle(bundle, || SshConnection::detect_os("10.0.0.9", Some("root")));
+        assert_eq!(os.as_deref(), Some("freebsd"));
+    }
+
+    #[test]
+    fn destinations_cannot_become_ssh_options() {
+        assert_eq!(SshConnection::destination("10.0.0.12", Some("ci")).unwrap(), "ci@10.0.0.12");
+        assert_eq!(SshConnection::destination("[fe80::1]", None).unwrap(), "[fe80::1]");
+        assert!(SshConnection::destination("-oProxyCommand=touch /tmp/x", None).is_err());
+        assert!(SshConnection::destination("host", Some("-oProxyCommand=x")).is_err());
+        assert!(SshConnection::destination("host;reboot", None).is_err());
+        assert!(SshConnection::destination("host name", Some("ci")).is_err());
+        let refused = with_bundle(Bundle::new(), || SshConnection::check_connection("-F/tmp/evil", None));
+        assert!(matches!(refused, Err((None, _))));
+    }
+}
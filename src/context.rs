@@ -1,11 +1,13 @@
+use crate::storage::cache_file;
 use crate::storage::db::Database;
+use crate::system::containers::{ContainerInventory, ContainerScanner};
 use crate::system::discovery::Discovery;
+use crate::system::probe::{self, Probe};
+use crate::system::project::ProjectInfo;
 use crate::system::virt::{VirtManager, VirtualMachine};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::fs;
-use std::process::Command;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Partition {
@@ -34,16 +36,23 @@ pub struct SystemContext {
     pub mem_info: Value,
     pub block_devices: Value,
     pub pkg_manager: String,
+    /// Every active package manager, the distro's first (e.g. `dnf`, `flatpak`).
+    #[serde(default)]
+    pub pkg_managers: Vec<String>,
     pub is_vm: bool,
     pub git_user: String,
     pub partitions: Vec<Partition>,
     pub vms: Vec<VirtualMachine>,
+    #[serde(default)]
+    pub containers: ContainerInventory,
     pub env_vars: HashMap<String, String>,
     pub plugin_manager: Option<String>,
     pub ssh_auth_sock: Option<String>,
     pub locale: String,
     pub cloud_nodes: Vec<CloudStorageNode>,
     pub sync_edges: Vec<SyncEdge>,
+    #[serde(default)]
+    pub project: Option<ProjectInfo>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,11 +62,81 @@ pub struct CloudStorageNode {
     pub status: String,
 }
 
+/// Default freshness SLA when `[sync] freshness_hours` is unset.
+pub const DEFAULT_FRESHNESS_HOURS: i64 = 24;
+
+/// A directory VEGA keeps in sync with a remote, updated by every sync or backup run.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncEdge {
     pub source: String,
     pub destination: String,
-    pub last_sync: String,
+    pub kind: String, // sync | backup
+    pub last_sync: i64,
+    pub last_success: Option<i64>,
+    pub bytes: i64,
+    pub success: bool,
+    pub error: Option<String>,
+    pub runs: i64,
+    #[serde(default)]
+    pub stale: bool,
+}
+
+impl SyncEdge {
+    /// Stale when the last successful run is older than the SLA, or there never was one.
+    pub fn is_stale(&self, now: i64, sla_hours: i64) -> bool {
+        match self.last_success {
+            Some(ts) => now - ts > sla_hours * 3600,
+            None => true,
+        }
+    }
+
+    /// Loads all edges and flags the stale ones against the configured SLA.
+    pub fn load(db: &Database) -> Vec<SyncEdge> {
+        let sla = Self::freshness_hours();
+        let now = crate::storage::events::now_secs();
+        let mut edges = db.list_sync_edges().unwrap_or_default();
+        for edge in &mut edges {
+            edge.stale = edge.is_stale(now, sla);
+        }
+        edges
+    }
+
+    /// One-line summary for the AI prompt, e.g.
+    /// `/home/me/docs -> REMOTE_01:vega_backups/docs (backup, last success 3h ago)`.
+    pub fn describe(&self, now: i64) -> String {
+        let last = match self.last_success {
+            Some(ts) => format!("last success {}", age_label(now - ts)),
+            None => "never succeeded".to_string(),
+        };
+        let mut flags = String::new();
+        if !self.success {
+            flags.push_str(", LAST RUN FAILED");
+        }
+        if self.stale {
+            flags.push_str(", STALE");
+        }
+        format!("{} -> {} ({}, {}{})", self.source, self.destination, self.kind, last, flags)
+    }
+
+    pub fn freshness_hours() -> i64 {
+        crate::config::VegaConfig::load(&crate::init::get_config_path().to_string_lossy())
+            .ok()
+            .and_then(|c| c.sync)
+            .and_then(|s| s.freshness_hours)
+            .unwrap_or(DEFAULT_FRESHNESS_HOURS)
+    }
+}
+
+/// Compact age such as `45m ago`, `3h ago` or `12d ago`.
+pub fn age_label(secs: i64) -> String {
+    let secs = secs.max(0);
+    if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86400)
+    }
 }
 
 impl SystemContext {
@@ -69,67 +148,167 @@ impl SystemContext {
             mem_info: Value::Null,
             block_devices: Value::Null,
             pkg_manager: "unknown".to_string(),
+            pkg_managers: Vec::new(),
             is_vm: false,
             git_user: "Unknown".to_string(),
             partitions: Vec::new(),
             vms: Vec::new(),
+            containers: ContainerInventory::default(),
             env_vars: HashMap::new(),
             plugin_manager: None,
             ssh_auth_sock: None,
             locale: "en_US.UTF-8".to_string(),
             cloud_nodes: Vec::new(),
             sync_edges: Vec::new(),
+            project: None,
         }
     }
 
+    /// Context for this process. Collected once (see `system::global`) and shared by
+    /// every caller; slow probes are served from the on-disk cache while their TTL lasts.
     pub fn collect() -> Self {
-        let mut cloud_nodes = Vec::new();
-        let sync_edges = Vec::new();
-
-        // Persistent Discovery & Loading
-        if let Ok(db) = Database::new() {
-            // 1. Run Discovery
-            if let Ok(discovery) = Discovery::run() {
-                // Persist found remotes and populate context
-                let mut masker = crate::remote::RemoteMasker::new();
-                for remote in discovery.cloud_remotes {
-                    let _ = db.set_metadata(&format!("cloud_remote:{}", remote), "discovered");
-                    cloud_nodes.push(CloudStorageNode {
-                        name: masker.mask(&remote),
-                        provider: "rclone".to_string(),
-                        status: "Available".to_string(),
-                    });
+        crate::system::global::get_context().clone()
+    }
+
+    /// Runs all probes now, from a synchronous caller.
+    pub fn gather_blocking() -> Self {
+        block_on(Self::gather())
+    }
+
+    /// Runs every probe concurrently with its own timeout, reusing cached fields that are
+    /// still fresh. A probe that times out falls back to its last cached value.
+    pub async fn gather() -> Self {
+        let collector = Collector::new();
+        let (
+            os_name,
+            kernel_version,
+            load_avg,
+            mem_info,
+            block_devices,
+            pkg_manager,
+            pkg_managers,
+            is_vm,
+            git_user,
+            partitions,
+            vms,
+            containers,
+            plugin_manager,
+            locale,
+            remotes,
+            edges,
+            mut project,
+        ) = tokio::join!(
+            collector.field("os_name", "Unknown".to_string(), Self::get_os_info),
+            collector.field("kernel_version", "Unknown".to_string(), Self::get_kernel_version),
+            collector.field("load_avg", Vec::new(), Self::get_load_avg),
+            collector.field("mem_info", Value::Null, Self::get_mem_info),
+            collector.field("block_devices", Value::Null, Self::get_block_devices),
+            collector.field("pkg_manager", "unknown".to_string(), Self::detect_pkg_manager),
+            collector.field("pkg_managers", Vec::new(), Self::detect_pkg_managers),
+            collector.field("is_vm", false, Self::detect_vm),
+            collector.field("git_user", "Unknown".to_string(), Self::detect_git_user),
+            collector.field("partitions", Vec::new(), Self::scan_partitions),
+            collector.field("vms", Vec::new(), VirtManager::list_vms),
+            collector.field("containers", ContainerInventory::default(), ContainerScanner::scan),
+            collector.field("plugin_manager", None, Self::detect_plugin_manager),
+            collector.field("locale", "en_US.UTF-8".to_string(), Self::get_locale),
+            collector.field("cloud_remotes", Vec::new(), Self::discover_remotes),
+            collector.field("sync_edges", Vec::new(), Self::load_sync_edges),
+            collector.field("project", None, ProjectInfo::detect),
+        );
+        collector.save();
+
+        // Mask remotes in `rclone listremotes` order so cloud nodes, sync edges and project remotes agree,
+        // whichever of them came from the cache
+        let mut masker = crate::remote::RemoteMasker::new();
+        let cloud_nodes = remotes
+            .iter()
+            .map(|(remote, backend)| CloudStorageNode {
+                name: masker.mask(remote),
+                provider: format!("rclone/{}", backend),
+                status: "Available".to_string(),
+            })
+            .collect();
+        let mut sync_edges = edges;
+        for edge in &mut sync_edges {
+            for spec in [&mut edge.source, &mut edge.destination] {
+                if spec.starts_with('/') {
+                    continue;
                 }
-                for host in discovery.ssh_hosts {
-                    let _ = db.set_metadata(&format!("ssh_host:{}", host), "discovered");
+                if let Some((remote, path)) = spec.split_once(':') {
+                    *spec = format!("{}:{}", masker.mask(remote), path);
+                }
+            }
+        }
+        if let Some(project) = &mut project {
+            for spec in project.profile.remotes.values_mut() {
+                if let Some((remote, path)) = spec.split_once(':') {
+                    *spec = format!("{}:{}", masker.mask(remote), path);
                 }
             }
-
-            // 2. Load from Metadata
-            // This is a bit inefficient to iterate all, but for now it works
-            // In a real scenario, we'd query the metadata table for specific keys
-            // But since we don't have a 'get_all_with_prefix' yet, we'll just use the discovery results we have
-            // and maybe some hardcoded lookups if we added manual ones.
         }
 
         SystemContext {
-            os_name: Self::get_os_info(),
-            kernel_version: Self::get_kernel_version(),
-            load_avg: Self::get_load_avg(),
-            mem_info: Self::get_mem_info(),
-            block_devices: Self::get_block_devices(),
-            pkg_manager: Self::detect_pkg_manager(),
-            is_vm: Self::detect_vm(),
-            git_user: Self::detect_git_user(),
-            partitions: Self::scan_partitions(),
-            vms: VirtManager::list_vms(),
+            os_name,
+            kernel_version,
+            load_avg,
+            mem_info,
+            block_devices,
+            pkg_manager,
+            pkg_managers,
+            is_vm,
+            git_user,
+            partitions,
+            vms,
+            containers,
             env_vars: HashMap::new(),
-            plugin_manager: Self::detect_plugin_manager(),
+            plugin_manager,
             ssh_auth_sock: std::env::var("SSH_AUTH_SOCK").ok(),
-            locale: Self::get_locale(),
+            locale,
             cloud_nodes,
             sync_edges,
+            project,
+        }
+    }
+
+    /// Drops cached fields after an action that changed them (e.g. `vms` after `vega vm start`).
+    pub fn invalidate(fields: &[&str]) {
+        let mut cache = ContextCache::load();
+        for field in fields {
+            cache.fields.remove(*field);
         }
+        cache.save();
+    }
+
+    /// rclone remotes with their backend type, in `listremotes` order. Discovery results
+    /// are persisted as metadata, except when replaying another machine's probes.
+    fn discover_remotes() -> Vec<(String, String)> {
+        let discovery = match Discovery::run() {
+            Ok(d) => d,
+            Err(_) => return Vec::new(),
+        };
+        let db = if probe::is_live() { Database::new().ok() } else { None };
+        if let Some(db) = &db {
+            for remote in &discovery.cloud_remotes {
+                let _ = db.set_metadata(&format!("cloud_remote:{}", remote), "discovered");
+            }
+            for host in &discovery.ssh_hosts {
+                let _ = db.set_metadata(&format!("ssh_host:{}", host), "discovered");
+            }
+        }
+        let backends = crate::remote::rclone::RcloneProvider::backend_types();
+        discovery
+            .cloud_remotes
+            .into_iter()
+            .map(|remote| {
+                let backend = backends.get(&remote).cloned().unwrap_or_else(|| "unknown".to_string());
+                (remote, backend)
+            })
+            .collect()
+    }
+
+    fn load_sync_edges() -> Vec<SyncEdge> {
+        Database::new().map(|db| SyncEdge::load(&db)).unwrap_or_default()
     }
 
     fn get_locale() -> String {
@@ -138,7 +317,7 @@ impl SystemContext {
             .or_else(|_| std::env::var("LC_ALL"))
             .or_else(|_| std::env::var("LANG"))
             .unwrap_or_else(|_| {
-                Command::new("locale")
+                Probe::new("locale")
                     .arg("-a")
                     .output()
                     .ok()
@@ -156,7 +335,7 @@ impl SystemContext {
         let mut partitions = Vec::new();
         // Execute df -h
         // Expected output format: Filesystem Size Used Avail Use% Mounted on
-        let output = Command::new("df").arg("-h").output();
+        let output = Probe::new("df").arg("-h").output();
 
         if let Ok(output) = output {
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -228,7 +407,7 @@ impl SystemContext {
     }
 
     fn get_os_info() -> String {
-        fs::read_to_string("/etc/os-release")
+        probe::read_to_string("/etc/os-release")
             .unwrap_or_default()
             .lines()
             .find(|l| l.starts_with("PRETTY_NAME="))
@@ -237,7 +416,7 @@ impl SystemContext {
     }
 
     fn get_kernel_version() -> String {
-        fs::read_to_string("/proc/version")
+        probe::read_to_string("/proc/version")
             .unwrap_or_else(|_| "Unknown".to_string())
             .split_whitespace()
             .take(3)
@@ -246,7 +425,7 @@ impl SystemContext {
     }
 
     fn get_load_avg() -> Vec<f64> {
-        fs::read_to_string("/proc/loadavg")
+        probe::read_to_string("/proc/loadavg")
             .unwrap_or_default()
             .split_whitespace()
             .take(3)
@@ -255,7 +434,7 @@ impl SystemContext {
     }
 
     fn get_mem_info() -> Value {
-        let content = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+        let content = probe::read_to_string("/proc/meminfo").unwrap_or_default();
         let mut map = serde_json::Map::new();
         for line in content.lines() {
             let parts: Vec<&str> = line.split(':').collect();
@@ -270,7 +449,7 @@ impl SystemContext {
     }
 
     fn get_block_devices() -> Value {
-        let output = Command::new("lsblk")
+        let output = Probe::new("lsblk")
             .args(&["-J", "-o", "NAME,SIZE,TYPE,MOUNTPOINT"])
             .output();
 
@@ -284,7 +463,7 @@ impl SystemContext {
     }
 
     fn detect_pkg_manager() -> String {
-        let content = fs::read_to_string("/etc/os-release").unwrap_or_default();
+        let content = probe::read_to_string("/etc/os-release").unwrap_or_default();
         let mut id = "unknown";
         let mut id_like = "";
 
@@ -301,6 +480,11 @@ impl SystemContext {
             "ubuntu" | "debian" | "kali" | "pop" => return "apt".to_string(),
             "fedora" | "rhel" | "centos" | "almalinux" => return "dnf".to_string(),
             "arch" | "manjaro" | "endeavouros" => return "pacman".to_string(),
+            "opensuse-leap" | "opensuse-tumbleweed" | "sles" => return "zypper".to_string(),
+            "alpine" => return "apk".to_string(),
+            "void" => return "xbps".to_string(),
+            "gentoo" => return "emerge".to_string(),
+            "nixos" => return "nix".to_string(),
             _ => {}
         }
 
@@ -313,19 +497,26 @@ impl SystemContext {
         if id_like.contains("arch") {
             return "pacman".to_string();
         }
+        if id_like.contains("suse") {
+            return "zypper".to_string();
+        }
 
         "unknown".to_string()
     }
 
+    fn detect_pkg_managers() -> Vec<String> {
+        crate::executor::pkg::active_managers(&Self::detect_pkg_manager())
+    }
+
     fn detect_vm() -> bool {
-        let output = Command::new("lsmod").output();
+        let output = Probe::new("lsmod").output();
         if let Ok(o) = output {
             let s = String::from_utf8_lossy(&o.stdout);
             if s.contains("kvm") || s.contains("vbox") {
                 return true;
             }
         }
-        if let Ok(o) = Command::new("systemd-detect-virt").output() {
+        if let Ok(o) = Probe::new("systemd-detect-virt").output() {
             if !o.stdout.is_empty() && String::from_utf8_lossy(&o.stdout).trim() != "none" {
                 return true;
             }
@@ -334,7 +525,7 @@ impl SystemContext {
     }
 
     fn detect_git_user() -> String {
-        Command::new("git")
+        Probe::new("git")
             .args(&["config", "--get", "user.name"])
             .output()
             .ok()
@@ -346,17 +537,270 @@ impl SystemContext {
         crate::system::discovery::Discovery::detect_plugin_manager()
     }
 
-    #[allow(dead_code)]
-    pub fn detect_current_project() -> String {
-        let cwd = std::env::current_dir().unwrap_or_default();
-        let path_str = cwd.to_string_lossy();
+}
+
+/// Per-probe cache TTL and timeout: (field, TTL secs, timeout secs). TTL 0 means the
+/// probe runs on every collection.
+const PROBES: &[(&str, i64, u64)] = &[
+    ("os_name", 86400, 2),
+    ("kernel_version", 3600, 2),
+    ("load_avg", 0, 2),
+    ("mem_info", 0, 2),
+    ("block_devices", 300, 5),
+    ("pkg_manager", 86400, 2),
+    ("pkg_managers", 86400, 5),
+    ("is_vm", 86400, 5),
+    ("git_user", 86400, 3),
+    ("partitions", 300, 5),
+    ("vms", 60, 10),
+    ("containers", 60, 10),
+    ("plugin_manager", 86400, 2),
+    ("locale", 0, 3),
+    ("cloud_remotes", 3600, 20),
+    ("sync_edges", 0, 5),
+    ("project", 0, 5),
+];
+const DEFAULT_PROBE_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedField {
+    value: Value,
+    collected_at: i64,
+}
+
+/// Probe results kept between runs in `<data dir>/vega/context_cache.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ContextCache {
+    fields: HashMap<String, CachedField>,
+}
+
+impl ContextCache {
+    fn path() -> std::path::PathBuf {
+        cache_file::data_path("context_cache.json")
+    }
+
+    fn load() -> Self {
+        cache_file::load(&Self::path())
+    }
+
+    fn save(&self) {
+        let _ = cache_file::save(&Self::path(), self);
+    }
+}
+
+/// Runs probes for one collection. The disk cache is only used against the live system,
+/// never while a probe bundle is being captured or replayed.
+struct Collector {
+    cache: ContextCache,
+    use_cache: bool,
+    now: i64,
+    scope: probe::ProbeScope,
+    updates: parking_lot::Mutex<Vec<(&'static str, CachedField)>>,
+}
+
+impl Collector {
+    fn new() -> Self {
+        let use_cache = probe::is_live();
+        Collector {
+            cache: if use_cache { ContextCache::load() } else { ContextCache::default() },
+            use_cache,
+            now: crate::storage::events::now_secs(),
+            scope: probe::ProbeScope::current(),
+            updates: parking_lot::Mutex::new(Vec::new()),
+        }
+    }
+
+    async fn field<T>(&self, name: &'static str, fallback: T, run: fn() -> T) -> T
+    where
+        T: Serialize + serde::de::DeserializeOwned + Send + 'static,
+    {
+        let (ttl, timeout) = PROBES
+            .iter()
+            .find(|(field, _, _)| *field == name)
+            .map(|(_, ttl, timeout)| (*ttl, *timeout))
+            .unwrap_or((0, DEFAULT_PROBE_TIMEOUT_SECS));
+        let cached = self
+            .cache
+            .fields
+            .get(name)
+            .and_then(|c| serde_json::from_value::<T>(c.value.clone()).ok().map(|v| (v, c.collected_at)));
+        if let Some((value, collected_at)) = cached {
+            if ttl > 0 && self.now - collected_at < ttl {
+                return value;
+            }
+            return self.probe(name, ttl, timeout, run).await.unwrap_or(value);
+        }
+        self.probe(name, ttl, timeout, run).await.unwrap_or(fallback)
+    }
 
-        if path_str.contains("doom") || fs::metadata("DOOM.WAD").is_ok() {
-            return "Project: DooM Engine".to_string();
+    /// Runs `run` on its own thread so a hung command (a stale NFS mount under `df`,
+    /// an unresponsive libvirtd) cannot hold up the others; it is abandoned on timeout.
+    async fn probe<T>(&self, name: &'static str, ttl: i64, timeout: u64, run: fn() -> T) -> Option<T>
+    where
+        T: Serialize + Send + 'static,
+    {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let scope = self.scope.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(scope.run(run));
+        });
+        match tokio::time::timeout(std::time::Duration::from_secs(timeout), rx).await {
+            Ok(Ok(value)) => {
+                if self.use_cache && ttl > 0 {
+                    if let Ok(json) = serde_json::to_value(&value) {
+                        self.updates.lock().push((
+                            name,
+                            CachedField {
+                                value: json,
+                                collected_at: self.now,
+                            },
+                        ));
+                    }
+                }
+                Some(value)
+            }
+            Ok(Err(_)) => None, // the probe panicked
+            Err(_) => {
+                log::warn!("Context probe '{}' timed out after {}s", name, timeout);
+                None
+            }
         }
-        if path_str.contains("vega") || fs::metadata("vega_config.toml").is_ok() {
-            return "Project: Vega Agent".to_string();
+    }
+
+    fn save(self) {
+        if !self.use_cache {
+            return;
         }
-        "Project: Unknown".to_string()
+        let updates = self.updates.into_inner();
+        if updates.is_empty() {
+            return;
+        }
+        // Re-read so fields written by a concurrent vega process are kept
+        let mut cache = ContextCache::load();
+        for (name, field) in updates {
+            cache.fields.insert(name.to_string(), field);
+        }
+        cache.save();
+    }
+}
+
+/// Drives `future` to completion from synchronous code, inside or outside a runtime.
+fn block_on<F>(future: F) -> F::Output
+where
+    F: std::future::Future + Send,
+    F::Output: Send,
+{
+    use tokio::runtime::{Handle, RuntimeFlavor};
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| handle.block_on(future))
+        }
+        // No runtime, or one that cannot block in place: use a private one on its own thread
+        _ => std::thread::scope(|s| {
+            s.spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("tokio runtime")
+                    .block_on(future)
+            })
+            .join()
+            .expect("context collection panicked")
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::probe::{with_bundle, Bundle};
+
+    fn fixture() -> Bundle {
+        Bundle::from_json(include_str!("../tests/fixtures/fedora-workstation.json")).unwrap()
+    }
+
+    #[test]
+    fn os_probes_from_capture() {
+        with_bundle(fixture(), || {
+            assert_eq!(SystemContext::get_os_info(), "Fedora Linux 40 (Workstation Edition)");
+            assert_eq!(SystemContext::get_kernel_version(), "Linux version 6.10.11-200.fc40.x86_64");
+            assert_eq!(SystemContext::get_load_avg(), vec![0.52, 0.61, 0.70]);
+            assert_eq!(SystemContext::detect_pkg_manager(), "dnf");
+            assert_eq!(SystemContext::detect_git_user(), "Dog Sinatas");
+            assert!(SystemContext::detect_vm());
+            let mem = SystemContext::get_mem_info();
+            assert_eq!(mem["MemTotal"], "32651128 kB");
+            assert!(mem.get("MemFree").is_none());
+        });
+    }
+
+    #[test]
+    fn partitions_skip_pseudo_filesystems() {
+        with_bundle(fixture(), || {
+            let partitions = SystemContext::scan_partitions();
+            let mounts: Vec<&str> = partitions.iter().map(|p| p.mount_point.as_str()).collect();
+            assert_eq!(mounts, vec!["/", "/home", "/boot", "/mnt/HDD", "/run/media/me/USB STICK"]);
+            assert_eq!(partitions[0].partition_type, PartitionType::Root);
+            assert_eq!(partitions[1].partition_type, PartitionType::User);
+            assert_eq!(partitions[3].partition_type, PartitionType::Media);
+            assert_eq!(partitions[4].partition_type, PartitionType::Media);
+        });
+    }
+
+    #[test]
+    fn block_devices_parse_lsblk_json() {
+        with_bundle(fixture(), || {
+            let devices = SystemContext::get_block_devices();
+            assert_eq!(devices["blockdevices"][1]["children"][0]["mountpoint"], "/mnt/HDD");
+        });
+    }
+
+    #[test]
+    fn collector_carries_replay_into_probe_threads() {
+        with_bundle(fixture(), || {
+            let collector = Collector::new();
+            assert!(!collector.use_cache);
+            let os = block_on(collector.field("os_name", "Unknown".to_string(), SystemContext::get_os_info));
+            assert_eq!(os, "Fedora Linux 40 (Workstation Edition)");
+        });
+    }
+
+    #[test]
+    fn cached_fields_respect_ttl() {
+        let cached = |age: i64| Collector {
+            cache: ContextCache {
+                fields: HashMap::from([(
+                    "git_user".to_string(),
+                    CachedField {
+                        value: Value::String("cached".to_string()),
+                        collected_at: 1_000_000 - age,
+                    },
+                )]),
+            },
+            use_cache: true,
+            now: 1_000_000,
+            scope: probe::ProbeScope::current(),
+            updates: parking_lot::Mutex::new(Vec::new()),
+        };
+        let probe_value = || "probed".to_string();
+        with_bundle(Bundle::new(), || {
+            let fresh = cached(60);
+            assert_eq!(block_on(fresh.field("git_user", String::new(), probe_value)), "cached");
+            assert!(fresh.updates.lock().is_empty());
+
+            let stale = cached(2 * 86400);
+            assert_eq!(block_on(stale.field("git_user", String::new(), probe_value)), "probed");
+            assert_eq!(stale.updates.lock().len(), 1);
+        });
+    }
+
+    #[test]
+    fn missing_tools_degrade_quietly() {
+        with_bundle(Bundle::new(), || {
+            assert_eq!(SystemContext::get_os_info(), "Unknown");
+            assert!(SystemContext::scan_partitions().is_empty());
+            assert!(SystemContext::get_block_devices().is_null());
+            assert!(!SystemContext::detect_vm());
+        });
     }
 }
@@ -1,5 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+/// One position in a command's argv, as laid out by its tool template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Arg {
+    Word(String),
+    /// Where the generated `options` go.
+    Options,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandAst {
     pub tool: String,
@@ -8,6 +16,20 @@ pub struct CommandAst {
     pub destination: Option<String>,
     pub options: Vec<String>,
     pub target_server: Option<String>,
+    /// argv with the template's slots bound; see `executor::template`.
+    #[serde(default)]
+    pub args: Vec<Arg>,
+    /// Shell text run after the argv (`| grep x`, `> out.log`); set only on explicit request.
+    #[serde(default)]
+    pub shell: Option<ShellOptIn>,
+}
+
+/// A pipeline or redirection the user asked for, which makes the command run
+/// under `sh -c` instead of directly. Recorded with the reason it was allowed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShellOptIn {
+    pub fragment: String,
+    pub reason: String,
 }
 
 impl CommandAst {
@@ -19,24 +41,86 @@ impl CommandAst {
             destination: None,
             options: Vec::new(),
             target_server: None,
+            args: Vec::new(),
+            shell: None,
         }
     }
 
-    pub fn to_shell_command(&self) -> String {
-        let mut cmd = format!("{} {}", self.tool, self.operation);
-        
-        if let Some(src) = &self.source {
-            cmd.push_str(&format!(" {}", src));
-        }
-        
-        if let Some(dst) = &self.destination {
-            cmd.push_str(&format!(" {}", dst));
+    /// The program and its arguments, options placed where the template puts them.
+    pub fn argv(&self) -> Vec<String> {
+        let mut argv = Vec::new();
+        for arg in &self.args {
+            match arg {
+                Arg::Word(word) => argv.push(word.clone()),
+                Arg::Options => argv.extend(self.options.iter().cloned()),
+            }
         }
-        
-        if !self.options.is_empty() {
-            cmd.push_str(&format!(" {}", self.options.join(" ")));
+        argv
+    }
+
+    pub fn with_shell(mut self, fragment: &str, reason: &str) -> Self {
+        self.shell = Some(ShellOptIn {
+            fragment: fragment.to_string(),
+            reason: reason.to_string(),
+        });
+        self
+    }
+
+    /// The command as one shell line, for display and for SSH: every argv word
+    /// quoted, followed by the opted-in shell fragment if there is one.
+    pub fn to_shell_command(&self) -> String {
+        let line = self.argv().iter().map(|a| quote(a)).collect::<Vec<_>>().join(" ");
+        match &self.shell {
+            Some(opt_in) => format!("{} {}", line, opt_in.fragment.trim()),
+            None => line,
         }
-        
-        cmd
+    }
+}
+
+/// POSIX single-quote `arg` unless it is made only of characters the shell leaves alone.
+pub fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && !arg.starts_with('#')
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:+=@%#,".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_only_what_the_shell_would_touch() {
+        assert_eq!(quote("--dry-run"), "--dry-run");
+        assert_eq!(quote("gdrive:backup/2024"), "gdrive:backup/2024");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("#x"), "'#x'");
+        assert_eq!(quote("a b; rm -rf /"), "'a b; rm -rf /'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn renders_argv_and_shell_opt_in() {
+        let mut ast = CommandAst::new("rclone", "sync");
+        ast.args = vec![
+            Arg::Word("rclone".into()),
+            Arg::Word("sync".into()),
+            Arg::Options,
+            Arg::Word("/home/me/My Docs".into()),
+        ];
+        ast.options = vec!["--dry-run".into()];
+        assert_eq!(ast.to_shell_command(), "rclone sync --dry-run '/home/me/My Docs'");
+
+        let ast = ast.with_shell("| tail -n 5", "requested with --shell");
+        assert_eq!(
+            ast.to_shell_command(),
+            "rclone sync --dry-run '/home/me/My Docs' | tail -n 5"
+        );
     }
 }
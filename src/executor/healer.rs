@@ -1,10 +1,11 @@
 use super::ExecuteResult;
-use crate::system::os::{OsInfo, PackageManager};
+use crate::context::SystemContext;
+use crate::executor::pkg;
 
 pub struct Healer;
 
 impl Healer {
-    pub fn diagnose(result: &ExecuteResult, os_info: &OsInfo, original_cmd: &str) -> Option<String> {
+    pub fn diagnose(result: &ExecuteResult, ctx: &SystemContext, original_cmd: &str) -> Option<String> {
         if result.success {
             return None;
         }
@@ -25,30 +26,34 @@ impl Healer {
         }
 
         // 1. Check for Permission Denied
-        if combined.contains("permission denied") || result.exit_code == 126 {
-            if !original_cmd.trim().starts_with("sudo") {
-                return Some(format!("sudo {}", original_cmd));
-            }
+        if (combined.contains("permission denied") || result.exit_code == Some(126))
+            && !original_cmd.trim().starts_with("sudo")
+        {
+            return Some(format!("sudo {}", original_cmd));
         }
 
         // 2. Check for Command Not Found
-        if combined.contains("command not found") || combined.contains("not found") {
-            // Extract command name (naive approach)
-            // e.g., "/bin/sh: 1: cargo: not found"
+        if combined.contains("not found") || result.exit_code == Some(127) {
+            // e.g. "/bin/sh: 1: cargo: not found" or "bash: cargo: command not found"
             let parts: Vec<&str> = result.stderr.split(':').collect();
-            for part in parts {
+            for (i, part) in parts.iter().enumerate() {
                 if part.contains("not found") {
-                     // Try to get the word before "not found"
-                     let potential_cmd = part.replace("not found", "").trim().to_string();
-                     if !potential_cmd.is_empty() {
-                         return Some(Self::suggest_install(&potential_cmd, &os_info.pkg_manager));
-                     }
+                    let mut potential_cmd = part.replace("command not found", "").replace("not found", "").trim().to_string();
+                    if potential_cmd.is_empty() && i > 0 {
+                        potential_cmd = parts[i - 1].trim().to_string();
+                    }
+                    if !potential_cmd.is_empty() {
+                        return Some(Self::suggest_install(&potential_cmd, ctx));
+                    }
                 }
             }
             // Fallback: try to guess from original command
-            let cmd_name = original_cmd.split_whitespace().next().unwrap_or("");
+            let cmd_name = original_cmd
+                .split_whitespace()
+                .find(|w| *w != "sudo" && !w.contains('='))
+                .unwrap_or("");
             if !cmd_name.is_empty() {
-                return Some(Self::suggest_install(cmd_name, &os_info.pkg_manager));
+                return Some(Self::suggest_install(cmd_name, ctx));
             }
         }
 
@@ -65,12 +70,43 @@ impl Healer {
         None
     }
 
-    fn suggest_install(cmd: &str, pkg_manager: &PackageManager) -> String {
-        match pkg_manager {
-            PackageManager::Apt => format!("sudo apt install -y {}", cmd),
-            PackageManager::Dnf => format!("sudo dnf install -y {}", cmd),
-            PackageManager::Pacman => format!("sudo pacman -S --noconfirm {}", cmd),
-            PackageManager::Unknown => format!("echo 'Please install {} manually'", cmd),
+    /// Installs the package that provides `cmd` (`dig` -> `bind-utils` on Fedora).
+    fn suggest_install(cmd: &str, ctx: &SystemContext) -> String {
+        match pkg::detect(ctx) {
+            Some(pm) => {
+                let package = pkg::provides(pm.as_ref(), cmd)
+                    .map(|p| p.package)
+                    .unwrap_or_else(|| cmd.to_string());
+                match pm.install(&package) {
+                    Ok(command) => command.to_shell(),
+                    Err(_) => format!("echo 'Please install {} manually'", cmd),
+                }
+            }
+            None => format!("echo 'Please install {} manually'", cmd),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::probe::{with_bundle, Bundle};
+
+    #[test]
+    fn missing_command_suggests_the_providing_package() {
+        let mut ctx = SystemContext::new();
+        ctx.pkg_manager = "dnf".to_string();
+        let failed = |stderr: &str| ExecuteResult {
+            success: false,
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+            exit_code: Some(127),
+        };
+        with_bundle(Bundle::new(), || {
+            let fix = Healer::diagnose(&failed("bash: dig: command not found"), &ctx, "dig example.com").unwrap();
+            assert!(fix.ends_with("sudo dnf install -y bind-utils"), "{}", fix);
+            let fix = Healer::diagnose(&failed(""), &ctx, "sudo nc -l 8080").unwrap();
+            assert!(fix.ends_with("sudo dnf install -y nmap-ncat"), "{}", fix);
+        });
+    }
+}
@@ -1,11 +1,14 @@
 use serde::{Deserialize, Serialize};
 
+pub mod healer;
 pub mod orchestrator;
 pub mod pkg;
+pub mod pkg_map;
 pub mod status;
 pub mod ast;
 pub mod pipeline;
 pub mod template;
+pub mod update;
 pub mod virt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,9 +1,16 @@
+use crate::executor::update::{self, Host, UpdateOptions, UpdateReport};
 use crate::knowledge::KnowledgeBase;
+use crate::remote::rc::{self, RcClient};
 use crate::remote::rclone::RcloneProvider;
 use crate::remote::RemoteProvider;
 use crate::safety::{confirm_action, SafetyRegistry};
+use crate::storage::db::with_db;
+use crate::system::storage::SmartStorage;
+use crate::storage::events::{Event, EventKind};
 use log::info;
+use serde_json::json;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[allow(dead_code, unused_variables)]
 pub async fn execute_task(provider: Arc<dyn RemoteProvider>, cmd: &str) -> Result<String, String> {
@@ -31,7 +38,7 @@ pub async fn sync_cloud_storage(
         return Err("Sync cancelled by user".to_string());
     }
 
-    if let Ok(output) = provider.execute_rclone(vec!["size", "--json", source]) {
+    if let Ok(output) = provider.query_rclone(vec!["size", "--json", source]) {
         if let Ok(size_info) = serde_json::from_str::<serde_json::Value>(&output) {
             let total_bytes = size_info["bytes"].as_u64().unwrap_or(0);
             let limit_gb = 1; // Default 1GB limit as per blueprint
@@ -44,14 +51,134 @@ pub async fn sync_cloud_storage(
         }
     }
 
-    provider.sync(source, destination).await
+    // Prefer the rc API (progress, Ctrl-C cancels the job); fall back to the CLI
+    let target = provider.qualify(destination);
+    let rc_config = SmartStorage::new().rc_config(&target);
+    let rc_outcome = match RcClient::connect().await {
+        Ok(rc) => Ok(rc::sync_with_progress(&rc, source, &target, rc_config).await),
+        Err(e) => Err(e),
+    };
+    let (result, stats) = match rc_outcome {
+        Ok(Ok(outcome)) => {
+            let result = if outcome.success {
+                Ok(())
+            } else if outcome.cancelled {
+                Err("Sync cancelled".to_string())
+            } else {
+                Err(outcome.error.unwrap_or_else(|| "rclone job failed".to_string()))
+            };
+            (result, Some(outcome.stats))
+        }
+        Ok(Err(e)) => (Err(e), None),
+        Err(e) => {
+            info!("rclone rc unavailable ({}), using the CLI", e);
+            (provider.sync(source, destination).await, None)
+        }
+    };
+
+    // Record the sync in the event log
+    let event = Event::new(
+        EventKind::Sync,
+        &format!("rclone sync {} {}", source, target),
+    )
+    .with_result(result.is_ok(), None)
+    .with_payload(json!({
+        "direction": "sync",
+        "source": source,
+        "remote": provider.remote_name,
+        "destination": target,
+        "error": result.as_ref().err(),
+        "stats": stats.as_ref().map(|s| s.to_json()),
+    }));
+    let edge_source = source.to_string();
+    let bytes = stats.map(|s| s.bytes).unwrap_or(0);
+    let error = result.as_ref().err().cloned();
+    let _ = with_db(move |db| {
+        db.record_sync_edge(&edge_source, &target, "sync", error.is_none(), bytes, error.as_deref())?;
+        db.record_event(&event)
+    })
+    .await;
+
+    result
 }
 
-#[allow(dead_code)]
-pub async fn update_all(_kb: &KnowledgeBase) {
-    // ... existing fleet update logic can be refactored to use RemoteProvider ...
-    // Keeping existing for BC for now, but added generic hooks above.
+/// How long a rebooted host gets to answer SSH again.
+const REBOOT_WAIT_SECS: u64 = 300;
+
+/// Rolls the update across `hosts` one at a time. A host that fails pre-flight, the
+/// upgrade, or the health check afterwards (unreachable, or systemd units failing that
+/// were fine before) stops the roll; the remaining hosts are left untouched.
+pub async fn update_all(kb: &KnowledgeBase, hosts: &[String], opts: &UpdateOptions) -> Vec<UpdateReport> {
+    let mut reports = Vec::new();
+    for (i, name) in hosts.iter().enumerate() {
+        println!("\n🌐 [{}/{}] {}", i + 1, hosts.len(), name);
+        let halted = match update_one(kb, name, opts).await {
+            Ok(report) => {
+                if !opts.dry_run {
+                    report.print();
+                }
+                reports.push(report);
+                None
+            }
+            Err(e) => Some(e),
+        };
+        if let Some(e) = halted {
+            println!("❌ {}", e);
+            let rest = &hosts[i + 1..];
+            if !rest.is_empty() {
+                println!("⏸️  Roll halted; not updated: {}", rest.join(", "));
+            }
+            break;
+        }
+    }
+    reports
 }
+
+async fn update_one(kb: &KnowledgeBase, name: &str, opts: &UpdateOptions) -> Result<UpdateReport, String> {
+    let entry = kb
+        .get(name)
+        .ok_or_else(|| format!("Target '{}' not found in Knowledge Base.", name))?;
+    let host = Host::remote(name, entry);
+
+    let before = host.health();
+    if !before.reachable {
+        return Err(format!("{} is unreachable over SSH.", name));
+    }
+    let report = update::update_host(&host, opts)?;
+    if !report.success {
+        report.print();
+        return Err(format!("Update failed on {}.", name));
+    }
+    if opts.dry_run {
+        return Ok(report);
+    }
+
+    if report.reboot.is_some() && opts.reboot {
+        println!("🔁 Rebooting {}...", name);
+        host.reboot();
+        tokio::time::sleep(Duration::from_secs(15)).await;
+        let deadline = Instant::now() + Duration::from_secs(REBOOT_WAIT_SECS);
+        while !host.health().reachable {
+            if Instant::now() > deadline {
+                return Err(format!("{} did not come back within {}s of the reboot.", name, REBOOT_WAIT_SECS));
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    let after = host.health();
+    if !after.reachable {
+        return Err(format!("{} stopped answering after the update.", name));
+    }
+    let failures = after.new_failures(&before);
+    if !failures.is_empty() {
+        report.print();
+        return Err(format!("{} is unhealthy after the update; failed units: {}", name, failures.join(", ")));
+    }
+    println!("❤️  {} is healthy.", name);
+    Ok(report)
+}
+
 pub async fn sync_all_cloud(
     ctx: &crate::context::SystemContext,
     primary_remote: Option<String>,
@@ -73,9 +200,8 @@ pub async fn sync_all_cloud(
     Ok(())
 }
 pub async fn summarize_session(session_id: i64) -> Result<String, String> {
-    let db = crate::storage::db::Database::new().map_err(|e| e.to_string())?;
-    let tasks = db
-        .get_session_tasks(session_id)
+    let tasks = with_db(move |db| db.get_session_tasks(session_id))
+        .await
         .map_err(|e| e.to_string())?;
 
     if tasks.is_empty() {
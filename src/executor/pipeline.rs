@@ -1,13 +1,20 @@
-use crate::executor::ast::CommandAst;
+use crate::executor::ast::{CommandAst, ShellOptIn};
 use crate::executor::ExecuteResult;
+use crate::storage::db::with_db;
+use crate::storage::events::{new_uid, Event, EventKind};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Intent {
     pub tool: String,
     pub operation: String,
     pub target: Option<String>,
+    /// Named values beyond the target (`path`, `remote`, `package`, ...).
+    #[serde(default)]
+    pub slots: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,58 +59,114 @@ pub struct PipelineOrchestrator {
     pub vee: Box<dyn VirtualExecutionEngine + Send + Sync>,
     pub risk_evaluator: Box<dyn RiskEvaluator + Send + Sync>,
     pub execution_provider: Box<dyn ExecutionProvider + Send + Sync>,
+    /// Pipeline or redirection appended to the built command; without it the
+    /// command runs as a plain argv, never through a shell.
+    pub shell: Option<ShellOptIn>,
 }
 
 impl PipelineOrchestrator {
     pub async fn run_pipeline(&self, input: &str) -> anyhow::Result<ExecuteResult> {
+        // Every stage is recorded in the event log under one correlation id
+        let correlation_id = new_uid();
+        let record = |event: Event| {
+            let event = event.with_correlation(&correlation_id);
+            async move {
+                let _ = with_db(move |db| db.record_event(&event)).await;
+            }
+        };
+
+        record(Event::new(EventKind::Request, input)).await;
+
         // 1. Intent Resolution
         let intent = self.intent_resolver.resolve(input).await?;
-        let intent_str = format!("{:?}", intent);
+        record(
+            Event::new(EventKind::Resolution, input)
+                .with_payload(serde_json::to_value(&intent).unwrap_or_default()),
+        ).await;
         
         // 2. Template Building
         let mut ast = self.template_builder.build(&intent)?;
         
         // 3. AI Option Generation
         self.option_generator.generate_options(&mut ast).await?;
+        if let Some(opt_in) = &self.shell {
+            println!("🐚 Running through sh -c with `{}` ({})", opt_in.fragment, opt_in.reason);
+            ast.shell = Some(opt_in.clone());
+        }
         let final_cmd = ast.to_shell_command();
+
+        // A project's allowed_commands apply to pipeline commands as well
+        if let Some(project) = crate::system::project::ProjectRoot::current() {
+            if let Err(step) = project.profile.check_command(&final_cmd) {
+                anyhow::bail!(
+                    "'{}' is not allowed by the allowed_commands of {} ({}).",
+                    step,
+                    project.name,
+                    crate::system::project::PROFILE_FILE
+                );
+            }
+        }
         
         // 4. Virtual Execution (Simulation)
         let sim_log = self.vee.simulate(&ast)?;
-        let sim_log_str = format!("{:?}", sim_log);
-        let risk_score = sim_log.risk_score;
         
         // 5. Risk Evaluation
-        if !self.risk_evaluator.evaluate(&sim_log) {
-            // Log rejection
-            if let Ok(db) = crate::storage::db::Database::new() {
-                let _ = db.log_decision_lineage(input, &intent_str, &final_cmd, &sim_log_str, risk_score, "DENIED");
-            }
+        let approved = self.risk_evaluator.evaluate(&sim_log);
+        record(
+            Event::new(EventKind::Simulation, &final_cmd).with_payload(json!({
+                "sim_log": sim_log,
+                "approved": approved,
+                "argv": ast.argv(),
+                "shell": ast.shell,
+            })),
+        ).await;
+        if !approved {
             anyhow::bail!("Execution denied by Risk Evaluation Engine.");
         }
         
         // 6. Execution
+        let started = std::time::Instant::now();
         let result = self.execution_provider.execute(&ast).await?;
         
         // 7. Decision Lineage Persistence
-        if let Ok(db) = crate::storage::db::Database::new() {
-            let res_str = format!("Success: {}, ExitCode: {:?}", result.success, result.exit_code);
-            let _ = db.log_decision_lineage(input, &intent_str, &final_cmd, &sim_log_str, risk_score, &res_str);
-        }
+        record(
+            Event::new(EventKind::Execution, &final_cmd)
+                .with_result(result.success, result.exit_code)
+                .with_duration(started.elapsed())
+                .with_payload(json!({
+                    "project_name": crate::system::project::current_name(),
+                    "stdout": result.stdout,
+                    "stderr": result.stderr,
+                    "shell": ast.shell,
+                })),
+        ).await;
         
         Ok(result)
     }
 }
 
+/// The process for `ast`: its argv run directly, or `sh -c` when it carries a shell opt-in.
+pub fn command_for(ast: &CommandAst) -> anyhow::Result<std::process::Command> {
+    if ast.shell.is_some() {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.arg("-c").arg(ast.to_shell_command());
+        return Ok(cmd);
+    }
+    let argv = ast.argv();
+    if argv.is_empty() {
+        anyhow::bail!("Command for '{} {}' has an empty argv.", ast.tool, ast.operation);
+    }
+    let mut cmd = std::process::Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    Ok(cmd)
+}
+
 pub struct LocalExecutionProvider;
 
 #[async_trait]
 impl ExecutionProvider for LocalExecutionProvider {
     async fn execute(&self, ast: &CommandAst) -> anyhow::Result<ExecuteResult> {
-        let cmd_str = ast.to_shell_command();
-        let output = std::process::Command::new("sh")
-            .arg("-c")
-            .arg(&cmd_str)
-            .output()?;
+        let output = command_for(ast)?.output()?;
         
         Ok(ExecuteResult {
             success: output.status.success(),
@@ -122,6 +185,7 @@ pub struct RemoteExecutionProvider {
 #[async_trait]
 impl ExecutionProvider for RemoteExecutionProvider {
     async fn execute(&self, ast: &CommandAst) -> anyhow::Result<ExecuteResult> {
+        // ssh hands the remote side one string, so the argv goes over quoted
         let cmd_str = ast.to_shell_command();
         let output = crate::connection::ssh::SshConnection::execute_remote_async(&self.ip, &cmd_str).await
             .map_err(|e| anyhow::anyhow!("SSH Execution Failed: {}", e))?;
@@ -134,3 +198,57 @@ impl ExecutionProvider for RemoteExecutionProvider {
         })
     }
 }
+
+/// Runs the command inside a container with `<runtime> exec <name> <argv>`,
+/// or `sh -c` when the command carries a shell opt-in.
+pub struct ContainerExecutionProvider {
+    pub exec: crate::system::containers::ContainerExec,
+}
+
+#[async_trait]
+impl ExecutionProvider for ContainerExecutionProvider {
+    async fn execute(&self, ast: &CommandAst) -> anyhow::Result<ExecuteResult> {
+        let output = if ast.shell.is_some() {
+            self.exec.output(&ast.to_shell_command())
+        } else {
+            self.exec.output_argv(&ast.argv())
+        };
+        let output = output
+            .map_err(|e| anyhow::anyhow!("Container Execution Failed: {}", e))?;
+
+        Ok(ExecuteResult {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            exit_code: output.status.code(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::ast::Arg;
+
+    fn ls(path: &str) -> CommandAst {
+        let mut ast = CommandAst::new("ls", "list");
+        ast.args = vec![Arg::Word("ls".into()), Arg::Options, Arg::Word(path.into())];
+        ast.options = vec!["-la".into()];
+        ast
+    }
+
+    #[test]
+    fn runs_argv_directly_unless_shell_is_opted_in() {
+        let cmd = command_for(&ls("/tmp/a; rm -rf ~")).unwrap();
+        assert_eq!(cmd.get_program(), "ls");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, ["-la", "/tmp/a; rm -rf ~"]);
+
+        let cmd = command_for(&ls("/tmp").with_shell("| wc -l", "test")).unwrap();
+        assert_eq!(cmd.get_program(), "sh");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, ["-c", "ls -la /tmp | wc -l"]);
+
+        assert!(command_for(&CommandAst::new("ls", "list")).is_err());
+    }
+}
@@ -1,54 +1,241 @@
 use crate::context::SystemContext;
+use crate::executor::ast::quote;
+use crate::executor::pkg_map::PackageMap;
+use crate::system::probe::{self, Probe};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PackageSystem {
     Apt,
     Dnf,
     Pacman,
-    #[allow(dead_code)]
+    Zypper,
+    Apk,
     Flatpak,
-    #[allow(dead_code)]
-    Unknown,
-}
-
-pub trait PackageManager {
-    fn install(&self, package: &str) -> String;
-    #[allow(dead_code)]
-    fn update(&self) -> String;
-    #[allow(dead_code)]
-    fn remove(&self, package: &str) -> String;
-    #[allow(dead_code)]
-    fn search(&self, query: &str) -> String;
+    Snap,
+    Nix,
+}
+
+impl PackageSystem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PackageSystem::Apt => "apt",
+            PackageSystem::Dnf => "dnf",
+            PackageSystem::Pacman => "pacman",
+            PackageSystem::Zypper => "zypper",
+            PackageSystem::Apk => "apk",
+            PackageSystem::Flatpak => "flatpak",
+            PackageSystem::Snap => "snap",
+            PackageSystem::Nix => "nix",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "apt" => Some(PackageSystem::Apt),
+            "dnf" => Some(PackageSystem::Dnf),
+            "pacman" => Some(PackageSystem::Pacman),
+            "zypper" => Some(PackageSystem::Zypper),
+            "apk" => Some(PackageSystem::Apk),
+            "flatpak" => Some(PackageSystem::Flatpak),
+            "snap" => Some(PackageSystem::Snap),
+            "nix" | "nix-env" | "nix-profile" => Some(PackageSystem::Nix),
+            _ => None,
+        }
+    }
+
+    /// Managers that live next to the distro's own one.
+    pub const UNIVERSAL: [PackageSystem; 3] = [PackageSystem::Flatpak, PackageSystem::Snap, PackageSystem::Nix];
+
+    /// Whether the manager answers on this host (`flatpak --version`, ...).
+    pub fn is_present(&self) -> bool {
+        let (program, arg) = match self {
+            PackageSystem::Apt => ("apt-get", "--version"),
+            PackageSystem::Dnf => ("dnf", "--version"),
+            PackageSystem::Pacman => ("pacman", "--version"),
+            PackageSystem::Zypper => ("zypper", "--version"),
+            PackageSystem::Apk => ("apk", "--version"),
+            PackageSystem::Flatpak => ("flatpak", "--version"),
+            PackageSystem::Snap => ("snap", "version"),
+            PackageSystem::Nix => ("nix-env", "--version"),
+        };
+        Probe::new(program)
+            .arg(arg)
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    pub fn backend(&self) -> Box<dyn PackageManager> {
+        match self {
+            PackageSystem::Apt => Box::new(Apt),
+            PackageSystem::Dnf => Box::new(Dnf),
+            PackageSystem::Pacman => Box::new(Pacman),
+            PackageSystem::Zypper => Box::new(Zypper),
+            PackageSystem::Apk => Box::new(Apk),
+            PackageSystem::Flatpak => Box::new(Flatpak),
+            PackageSystem::Snap => Box::new(Snap),
+            PackageSystem::Nix => Box::new(Nix::detect()),
+        }
+    }
+}
+
+/// One package manager invocation, kept as argv until it is run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PkgCommand {
+    pub argv: Vec<String>,
+    pub sudo: bool,
+}
+
+impl PkgCommand {
+    fn user(argv: &[&str]) -> Self {
+        PkgCommand {
+            argv: argv.iter().map(|a| a.to_string()).collect(),
+            sudo: false,
+        }
+    }
+
+    fn root(argv: &[&str]) -> Self {
+        PkgCommand {
+            sudo: true,
+            ..Self::user(argv)
+        }
+    }
+
+    fn arg(mut self, arg: &str) -> Self {
+        self.argv.push(arg.to_string());
+        self
+    }
+
+    pub fn to_shell(&self) -> String {
+        let cmd = self.argv.iter().map(|a| quote(a)).collect::<Vec<_>>().join(" ");
+        if self.sudo {
+            prepend_auth_sock(format!("sudo {}", cmd))
+        } else {
+            cmd
+        }
+    }
+
+    /// For a host reached over SSH: no local agent socket, and sudo must not prompt.
+    pub fn to_remote_shell(&self) -> String {
+        let cmd = self.argv.iter().map(|a| quote(a)).collect::<Vec<_>>().join(" ");
+        if self.sudo {
+            format!("sudo -n {}", cmd)
+        } else {
+            cmd
+        }
+    }
+
+    /// Steps of a multi-command action (`apt update && apt upgrade`) as one shell line.
+    pub fn join(steps: &[PkgCommand]) -> String {
+        steps.iter().map(|s| s.to_shell()).collect::<Vec<_>>().join(" && ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PackageSummary {
+    pub name: String,
+    pub version: Option<String>,
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: Option<String>,
+    pub summary: String,
+    /// Repository, remote or channel the package comes from.
+    pub source: Option<String>,
+    /// Installed version, `None` when not installed.
+    pub installed: Option<String>,
+}
+
+pub trait PackageManager: Send + Sync {
+    /// Install argv for a name that already passed `check_package_name`; use `install`.
+    fn install_command(&self, package: &str) -> PkgCommand;
+    fn update(&self) -> Vec<PkgCommand>;
+    /// Remove argv for a name that already passed `check_package_name`; use `remove`.
+    fn remove_command(&self, package: &str) -> PkgCommand;
+    fn search(&self, query: &str) -> Vec<PackageSummary>;
+    /// Repository metadata for `package`, `None` when no repository has it.
+    fn info(&self, package: &str) -> Option<PackageInfo>;
+    /// Installed version of `package`, `None` when it is not installed.
+    fn installed_version(&self, package: &str) -> Option<String>;
+    /// Package that ships `command`, from the manager's file index (`apt-file`,
+    /// `dnf provides`, `pkgfile`, ...) when it has one.
+    fn provides(&self, _command: &str) -> Option<String> {
+        None
+    }
     fn name(&self) -> &str;
     fn kind(&self) -> PackageSystem;
+
+    fn install(&self, package: &str) -> Result<PkgCommand, String> {
+        check_package_name(package)?;
+        Ok(self.install_command(package))
+    }
+
+    fn remove(&self, package: &str) -> Result<PkgCommand, String> {
+        check_package_name(package)?;
+        Ok(self.remove_command(package))
+    }
+}
+
+/// Rejects names the package manager would read as an option (`-oDpkg::Pre-Invoke=...`)
+/// or that are not a single word, whether typed, mapped or suggested.
+pub fn check_package_name(package: &str) -> Result<(), String> {
+    if package.is_empty() {
+        return Err("empty package name".to_string());
+    }
+    if package.starts_with('-') {
+        return Err(format!("'{}' looks like an option, not a package", package));
+    }
+    if package.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(format!("'{}' is not a valid package name", package.escape_default()));
+    }
+    Ok(())
 }
 
-fn normalize(pkg: &str, kind: &PackageSystem) -> String {
-    match (pkg, kind) {
-        ("obs", PackageSystem::Apt) => "obs-studio".to_string(),
-        ("docker", PackageSystem::Apt) => "docker.io".to_string(), // Debian legacy
-        ("docker", PackageSystem::Dnf) => "docker-ce".to_string(),
-        // Add more known aliases here
-        _ => pkg.to_string(),
+/// Stdout of a read-only query, `None` when it fails.
+fn query(argv: &[&str]) -> Option<String> {
+    let output = Probe::new(argv[0]).args(&argv[1..]).output().ok()?;
+    if !output.status.success() {
+        return None;
     }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
-#[allow(dead_code)]
 pub struct Apt;
 impl PackageManager for Apt {
-    fn install(&self, package: &str) -> String {
-        prepend_auth_sock(format!(
-            "sudo apt install -y {}",
-            normalize(package, &self.kind())
-        ))
+    fn install_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["apt", "install", "-y"]).arg(package)
+    }
+    fn update(&self) -> Vec<PkgCommand> {
+        vec![
+            PkgCommand::root(&["apt", "update"]),
+            PkgCommand::root(&["apt", "upgrade", "-y"]),
+        ]
+    }
+    fn remove_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["apt", "remove", "-y"]).arg(package)
     }
-    fn update(&self) -> String {
-        prepend_auth_sock("sudo apt update && sudo apt upgrade -y".to_string())
+    fn search(&self, query_str: &str) -> Vec<PackageSummary> {
+        query(&["apt-cache", "search", "--names-only", query_str])
+            .map(|out| parse_dash_list(&out))
+            .unwrap_or_default()
     }
-    fn remove(&self, package: &str) -> String {
-        prepend_auth_sock(format!("sudo apt remove -y {}", package))
+    fn info(&self, package: &str) -> Option<PackageInfo> {
+        let mut info = parse_deb_control(&query(&["apt-cache", "show", package])?)?;
+        info.installed = self.installed_version(package);
+        Some(info)
     }
-    fn search(&self, query: &str) -> String {
-        format!("apt search {}", query)
+    fn installed_version(&self, package: &str) -> Option<String> {
+        parse_dpkg_status(&query(&["dpkg-query", "-W", "-f=${Status}\t${Version}\n", package])?)
+    }
+    fn provides(&self, command: &str) -> Option<String> {
+        let pattern = format!("^/(usr/)?s?bin/{}$", regex::escape(command));
+        parse_apt_file(&query(&["apt-file", "search", "--regexp", &pattern])?)
     }
     fn name(&self) -> &str {
         "apt (Debian/Ubuntu)"
@@ -58,23 +245,32 @@ impl PackageManager for Apt {
     }
 }
 
-#[allow(dead_code)]
 pub struct Dnf;
 impl PackageManager for Dnf {
-    fn install(&self, package: &str) -> String {
-        prepend_auth_sock(format!(
-            "sudo dnf install -y {}",
-            normalize(package, &self.kind())
-        ))
+    fn install_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["dnf", "install", "-y"]).arg(package)
+    }
+    fn update(&self) -> Vec<PkgCommand> {
+        vec![PkgCommand::root(&["dnf", "update", "-y"])]
+    }
+    fn remove_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["dnf", "remove", "-y"]).arg(package)
     }
-    fn update(&self) -> String {
-        prepend_auth_sock("sudo dnf update -y".to_string())
+    fn search(&self, query_str: &str) -> Vec<PackageSummary> {
+        query(&["dnf", "search", "-q", query_str])
+            .map(|out| parse_dnf_search(&out))
+            .unwrap_or_default()
     }
-    fn remove(&self, package: &str) -> String {
-        prepend_auth_sock(format!("sudo dnf remove -y {}", package))
+    fn info(&self, package: &str) -> Option<PackageInfo> {
+        let mut info = parse_field_info(&query(&["dnf", "info", "-q", package])?)?;
+        info.installed = self.installed_version(package);
+        Some(info)
     }
-    fn search(&self, query: &str) -> String {
-        format!("dnf search {}", query)
+    fn installed_version(&self, package: &str) -> Option<String> {
+        rpm_version(package)
+    }
+    fn provides(&self, command: &str) -> Option<String> {
+        parse_dnf_provides(&query(&["dnf", "provides", "-q", &format!("*bin/{}", command)])?)
     }
     fn name(&self) -> &str {
         "dnf (Fedora/RHEL)"
@@ -84,23 +280,37 @@ impl PackageManager for Dnf {
     }
 }
 
-#[allow(dead_code)]
 pub struct Pacman;
 impl PackageManager for Pacman {
-    fn install(&self, package: &str) -> String {
-        prepend_auth_sock(format!(
-            "sudo pacman -S --noconfirm {}",
-            normalize(package, &self.kind())
-        ))
+    fn install_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["pacman", "-S", "--noconfirm"]).arg(package)
+    }
+    fn update(&self) -> Vec<PkgCommand> {
+        vec![PkgCommand::root(&["pacman", "-Syu", "--noconfirm"])]
+    }
+    fn remove_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["pacman", "-Rns", "--noconfirm"]).arg(package)
     }
-    fn update(&self) -> String {
-        prepend_auth_sock("sudo pacman -Syu --noconfirm".to_string())
+    fn search(&self, query_str: &str) -> Vec<PackageSummary> {
+        query(&["pacman", "-Ss", query_str])
+            .map(|out| parse_pacman_search(&out))
+            .unwrap_or_default()
     }
-    fn remove(&self, package: &str) -> String {
-        prepend_auth_sock(format!("sudo pacman -Rns --noconfirm {}", package))
+    fn info(&self, package: &str) -> Option<PackageInfo> {
+        let mut info = parse_field_info(&query(&["pacman", "-Si", package])?)?;
+        info.installed = self.installed_version(package);
+        Some(info)
     }
-    fn search(&self, query: &str) -> String {
-        format!("pacman -Ss {}", query)
+    fn installed_version(&self, package: &str) -> Option<String> {
+        // `pacman -Q htop` -> `htop 3.3.0-1`
+        let out = query(&["pacman", "-Q", package])?;
+        out.split_whitespace().nth(1).map(str::to_string)
+    }
+    fn provides(&self, command: &str) -> Option<String> {
+        // `pkgfile -b htop` -> `extra/htop`
+        let out = query(&["pkgfile", "-b", command])?;
+        let line = out.lines().next()?.trim();
+        line.rsplit('/').next().filter(|p| !p.is_empty()).map(str::to_string)
     }
     fn name(&self) -> &str {
         "pacman (Arch)"
@@ -110,20 +320,116 @@ impl PackageManager for Pacman {
     }
 }
 
-#[allow(dead_code)]
+pub struct Zypper;
+impl PackageManager for Zypper {
+    fn install_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["zypper", "--non-interactive", "install"]).arg(package)
+    }
+    fn update(&self) -> Vec<PkgCommand> {
+        vec![
+            PkgCommand::root(&["zypper", "--non-interactive", "refresh"]),
+            PkgCommand::root(&["zypper", "--non-interactive", "update"]),
+        ]
+    }
+    fn remove_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["zypper", "--non-interactive", "remove"]).arg(package)
+    }
+    fn search(&self, query_str: &str) -> Vec<PackageSummary> {
+        query(&["zypper", "--non-interactive", "--quiet", "search", query_str])
+            .map(|out| parse_zypper_search(&out))
+            .unwrap_or_default()
+    }
+    fn info(&self, package: &str) -> Option<PackageInfo> {
+        let mut info = parse_field_info(&query(&["zypper", "--non-interactive", "info", package])?)?;
+        info.installed = self.installed_version(package);
+        Some(info)
+    }
+    fn installed_version(&self, package: &str) -> Option<String> {
+        rpm_version(package)
+    }
+    fn provides(&self, command: &str) -> Option<String> {
+        let path = format!("/usr/bin/{}", command);
+        let out = query(&["zypper", "--non-interactive", "--quiet", "search", "--provides", "--match-exact", &path])?;
+        parse_zypper_search(&out).into_iter().next().map(|p| p.name)
+    }
+    fn name(&self) -> &str {
+        "zypper (openSUSE/SLES)"
+    }
+    fn kind(&self) -> PackageSystem {
+        PackageSystem::Zypper
+    }
+}
+
+pub struct Apk;
+impl PackageManager for Apk {
+    fn install_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["apk", "add"]).arg(package)
+    }
+    fn update(&self) -> Vec<PkgCommand> {
+        vec![PkgCommand::root(&["apk", "update"]), PkgCommand::root(&["apk", "upgrade"])]
+    }
+    fn remove_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["apk", "del"]).arg(package)
+    }
+    fn search(&self, query_str: &str) -> Vec<PackageSummary> {
+        query(&["apk", "search", "-v", query_str])
+            .map(|out| parse_apk_search(&out))
+            .unwrap_or_default()
+    }
+    fn info(&self, package: &str) -> Option<PackageInfo> {
+        let mut info = parse_apk_info(&query(&["apk", "info", "-d", package])?)?;
+        info.installed = self.installed_version(package);
+        Some(info)
+    }
+    fn installed_version(&self, package: &str) -> Option<String> {
+        parse_apk_installed(&query(&["apk", "list", "--installed", package])?, package)
+    }
+    fn provides(&self, command: &str) -> Option<String> {
+        // `apk search cmd:htop` -> `htop-3.3.0-r0`
+        let out = query(&["apk", "search", &format!("cmd:{}", command)])?;
+        out.lines().find_map(|l| split_name_version(l.trim())).map(|(name, _)| name)
+    }
+    fn name(&self) -> &str {
+        "apk (Alpine)"
+    }
+    fn kind(&self) -> PackageSystem {
+        PackageSystem::Apk
+    }
+}
+
 pub struct Flatpak;
 impl PackageManager for Flatpak {
-    fn install(&self, package: &str) -> String {
-        format!("flatpak install -y flathub {}", package)
+    fn install_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::user(&["flatpak", "install", "-y", "--noninteractive", "flathub"]).arg(package)
+    }
+    fn update(&self) -> Vec<PkgCommand> {
+        vec![PkgCommand::user(&["flatpak", "update", "-y", "--noninteractive"])]
+    }
+    fn remove_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::user(&["flatpak", "uninstall", "-y", "--noninteractive"]).arg(package)
     }
-    fn update(&self) -> String {
-        "flatpak update -y".to_string()
+    fn search(&self, query_str: &str) -> Vec<PackageSummary> {
+        query(&["flatpak", "search", "--columns=application,version,name,description,remotes", query_str])
+            .map(|out| parse_flatpak_search(&out).into_iter().map(|(summary, _, _)| summary).collect())
+            .unwrap_or_default()
     }
-    fn remove(&self, package: &str) -> String {
-        format!("flatpak uninstall -y {}", package)
+    fn info(&self, package: &str) -> Option<PackageInfo> {
+        let out = query(&["flatpak", "search", "--columns=application,version,name,description,remotes", package])?;
+        let (summary, _, remote) = parse_flatpak_search(&out)
+            .into_iter()
+            .find(|(s, display, _)| flatpak_matches(&s.name, display, package))?;
+        let installed = self.installed_version(&summary.name);
+        Some(PackageInfo {
+            name: summary.name,
+            version: summary.version,
+            summary: summary.summary,
+            source: remote,
+            installed,
+        })
     }
-    fn search(&self, query: &str) -> String {
-        format!("flatpak search {}", query)
+    fn installed_version(&self, package: &str) -> Option<String> {
+        let out = query(&["flatpak", "list", "--app", "--columns=application,version,name"])?;
+        parse_flatpak_installed(&out, package)
     }
     fn name(&self) -> &str {
         "flatpak (Universal)"
@@ -133,18 +439,745 @@ impl PackageManager for Flatpak {
     }
 }
 
-pub fn detect(ctx: &SystemContext) -> Box<dyn PackageManager> {
-    match ctx.pkg_manager.as_str() {
-        "apt" => Box::new(Apt),
-        "dnf" => Box::new(Dnf),
-        "pacman" => Box::new(Pacman),
-        _ => Box::new(Apt), // Default fallback
+pub struct Snap;
+impl PackageManager for Snap {
+    fn install_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["snap", "install"]).arg(package)
+    }
+    fn update(&self) -> Vec<PkgCommand> {
+        vec![PkgCommand::root(&["snap", "refresh"])]
+    }
+    fn remove_command(&self, package: &str) -> PkgCommand {
+        PkgCommand::root(&["snap", "remove"]).arg(package)
+    }
+    fn search(&self, query_str: &str) -> Vec<PackageSummary> {
+        query(&["snap", "find", query_str])
+            .map(|out| parse_snap_find(&out))
+            .unwrap_or_default()
+    }
+    fn info(&self, package: &str) -> Option<PackageInfo> {
+        parse_snap_info(&query(&["snap", "info", package])?)
+    }
+    fn installed_version(&self, package: &str) -> Option<String> {
+        // `snap list htop` -> header, then `htop  3.3.0  4  latest/stable  maxiberta  -`
+        let out = query(&["snap", "list", package])?;
+        out.lines()
+            .skip(1)
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+            .find(|cols| cols.first() == Some(&package))
+            .and_then(|cols| cols.get(1).map(|v| v.to_string()))
+    }
+    fn name(&self) -> &str {
+        "snap (Universal)"
+    }
+    fn kind(&self) -> PackageSystem {
+        PackageSystem::Snap
+    }
+}
+
+/// Nix through `nix profile` when the user profile is flake-based, `nix-env` otherwise.
+pub struct Nix {
+    profile: bool,
+}
+
+const NIX_FLAGS: [&str; 2] = ["--extra-experimental-features", "nix-command flakes"];
+
+impl Nix {
+    pub fn detect() -> Self {
+        let profile = dirs::home_dir()
+            .map(|home| probe::exists(home.join(".nix-profile/manifest.json")))
+            .unwrap_or(false);
+        Nix { profile }
+    }
+
+    fn nix(args: &[&str]) -> Vec<String> {
+        let mut argv = vec!["nix".to_string()];
+        argv.extend(NIX_FLAGS.iter().map(|a| a.to_string()));
+        argv.extend(args.iter().map(|a| a.to_string()));
+        argv
+    }
+}
+
+impl PackageManager for Nix {
+    fn install_command(&self, package: &str) -> PkgCommand {
+        if self.profile {
+            PkgCommand {
+                argv: Self::nix(&["profile", "install", &format!("nixpkgs#{}", package)]),
+                sudo: false,
+            }
+        } else {
+            PkgCommand::user(&["nix-env", "-iA"]).arg(&format!("nixpkgs.{}", package))
+        }
+    }
+    fn update(&self) -> Vec<PkgCommand> {
+        if self.profile {
+            vec![PkgCommand {
+                argv: Self::nix(&["profile", "upgrade", ".*"]),
+                sudo: false,
+            }]
+        } else {
+            vec![PkgCommand::user(&["nix-channel", "--update"]), PkgCommand::user(&["nix-env", "-u"])]
+        }
+    }
+    fn remove_command(&self, package: &str) -> PkgCommand {
+        if self.profile {
+            PkgCommand {
+                argv: Self::nix(&["profile", "remove", package]),
+                sudo: false,
+            }
+        } else {
+            PkgCommand::user(&["nix-env", "-e"]).arg(package)
+        }
+    }
+    fn search(&self, query_str: &str) -> Vec<PackageSummary> {
+        let argv = Self::nix(&["search", "nixpkgs", query_str, "--json"]);
+        let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+        query(&argv).map(|out| parse_nix_search(&out)).unwrap_or_default()
+    }
+    fn info(&self, package: &str) -> Option<PackageInfo> {
+        let pattern = format!("^{}$", regex::escape(package));
+        let found = self.search(&pattern).into_iter().find(|p| p.name == package)?;
+        Some(PackageInfo {
+            name: found.name,
+            version: found.version,
+            summary: found.summary,
+            source: Some("nixpkgs".to_string()),
+            installed: self.installed_version(package),
+        })
+    }
+    fn installed_version(&self, package: &str) -> Option<String> {
+        if self.profile {
+            let argv = Self::nix(&["profile", "list", "--json"]);
+            let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+            parse_nix_profile(&query(&argv)?, package)
+        } else {
+            query(&["nix-env", "-q"])?
+                .lines()
+                .filter_map(|l| split_name_version(l.trim()))
+                .find(|(name, _)| name == package)
+                .map(|(_, version)| version)
+        }
+    }
+    fn provides(&self, command: &str) -> Option<String> {
+        let path = format!("/bin/{}", command);
+        parse_nix_locate(&query(&["nix-locate", "--top-level", "--whole-name", "--at-root", &path])?)
+    }
+    fn name(&self) -> &str {
+        if self.profile {
+            "nix profile (Universal)"
+        } else {
+            "nix-env (Universal)"
+        }
+    }
+    fn kind(&self) -> PackageSystem {
+        PackageSystem::Nix
+    }
+}
+
+/// The distro's own package manager.
+pub fn detect(ctx: &SystemContext) -> Option<Box<dyn PackageManager>> {
+    PackageSystem::parse(&ctx.pkg_manager).map(|kind| kind.backend())
+}
+
+/// Every package manager active on this host, the distro's first.
+pub fn detect_all(ctx: &SystemContext) -> Vec<Box<dyn PackageManager>> {
+    let mut names = ctx.pkg_managers.clone();
+    if names.is_empty() {
+        names.push(ctx.pkg_manager.clone());
+    }
+    names
+        .iter()
+        .filter_map(|name| PackageSystem::parse(name))
+        .map(|kind| kind.backend())
+        .collect()
+}
+
+/// Names of the active managers for the system context: `system` (from os-release)
+/// followed by the universal ones that answer.
+pub fn active_managers(system: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    if PackageSystem::parse(system).is_some() {
+        names.push(system.to_string());
+    }
+    for kind in PackageSystem::UNIVERSAL {
+        if kind.is_present() {
+            names.push(kind.as_str().to_string());
+        }
+    }
+    names
+}
+
+/// A package that ships a command, and where that answer came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provider {
+    pub package: String,
+    pub via: &'static str,
+}
+
+/// Which package provides `command`: the manager's own file index when available,
+/// then the bundled package map.
+pub fn provides(pm: &dyn PackageManager, command: &str) -> Option<Provider> {
+    if let Some(package) = pm.provides(command) {
+        let via = match pm.kind() {
+            PackageSystem::Apt => "apt-file",
+            PackageSystem::Dnf => "dnf provides",
+            PackageSystem::Pacman => "pkgfile",
+            PackageSystem::Zypper => "zypper search --provides",
+            PackageSystem::Apk => "apk cmd: index",
+            PackageSystem::Nix => "nix-locate",
+            PackageSystem::Flatpak | PackageSystem::Snap => "package index",
+        };
+        return Some(Provider { package, via });
+    }
+    PackageMap::global()
+        .package_for_command(command, pm.kind())
+        .map(|package| Provider {
+            package,
+            via: "package map",
+        })
+}
+
+/// Names to try for `thing` on `pm`: the package map's name first, then `thing` itself.
+fn candidates(pm: &dyn PackageManager, thing: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Some(mapped) = PackageMap::global().package_for(thing, pm.kind()) {
+        names.push(mapped);
+    }
+    if !names.iter().any(|n| n == thing) {
+        names.push(thing.to_string());
+    }
+    names
+}
+
+/// `thing` (a package or a command) as `pm` knows it: through the package map, as is,
+/// or as the package that provides that command.
+pub fn resolve(pm: &dyn PackageManager, thing: &str) -> Option<PackageInfo> {
+    for candidate in candidates(pm, thing) {
+        if let Some(info) = pm.info(&candidate) {
+            return Some(info);
+        }
     }
+    let package = pm.provides(thing)?;
+    pm.info(&package)
+}
+
+/// (package, version) when `thing` is already installed through `pm`.
+pub fn installed(pm: &dyn PackageManager, thing: &str) -> Option<(String, String)> {
+    candidates(pm, thing)
+        .into_iter()
+        .find_map(|candidate| pm.installed_version(&candidate).map(|version| (candidate, version)))
 }
 
 fn prepend_auth_sock(cmd: String) -> String {
     if let Ok(sock) = std::env::var("SSH_AUTH_SOCK") {
-        return format!("SSH_AUTH_SOCK={} {}", sock, cmd);
+        return format!("SSH_AUTH_SOCK={} {}", quote(&sock), cmd);
     }
     cmd
 }
+
+fn rpm_version(package: &str) -> Option<String> {
+    let out = query(&["rpm", "-q", "--qf", "%{VERSION}-%{RELEASE}\n", package])?;
+    out.lines().next().map(|l| l.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// --- Output parsers ---
+
+/// `dpkg-query -W -f='${Status}\t${Version}\n'`: `install ok installed\t3.3.0-4`.
+pub fn parse_dpkg_status(out: &str) -> Option<String> {
+    let (status, version) = out.lines().next()?.split_once('\t')?;
+    if status.trim().ends_with(" installed") && !version.trim().is_empty() {
+        Some(version.trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// `apt-file search`: `htop: /usr/bin/htop`.
+pub fn parse_apt_file(out: &str) -> Option<String> {
+    out.lines()
+        .find_map(|l| l.split_once(": "))
+        .map(|(package, _)| package.trim().to_string())
+}
+
+/// `dnf provides`: `htop-3.3.0-2.fc40.x86_64 : Interactive process viewer` (the NEVRA
+/// carries an epoch on dnf5: `htop-0:3.3.0-2.fc40.x86_64`).
+pub fn parse_dnf_provides(out: &str) -> Option<String> {
+    let nevra = out
+        .lines()
+        .filter(|l| !l.starts_with(' ') && !l.starts_with("Last metadata"))
+        .find_map(|l| l.split_once(" : "))
+        .map(|(nevra, _)| nevra.trim())?;
+    let without_arch = nevra.rsplit_once('.').map(|(n, _)| n).unwrap_or(nevra);
+    let parts: Vec<&str> = without_arch.rsplitn(3, '-').collect();
+    (parts.len() == 3).then(|| parts[2].to_string())
+}
+
+/// `nix-locate`: `htop.out   284,464 x /nix/store/...-htop-3.3.0/bin/htop`.
+pub fn parse_nix_locate(out: &str) -> Option<String> {
+    let attr = out.lines().find_map(|l| l.split_whitespace().next())?;
+    let attr = attr.strip_prefix('(').unwrap_or(attr).trim_end_matches(')');
+    let attr = attr.strip_suffix(".out").or_else(|| attr.strip_suffix(".bin")).unwrap_or(attr);
+    Some(attr.to_string())
+}
+
+/// `name - summary` lines (`apt-cache search`).
+pub fn parse_dash_list(out: &str) -> Vec<PackageSummary> {
+    out.lines()
+        .filter_map(|line| line.split_once(" - "))
+        .map(|(name, summary)| PackageSummary {
+            name: name.trim().to_string(),
+            version: None,
+            summary: summary.trim().to_string(),
+        })
+        .collect()
+}
+
+/// First stanza of `apt-cache show`.
+pub fn parse_deb_control(out: &str) -> Option<PackageInfo> {
+    let stanza = out.split("\n\n").next()?;
+    let field = |key: &str| {
+        stanza
+            .lines()
+            .find_map(|l| l.strip_prefix(key).and_then(|r| r.strip_prefix(':')))
+            .map(|v| v.trim().to_string())
+    };
+    Some(PackageInfo {
+        name: field("Package")?,
+        version: field("Version"),
+        summary: field("Description").or_else(|| field("Description-en")).unwrap_or_default(),
+        source: None, // apt-cache does not name the archive
+        installed: None,
+    })
+}
+
+/// `htop.x86_64 : Interactive process viewer` (dnf4) or `htop.x86_64\tInteractive ...` (dnf5).
+pub fn parse_dnf_search(out: &str) -> Vec<PackageSummary> {
+    out.lines()
+        .filter(|l| !l.starts_with('=') && !l.starts_with("Matched") && !l.starts_with("Last metadata"))
+        .filter_map(|l| l.split_once(" : ").or_else(|| l.trim_start().split_once('\t')))
+        .map(|(name, summary)| {
+            let name = name.trim();
+            let name = name.rsplit_once('.').map(|(n, _)| n).unwrap_or(name);
+            PackageSummary {
+                name: name.to_string(),
+                version: None,
+                summary: summary.trim().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// `Key : Value` blocks as printed by `dnf info`, `zypper info` and `pacman -Si`.
+/// Only the first package is read.
+pub fn parse_field_info(out: &str) -> Option<PackageInfo> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in out.lines() {
+        if line.starts_with(' ') {
+            continue; // wrapped description
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            let key = key.trim();
+            if key == "Name" && fields.iter().any(|(k, _)| k == "Name") {
+                break;
+            }
+            fields.push((key.to_string(), value.trim().to_string()));
+        }
+    }
+    let field = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| fields.iter().find(|(key, _)| key == k).map(|(_, v)| v.clone()))
+            .filter(|v| !v.is_empty())
+    };
+    let version = match (field(&["Version"]), field(&["Release"])) {
+        (Some(v), Some(r)) => Some(format!("{}-{}", v, r)),
+        (v, _) => v,
+    };
+    Some(PackageInfo {
+        name: field(&["Name"])?,
+        version,
+        summary: field(&["Summary", "Description"]).unwrap_or_default(),
+        source: field(&["Repository", "From repo", "Repo"]),
+        installed: None,
+    })
+}
+
+/// `extra/htop 3.3.0-1 [installed]` followed by an indented description.
+pub fn parse_pacman_search(out: &str) -> Vec<PackageSummary> {
+    let mut results: Vec<PackageSummary> = Vec::new();
+    for line in out.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = results.last_mut() {
+                last.summary = line.trim().to_string();
+            }
+            continue;
+        }
+        let mut cols = line.split_whitespace();
+        if let (Some(repo_name), Some(version)) = (cols.next(), cols.next()) {
+            let name = repo_name.rsplit('/').next().unwrap_or(repo_name);
+            results.push(PackageSummary {
+                name: name.to_string(),
+                version: Some(version.to_string()),
+                summary: String::new(),
+            });
+        }
+    }
+    results
+}
+
+/// `zypper search` table: `S | Name | Summary | Type`.
+pub fn parse_zypper_search(out: &str) -> Vec<PackageSummary> {
+    out.lines()
+        .filter(|l| l.contains('|') && !l.starts_with("--"))
+        .map(|l| l.split('|').map(str::trim).collect::<Vec<_>>())
+        .filter(|cols| cols.len() >= 3 && cols[1] != "Name")
+        .filter(|cols| cols.get(3).map(|t| *t == "package").unwrap_or(true))
+        .map(|cols| PackageSummary {
+            name: cols[1].to_string(),
+            version: None,
+            summary: cols[2].to_string(),
+        })
+        .collect()
+}
+
+/// `htop-3.3.0-r0` -> (`htop`, `3.3.0-r0`); Nix's `htop-3.3.0` -> (`htop`, `3.3.0`).
+pub fn split_name_version(nv: &str) -> Option<(String, String)> {
+    // apk: <name>-<version>-r<N>
+    let parts: Vec<&str> = nv.rsplitn(3, '-').collect();
+    if parts.len() == 3 && parts[0].starts_with('r') && parts[0][1..].chars().all(|c| c.is_ascii_digit()) {
+        return Some((parts[2].to_string(), format!("{}-{}", parts[1], parts[0])));
+    }
+    // Nix: the version starts at the first `-` followed by a digit
+    let idx = nv
+        .char_indices()
+        .find(|(i, c)| *c == '-' && nv[i + 1..].starts_with(|d: char| d.is_ascii_digit()))
+        .map(|(i, _)| i)?;
+    Some((nv[..idx].to_string(), nv[idx + 1..].to_string()))
+}
+
+/// `htop-3.3.0-r0 - Interactive process viewer`.
+pub fn parse_apk_search(out: &str) -> Vec<PackageSummary> {
+    out.lines()
+        .filter_map(|line| {
+            let (nv, summary) = line.split_once(" - ")?;
+            let (name, version) = split_name_version(nv.trim())?;
+            Some(PackageSummary {
+                name,
+                version: Some(version),
+                summary: summary.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `apk info -d htop`: `htop-3.3.0-r0 description:` then the description line.
+pub fn parse_apk_info(out: &str) -> Option<PackageInfo> {
+    let mut lines = out.lines();
+    let header = lines.next()?;
+    let nv = header.strip_suffix(" description:")?;
+    let (name, version) = split_name_version(nv.trim())?;
+    Some(PackageInfo {
+        name,
+        version: Some(version),
+        summary: lines.find(|l| !l.trim().is_empty()).unwrap_or("").trim().to_string(),
+        source: None,
+        installed: None,
+    })
+}
+
+/// `apk list --installed htop`: `htop-3.3.0-r0 x86_64 {htop} (GPL-2.0) [installed]`.
+pub fn parse_apk_installed(out: &str, package: &str) -> Option<String> {
+    out.lines()
+        .filter(|l| l.contains("[installed]"))
+        .filter_map(|l| split_name_version(l.split_whitespace().next()?))
+        .find(|(name, _)| name == package)
+        .map(|(_, version)| version)
+}
+
+/// An app id matches its full id, its last segment or its display name:
+/// `org.gimp.GIMP` matches `gimp`.
+fn flatpak_matches(app_id: &str, display: &str, package: &str) -> bool {
+    app_id.eq_ignore_ascii_case(package)
+        || app_id.rsplit('.').next().map(|s| s.eq_ignore_ascii_case(package)).unwrap_or(false)
+        || display.eq_ignore_ascii_case(package)
+}
+
+/// `flatpak search --columns=application,version,name,description,remotes`, tab-separated:
+/// (app, display name, remote).
+pub fn parse_flatpak_search(out: &str) -> Vec<(PackageSummary, String, Option<String>)> {
+    out.lines()
+        .map(|l| l.split('\t').map(str::trim).collect::<Vec<_>>())
+        .filter(|cols| cols.len() >= 4)
+        .map(|cols| {
+            let summary = PackageSummary {
+                name: cols[0].to_string(),
+                version: Some(cols[1].to_string()).filter(|v| !v.is_empty()),
+                summary: cols[3].to_string(),
+            };
+            let remote = cols.get(4).map(|r| r.to_string()).filter(|r| !r.is_empty());
+            (summary, cols[2].to_string(), remote)
+        })
+        .collect()
+}
+
+/// `flatpak list --app --columns=application,version,name`.
+pub fn parse_flatpak_installed(out: &str, package: &str) -> Option<String> {
+    out.lines()
+        .map(|l| l.split('\t').map(str::trim).collect::<Vec<_>>())
+        .find(|cols| cols.len() >= 3 && flatpak_matches(cols[0], cols[2], package))
+        .map(|cols| cols[1].to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// `snap find`: `Name  Version  Publisher  Notes  Summary`.
+pub fn parse_snap_find(out: &str) -> Vec<PackageSummary> {
+    out.lines()
+        .skip(1)
+        .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .filter(|cols| cols.len() >= 5)
+        .map(|cols| PackageSummary {
+            name: cols[0].to_string(),
+            version: Some(cols[1].to_string()),
+            summary: cols[4..].join(" "),
+        })
+        .collect()
+}
+
+/// `snap info`: YAML-like `key: value` lines with a `channels:` list.
+pub fn parse_snap_info(out: &str) -> Option<PackageInfo> {
+    let field = |key: &str| {
+        out.lines()
+            .find_map(|l| l.strip_prefix(key).and_then(|r| r.strip_prefix(':')))
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    // `installed:          3.3.0  (4) 1MB -`, `  latest/stable:    3.3.0  2024-01-01 (4) 1MB -`
+    let installed = field("installed").and_then(|v| v.split_whitespace().next().map(str::to_string));
+    let tracking = field("tracking").unwrap_or_else(|| "latest/stable".to_string());
+    let version = out
+        .lines()
+        .find_map(|l| l.trim().strip_prefix(&format!("{}:", tracking)))
+        .and_then(|v| v.split_whitespace().next())
+        .filter(|v| *v != "--" && *v != "^")
+        .map(str::to_string);
+    Some(PackageInfo {
+        name: field("name")?,
+        version,
+        summary: field("summary").unwrap_or_default(),
+        source: Some(tracking),
+        installed,
+    })
+}
+
+/// `nix search nixpkgs <q> --json`: attribute path -> `{pname, version, description}`.
+pub fn parse_nix_search(out: &str) -> Vec<PackageSummary> {
+    let json: Value = match serde_json::from_str(out) {
+        Ok(json) => json,
+        Err(_) => return Vec::new(),
+    };
+    let mut results: Vec<PackageSummary> = json
+        .as_object()
+        .map(|packages| {
+            packages
+                .values()
+                .map(|p| PackageSummary {
+                    name: p["pname"].as_str().unwrap_or_default().to_string(),
+                    version: p["version"].as_str().map(str::to_string).filter(|v| !v.is_empty()),
+                    summary: p["description"].as_str().unwrap_or_default().to_string(),
+                })
+                .filter(|p| !p.name.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    results.sort_by(|a, b| a.name.cmp(&b.name));
+    results
+}
+
+/// `nix profile list --json`. Elements are a name-keyed map in recent Nix and a list in
+/// older releases; the version comes from the store path (`/nix/store/<hash>-htop-3.3.0`).
+pub fn parse_nix_profile(out: &str, package: &str) -> Option<String> {
+    let json: Value = serde_json::from_str(out).ok()?;
+    let elements: Vec<(Option<&str>, &Value)> = match &json["elements"] {
+        Value::Object(map) => map.iter().map(|(k, v)| (Some(k.as_str()), v)).collect(),
+        Value::Array(list) => list.iter().map(|v| (None, v)).collect(),
+        _ => return None,
+    };
+    elements.into_iter().find_map(|(key, element)| {
+        let attr = element["attrPath"].as_str().unwrap_or_default();
+        let matches = key == Some(package) || attr.rsplit('.').next() == Some(package);
+        if !matches {
+            return None;
+        }
+        let store_path = element["storePaths"][0].as_str()?;
+        let file = store_path.rsplit('/').next()?;
+        let (_, nv) = file.split_once('-')?;
+        split_name_version(nv).map(|(_, version)| version)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::probe::{with_bundle, Bundle};
+
+    #[test]
+    fn apt_reads_dpkg_and_apt_cache() {
+        assert_eq!(parse_dpkg_status("install ok installed\t3.3.0-4\n"), Some("3.3.0-4".to_string()));
+        assert_eq!(parse_dpkg_status("deinstall ok config-files\t3.3.0-4\n"), None);
+        let info = parse_deb_control(
+            "Package: htop\nVersion: 3.3.0-4\nSection: utils\nDescription: interactive processes viewer\n\nPackage: htop\nVersion: 3.2.2-2\n",
+        )
+        .unwrap();
+        assert_eq!(info.version.as_deref(), Some("3.3.0-4"));
+        assert_eq!(info.summary, "interactive processes viewer");
+        let found = parse_dash_list("htop - interactive processes viewer\nbtop - resource monitor\n");
+        assert_eq!(found[1].name, "btop");
+    }
+
+    #[test]
+    fn field_info_covers_dnf_zypper_and_pacman() {
+        let dnf = parse_field_info(
+            "Available Packages\nName         : htop\nVersion      : 3.3.0\nRelease      : 2.fc40\nArchitecture : x86_64\nSummary      : Interactive process viewer\nRepository   : fedora\nDescription  : htop is an interactive\n             : text-mode process viewer\n",
+        )
+        .unwrap();
+        assert_eq!(dnf.version.as_deref(), Some("3.3.0-2.fc40"));
+        assert_eq!(dnf.source.as_deref(), Some("fedora"));
+        assert_eq!(dnf.summary, "Interactive process viewer");
+
+        let zypper = parse_field_info(
+            "Information for package htop:\n------------------------------\nRepository     : Main Repository (OSS)\nName           : htop\nVersion        : 3.2.2-1.4\nInstalled      : No\nSummary        : An interactive process viewer\n",
+        )
+        .unwrap();
+        assert_eq!(zypper.version.as_deref(), Some("3.2.2-1.4"));
+        assert_eq!(zypper.source.as_deref(), Some("Main Repository (OSS)"));
+
+        let pacman = parse_field_info(
+            "Repository      : extra\nName            : htop\nVersion         : 3.3.0-1\nDescription     : Interactive process viewer\n",
+        )
+        .unwrap();
+        assert_eq!(pacman.summary, "Interactive process viewer");
+    }
+
+    #[test]
+    fn search_parsers() {
+        let dnf = parse_dnf_search("======= Name Exactly Matched: htop =======\nhtop.x86_64 : Interactive process viewer\n");
+        assert_eq!(dnf, vec![PackageSummary { name: "htop".into(), version: None, summary: "Interactive process viewer".into() }]);
+        let pacman = parse_pacman_search("extra/htop 3.3.0-1 [installed]\n    Interactive process viewer\n");
+        assert_eq!(pacman[0].version.as_deref(), Some("3.3.0-1"));
+        assert_eq!(pacman[0].summary, "Interactive process viewer");
+        let zypper = parse_zypper_search(
+            "S  | Name      | Summary                       | Type\n---+-----------+-------------------------------+--------\ni+ | htop      | An interactive process viewer | package\n   | htop      | An interactive process viewer | srcpackage\n",
+        );
+        assert_eq!(zypper.len(), 1);
+        let snap = parse_snap_find("Name  Version  Publisher     Notes  Summary\nhtop  3.3.0    maxiberta     -      Interactive processes viewer\n");
+        assert_eq!(snap[0].summary, "Interactive processes viewer");
+    }
+
+    #[test]
+    fn apk_and_nix_versions() {
+        assert_eq!(split_name_version("py3-pip-24.0-r2"), Some(("py3-pip".into(), "24.0-r2".into())));
+        assert_eq!(split_name_version("python3-3.12.4"), Some(("python3".into(), "3.12.4".into())));
+        assert_eq!(
+            parse_apk_installed("htop-3.3.0-r0 x86_64 {htop} (GPL-2.0-or-later) [installed]\n", "htop"),
+            Some("3.3.0-r0".to_string())
+        );
+        assert_eq!(parse_apk_search("htop-3.3.0-r0 - Interactive process viewer\n")[0].name, "htop");
+        let info = parse_apk_info("htop-3.3.0-r0 description:\nInteractive process viewer\n\n").unwrap();
+        assert_eq!(info.version.as_deref(), Some("3.3.0-r0"));
+
+        let map = r#"{"elements":{"htop":{"attrPath":"legacyPackages.x86_64-linux.htop","storePaths":["/nix/store/0c3bdv6yv2k1mq36ywgf8i2qlxlq2pfh-htop-3.3.0"]}}}"#;
+        assert_eq!(parse_nix_profile(map, "htop"), Some("3.3.0".to_string()));
+        let list = r#"{"elements":[{"attrPath":"legacyPackages.x86_64-linux.ripgrep","storePaths":["/nix/store/aaaa-ripgrep-14.1.0"]}]}"#;
+        assert_eq!(parse_nix_profile(list, "ripgrep"), Some("14.1.0".to_string()));
+        let search = parse_nix_search(r#"{"legacyPackages.x86_64-linux.htop":{"pname":"htop","version":"3.3.0","description":"An interactive process viewer"}}"#);
+        assert_eq!(search[0].version.as_deref(), Some("3.3.0"));
+    }
+
+    #[test]
+    fn flatpak_and_snap_match_apps() {
+        let listed = "org.gimp.GIMP\t2.10.38\tGNU Image Manipulation Program\n";
+        assert_eq!(parse_flatpak_installed(listed, "gimp"), Some("2.10.38".to_string()));
+        assert_eq!(parse_flatpak_installed(listed, "inkscape"), None);
+        let found = parse_flatpak_search("org.gimp.GIMP\t2.10.38\tGIMP\tCreate images and edit photographs\tflathub\n");
+        assert_eq!(found[0].0.name, "org.gimp.GIMP");
+        assert_eq!(found[0].1, "GIMP");
+        assert_eq!(found[0].2.as_deref(), Some("flathub"));
+
+        let snap = parse_snap_info(
+            "name:      htop\nsummary:   Interactive processes viewer\npublisher: Maximiliano Bertacchini (maxiberta)\ntracking:  latest/stable\ninstalled: 3.3.0 (4) 1MB -\nchannels:\n  latest/stable:    3.3.0 2024-01-13 (4) 1MB -\n  latest/edge:      ^\n",
+        )
+        .unwrap();
+        assert_eq!(snap.installed.as_deref(), Some("3.3.0"));
+        assert_eq!(snap.version.as_deref(), Some("3.3.0"));
+    }
+
+    #[test]
+    fn provides_parsers() {
+        assert_eq!(parse_apt_file("htop: /usr/bin/htop\n").as_deref(), Some("htop"));
+        assert_eq!(
+            parse_dnf_provides("Last metadata expiration check: 0:10:00 ago\nbind-utils-32:9.18.28-1.fc40.x86_64 : Utilities for querying DNS\nRepo        : updates\n").as_deref(),
+            Some("bind-utils")
+        );
+        assert_eq!(
+            parse_nix_locate("ripgrep.out   4,190,296 x /nix/store/aaaa-ripgrep-14.1.0/bin/rg\n").as_deref(),
+            Some("ripgrep")
+        );
+    }
+
+    #[test]
+    fn provides_falls_back_to_the_package_map() {
+        let bundle = Bundle::new().with_command("dnf", &["provides", "-q", "*bin/nc"], "nmap-ncat-3:7.92-1.fc40.x86_64 : Netcat\n");
+        with_bundle(bundle, || {
+            let found = provides(&Dnf, "nc").unwrap();
+            assert_eq!((found.package.as_str(), found.via), ("nmap-ncat", "dnf provides"));
+            // no apt-file on this host
+            let found = provides(&Apt, "dig").unwrap();
+            assert_eq!((found.package.as_str(), found.via), ("dnsutils", "package map"));
+            assert_eq!(provides(&Apt, "no-such-tool"), None);
+        });
+    }
+
+    #[test]
+    fn installed_state_is_queried_before_install() {
+        let bundle = Bundle::new()
+            .with_command("rpm", &["-q", "--qf", "%{VERSION}-%{RELEASE}\n", "htop"], "3.3.0-2.fc40\n")
+            .with_command("flatpak", &["--version"], "Flatpak 1.14.8\n");
+        with_bundle(bundle, || {
+            assert_eq!(Dnf.installed_version("htop"), Some("3.3.0-2.fc40".to_string()));
+            assert_eq!(Dnf.installed_version("btop"), None);
+            assert_eq!(active_managers("dnf"), vec!["dnf", "flatpak"]);
+            assert_eq!(active_managers("xbps"), vec!["flatpak"]);
+        });
+        let unprivileged = PkgCommand { sudo: false, ..Apt.install("it's").unwrap() };
+        assert_eq!(unprivileged.to_shell(), "apt install -y 'it'\\''s'");
+    }
+
+    #[test]
+    fn option_like_package_names_are_rejected() {
+        for name in ["-oAPT::Update::Pre-Invoke::=sh", "--allow-unauthenticated", "", "htop nmap", "a\nb"] {
+            assert!(Apt.install(name).is_err(), "{:?}", name);
+            assert!(Dnf.remove(name).is_err(), "{:?}", name);
+        }
+        let nix = Nix { profile: true };
+        assert!(nix.install("-L").is_err());
+        assert_eq!(Pacman.remove("lib32-gcc-libs").unwrap().argv.last().map(String::as_str), Some("lib32-gcc-libs"));
+    }
+
+    #[test]
+    fn nix_info_matches_the_name_literally() {
+        let flags = ["--extra-experimental-features", "nix-command flakes"];
+        let search = |pattern: &'static str| -> Vec<&'static str> {
+            let mut argv = flags.to_vec();
+            argv.extend(["search", "nixpkgs", pattern, "--json"]);
+            argv
+        };
+        let bundle = Bundle::new().with_command(
+            "nix",
+            &search("^gtk\\+3$"),
+            r#"{"legacyPackages.x86_64-linux.gtk3":{"pname":"gtk+3","version":"3.24.41","description":"GTK toolkit"}}"#,
+        );
+        with_bundle(bundle, || {
+            let info = Nix { profile: true }.info("gtk+3").unwrap();
+            assert_eq!((info.name.as_str(), info.version.as_deref()), ("gtk+3", Some("3.24.41")));
+        });
+    }
+}
@@ -0,0 +1,220 @@
+use crate::executor::pkg::{check_package_name, PackageSystem};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Shipped with the binary; see the header of `data/packages.toml` for the format.
+const BUNDLED: &str = include_str!("../../data/packages.toml");
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PackageEntry {
+    /// Commands the package provides.
+    #[serde(default)]
+    pub bins: Vec<String>,
+    /// Manager (`apt`, `dnf`, ...) -> package name there.
+    #[serde(flatten)]
+    pub names: BTreeMap<String, String>,
+}
+
+/// Canonical package names per distro family.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PackageMap {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageEntry>,
+}
+
+impl PackageMap {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let map: PackageMap = toml::from_str(content).map_err(|e| format!("Invalid package map: {}", e))?;
+        // Mapped names end up in install/remove argv
+        for (name, entry) in &map.packages {
+            for (manager, package) in &entry.names {
+                check_package_name(package)
+                    .map_err(|e| format!("Invalid package map: packages.{}.{}: {}", name, manager, e))?;
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED).expect("bundled data/packages.toml is valid")
+    }
+
+    /// `~/.config/vega/packages.toml`, merged over the bundled dataset.
+    pub fn override_path() -> PathBuf {
+        crate::init::get_config_path().with_file_name("packages.toml")
+    }
+
+    /// Bundled dataset plus the local override, loaded once per process.
+    pub fn global() -> &'static PackageMap {
+        static MAP: OnceLock<PackageMap> = OnceLock::new();
+        MAP.get_or_init(|| {
+            let mut map = Self::bundled();
+            if let Ok(content) = std::fs::read_to_string(Self::override_path()) {
+                match Self::parse(&content) {
+                    Ok(local) => map.merge(local),
+                    Err(e) => eprintln!("⚠️  Ignoring {}: {}", Self::override_path().display(), e),
+                }
+            }
+            map
+        })
+    }
+
+    /// Entries in `other` add to or override ours, name by name.
+    pub fn merge(&mut self, other: PackageMap) {
+        for (name, entry) in other.packages {
+            let ours = self.packages.entry(name).or_default();
+            for bin in entry.bins {
+                if !ours.bins.contains(&bin) {
+                    ours.bins.push(bin);
+                }
+            }
+            ours.names.extend(entry.names);
+        }
+    }
+
+    /// What replacing the local override with `content` changes in the merged map,
+    /// one line per entry or manager (`~ docker.dnf: docker-ce -> moby-engine`).
+    pub fn preview_override(content: &str) -> Result<Vec<String>, String> {
+        let mut proposed = Self::bundled();
+        proposed.merge(Self::parse(content)?);
+        Ok(Self::global().diff(&proposed))
+    }
+
+    pub fn diff(&self, other: &PackageMap) -> Vec<String> {
+        let mut lines = Vec::new();
+        let names: BTreeSet<&String> = self.packages.keys().chain(other.packages.keys()).collect();
+        for name in names {
+            match (self.packages.get(name), other.packages.get(name)) {
+                (None, Some(new)) => {
+                    let mut fields: Vec<String> = new.names.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                    if !new.bins.is_empty() {
+                        fields.push(format!("bins={}", new.bins.join(",")));
+                    }
+                    lines.push(format!("+ {}: {}", name, fields.join(" ")));
+                }
+                (Some(_), None) => lines.push(format!("- {}", name)),
+                (Some(old), Some(new)) => {
+                    let managers: BTreeSet<&String> = old.names.keys().chain(new.names.keys()).collect();
+                    for manager in managers {
+                        let (before, after) = (old.names.get(manager), new.names.get(manager));
+                        if before != after {
+                            lines.push(format!(
+                                "~ {}.{}: {} -> {}",
+                                name,
+                                manager,
+                                before.map(String::as_str).unwrap_or("(none)"),
+                                after.map(String::as_str).unwrap_or("(none)")
+                            ));
+                        }
+                    }
+                    if old.bins != new.bins {
+                        lines.push(format!("~ {}.bins: [{}] -> [{}]", name, old.bins.join(","), new.bins.join(",")));
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        lines
+    }
+
+    /// Validates `content` and stores it as the local override. Returns its entry count.
+    pub fn save_override(content: &str) -> Result<usize, String> {
+        let map = Self::parse(content)?;
+        let path = Self::override_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&path, content).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        Ok(map.packages.len())
+    }
+
+    /// What `kind` calls `thing`, an entry name or a command it provides:
+    /// `fd` -> `fd-find` on apt, `gimp` -> `org.gimp.GIMP` on flatpak.
+    pub fn package_for(&self, thing: &str, kind: PackageSystem) -> Option<String> {
+        let (name, entry) = self
+            .packages
+            .get_key_value(thing)
+            .or_else(|| self.packages.iter().find(|(_, e)| e.bins.iter().any(|b| b == thing)))?;
+        Self::name_in(name, entry, kind)
+    }
+
+    /// Like `package_for`, but only by command name.
+    pub fn package_for_command(&self, command: &str, kind: PackageSystem) -> Option<String> {
+        let (name, entry) = self
+            .packages
+            .iter()
+            .find(|(_, e)| e.bins.iter().any(|b| b == command))?;
+        Self::name_in(name, entry, kind)
+    }
+
+    fn name_in(name: &str, entry: &PackageEntry, kind: PackageSystem) -> Option<String> {
+        match entry.names.get(kind.as_str()) {
+            Some(package) => Some(package.clone()),
+            // Flatpak needs an app id; everyone else defaults to the entry name
+            None if kind == PackageSystem::Flatpak => None,
+            None => Some(name.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_map_resolves_names_and_commands() {
+        let map = PackageMap::bundled();
+        assert_eq!(map.package_for("docker", PackageSystem::Apt).as_deref(), Some("docker.io"));
+        assert_eq!(map.package_for("fdfind", PackageSystem::Apt).as_deref(), Some("fd-find"));
+        assert_eq!(map.package_for("fd", PackageSystem::Pacman).as_deref(), Some("fd"));
+        assert_eq!(map.package_for("dig", PackageSystem::Dnf).as_deref(), Some("bind-utils"));
+        assert_eq!(map.package_for("gimp", PackageSystem::Flatpak).as_deref(), Some("org.gimp.GIMP"));
+        assert_eq!(map.package_for("rg", PackageSystem::Flatpak), None);
+        assert_eq!(map.package_for("htop", PackageSystem::Apt), None);
+        assert_eq!(map.package_for_command("docker", PackageSystem::Dnf).as_deref(), Some("docker-ce"));
+        assert_eq!(map.package_for_command("obs-studio", PackageSystem::Dnf), None);
+    }
+
+    #[test]
+    fn local_entries_override_bundled_ones() {
+        let mut map = PackageMap::bundled();
+        map.merge(
+            PackageMap::parse("[packages.docker]\nbins = [\"dockerd\"]\ndnf = \"moby-engine\"\n\n[packages.btop]\nbins = [\"btop\"]\napk = \"btop\"\n")
+                .unwrap(),
+        );
+        assert_eq!(map.package_for("docker", PackageSystem::Dnf).as_deref(), Some("moby-engine"));
+        assert_eq!(map.package_for("docker", PackageSystem::Apt).as_deref(), Some("docker.io"));
+        assert_eq!(map.package_for("dockerd", PackageSystem::Dnf).as_deref(), Some("moby-engine"));
+        assert_eq!(map.package_for("btop", PackageSystem::Apk).as_deref(), Some("btop"));
+        assert!(PackageMap::parse("[packages.x]\napt = 3\n").is_err());
+    }
+
+    #[test]
+    fn option_like_names_are_rejected() {
+        let err = PackageMap::parse("[packages.curl]\napt = \"-oAPT::Update::Pre-Invoke::=sh\"\n").unwrap_err();
+        assert!(err.contains("packages.curl.apt"), "{}", err);
+    }
+
+    #[test]
+    fn diff_lists_added_changed_and_dropped_entries() {
+        let old = PackageMap::parse("[packages.docker]\nbins = [\"docker\"]\ndnf = \"docker-ce\"\n\n[packages.gone]\napt = \"gone\"\n").unwrap();
+        let new = PackageMap::parse(
+            "[packages.docker]\nbins = [\"docker\"]\ndnf = \"moby-engine\"\napt = \"docker.io\"\n\n[packages.btop]\nbins = [\"btop\"]\napk = \"btop\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                "+ btop: apk=btop bins=btop",
+                "~ docker.apt: (none) -> docker.io",
+                "~ docker.dnf: docker-ce -> moby-engine",
+                "- gone",
+            ]
+        );
+        assert!(new.diff(&new).is_empty());
+    }
+}
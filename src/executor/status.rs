@@ -1,3 +1,4 @@
+use crate::context::{age_label, SyncEdge};
 use crate::knowledge::KnowledgeBase;
 use crate::reporting::analytics::Analytics;
 use crate::storage::db::Database;
@@ -21,6 +22,8 @@ pub fn show_status(kb: &KnowledgeBase) {
 
     // Usage Analytics Integration
     if let Ok(db) = Database::new() {
+        show_sync_topology(&db);
+
         if let Ok(sessions) = db.get_recent_sessions(5) {
             let data: Vec<(String, i32)> = sessions
                 .into_iter()
@@ -34,3 +37,46 @@ pub fn show_status(kb: &KnowledgeBase) {
         }
     }
 }
+
+/// Renders every recorded `source -> remote` edge and flags the ones past the freshness SLA.
+fn show_sync_topology(db: &Database) {
+    let edges = SyncEdge::load(db);
+    if edges.is_empty() {
+        return;
+    }
+    let sla = SyncEdge::freshness_hours();
+    let now = crate::storage::events::now_secs();
+
+    println!("\n🔗 Sync Topology (freshness SLA: {}h)", sla);
+    for edge in &edges {
+        let icon = if !edge.success {
+            "❌"
+        } else if edge.stale {
+            "⚠️ "
+        } else {
+            "✅"
+        };
+        let last = edge
+            .last_success
+            .map(|ts| age_label(now - ts))
+            .unwrap_or_else(|| "never".to_string());
+        println!("   {} {} ──{}──▶ {}", icon, edge.source, edge.kind, edge.destination);
+        println!(
+            "      last success {}, {:.1} MiB last run, {} runs",
+            last,
+            edge.bytes as f64 / (1024.0 * 1024.0),
+            edge.runs
+        );
+        if let Some(error) = edge.error.as_deref().filter(|_| !edge.success) {
+            println!("      error: {}", error);
+        }
+    }
+
+    let stale: Vec<&SyncEdge> = edges.iter().filter(|e| e.stale).collect();
+    if !stale.is_empty() {
+        println!("\n⚠️  {} of {} sync targets are older than {}h:", stale.len(), edges.len(), sla);
+        for edge in stale {
+            println!("   - {} -> {}", edge.source, edge.destination);
+        }
+    }
+}
@@ -1,38 +1,437 @@
+use crate::context::SystemContext;
+use crate::executor::ast::{Arg, CommandAst};
 use crate::executor::pipeline::{Intent, TemplateBuilder};
-use crate::executor::ast::CommandAst;
+use crate::executor::pkg::{self, PackageManager};
+use crate::executor::pkg_map::PackageMap;
+use crate::system::storage::SmartStorage;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// What a slot accepts. Values are checked (and remotes resolved) when an intent is bound,
+/// so a target can never turn into an option or a second command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlotKind {
+    /// Local file or directory.
+    Path,
+    /// Local path or `host:path` / `remote:path`.
+    Location,
+    /// rclone remote or storage alias (`구드` -> `gdrive:backup`).
+    Remote,
+    /// `[user@]host`.
+    Host,
+    Package,
+    /// systemd unit.
+    Unit,
+    Container,
+}
+
+impl SlotKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SlotKind::Path => "path",
+            SlotKind::Location => "location",
+            SlotKind::Remote => "remote",
+            SlotKind::Host => "host",
+            SlotKind::Package => "package",
+            SlotKind::Unit => "systemd unit",
+            SlotKind::Container => "container",
+        }
+    }
+
+    fn pattern(&self) -> Option<&'static Regex> {
+        static HOST: OnceLock<Regex> = OnceLock::new();
+        static PACKAGE: OnceLock<Regex> = OnceLock::new();
+        static UNIT: OnceLock<Regex> = OnceLock::new();
+        static CONTAINER: OnceLock<Regex> = OnceLock::new();
+        let (cell, re) = match self {
+            SlotKind::Host => (&HOST, r"^([A-Za-z0-9._-]+@)?([A-Za-z0-9._-]+|\[[0-9A-Fa-f:]+\])$"),
+            SlotKind::Package => (&PACKAGE, r"^[A-Za-z0-9][A-Za-z0-9.+_@:/#-]*$"),
+            SlotKind::Unit => (&UNIT, r"^[A-Za-z0-9][A-Za-z0-9@._:-]*$"),
+            SlotKind::Container => (&CONTAINER, r"^[A-Za-z0-9][A-Za-z0-9_.-]*$"),
+            _ => return None,
+        };
+        Some(cell.get_or_init(|| Regex::new(re).unwrap()))
+    }
+
+    /// Whether `value` can go into argv as it is: never an option, never a second command.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        if value.is_empty() {
+            return Err(format!("empty {}", self.label()));
+        }
+        if value.starts_with('-') {
+            return Err(format!("'{}' looks like an option, not a {}", value, self.label()));
+        }
+        if value.chars().any(|c| c.is_control()) {
+            return Err(format!("'{}' contains control characters", value.escape_default()));
+        }
+        if let Some(re) = self.pattern() {
+            if !re.is_match(value) {
+                return Err(format!("'{}' is not a valid {}", value, self.label()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The value as it goes into argv, or why it cannot.
+    pub fn bind(&self, value: &str, storage: &SmartStorage) -> Result<String, String> {
+        let value = value.trim();
+        self.check(value)?;
+        if *self == SlotKind::Remote && !value.starts_with(['/', '.', '~']) {
+            let remote = storage.resolve(value);
+            return Ok(if remote.ends_with(':') {
+                format!("{}backup", remote)
+            } else {
+                remote
+            });
+        }
+        Ok(value.to_string())
+    }
+}
+
+pub struct Slot {
+    /// Placeholder name in the template's argv (`{source}`).
+    pub name: &'static str,
+    pub kind: SlotKind,
+    /// Intent slots that fill it, in order; `target` is the intent's main target.
+    pub from: &'static [&'static str],
+    pub default: Option<&'static str>,
+    pub required: bool,
+}
+
+const fn required(name: &'static str, kind: SlotKind, from: &'static [&'static str]) -> Slot {
+    Slot {
+        name,
+        kind,
+        from,
+        default: None,
+        required: true,
+    }
+}
+
+const fn optional(
+    name: &'static str,
+    kind: SlotKind,
+    from: &'static [&'static str],
+    default: Option<&'static str>,
+) -> Slot {
+    Slot {
+        name,
+        kind,
+        from,
+        default,
+        required: false,
+    }
+}
+
+/// How one tool is invoked for a set of operations.
+pub struct ToolTemplate {
+    pub tool: &'static str,
+    pub operations: &'static [&'static str],
+    /// argv in order. `{operation}` is the intent's operation, `{options}` marks where
+    /// generated options go, `{<slot>}` is a bound slot; a word whose optional slot
+    /// stays empty is dropped.
+    pub argv: &'static [&'static str],
+    pub slots: &'static [Slot],
+}
+
+use SlotKind::*;
+
+const TEMPLATES: &[ToolTemplate] = &[
+    ToolTemplate {
+        tool: "rclone",
+        operations: &["sync", "copy"],
+        argv: &["rclone", "{operation}", "{options}", "{source}", "{destination}"],
+        slots: &[
+            optional("source", Path, &["path", "source"], Some(".")),
+            required("destination", Remote, &["remote", "destination", "target"]),
+        ],
+    },
+    ToolTemplate {
+        tool: "rsync",
+        operations: &["sync", "copy"],
+        argv: &["rsync", "-a", "{options}", "{source}", "{destination}"],
+        slots: &[
+            optional("source", Location, &["path", "source"], Some(".")),
+            required("destination", Location, &["destination", "target"]),
+        ],
+    },
+    ToolTemplate {
+        tool: "ssh",
+        operations: &["connect"],
+        argv: &["ssh", "{options}", "{host}"],
+        slots: &[required("host", Host, &["target", "host"])],
+    },
+    ToolTemplate {
+        tool: "scp",
+        operations: &["copy"],
+        argv: &["scp", "{options}", "{source}", "{destination}"],
+        slots: &[
+            required("source", Location, &["path", "source"]),
+            required("destination", Location, &["destination", "target"]),
+        ],
+    },
+    ToolTemplate {
+        tool: "systemctl",
+        operations: &["status"],
+        argv: &["systemctl", "{options}", "status", "{unit}"],
+        slots: &[required("unit", Unit, &["target", "unit"])],
+    },
+    ToolTemplate {
+        tool: "systemctl",
+        operations: &["start", "stop", "restart", "reload", "enable", "disable"],
+        argv: &["sudo", "systemctl", "{options}", "{operation}", "{unit}"],
+        slots: &[required("unit", Unit, &["target", "unit"])],
+    },
+    ToolTemplate {
+        tool: "journalctl",
+        operations: &["logs", "show"],
+        argv: &["journalctl", "--no-pager", "{options}", "--unit={unit}"],
+        slots: &[optional("unit", Unit, &["target", "unit"], None)],
+    },
+    ToolTemplate {
+        tool: "docker",
+        operations: &["ps", "images"],
+        argv: &["docker", "{operation}", "{options}"],
+        slots: &[],
+    },
+    ToolTemplate {
+        tool: "docker",
+        operations: &["logs", "start", "stop", "restart", "inspect"],
+        argv: &["docker", "{operation}", "{options}", "{container}"],
+        slots: &[required("container", Container, &["target", "container"])],
+    },
+    ToolTemplate {
+        tool: "tar",
+        operations: &["create"],
+        argv: &["tar", "{options}", "-czf", "{destination}", "{source}"],
+        slots: &[
+            required("source", Path, &["path", "source", "target"]),
+            required("destination", Path, &["destination"]),
+        ],
+    },
+    ToolTemplate {
+        tool: "tar",
+        operations: &["extract"],
+        argv: &["tar", "{options}", "-xf", "{source}", "--directory={destination}"],
+        slots: &[
+            required("source", Path, &["path", "source", "target"]),
+            optional("destination", Path, &["destination"], None),
+        ],
+    },
+];
+
+/// `pkg` goes through the detected package manager rather than a fixed template.
+const PKG_OPERATIONS: [&str; 2] = ["install", "remove"];
+
+pub fn lookup(tool: &str, operation: &str) -> Option<&'static ToolTemplate> {
+    TEMPLATES
+        .iter()
+        .find(|t| t.tool == tool && t.operations.contains(&operation))
+}
+
+/// Whether an intent can be compiled at all.
+pub fn supports(tool: &str, operation: &str) -> bool {
+    (tool == "pkg" && PKG_OPERATIONS.contains(&operation)) || lookup(tool, operation).is_some()
+}
+
+/// `tool operation` for everything in the registry, for error messages.
+pub fn known() -> Vec<String> {
+    let mut known: Vec<String> = PKG_OPERATIONS.iter().map(|op| format!("pkg {}", op)).collect();
+    for t in TEMPLATES {
+        known.extend(t.operations.iter().map(|op| format!("{} {}", t.tool, op)));
+    }
+    known
+}
+
+fn intent_value(intent: &Intent, name: &str) -> Option<String> {
+    if name == "target" {
+        return intent.target.clone();
+    }
+    intent.slots.get(name).cloned()
+}
+
+/// Binds the intent's values to the template's slots and lays out argv.
+pub fn compile(template: &ToolTemplate, intent: &Intent, storage: &SmartStorage) -> Result<CommandAst, String> {
+    let mut bound: BTreeMap<&str, String> = BTreeMap::new();
+    for slot in template.slots {
+        let value = slot
+            .from
+            .iter()
+            .find_map(|name| intent_value(intent, name))
+            .or_else(|| slot.default.map(str::to_string));
+        match value {
+            Some(value) => {
+                let value = slot
+                    .kind
+                    .bind(&value, storage)
+                    .map_err(|e| format!("{} {}: {}", intent.tool, intent.operation, e))?;
+                bound.insert(slot.name, value);
+            }
+            None if slot.required => {
+                return Err(format!(
+                    "'{} {}' needs a {} ({})",
+                    intent.tool,
+                    intent.operation,
+                    slot.name,
+                    slot.kind.label()
+                ));
+            }
+            None => {}
+        }
+    }
+
+    let mut ast = CommandAst::new(&intent.tool, &intent.operation);
+    for token in template.argv {
+        if *token == "{options}" {
+            ast.args.push(Arg::Options);
+            continue;
+        }
+        let mut word = token.replace("{operation}", &intent.operation);
+        let mut complete = true;
+        for slot in template.slots {
+            let placeholder = format!("{{{}}}", slot.name);
+            if word.contains(&placeholder) {
+                match bound.get(slot.name) {
+                    Some(value) => word = word.replace(&placeholder, value),
+                    None => complete = false,
+                }
+            }
+        }
+        if complete {
+            ast.args.push(Arg::Word(word));
+        }
+    }
+    ast.source = bound.get("source").cloned();
+    ast.destination = bound.get("destination").cloned();
+    ast.target_server = bound.get("host").cloned();
+    Ok(ast)
+}
+
+/// `pkg install|remove` through `pm`, with the package map's name for that manager.
+pub fn compile_pkg(pm: &dyn PackageManager, intent: &Intent) -> Result<CommandAst, String> {
+    if intent.operation == "update" {
+        return Err("System upgrades run through `vega update --all`.".to_string());
+    }
+    if !PKG_OPERATIONS.contains(&intent.operation.as_str()) {
+        return Err(format!("No template for 'pkg {}'", intent.operation));
+    }
+    let requested = intent_value(intent, "package")
+        .or_else(|| intent.target.clone())
+        .ok_or_else(|| format!("'pkg {}' needs a package", intent.operation))?;
+    let package = SlotKind::Package.bind(&requested, &SmartStorage::with_aliases(Default::default()))?;
+    let package = PackageMap::global().package_for(&package, pm.kind()).unwrap_or(package);
+
+    let command = if intent.operation == "install" {
+        pm.install(&package)?
+    } else {
+        pm.remove(&package)?
+    };
+    let mut ast = CommandAst::new(&intent.tool, &intent.operation);
+    if command.sudo {
+        ast.args.push(Arg::Word("sudo".to_string()));
+    }
+    // The package is always the last word; options go right before it
+    let (last, head) = command.argv.split_last().ok_or("empty package command")?;
+    ast.args.extend(head.iter().cloned().map(Arg::Word));
+    ast.args.push(Arg::Options);
+    ast.args.push(Arg::Word(last.clone()));
+    Ok(ast)
+}
 
 pub struct BasicTemplateBuilder;
 
 impl TemplateBuilder for BasicTemplateBuilder {
     fn build(&self, intent: &Intent) -> anyhow::Result<CommandAst> {
-        let mut ast = CommandAst::new(&intent.tool, &intent.operation);
-        
-        match intent.tool.as_str() {
-            "rclone" => {
-                // Assume operation is sync or copy
-                if let Some(target) = &intent.target {
-                    ast.source = Some(".".to_string()); // Default to current dir for NL
-                    ast.destination = Some(format!("{}:backup", target));
-                }
-            },
-            "ssh" => {
-                if let Some(target) = &intent.target {
-                    ast.target_server = Some(target.clone());
-                }
-            },
-            "pkg" => {
-                if let Some(target) = &intent.target {
-                    ast.options.push(target.clone());
-                }
-            },
-            _ => {
-                // For unknown tools, just use the target as source if available
-                if let Some(target) = &intent.target {
-                    ast.source = Some(target.clone());
-                }
-            }
+        if intent.tool == "pkg" {
+            let ctx = SystemContext::collect();
+            let pm = pkg::detect(&ctx)
+                .ok_or_else(|| anyhow::anyhow!("No supported package manager found (detected: {})", ctx.pkg_manager))?;
+            return compile_pkg(pm.as_ref(), intent).map_err(anyhow::Error::msg);
+        }
+        let template = lookup(&intent.tool, &intent.operation).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No template for '{} {}'. Supported: {}",
+                intent.tool,
+                intent.operation,
+                known().join(", ")
+            )
+        })?;
+        compile(template, intent, &SmartStorage::new()).map_err(anyhow::Error::msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::pkg::{Apt, Nix};
+
+    fn intent(tool: &str, operation: &str, target: Option<&str>, slots: &[(&str, &str)]) -> Intent {
+        Intent {
+            tool: tool.to_string(),
+            operation: operation.to_string(),
+            target: target.map(str::to_string),
+            slots: slots.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
         }
-        
-        Ok(ast)
+    }
+
+    fn build(intent: &Intent, options: &[&str]) -> Result<Vec<String>, String> {
+        let storage = SmartStorage::with_aliases([("구드".to_string(), "gdrive:".to_string())].into());
+        let template = lookup(&intent.tool, &intent.operation).ok_or("unknown")?;
+        let mut ast = compile(template, intent, &storage)?;
+        ast.options = options.iter().map(|o| o.to_string()).collect();
+        Ok(ast.argv())
+    }
+
+    #[test]
+    fn templates_order_options_and_slots() {
+        let backup = intent("rclone", "sync", Some("구드"), &[("path", "thesis")]);
+        assert_eq!(
+            build(&backup, &["--progress"]).unwrap(),
+            ["rclone", "sync", "--progress", "thesis", "gdrive:backup"]
+        );
+        assert_eq!(
+            build(&intent("ssh", "connect", Some("ops@web1"), &[]), &["-p", "2222"]).unwrap(),
+            ["ssh", "-p", "2222", "ops@web1"]
+        );
+        assert_eq!(
+            build(&intent("systemctl", "restart", Some("nginx.service"), &[]), &[]).unwrap(),
+            ["sudo", "systemctl", "restart", "nginx.service"]
+        );
+        assert_eq!(
+            build(&intent("journalctl", "logs", None, &[]), &["-n", "50"]).unwrap(),
+            ["journalctl", "--no-pager", "-n", "50"]
+        );
+        assert_eq!(
+            build(&intent("tar", "create", Some("src"), &[("destination", "src.tar.gz")]), &[]).unwrap(),
+            ["tar", "-czf", "src.tar.gz", "src"]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_tools_and_bad_slot_values() {
+        assert!(lookup("curl", "get").is_none());
+        assert!(!supports("curl", "get"));
+        assert!(supports("pkg", "install"));
+        assert!(!supports("pkg", "update"));
+
+        let err = build(&intent("ssh", "connect", Some("-oProxyCommand=sh"), &[]), &[]).unwrap_err();
+        assert!(err.contains("looks like an option"));
+        assert!(build(&intent("ssh", "connect", Some("web1; rm -rf ~"), &[]), &[]).is_err());
+        assert!(build(&intent("docker", "logs", None, &[]), &[]).unwrap_err().contains("needs a container"));
+    }
+
+    #[test]
+    fn pkg_goes_through_the_package_manager() {
+        let mut ast = compile_pkg(&Apt, &intent("pkg", "install", Some("fd"), &[])).unwrap();
+        ast.options.push("--no-install-recommends".to_string());
+        assert_eq!(
+            ast.argv(),
+            ["sudo", "apt", "install", "-y", "--no-install-recommends", "fd-find"]
+        );
+        assert!(compile_pkg(&Apt, &intent("pkg", "update", None, &[])).is_err());
+
+        let nix = compile_pkg(&Nix::detect(), &intent("pkg", "remove", Some("htop"), &[])).unwrap();
+        assert_eq!(nix.argv().last().map(String::as_str), Some("htop"));
     }
 }
@@ -0,0 +1,651 @@
+use crate::context::SystemContext;
+use crate::executor::pkg::{self, PackageManager, PackageSystem, PkgCommand};
+use crate::knowledge::KnowledgeEntry;
+use crate::storage::db::Database;
+use crate::storage::events::{Event, EventKind};
+use crate::system::probe::Probe;
+use serde::Serialize;
+use serde_json::json;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+/// Free space (MB) a mount point needs before an upgrade is started.
+const MIN_FREE_MB: [(&str, u64); 2] = [("/", 1024), ("/boot", 100)];
+
+const DISK_SCRIPT: &str = "df -Pm / $(test -d /boot && echo /boot)";
+const LOCK_SCRIPT: &str =
+    "pgrep -a -x 'apt|apt-get|aptitude|dpkg|unattended-upgr|dnf|dnf5|yum|rpm|pacman|zypper|apk'";
+const PACMAN_LOCK_SCRIPT: &str = "test -e /var/lib/pacman/db.lck";
+const MANAGER_SCRIPT: &str =
+    "for m in apt dnf pacman zypper apk; do command -v $m >/dev/null 2>&1 && echo $m && break; done";
+const REBOOT_FILE_SCRIPT: &str =
+    "test -e /var/run/reboot-required && { cat /var/run/reboot-required.pkgs 2>/dev/null; true; }";
+const NEEDS_RESTARTING_SCRIPT: &str = "needs-restarting -r";
+const KERNEL_SCRIPT: &str = "test ! -d /usr/lib/modules || test -d /usr/lib/modules/$(uname -r)";
+const FAILED_UNITS_SCRIPT: &str = "systemctl --failed --no-legend --plain";
+
+/// Where an update runs: this machine, or a knowledge-base host over SSH.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Host {
+    Local,
+    Remote {
+        name: String,
+        target: String,
+        port: Option<u16>,
+    },
+}
+
+impl Host {
+    pub fn remote(name: &str, entry: &KnowledgeEntry) -> Self {
+        let target = match &entry.user {
+            Some(user) => format!("{}@{}", user, entry.ip),
+            None => entry.ip.clone(),
+        };
+        Host::Remote {
+            name: name.to_string(),
+            target,
+            port: entry.port,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Host::Local => "localhost",
+            Host::Remote { name, .. } => name,
+        }
+    }
+
+    fn ssh_args(target: &str, port: Option<u16>) -> Vec<String> {
+        let mut args: Vec<String> = ["-o", "BatchMode=yes", "-o", "ConnectTimeout=10", "-o", "StrictHostKeyChecking=no"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        if let Some(port) = port.filter(|p| *p != 22) {
+            args.push("-p".to_string());
+            args.push(port.to_string());
+        }
+        args.push(target.to_string());
+        args
+    }
+
+    /// Exit code and stdout of a read-only shell snippet, `None` when it cannot be run.
+    fn run(&self, script: &str) -> Option<(Option<i32>, String)> {
+        let probe = match self {
+            Host::Local => Probe::new("sh").arg("-c").arg(script),
+            Host::Remote { target, port, .. } => Probe::new("ssh").args(Self::ssh_args(target, *port)).arg(script),
+        };
+        let output = probe.output().ok()?;
+        Some((output.status.code(), String::from_utf8_lossy(&output.stdout).to_string()))
+    }
+
+    /// Stdout of a read-only shell snippet, `None` when it fails.
+    fn query(&self, script: &str) -> Option<String> {
+        match self.run(script)? {
+            (Some(0), stdout) => Some(stdout),
+            _ => None,
+        }
+    }
+
+    /// Runs `cmd`, echoing its output as it arrives. Returns the exit code and stdout.
+    fn stream(&self, cmd: &str) -> (Option<i32>, String) {
+        let mut command = match self {
+            Host::Local => {
+                let mut c = Command::new("sh");
+                c.arg("-c").arg(cmd);
+                c
+            }
+            Host::Remote { target, port, .. } => {
+                let mut c = Command::new("ssh");
+                // No stdin: a prompt on the far side gets EOF instead of hanging the roll
+                c.args(Self::ssh_args(target, *port)).arg(cmd).stdin(Stdio::null());
+                c
+            }
+        };
+        let mut child = match command.stdout(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) => return (None, e.to_string()),
+        };
+        let mut output = String::new();
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                println!("   │ {}", line);
+                output.push_str(&line);
+                output.push('\n');
+            }
+        }
+        let code = child.wait().ok().and_then(|s| s.code());
+        (code, output)
+    }
+
+    pub fn reboot(&self) {
+        // The connection drops mid-command, so the exit code says nothing
+        let _ = self.stream("sudo -n systemctl reboot");
+    }
+
+    /// The distro package manager on the host.
+    pub fn package_manager(&self) -> Option<Box<dyn PackageManager>> {
+        match self {
+            Host::Local => pkg::detect(&SystemContext::collect()),
+            Host::Remote { .. } => {
+                let name = self.query(MANAGER_SCRIPT)?;
+                PackageSystem::parse(name.trim()).map(|kind| kind.backend())
+            }
+        }
+    }
+
+    /// Whether the host answers and which systemd units have failed there.
+    pub fn health(&self) -> Health {
+        let reachable = self.query("true").is_some();
+        let failed_units = if reachable {
+            self.query(FAILED_UNITS_SCRIPT)
+                .map(|out| parse_failed_units(&out))
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        Health {
+            reachable,
+            failed_units,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Health {
+    pub reachable: bool,
+    pub failed_units: Vec<String>,
+}
+
+impl Health {
+    /// Units failing now that were fine in `before`.
+    pub fn new_failures(&self, before: &Health) -> Vec<String> {
+        self.failed_units
+            .iter()
+            .filter(|u| !before.failed_units.contains(u))
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateOptions {
+    pub dry_run: bool,
+    /// Run even when a pre-flight check fails.
+    pub force: bool,
+    /// Reboot fleet hosts that need it before moving on.
+    pub reboot: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskSpace {
+    pub mount: String,
+    pub available_mb: u64,
+}
+
+/// What the host looks like before anything is changed.
+#[derive(Debug, Clone, Default)]
+pub struct Preflight {
+    pub disks: Vec<DiskSpace>,
+    /// Packages pinned by the manager; they stay at their version.
+    pub held: Vec<String>,
+    /// Package-manager processes or lock files in the way.
+    pub locks: Vec<String>,
+}
+
+impl Preflight {
+    pub fn run(host: &Host, kind: PackageSystem) -> Self {
+        let disks = host.query(DISK_SCRIPT).map(|out| parse_df(&out)).unwrap_or_default();
+        let held = held_script(kind)
+            .and_then(|script| host.query(script))
+            .map(|out| parse_held(kind, &out))
+            .unwrap_or_default();
+        let mut locks: Vec<String> = host
+            .query(LOCK_SCRIPT)
+            .map(|out| out.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+            .unwrap_or_default();
+        if kind == PackageSystem::Pacman && host.query(PACMAN_LOCK_SCRIPT).is_some() {
+            locks.push("/var/lib/pacman/db.lck".to_string());
+        }
+        Preflight { disks, held, locks }
+    }
+
+    /// Reasons not to start the upgrade.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (mount, min) in MIN_FREE_MB {
+            if let Some(disk) = self.disks.iter().find(|d| d.mount == mount) {
+                if disk.available_mb < min {
+                    problems.push(format!(
+                        "Only {} MB free on {} (need {} MB)",
+                        disk.available_mb, mount, min
+                    ));
+                }
+            }
+        }
+        if !self.locks.is_empty() {
+            problems.push(format!("Package manager busy: {}", self.locks.join(", ")));
+        }
+        problems
+    }
+
+    pub fn print(&self) {
+        for disk in &self.disks {
+            println!("   💽 {:<6} {} MB free", disk.mount, disk.available_mb);
+        }
+        if !self.held.is_empty() {
+            println!("   📌 Held back: {}", self.held.join(", "));
+        }
+        for problem in self.problems() {
+            println!("   ⚠️  {}", problem);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Upgrade {
+    pub name: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateReport {
+    pub host: String,
+    pub manager: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub upgrades: Vec<Upgrade>,
+    /// Why a reboot is needed, `None` when it is not.
+    pub reboot: Option<String>,
+}
+
+impl UpdateReport {
+    pub fn print(&self) {
+        if !self.success {
+            println!("❌ Update failed on {} (exit code {:?}).", self.host, self.exit_code);
+            return;
+        }
+        if self.upgrades.is_empty() {
+            println!("✅ {} is up to date ({}).", self.host, self.manager);
+        } else {
+            println!("📦 {} package(s) upgraded on {}:", self.upgrades.len(), self.host);
+            for u in &self.upgrades {
+                match (&u.from, &u.to) {
+                    (Some(from), Some(to)) => println!("   {} {} → {}", u.name, from, to),
+                    (None, Some(to)) => println!("   {} → {}", u.name, to),
+                    _ => println!("   {}", u.name),
+                }
+            }
+        }
+        match &self.reboot {
+            Some(reason) => println!("🔁 Reboot required: {}", reason),
+            None => println!("✅ No reboot required."),
+        }
+    }
+}
+
+/// Pre-flight checks, the distro upgrade, its parsed summary and the reboot check for one host.
+pub fn update_host(host: &Host, opts: &UpdateOptions) -> Result<UpdateReport, String> {
+    let pm = host
+        .package_manager()
+        .ok_or_else(|| format!("No supported package manager found on {}.", host.label()))?;
+    println!("🛠️  Updating {} via {}", host.label(), pm.name());
+
+    let preflight = Preflight::run(host, pm.kind());
+    preflight.print();
+    let problems = preflight.problems();
+    if !problems.is_empty() && !opts.force {
+        return Err(format!("Pre-flight failed on {}: {} (use --force to override)", host.label(), problems.join("; ")));
+    }
+
+    let steps = pm.update();
+    let cmd = match host {
+        Host::Local => PkgCommand::join(&steps),
+        Host::Remote { .. } => steps.iter().map(|s| s.to_remote_shell()).collect::<Vec<_>>().join(" && "),
+    };
+    println!("🚀 Proposed Command: {}", cmd);
+    if opts.dry_run {
+        println!("🛑 Dry-Run: Execution Skipped.");
+        return Ok(UpdateReport {
+            host: host.label().to_string(),
+            manager: pm.kind().as_str().to_string(),
+            success: true,
+            exit_code: None,
+            upgrades: Vec::new(),
+            reboot: None,
+        });
+    }
+
+    println!("⚡ Executing...");
+    let (exit_code, output) = host.stream(&cmd);
+    let success = exit_code == Some(0);
+    let report = UpdateReport {
+        host: host.label().to_string(),
+        manager: pm.kind().as_str().to_string(),
+        success,
+        exit_code,
+        upgrades: parse_upgrades(pm.kind(), &output),
+        reboot: if success { reboot_required(host, pm.kind()) } else { None },
+    };
+
+    let event = Event::new(EventKind::Execution, &cmd)
+        .with_result(success, exit_code)
+        .with_payload(json!({
+            "action": "update",
+            "host": report.host,
+            "manager": report.manager,
+            "upgrades": report.upgrades,
+            "held": preflight.held,
+            "reboot_required": report.reboot,
+        }));
+    if let Ok(db) = Database::new() {
+        let _ = db.record_event(&event);
+    }
+    Ok(report)
+}
+
+/// Why the host needs a reboot: the Debian marker file, `needs-restarting -r` on the
+/// RPM side, or (Arch) a running kernel whose modules were upgraded away.
+pub fn reboot_required(host: &Host, kind: PackageSystem) -> Option<String> {
+    if let Some(pkgs) = host.query(REBOOT_FILE_SCRIPT) {
+        let pkgs: Vec<&str> = pkgs.split_whitespace().collect();
+        return Some(if pkgs.is_empty() {
+            "/var/run/reboot-required".to_string()
+        } else {
+            format!("/var/run/reboot-required ({})", pkgs.join(", "))
+        });
+    }
+    if matches!(kind, PackageSystem::Dnf | PackageSystem::Zypper) {
+        // Exit code 1 means "reboot needed"; anything else is "no" or "not installed"
+        if let Some((Some(1), _)) = host.run(NEEDS_RESTARTING_SCRIPT) {
+            return Some("needs-restarting -r".to_string());
+        }
+    }
+    if kind == PackageSystem::Pacman {
+        if let Some((Some(1), _)) = host.run(KERNEL_SCRIPT) {
+            return Some("running kernel was upgraded".to_string());
+        }
+    }
+    None
+}
+
+fn held_script(kind: PackageSystem) -> Option<&'static str> {
+    match kind {
+        PackageSystem::Apt => Some("apt-mark showhold"),
+        PackageSystem::Dnf => Some("dnf versionlock list -q"),
+        PackageSystem::Pacman => Some("grep -E '^[[:space:]]*IgnorePkg' /etc/pacman.conf"),
+        PackageSystem::Zypper => Some("zypper --non-interactive --quiet locks"),
+        PackageSystem::Apk => Some("grep -E '[=<>~]' /etc/apk/world"),
+        _ => None,
+    }
+}
+
+// --- Output parsers ---
+
+/// `df -Pm`: `/dev/sda2  100000  50000  45000  53% /`.
+pub fn parse_df(out: &str) -> Vec<DiskSpace> {
+    let mut disks: Vec<DiskSpace> = Vec::new();
+    for line in out.lines().skip(1) {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 6 {
+            continue;
+        }
+        if let Ok(available_mb) = cols[3].parse() {
+            let mount = cols[5..].join(" ");
+            if !disks.iter().any(|d| d.mount == mount) {
+                disks.push(DiskSpace { mount, available_mb });
+            }
+        }
+    }
+    disks
+}
+
+pub fn parse_held(kind: PackageSystem, out: &str) -> Vec<String> {
+    let lines = out.lines().map(str::trim).filter(|l| !l.is_empty());
+    match kind {
+        // `IgnorePkg = linux linux-headers`
+        PackageSystem::Pacman => lines
+            .filter_map(|l| l.split_once('=').map(|(_, v)| v))
+            .flat_map(|v| v.split_whitespace().map(str::to_string))
+            .collect(),
+        // `1 | kernel-default | package | (any)`
+        PackageSystem::Zypper => lines
+            .filter_map(|l| {
+                let cols: Vec<&str> = l.split('|').map(str::trim).collect();
+                (cols.len() >= 2 && cols[0].parse::<u32>().is_ok()).then(|| cols[1].to_string())
+            })
+            .collect(),
+        _ => lines
+            .filter(|l| !l.starts_with('#') && !l.starts_with("Last metadata"))
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// `systemctl --failed --no-legend --plain`: `nginx.service loaded failed failed ...`.
+pub fn parse_failed_units(out: &str) -> Vec<String> {
+    out.lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Packages an upgrade run reports as upgraded.
+pub fn parse_upgrades(kind: PackageSystem, out: &str) -> Vec<Upgrade> {
+    match kind {
+        PackageSystem::Apt => parse_apt_upgrades(out),
+        PackageSystem::Dnf => parse_dnf_upgrades(out),
+        PackageSystem::Pacman => parse_pacman_upgrades(out),
+        PackageSystem::Zypper => parse_zypper_upgrades(out),
+        PackageSystem::Apk => parse_apk_upgrades(out),
+        _ => Vec::new(),
+    }
+}
+
+/// `Unpacking libc6:amd64 (2.39-0ubuntu8.3) over (2.39-0ubuntu8.2) ...`; new installs
+/// have no `over` and are skipped.
+fn parse_apt_upgrades(out: &str) -> Vec<Upgrade> {
+    out.lines()
+        .filter_map(|line| {
+            let (name, rest) = line.strip_prefix("Unpacking ")?.split_once(" (")?;
+            let (to, rest) = rest.split_once(')')?;
+            let (_, from) = rest.split_once("over (")?;
+            let from = from.split_once(')')?.0;
+            Some(Upgrade {
+                name: name.split(':').next().unwrap_or(name).to_string(),
+                from: Some(from.to_string()),
+                to: Some(to.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// The `Upgrading:` transaction table (dnf4 and dnf5); dnf5 adds `replacing` rows
+/// with the old version.
+fn parse_dnf_upgrades(out: &str) -> Vec<Upgrade> {
+    let mut upgrades: Vec<Upgrade> = Vec::new();
+    let mut in_table = false;
+    for line in out.lines() {
+        if line.trim() == "Upgrading:" {
+            in_table = true;
+            continue;
+        }
+        if !in_table {
+            continue;
+        }
+        if !line.starts_with(' ') || line.trim().is_empty() {
+            in_table = false;
+            continue;
+        }
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols[0] == "replacing" {
+            if let (Some(last), Some(from)) = (upgrades.last_mut(), cols.get(3)) {
+                last.from = Some(from.to_string());
+            }
+        } else if cols.len() >= 3 {
+            upgrades.push(Upgrade {
+                name: cols[0].to_string(),
+                from: None,
+                to: Some(cols[2].to_string()),
+            });
+        }
+    }
+    upgrades
+}
+
+/// `Packages (2) bash-5.2.026-2  curl-8.8.0-1`, wrapped onto indented lines when long.
+fn parse_pacman_upgrades(out: &str) -> Vec<Upgrade> {
+    let mut tokens = Vec::new();
+    let mut lines = out.lines();
+    while let Some(line) = lines.next() {
+        if let Some(rest) = line.strip_prefix("Packages (") {
+            tokens.extend(rest.split_whitespace().skip(1));
+            for cont in lines.by_ref() {
+                if !cont.starts_with(' ') || cont.trim().is_empty() {
+                    break;
+                }
+                tokens.extend(cont.split_whitespace());
+            }
+            break;
+        }
+    }
+    tokens
+        .into_iter()
+        .filter_map(|nv| {
+            let parts: Vec<&str> = nv.rsplitn(3, '-').collect();
+            (parts.len() == 3).then(|| Upgrade {
+                name: parts[2].to_string(),
+                from: None,
+                to: Some(format!("{}-{}", parts[1], parts[0])),
+            })
+        })
+        .collect()
+}
+
+/// `The following 2 packages are going to be upgraded:` followed by indented names.
+fn parse_zypper_upgrades(out: &str) -> Vec<Upgrade> {
+    let mut upgrades = Vec::new();
+    let mut lines = out.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with("The following") && line.trim_end().ends_with("going to be upgraded:") {
+            for cont in lines.by_ref() {
+                if !cont.starts_with(' ') || cont.trim().is_empty() {
+                    break;
+                }
+                upgrades.extend(cont.split_whitespace().map(|name| Upgrade {
+                    name: name.to_string(),
+                    from: None,
+                    to: None,
+                }));
+            }
+        }
+    }
+    upgrades
+}
+
+/// `(1/3) Upgrading musl (1.2.4-r1 -> 1.2.4-r2)`.
+fn parse_apk_upgrades(out: &str) -> Vec<Upgrade> {
+    out.lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(") Upgrading ")?;
+            let (name, versions) = rest.split_once(" (")?;
+            let (from, to) = versions.trim_end_matches(')').split_once(" -> ")?;
+            Some(Upgrade {
+                name: name.to_string(),
+                from: Some(from.to_string()),
+                to: Some(to.to_string()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::probe::{with_bundle, Bundle, RecordedCommand};
+
+    fn exited(script: &str, status: i32) -> RecordedCommand {
+        RecordedCommand {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            status: Some(status),
+            stdout: String::new(),
+            stderr: String::new(),
+            spawn_error: None,
+        }
+    }
+
+    #[test]
+    fn parses_upgrade_summaries() {
+        let apt = "Preparing to unpack .../libc6_2.39-0ubuntu8.3_amd64.deb ...\n\
+                   Unpacking libc6:amd64 (2.39-0ubuntu8.3) over (2.39-0ubuntu8.2) ...\n\
+                   Unpacking linux-image-6.8.0-45-generic (6.8.0-45.45) ...\n";
+        assert_eq!(
+            parse_upgrades(PackageSystem::Apt, apt),
+            vec![Upgrade {
+                name: "libc6".to_string(),
+                from: Some("2.39-0ubuntu8.2".to_string()),
+                to: Some("2.39-0ubuntu8.3".to_string()),
+            }]
+        );
+
+        let dnf5 = "Transaction Summary:\nUpgrading:\n bash   x86_64 5.2.26-1.fc40 updates 8.1 MiB\n   replacing bash x86_64 5.2.21-1.fc40 fedora 8.1 MiB\n curl x86_64 8.6.0-10.fc40 updates 0.5 MiB\n\nComplete!\n";
+        let dnf = parse_upgrades(PackageSystem::Dnf, dnf5);
+        assert_eq!(dnf.len(), 2);
+        assert_eq!(dnf[0].from.as_deref(), Some("5.2.21-1.fc40"));
+        assert_eq!(dnf[1].to.as_deref(), Some("8.6.0-10.fc40"));
+
+        let pacman = "Packages (3) bash-5.2.026-2  curl-8.8.0-1\n             linux-6.9.7.arch1-1\n\nTotal Download Size: 3.1 MiB\n";
+        let names: Vec<String> = parse_upgrades(PackageSystem::Pacman, pacman).into_iter().map(|u| u.name).collect();
+        assert_eq!(names, ["bash", "curl", "linux"]);
+
+        let zypper = "The following 2 packages are going to be upgraded:\n  curl libcurl4\n\n2 packages to upgrade.\n";
+        assert_eq!(parse_upgrades(PackageSystem::Zypper, zypper).len(), 2);
+
+        let apk = "(1/2) Upgrading musl (1.2.4-r1 -> 1.2.4-r2)\n(2/2) Installing htop (3.3.0-r0)\nOK: 12 MiB in 20 packages\n";
+        let apk = parse_upgrades(PackageSystem::Apk, apk);
+        assert_eq!(apk.len(), 1);
+        assert_eq!(apk[0].to.as_deref(), Some("1.2.4-r2"));
+    }
+
+    #[test]
+    fn preflight_flags_low_disk_and_running_managers() {
+        let df = "Filesystem 1048576-blocks Used Available Capacity Mounted on\n\
+                  /dev/sda2 100000 50000 45000 53% /\n\
+                  /dev/sda1 500 450 50 90% /boot\n";
+        let bundle = Bundle::new()
+            .with_command("sh", &["-c", DISK_SCRIPT], df)
+            .with_command("sh", &["-c", "apt-mark showhold"], "linux-image-generic\n")
+            .with_command("sh", &["-c", LOCK_SCRIPT], "4242 /usr/bin/dpkg --configure -a\n");
+        let preflight = with_bundle(bundle, || Preflight::run(&Host::Local, PackageSystem::Apt));
+        assert_eq!(preflight.held, ["linux-image-generic"]);
+        let problems = preflight.problems();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("/boot"));
+        assert!(problems[1].contains("dpkg"));
+
+        assert_eq!(parse_held(PackageSystem::Pacman, "IgnorePkg   = linux linux-headers\n"), ["linux", "linux-headers"]);
+        assert_eq!(
+            parse_held(PackageSystem::Zypper, "# | Name | Type | Repository\n--+------+------+-----\n1 | kernel-default | package | (any)\n"),
+            ["kernel-default"]
+        );
+    }
+
+    #[test]
+    fn detects_pending_reboots() {
+        let debian = Bundle::new().with_command("sh", &["-c", REBOOT_FILE_SCRIPT], "linux-image-6.8.0-45-generic\n");
+        let reason = with_bundle(debian, || reboot_required(&Host::Local, PackageSystem::Apt));
+        assert_eq!(reason.as_deref(), Some("/var/run/reboot-required (linux-image-6.8.0-45-generic)"));
+
+        let mut fedora = Bundle::new();
+        fedora.commands.push(exited(REBOOT_FILE_SCRIPT, 1));
+        fedora.commands.push(exited(NEEDS_RESTARTING_SCRIPT, 1));
+        let reason = with_bundle(fedora.clone(), || reboot_required(&Host::Local, PackageSystem::Dnf));
+        assert_eq!(reason.as_deref(), Some("needs-restarting -r"));
+
+        let mut current = Bundle::new();
+        current.commands.push(exited(REBOOT_FILE_SCRIPT, 1));
+        current.commands.push(exited(NEEDS_RESTARTING_SCRIPT, 0));
+        assert_eq!(with_bundle(current, || reboot_required(&Host::Local, PackageSystem::Dnf)), None);
+    }
+}
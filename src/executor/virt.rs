@@ -1,5 +1,6 @@
 use crate::executor::pipeline::{SimLog, VirtualExecutionEngine};
 use crate::executor::ast::CommandAst;
+use crate::safety::{flags, RiskLevel};
 
 pub struct BasicVee;
 
@@ -48,7 +49,9 @@ impl VirtualExecutionEngine for BasicVee {
         // 5. State-based Path Verification
         if let Some(src) = &ast.source {
             let path = std::path::Path::new(src);
-            if !path.exists() && ast.tool != "ssh" { // ssh target is remote, don't check locally
+            // `host:path` / `remote:path` sources (scp, rsync) are not local either
+            let remote_spec = src.split_once(':').is_some_and(|(head, _)| !head.is_empty() && !head.contains('/'));
+            if !path.exists() && ast.tool != "ssh" && !remote_spec { // ssh target is remote, don't check locally
                 sim_log.is_safe = false;
                 sim_log.risk_score = 40;
                 sim_log.predicted_impact = format!("ERROR: Source path '{}' does not exist.", src);
@@ -66,6 +69,35 @@ impl VirtualExecutionEngine for BasicVee {
             }
         }
 
+        // 7. Destructive flags, as tagged by the option validator
+        for tag in flags::destructive(ast) {
+            let score = match tag.risk {
+                RiskLevel::Critical => 90,
+                RiskLevel::Warning => 60,
+                RiskLevel::Info => 0,
+            };
+            sim_log.risk_score = sim_log.risk_score.max(score);
+            sim_log
+                .predicted_impact
+                .push_str(&format!(" ({} {})", tag.flag, tag.reason));
+        }
+
+        // 8. Flags that run other programs never pass, whatever produced them
+        if let Some(flag) = flags::command_running(ast) {
+            sim_log.is_safe = false;
+            sim_log.risk_score = 100;
+            sim_log.predicted_impact = format!("CRITICAL: {}", flag);
+            sim_log.suggestion = Some("Drop the option; Vega does not run commands through tool flags.".to_string());
+        }
+
+        // 9. Shell opt-in: the fragment is run by sh, outside the argv checks above
+        if let Some(opt_in) = &ast.shell {
+            sim_log.risk_score = sim_log.risk_score.max(30);
+            sim_log
+                .predicted_impact
+                .push_str(&format!(" (via sh -c: `{}`)", opt_in.fragment));
+        }
+
         Ok(sim_log)
     }
 }
@@ -17,7 +17,10 @@ pub fn bootstrap() -> Result<VegaConfig, Box<dyn std::error::Error>> {
         println!("   - Git User: {}", ctx.git_user);
         
         // 2. Create Default Config
-        let config = VegaConfig::default();
+        let config = VegaConfig {
+            aliases: Some(crate::system::storage::default_aliases()),
+            ..Default::default()
+        };
         // pre-populate based on scan?
         // For now just basic default + save it.
         
@@ -12,6 +12,10 @@ pub struct KnowledgeEntry {
     pub port: Option<u16>,
     pub os_type: Option<String>, // Added for detection optimization
     pub last_success: String,
+    #[serde(default)]
+    pub mac: Option<String>, // VM NIC, used to find the guest again after its IP changes
+    #[serde(default)]
+    pub ip_source: Option<String>, // how the IP was resolved (agent, lease, arp, ...)
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -77,7 +81,7 @@ impl KnowledgeBase {
         self.targets.get(key)
     }
 
-    fn get_path() -> PathBuf {
+    pub fn get_path() -> PathBuf {
         let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
         path.push("vega");
         path.push("knowledge.json");
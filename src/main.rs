@@ -17,7 +17,6 @@ use crate::knowledge::{KnowledgeBase, KnowledgeEntry};
 use crate::system::virt::VmScanner;
 
 use crate::system::healer::Healer;
-use crate::system::storage::SmartStorage;
 use crate::system::virt::VmController;
 
 #[tokio::main]
@@ -34,6 +33,26 @@ async fn main() {
     let full_input = args[1..].join(" ");
     let full_input = full_input.trim();
 
+    // Storage policy (redaction, truncation, retention) applies to every command
+    let storage_config = config::VegaConfig::load(&init::get_config_path().to_string_lossy())
+        .ok()
+        .and_then(|c| c.storage);
+    crate::storage::retention::configure(storage_config.as_ref());
+
+    // Probe replay: serve system probes from a bundle made by `vega debug capture`
+    if let Ok(path) = env::var("VEGA_REPLAY") {
+        match crate::system::probe::Bundle::load(&path) {
+            Ok(bundle) => {
+                eprintln!("🎞️  Replaying system probes from {} (captured {})", path, bundle.captured_at);
+                crate::system::probe::replay(bundle);
+            }
+            Err(e) => {
+                eprintln!("❌ VEGA_REPLAY: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     if input == "setup" {
         if args.contains(&"--cookie".to_string()) {
             SetupWizard::setup_cookie();
@@ -74,21 +93,51 @@ async fn main() {
         return;
     }
 
+    // System update: vega update --all [--hosts a,b | --fleet] [--reboot] [--force] [--dry-run]
     if input == "update" {
         if args.contains(&"--all".to_string()) {
-            println!("🛠️  [SRE Fallback] Performing System Update...");
-            let status = Command::new("sudo").arg("apt").arg("update").status();
-
-            if status.is_ok() {
-                let _ = Command::new("sudo")
-                    .arg("apt")
-                    .arg("upgrade")
-                    .arg("-y")
-                    .status();
+            use crate::executor::update::{self, Host, UpdateOptions};
+
+            let opts = UpdateOptions {
+                dry_run: args.contains(&"--dry-run".to_string()),
+                force: args.contains(&"--force".to_string()),
+                reboot: args.contains(&"--reboot".to_string()),
+            };
+            let kb = KnowledgeBase::load();
+            let hosts: Option<Vec<String>> = if args.contains(&"--fleet".to_string()) {
+                let mut names: Vec<String> = kb.targets.keys().cloned().collect();
+                names.sort();
+                Some(names)
+            } else {
+                args.iter()
+                    .position(|a| a == "--hosts")
+                    .and_then(|p| args.get(p + 1))
+                    .map(|list| list.split(',').map(|h| h.trim().to_string()).filter(|h| !h.is_empty()).collect())
+            };
+
+            match hosts {
+                Some(hosts) if hosts.is_empty() => println!("❌ No hosts to update."),
+                Some(hosts) => {
+                    println!("🌐 Rolling update across {} host(s): {}", hosts.len(), hosts.join(", "));
+                    let reports = executor::orchestrator::update_all(&kb, &hosts, &opts).await;
+                    let reboots: Vec<&str> = reports
+                        .iter()
+                        .filter(|r| r.reboot.is_some())
+                        .map(|r| r.host.as_str())
+                        .collect();
+                    println!("\n📋 Updated {}/{} host(s).", reports.len(), hosts.len());
+                    if !reboots.is_empty() && !opts.reboot {
+                        println!("🔁 Awaiting reboot: {} (rerun with --reboot)", reboots.join(", "));
+                    }
+                }
+                None => match update::update_host(&Host::Local, &opts) {
+                    Ok(report) if !opts.dry_run => report.print(),
+                    Ok(_) => {}
+                    Err(e) => println!("❌ {}", e),
+                },
             }
-            println!("✅ System update attempt complete.");
         } else {
-            println!("⚠️  Usage: vega update --all");
+            println!("⚠️  Usage: vega update --all [--hosts a,b | --fleet] [--reboot] [--force] [--dry-run]");
         }
         return;
     }
@@ -102,8 +151,210 @@ async fn main() {
         return;
     }
 
+    // Database maintenance: vega db status|migrate|backup [path]
+    if input == "db" {
+        use crate::storage::db::Database;
+        use crate::storage::migrations;
+
+        let sub = args.get(2).map(|s| s.as_str()).unwrap_or("status");
+        // status/migrate/backup look at the file as it is; scrub/prune need the current schema
+        let opened = match sub {
+            "scrub" | "prune" => Database::new(),
+            _ => Database::open_raw(),
+        };
+        let db = match opened {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+
+        match sub {
+            "status" => {
+                let current = db.schema_version().unwrap_or(0);
+                println!("🗄️  Database: {}", db.path().display());
+                println!("   Schema Version: v{} (latest v{})", current, migrations::latest_version());
+                let pending: Vec<_> = migrations::MIGRATIONS
+                    .iter()
+                    .filter(|m| m.version > current)
+                    .collect();
+                if pending.is_empty() {
+                    println!("   ✅ Up to date.");
+                } else {
+                    println!("   ⏳ Pending:");
+                    for m in pending {
+                        println!("      v{} - {}", m.version, m.description);
+                    }
+                }
+                if let Ok(counts) = migrations::table_counts(&db.connection()) {
+                    println!("   Tables:");
+                    for (name, count) in counts {
+                        println!("      {:<20} {}", name, count);
+                    }
+                }
+            }
+            "migrate" => match db.run_migrations() {
+                Ok(applied) if applied.is_empty() => println!("✅ Schema already up to date."),
+                Ok(applied) => println!("✅ Applied migrations: {:?}", applied),
+                Err(e) => eprintln!("❌ Migration Failed: {}", e),
+            },
+            "backup" => {
+                let result = match args.get(3) {
+                    Some(dest) => migrations::backup_to(&db.connection(), std::path::Path::new(dest))
+                        .map(|_| std::path::PathBuf::from(dest)),
+                    None => db.backup("manual"),
+                };
+                match result {
+                    Ok(path) => println!("✅ Backup written: {}", path.display()),
+                    Err(e) => eprintln!("❌ Backup Failed: {}", e),
+                }
+            }
+            "scrub" => match db.scrub_stored() {
+                Ok(n) => println!("✅ Redacted/truncated {} stored entries.", n),
+                Err(e) => eprintln!("❌ Scrub Failed: {}", e),
+            },
+            "prune" => match db.apply_retention(crate::storage::retention::policy()) {
+                Ok(r) => {
+                    let _ = db.set_metadata(
+                        crate::storage::retention::LAST_RUN_KEY,
+                        &crate::storage::events::now_secs().to_string(),
+                    );
+                    println!(
+                        "✅ Retention applied: {} expired, {} trimmed ({} KB -> {} KB).",
+                        r.expired,
+                        r.trimmed,
+                        r.size_before / 1024,
+                        r.size_after / 1024
+                    );
+                    if r.over_budget {
+                        println!("⚠️  History still exceeds max_db_size_mb with every event trimmed; raise the cap.");
+                    }
+                }
+                Err(e) => eprintln!("❌ Retention Failed: {}", e),
+            },
+            _ => println!("⚠️  Usage: vega db [status|migrate|backup [path]|scrub|prune]"),
+        }
+        return;
+    }
+
+    // History: vega history [--markdown <YYYY-MM-DD> | --ndjson <YYYY-MM>]
     if input == "history" {
-        // ... (existing history code)
+        let db = match crate::storage::db::Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+        let flag_value = |flag: &str| {
+            args.iter()
+                .position(|a| a == flag)
+                .and_then(|p| args.get(p + 1))
+                .cloned()
+        };
+
+        if let Some(date) = flag_value("--markdown") {
+            match chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
+                Ok(d) => match crate::system::archivist::Archivist::export_daily_report(&db, d) {
+                    Ok(path) => println!("✅ Markdown view written: {}", path.display()),
+                    Err(e) => eprintln!("❌ Export Failed: {}", e),
+                },
+                Err(_) => println!("⚠️  Usage: vega history --markdown <YYYY-MM-DD>"),
+            }
+        } else if let Some(month) = flag_value("--ndjson") {
+            let parsed = chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d");
+            match parsed {
+                Ok(d) => {
+                    use chrono::Datelike;
+                    match crate::system::archivist::Archivist::export_ndjson(&db, d.year(), d.month()) {
+                        Ok(path) => println!("✅ NDJSON view written: {}", path.display()),
+                        Err(e) => eprintln!("❌ Export Failed: {}", e),
+                    }
+                }
+                Err(_) => println!("⚠️  Usage: vega history --ndjson <YYYY-MM>"),
+            }
+        } else {
+            match db.get_events(None, None, 20) {
+                Ok(events) => {
+                    println!("📜 Event Log (Last {}):", events.len());
+                    for e in events {
+                        let time = chrono::DateTime::from_timestamp(e.timestamp, 0)
+                            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                            .unwrap_or_default();
+                        let status = match e.success {
+                            Some(true) => "✅",
+                            Some(false) => "❌",
+                            None => "  ",
+                        };
+                        println!(
+                            "{} {} {:<10} {}",
+                            time,
+                            status,
+                            e.kind.as_str(),
+                            e.command.as_deref().unwrap_or("")
+                        );
+                    }
+                }
+                Err(e) => eprintln!("❌ DB Error: {}", e),
+            }
+        }
+        return;
+    }
+
+    // Probe bundles: vega debug capture [file] [--ssh] | vega debug replay <file>
+    if input == "debug" {
+        use crate::system::probe;
+        match args.get(2).map(|s| s.as_str()) {
+            Some("capture") => {
+                let out = args
+                    .iter()
+                    .skip(3)
+                    .find(|a| !a.starts_with("--"))
+                    .cloned()
+                    .unwrap_or_else(|| format!("vega-capture-{}.json", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+                println!("🎬 Capturing system probes...");
+                probe::start_recording();
+                let _ = SystemContext::collect();
+                let _ = ShellSnapshot::new();
+                if args.iter().any(|a| a == "--ssh") {
+                    for (name, entry) in &KnowledgeBase::load().targets {
+                        println!("   🔌 {}", name);
+                        if SshConnection::check_connection(&entry.ip, entry.user.as_deref()).is_ok() {
+                            let _ = SshConnection::detect_os(&entry.ip, entry.user.as_deref());
+                        }
+                    }
+                }
+                let bundle = match probe::finish_recording() {
+                    Some(b) => b,
+                    None => return,
+                };
+                match bundle.save(&out) {
+                    Ok(_) => {
+                        println!(
+                            "✅ Saved {} ({} commands, {} files)",
+                            out,
+                            bundle.commands.len(),
+                            bundle.files.len()
+                        );
+                        println!("   Credentials in JSON output are redacted; review the file before sharing it.");
+                        println!("   Reproduce with: VEGA_REPLAY={} vega <command>", out);
+                    }
+                    Err(e) => eprintln!("❌ {}", e),
+                }
+            }
+            Some("replay") if args.len() >= 4 => match probe::Bundle::load(&args[3]) {
+                Ok(bundle) => {
+                    println!("🎞️  Context collected from {} (captured {})", args[3], bundle.captured_at);
+                    probe::replay(bundle);
+                    let ctx = SystemContext::collect();
+                    println!("{}", serde_json::to_string_pretty(&ctx).unwrap_or_default());
+                }
+                Err(e) => eprintln!("❌ {}", e),
+            },
+            _ => println!("Usage: vega debug capture [file] [--ssh] | vega debug replay <file>"),
+        }
+        return;
     }
 
     if input == "debug-keyring" {
@@ -119,6 +370,264 @@ async fn main() {
         std::process::exit(1);
     });
 
+    // 1.5 Open (or continue) the session for this run
+    let session_timeout = config
+        .optimization
+        .as_ref()
+        .and_then(|o| o.session_timeout_minutes)
+        .map(|m| m * 60)
+        .unwrap_or(crate::storage::session::DEFAULT_TIMEOUT_SECS);
+    let _session_guard = match crate::storage::session::open(session_timeout) {
+        Ok(_) => Some(crate::storage::session::ExitGuard),
+        Err(e) => {
+            eprintln!("⚠️ Session tracking unavailable: {}", e);
+            None
+        }
+    };
+
+    // 1.6 Daily retention pass over stored history
+    if let Ok(db) = crate::storage::db::Database::new() {
+        match db.maybe_apply_retention() {
+            Ok(Some(r)) => {
+                if r.expired + r.trimmed > 0 {
+                    log::info!(
+                        "🧹 Retention removed {} expired and {} oversized history entries",
+                        r.expired,
+                        r.trimmed
+                    );
+                }
+                if r.over_budget {
+                    log::warn!("⚠️ History is over max_db_size_mb with every event trimmed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("⚠️ Retention pass failed: {}", e),
+        }
+        // 1.7 Keep the offline file index fresh without blocking this command
+        if input != "index" && crate::system::file_index::maybe_refresh_in_background(&db) {
+            log::info!("🗂️ File index refresh started in the background");
+        }
+    }
+
+    // Forget history: vega forget [<pattern>] [--session <id>] [--before <YYYY-MM-DD>] [--yes]
+    if input == "forget" {
+        use crate::storage::retention::ForgetFilter;
+
+        let flag_value = |flag: &str| {
+            args.iter()
+                .position(|a| a == flag)
+                .and_then(|p| args.get(p + 1))
+                .cloned()
+        };
+        let mut skip_next = false;
+        let mut words = Vec::new();
+        for arg in &args[2..] {
+            if skip_next {
+                skip_next = false;
+            } else if arg == "--session" || arg == "--before" {
+                skip_next = true;
+            } else if !arg.starts_with("--") {
+                words.push(arg.as_str());
+            }
+        }
+
+        let filter = ForgetFilter {
+            pattern: if words.is_empty() { None } else { Some(words.join(" ")) },
+            session_id: flag_value("--session").and_then(|s| s.parse().ok()),
+            before: flag_value("--before")
+                .and_then(|d| chrono::NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok())
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .and_then(|t| t.and_local_timezone(chrono::Local).single())
+                .map(|t| t.timestamp()),
+        };
+        if filter.is_empty() {
+            println!("⚠️  Usage: vega forget [<pattern>] [--session <id>] [--before <YYYY-MM-DD>] [--yes]");
+            return;
+        }
+
+        let db = match crate::storage::db::Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+        let counts = db.count_forgettable(&filter).unwrap_or_default();
+        let total: usize = counts.iter().map(|(_, n)| n).sum();
+        if total == 0 {
+            println!("✨ Nothing matches; history unchanged.");
+            return;
+        }
+        println!("🗑️  Matching history entries:");
+        for (table, n) in counts.iter().filter(|(_, n)| *n > 0) {
+            println!("   {:<18} {}", table, n);
+        }
+
+        if !args.contains(&"--yes".to_string()) {
+            print!("{} [y/N]: ", "Permanently delete these entries?".yellow());
+            use std::io::Write;
+            let _ = std::io::stdout().flush();
+            let mut answer = String::new();
+            let _ = std::io::stdin().read_line(&mut answer);
+            if answer.trim().to_lowercase() != "y" {
+                println!("❌ Cancelled.");
+                return;
+            }
+        }
+
+        match db.forget(&filter) {
+            Ok(n) => println!("✅ Forgot {} entries (search index and free pages purged).", n),
+            Err(e) => eprintln!("❌ Forget Failed: {}", e),
+        }
+        return;
+    }
+
+    // Session management: vega session list|show <id>|close [id]
+    if input == "session" {
+        let db = match crate::storage::db::Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+        let fmt_time = |ts: i64| {
+            chrono::DateTime::from_timestamp(ts, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+        let id_arg = args.get(3).and_then(|s| s.parse::<i64>().ok());
+
+        match args.get(2).map(|s| s.as_str()).unwrap_or("list") {
+            "list" => {
+                println!(
+                    "{:<6} | {:<16} | {:<16} | {:<12} | {:<6} | Summary",
+                    "ID", "Started", "Ended", "TTY", "Events"
+                );
+                println!("{:-<6}-|-{:-<16}-|-{:-<16}-|-{:-<12}-|-{:-<6}-|-{:-<20}", "", "", "", "", "", "");
+                for s in db.list_sessions(20).unwrap_or_default() {
+                    let marker = if Some(s.id) == crate::storage::session::active() { "*" } else { " " };
+                    println!(
+                        "{:<6} | {:<16} | {:<16} | {:<12} | {:<6} | {}",
+                        format!("{}{}", s.id, marker),
+                        fmt_time(s.start_time),
+                        s.end_time.map(fmt_time).unwrap_or_else(|| "(open)".to_string()),
+                        s.tty.as_deref().unwrap_or("-"),
+                        s.event_count,
+                        s.summary.as_deref().unwrap_or("")
+                    );
+                }
+            }
+            "show" => {
+                let sid = match id_arg.or_else(crate::storage::session::active) {
+                    Some(sid) => sid,
+                    None => {
+                        println!("❌ No current session; pass an id from 'vega session list'.");
+                        return;
+                    }
+                };
+                match db.get_session(sid) {
+                    Ok(Some(s)) => {
+                        println!("🗂️  Session #{}", s.id);
+                        println!("   Started : {}", fmt_time(s.start_time));
+                        println!(
+                            "   Ended   : {}",
+                            s.end_time.map(fmt_time).unwrap_or_else(|| "(open)".to_string())
+                        );
+                        println!("   TTY/PID : {} / {}", s.tty.as_deref().unwrap_or("-"), s.shell_pid.unwrap_or(0));
+                        println!(
+                            "   Summary : {}",
+                            s.summary
+                                .clone()
+                                .unwrap_or_else(|| db.summarize_session(s.id).unwrap_or_default())
+                        );
+                        for e in db.get_events(None, Some(s.id), 50).unwrap_or_default() {
+                            println!(
+                                "   {} {:<10} {}",
+                                fmt_time(e.timestamp),
+                                e.kind.as_str(),
+                                e.command.as_deref().unwrap_or("")
+                            );
+                        }
+                    }
+                    _ => println!("❌ Session {} not found.", sid),
+                }
+            }
+            "close" => {
+                let sid = match id_arg.or_else(crate::storage::session::active) {
+                    Some(sid) => sid,
+                    None => {
+                        println!("❌ No current session; pass an id from 'vega session list'.");
+                        return;
+                    }
+                };
+                match db.close_session(sid, false) {
+                    Ok(true) => println!("✅ Session {} closed.", sid),
+                    Ok(false) => println!("ℹ️ Session {} is not open.", sid),
+                    Err(e) => eprintln!("❌ Failed to close session: {}", e),
+                }
+            }
+            _ => println!("⚠️  Usage: vega session [list|show <id>|close [id]]"),
+        }
+        return;
+    }
+
+    // Cross-device state: vega state push|pull [--remote <name>]
+    if input == "state" {
+        let remote = args
+            .iter()
+            .position(|a| a == "--remote")
+            .and_then(|p| args.get(p + 1))
+            .cloned()
+            .or_else(|| config.optimization.as_ref().and_then(|o| o.primary_remote.clone()));
+        let remote = match remote {
+            Some(r) => r,
+            None => {
+                println!("⚠️  No remote configured. Set optimization.primary_remote or pass --remote <name>.");
+                return;
+            }
+        };
+        let state = crate::system::state_sync::StateSync::new(&remote);
+
+        match args.get(2).map(|s| s.as_str()) {
+            Some("push") => {
+                println!("⬆️  Pushing VEGA state to {}...", remote);
+                match state.push() {
+                    Ok(m) => println!(
+                        "✅ State pushed: {} events, schema v{} ({} files).",
+                        m.event_count,
+                        m.schema_version,
+                        m.files.len()
+                    ),
+                    Err(e) => eprintln!("❌ State Push Failed: {}", e),
+                }
+            }
+            Some("pull") => {
+                println!("⬇️  Pulling VEGA state from {}...", remote);
+                match state.pull() {
+                    Ok(r) => {
+                        if r.peers.is_empty() {
+                            println!("ℹ️  No snapshots from other devices found.");
+                        } else {
+                            println!("✅ Merged from: {}", r.peers.join(", "));
+                        }
+                        println!("   Events added : {}", r.events_added);
+                        println!("   Hosts        : {} added, {} updated", r.hosts_added, r.hosts_updated);
+                        if !r.conflicts.is_empty() {
+                            println!("⚠️  Conflicts:");
+                            for c in &r.conflicts {
+                                println!("   - {}", c);
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("❌ State Pull Failed: {}", e),
+                }
+            }
+            _ => println!("⚠️  Usage: vega state [push|pull] [--remote <name>]"),
+        }
+        return;
+    }
+
     // 2. Initialize Knowledge Base
     let mut kb = KnowledgeBase::load();
 
@@ -143,14 +652,34 @@ async fn main() {
         .unwrap_or_else(|| std::path::PathBuf::from("logs"));
 
     let cache_path = data_dir.join("cache.json").to_string_lossy().to_string();
-    let history_path = data_dir.join("history.jsonl").to_string_lossy().to_string();
 
-    let token_saver = TokenSaver::new(&cache_path, &history_path, keywords);
-    let logger = ExecutionLogger::new(&history_path);
+    let token_saver = TokenSaver::new(&cache_path, keywords);
+    let logger = ExecutionLogger::new();
 
     // v0.0.10 Pipeline Proof of Concept
     if input == "run-v10" && args.len() >= 3 {
-        let nli = args[2..].join(" ");
+        // `--shell "<fragment>"` is the only way a pipe or redirection reaches sh
+        let mut words: Vec<String> = args[2..].to_vec();
+        let mut shell = None;
+        if let Some(pos) = words.iter().position(|a| a == "--shell") {
+            if pos + 1 < words.len() {
+                let fragment = words.remove(pos + 1);
+                shell = Some(crate::executor::ast::ShellOptIn {
+                    fragment,
+                    reason: "requested with --shell".to_string(),
+                });
+            }
+            words.remove(pos);
+        }
+        // `--container <name>` picks the target explicitly; otherwise only "in <name>" does
+        let mut container_name = None;
+        if let Some(pos) = words.iter().position(|a| a == "--container") {
+            if pos + 1 < words.len() {
+                container_name = Some(words.remove(pos + 1));
+            }
+            words.remove(pos);
+        }
+        let nli = words.join(" ");
         println!("🚀 Running v0.0.10 Pipeline for: \"{}\"", nli);
 
         use crate::executor::pipeline::*;
@@ -160,6 +689,28 @@ async fn main() {
         use crate::executor::virt::BasicVee;
         use crate::safety::risk::DefaultRiskEvaluator;
 
+        // A running container the request runs "in" becomes the execution target
+        let inventory = crate::system::containers::ContainerScanner::scan();
+        let target = match &container_name {
+            Some(name) => match inventory.find_exact(name).filter(|c| c.is_running()) {
+                Some(container) => Some(container),
+                None => {
+                    println!("❌ No running container named '{}'.", name);
+                    return;
+                }
+            },
+            None => inventory.targeted_in(&nli),
+        };
+        let execution_provider: Box<dyn ExecutionProvider + Send + Sync> = match target {
+            Some(container) => {
+                println!("📦 Target: container '{}' ({})", container.name, container.runtime.binary());
+                Box::new(ContainerExecutionProvider {
+                    exec: crate::system::containers::ContainerExec::new(container),
+                })
+            }
+            None => Box::new(LocalExecutionProvider),
+        };
+
         let orchestrator = PipelineOrchestrator {
             intent_resolver: Box::new(HybridIntentResolver {
                 local: LocalIntentResolver,
@@ -169,7 +720,8 @@ async fn main() {
             option_generator: Box::new(AiOptionGenerator),
             vee: Box::new(BasicVee),
             risk_evaluator: Box::new(DefaultRiskEvaluator),
-            execution_provider: Box::new(LocalExecutionProvider),
+            execution_provider,
+            shell,
         };
 
         match orchestrator.run_pipeline(&nli).await {
@@ -192,33 +744,532 @@ async fn main() {
     // v2.0 Abstraction Commands
     let dry_run = args.contains(&"--dry-run".to_string());
 
-    // Pkg Manager: vega install <package>
+    // Pkg Manager: vega install <package> [--via <manager>]
     if input == "install" && args.len() >= 3 {
-        let pkg_name = &args[2];
+        let via = args
+            .iter()
+            .position(|a| a == "--via")
+            .and_then(|p| args.get(p + 1))
+            .map(|v| v.as_str());
+        install_package(&args[2], via, dry_run);
+        return;
+    }
+
+    // Intent grammar check: vega intent "<request>"
+    if input == "intent" && args.len() >= 3 {
+        use crate::ai::grammar::{IntentMatcher, Vocabulary};
+
+        let request = args[2..].join(" ");
+        let matcher = IntentMatcher::global();
+        let found = matcher.matches(&request, &Vocabulary::load());
+        if found.is_empty() {
+            println!("❓ No intent keyword in \"{}\"; it goes to the AI.", request);
+        }
+        for m in &found {
+            let slots: Vec<String> = m.slots.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            println!(
+                "   {:<10} {} {:<8} {:.2}  {}",
+                m.name,
+                m.tool,
+                m.operation,
+                m.confidence,
+                slots.join(" ")
+            );
+        }
+        match found.first() {
+            Some(best) if matcher.accepts(best) => {
+                println!("✅ Resolved locally as '{}' (threshold {:.2}).", best.name, matcher.threshold)
+            }
+            Some(_) => println!("🤖 Below the threshold ({:.2}); it goes to the AI.", matcher.threshold),
+            None => {}
+        }
+        return;
+    }
+
+    // Package queries: vega pkg managers | search <query> | info <package> | provides <command>
+    //                  | remove <package> [--via <manager>] | map update <file|https-url> [--yes]
+    if input == "pkg" {
         let ctx = SystemContext::collect();
-        let pm = pkg::detect(&ctx);
-        println!("📦 Package Manager Detected: {}", pm.name());
-        let cmd = pm.install(pkg_name);
-        println!("🚀 Proposed Command: {}", cmd);
-
-        if !dry_run {
-            println!("⚡ Executing...");
-            let _ = Command::new("sh").arg("-c").arg(&cmd).status();
-        } else {
-            println!("🛑 Dry-Run: Execution Skipped.");
+        let managers = pkg::detect_all(&ctx);
+        let sub = args.get(2).map(|s| s.as_str()).unwrap_or("managers");
+        let target = args.get(3).cloned().unwrap_or_default();
+        match sub {
+            "managers" => {
+                if managers.is_empty() {
+                    println!("❌ No supported package manager found (detected: {}).", ctx.pkg_manager);
+                }
+                for pm in &managers {
+                    println!("📦 {}", pm.name());
+                }
+            }
+            "search" if !target.is_empty() => {
+                for pm in &managers {
+                    let found = pm.search(&target);
+                    println!("🔎 {} ({} results)", pm.name(), found.len());
+                    for p in found.iter().take(20) {
+                        println!(
+                            "   {:<30} {:<16} {}",
+                            p.name,
+                            p.version.as_deref().unwrap_or("-"),
+                            p.summary
+                        );
+                    }
+                }
+            }
+            "info" if !target.is_empty() => {
+                let mut found = false;
+                for pm in &managers {
+                    if let Some(info) = pkg::resolve(pm.as_ref(), &target) {
+                        found = true;
+                        println!("📦 {} [{}]", info.name, pm.name());
+                        println!("   Version:   {}", info.version.as_deref().unwrap_or("unknown"));
+                        println!("   Installed: {}", info.installed.as_deref().unwrap_or("no"));
+                        if let Some(source) = &info.source {
+                            println!("   Source:    {}", source);
+                        }
+                        println!("   Summary:   {}", info.summary);
+                    }
+                }
+                if !found {
+                    println!("❌ '{}' was not found by any package manager.", target);
+                }
+            }
+            "remove" if !target.is_empty() => {
+                let via = args
+                    .iter()
+                    .position(|a| a == "--via")
+                    .and_then(|p| args.get(p + 1))
+                    .and_then(|v| pkg::PackageSystem::parse(v));
+                let installed = managers
+                    .iter()
+                    .filter(|pm| via.map(|v| pm.kind() == v).unwrap_or(true))
+                    .find_map(|pm| pkg::installed(pm.as_ref(), &target).map(|(package, _)| (pm, package)));
+                let (pm, package) = match installed {
+                    Some(found) => found,
+                    None => {
+                        println!("ℹ️  '{}' is not installed.", target);
+                        return;
+                    }
+                };
+                let cmd = match pm.remove(&package) {
+                    Ok(command) => command.to_shell(),
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        return;
+                    }
+                };
+                if dry_run {
+                    println!("🚀 Proposed Command: {}", cmd);
+                    println!("🛑 Dry-Run: Execution Skipped.");
+                } else if crate::safety::confirm_action(crate::safety::RiskLevel::Warning, &cmd) {
+                    let _ = Command::new("sh").arg("-c").arg(&cmd).status();
+                    if pm.installed_version(&package).is_none() {
+                        println!("✅ Removed {} ({}).", package, pm.name());
+                    } else {
+                        println!("❌ {} is still installed.", package);
+                    }
+                }
+            }
+            "provides" if !target.is_empty() => {
+                let mut found = false;
+                for pm in &managers {
+                    if let Some(provider) = pkg::provides(pm.as_ref(), &target) {
+                        found = true;
+                        println!("📦 {}: {} (via {})", pm.name(), provider.package, provider.via);
+                    }
+                }
+                if !found {
+                    println!("❌ No known package provides '{}'.", target);
+                }
+            }
+            "map" if args.get(3).map(|s| s.as_str()) == Some("update") => {
+                let source = match args.get(4) {
+                    Some(source) => source,
+                    None => {
+                        println!("⚠️  Usage: vega pkg map update <file|https-url> [--yes]");
+                        return;
+                    }
+                };
+                if source.starts_with("http://") {
+                    println!("❌ Refusing to fetch the package map over plain http; use https or a local file.");
+                    return;
+                }
+                let content = if source.starts_with("https://") {
+                    match reqwest::get(source.as_str()).await {
+                        Ok(resp) if resp.status().is_success() => resp.text().await.map_err(|e| e.to_string()),
+                        Ok(resp) => Err(format!("HTTP {}", resp.status())),
+                        Err(e) => Err(e.to_string()),
+                    }
+                } else {
+                    std::fs::read_to_string(source).map_err(|e| e.to_string())
+                };
+                let content = match content {
+                    Ok(content) => content,
+                    Err(e) => {
+                        eprintln!("❌ Package map not updated: {}", e);
+                        return;
+                    }
+                };
+                // Mapped names become install/remove arguments, so show what changes first
+                let changes = match crate::executor::pkg_map::PackageMap::preview_override(&content) {
+                    Ok(changes) => changes,
+                    Err(e) => {
+                        eprintln!("❌ Package map not updated: {}", e);
+                        return;
+                    }
+                };
+                if changes.is_empty() {
+                    println!("ℹ️  No mapping changes.");
+                } else {
+                    println!("📦 Package map changes from {}:", source);
+                    for line in &changes {
+                        println!("   {}", line);
+                    }
+                }
+                if !args.contains(&"--yes".to_string()) {
+                    print!("{} [y/N]: ", "Save this package map?".yellow());
+                    use std::io::Write;
+                    let _ = std::io::stdout().flush();
+                    let mut answer = String::new();
+                    let _ = std::io::stdin().read_line(&mut answer);
+                    if answer.trim().to_lowercase() != "y" {
+                        println!("❌ Cancelled.");
+                        return;
+                    }
+                }
+                match crate::executor::pkg_map::PackageMap::save_override(&content) {
+                    Ok(n) => println!(
+                        "✅ Package map updated: {} entries in {}",
+                        n,
+                        crate::executor::pkg_map::PackageMap::override_path().display()
+                    ),
+                    Err(e) => eprintln!("❌ Package map not updated: {}", e),
+                }
+            }
+            _ => println!("⚠️  Usage: vega pkg [managers|search <query>|info <package>|provides <command>|remove <package> [--via <manager>]|map update <file|https-url> [--yes]]"),
+        }
+        return;
+    }
+
+    // Storage: vega backup <source> <target_alias> | vega backup list
+    if input == "backup" && args.get(2).map(|s| s.as_str()) == Some("list") {
+        let db = match crate::storage::db::Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+        let runs = db.list_backup_runs(None, 30).unwrap_or_default();
+        if runs.is_empty() {
+            println!("ℹ️  No backups recorded yet.");
+            return;
+        }
+        println!(
+            "{:<5} | {:<17} | {:<8} | {:>7} | {:>10} | {:>10} | Destination",
+            "ID", "Snapshot", "Status", "Files", "Sent", "Total"
+        );
+        println!("{:-<5}-|-{:-<17}-|-{:-<8}-|-{:->7}-|-{:->10}-|-{:->10}-|-{:-<20}", "", "", "", "", "", "", "");
+        for r in runs {
+            let status = if r.pruned {
+                "pruned"
+            } else if r.success && r.verified {
+                "✅ ok"
+            } else if r.success {
+                "⚠️ unver"
+            } else {
+                "❌ fail"
+            };
+            println!(
+                "{:<5} | {:<17} | {:<8} | {:>7} | {:>7} KB | {:>7} KB | {}",
+                r.id,
+                r.snapshot,
+                status,
+                r.files_transferred,
+                r.bytes_transferred / 1024,
+                r.total_bytes / 1024,
+                r.destination
+            );
+            if let Some(err) = &r.error {
+                println!("{:<5}   └─ {}", "", err);
+            }
         }
         return;
     }
 
-    // Storage: vega backup <source> <target_alias>
     if input == "backup" && args.len() >= 4 {
-        let source = &args[2];
+        let source = &crate::system::backup::canonical_source(&args[2]);
         let target = &args[3];
-        let storage = SmartStorage::new();
-        let cmd = storage.backup_cmd(source, target);
-        println!("☁️  Smart Storage Backup:");
-        println!("   Command: {}", cmd);
-        // Execute...
+        let manager = crate::system::backup::BackupManager::new(config.backup.as_ref());
+        let destination = manager.destination(source, target);
+
+        let risk = crate::safety::SafetyRegistry::validate_rclone_command(&["sync"]);
+        if !crate::safety::confirm_action(risk, &format!("rclone sync {} {}/current", source, destination)) {
+            println!("❌ Backup cancelled.");
+            return;
+        }
+        let db = match crate::storage::db::Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+
+        println!("☁️  Smart Storage Backup: {} -> {}", source, destination);
+        match manager.run(&db, source, target).await {
+            Ok(run) if run.success && run.verified => println!(
+                "✅ Backup #{} ({}) verified: {} files sent ({} KB), {} files / {} KB total.",
+                run.id,
+                run.snapshot,
+                run.files_transferred,
+                run.bytes_transferred / 1024,
+                run.total_files,
+                run.total_bytes / 1024
+            ),
+            Ok(run) => eprintln!(
+                "❌ Backup #{} failed: {}",
+                run.id,
+                run.error.unwrap_or_else(|| "unknown error".to_string())
+            ),
+            Err(e) => eprintln!("❌ Backup Failed: {}", e),
+        }
+        return;
+    }
+
+    // Mount registry: vega mount <remote:path|host:path> <dir> [--systemd] [--no-remount]
+    //                 vega umount <dir> [--lazy] | vega mounts
+    if input == "mount" || input == "umount" || input == "mounts" {
+        use crate::system::mounts::{self, MountHealth, MountManager};
+
+        let db = match crate::storage::db::Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+        let manager = MountManager::new();
+        let positional: Vec<&String> = args.iter().skip(2).filter(|a| !a.starts_with("--")).collect();
+        let has_flag = |flag: &str| args.iter().any(|a| a == flag);
+
+        match input.as_str() {
+            "mount" if positional.len() >= 2 => {
+                let systemd = has_flag("--systemd");
+                match manager
+                    .mount(&db, &kb, positional[0], positional[1], systemd, !has_flag("--no-remount"))
+                    .await
+                {
+                    Ok(entry) => {
+                        println!("✅ Mounted {} at {} ({})", entry.source, entry.mount_point, entry.kind);
+                        if let Some(unit) = entry.systemd_unit {
+                            println!("   Managed by systemd user unit {}", unit);
+                        }
+                    }
+                    Err(e) => eprintln!("❌ Mount failed: {}", e),
+                }
+            }
+            "umount" if !positional.is_empty() => match manager.unmount(&db, positional[0], has_flag("--lazy")) {
+                Ok(true) => println!("✅ Lazily detached {} (endpoint was busy or hung).", positional[0]),
+                Ok(false) => println!("✅ Unmounted {}.", positional[0]),
+                Err(e) => eprintln!("❌ {}", e),
+            },
+            "mounts" => {
+                // Listing only reports; bringing dropped mounts back is asked for explicitly
+                if has_flag("--restore") {
+                    for (mount_point, result) in manager.remount_registered(&db).await {
+                        match result {
+                            Ok(_) => println!("🔌 Remounted {}", mount_point),
+                            Err(e) => eprintln!("⚠️ Remount of {} failed: {}", mount_point, e),
+                        }
+                    }
+                }
+                let registered = db.list_mounts().unwrap_or_default();
+                if registered.is_empty() {
+                    println!("ℹ️  No registered mounts. Use `vega mount <remote:path> <dir>`.");
+                }
+                for entry in registered {
+                    let health = mounts::probe(&entry.mount_point, mounts::HEALTH_TIMEOUT);
+                    let (icon, state, error) = match &health {
+                        MountHealth::Healthy => ("✅", "healthy", None),
+                        MountHealth::Stale(e) => ("⚠️ ", "stale", Some(e.as_str())),
+                        MountHealth::NotMounted => ("⭕", "not mounted", None),
+                    };
+                    let _ = db.update_mount_health(&entry.mount_point, health == MountHealth::Healthy, error);
+                    let managed = match (&entry.systemd_unit, entry.auto_remount) {
+                        (Some(_), _) => "systemd",
+                        (None, true) => "auto-remount",
+                        (None, false) => "manual",
+                    };
+                    println!(
+                        "   {} {:<30} {} [{}, {}] {}",
+                        icon, entry.mount_point, entry.source, entry.kind, managed, state
+                    );
+                    if let Some(e) = error {
+                        println!("      {} (try `vega umount --lazy {}`)", e, entry.mount_point);
+                    } else if health == MountHealth::NotMounted && entry.auto_remount && entry.systemd_unit.is_none() {
+                        println!("      Run `vega mounts --restore` to mount it again.");
+                    }
+                }
+            }
+            _ => println!("⚠️  Usage: vega mount <remote:path|host:path> <dir> [--systemd] [--no-remount] | vega umount <dir> [--lazy] | vega mounts [--restore]"),
+        }
+        SystemContext::invalidate(&["partitions"]);
+        return;
+    }
+
+    // Offline file index: vega index [status | <remote|host>] [--quiet]
+    if input == "index" {
+        use crate::system::file_index::FileIndexer;
+
+        let quiet = args.iter().any(|a| a == "--quiet");
+        let db = match crate::storage::db::Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+        let target = args.iter().skip(2).find(|a| !a.starts_with("--"));
+        if target.map(|t| t.as_str()) == Some("status") {
+            let origins = db.list_index_origins().unwrap_or_default();
+            if origins.is_empty() {
+                println!("ℹ️  Nothing indexed yet. Run `vega index`.");
+            }
+            let now = crate::storage::events::now_secs();
+            for o in origins {
+                let age = o
+                    .last_crawl
+                    .map(|t| crate::context::age_label(now - t))
+                    .unwrap_or_else(|| "never".to_string());
+                let state = o.error.as_deref().map(|e| format!("⚠️ {}", e)).unwrap_or_else(|| "✅".to_string());
+                println!("   {:<20} {:<6} {:>8} entries  crawled {:<8} {}", o.label, o.kind, o.entries, age, state);
+            }
+            return;
+        }
+
+        let indexer = FileIndexer::new(quiet);
+        let reports = match target {
+            Some(name) => match kb.get(name) {
+                Some(entry) => vec![indexer.crawl_host(&db, name, entry).await],
+                None => vec![indexer.crawl_remote(&db, name)],
+            },
+            None => indexer.crawl_all(&db, &kb).await,
+        };
+        if !quiet {
+            for report in reports {
+                match report {
+                    Ok(r) => println!(
+                        "✅ {}: {} entries ({} new/changed, {} removed) in {:.1}s{}{}",
+                        r.label,
+                        r.entries,
+                        r.changed,
+                        r.removed,
+                        r.duration_ms as f64 / 1000.0,
+                        if r.unchanged_dirs.is_empty() {
+                            String::new()
+                        } else {
+                            format!(", {} unchanged folders skipped", r.unchanged_dirs.len())
+                        },
+                        if r.failed_dirs.is_empty() {
+                            String::new()
+                        } else {
+                            format!(", {} folders kept from the last crawl", r.failed_dirs.len())
+                        }
+                    ),
+                    Err(e) => eprintln!("❌ {}", e.trim()),
+                }
+            }
+        }
+        return;
+    }
+
+    // Offline file lookup: vega find <words...>
+    if input == "find" && args.len() >= 3 {
+        match crate::storage::db::Database::new() {
+            Ok(db) => {
+                crate::system::file_index::show_matches(&db, &args[2..].join(" "), 50);
+            }
+            Err(e) => eprintln!("❌ DB Error: {}", e),
+        }
+        return;
+    }
+
+    // rclone jobs on an attached rcd: vega rc jobs | vega rc stop <jobid>
+    if input == "rc" {
+        if config.rclone.as_ref().and_then(|r| r.rc_url.as_ref()).is_none() {
+            println!("ℹ️  No shared rclone rcd configured. Set [rclone] rc_url to list or stop jobs.");
+            return;
+        }
+        let rc = match crate::remote::rc::RcClient::connect().await {
+            Ok(rc) => rc,
+            Err(e) => {
+                eprintln!("❌ rclone rc unavailable: {}", e);
+                return;
+            }
+        };
+        match (args.get(2).map(|s| s.as_str()), args.get(3).and_then(|s| s.parse::<i64>().ok())) {
+            (Some("stop"), Some(id)) => match rc.stop_job(id).await {
+                Ok(_) => println!("🛑 Job {} stopped.", id),
+                Err(e) => eprintln!("❌ Failed to stop job {}: {}", id, e),
+            },
+            (Some("jobs") | None, _) => {
+                let ids = rc.list_jobs().await.unwrap_or_default();
+                if ids.is_empty() {
+                    println!("ℹ️  No rclone jobs.");
+                }
+                for id in ids {
+                    let status = rc.job_status(id).await.unwrap_or_default();
+                    let state = if !status["finished"].as_bool().unwrap_or(false) {
+                        "running"
+                    } else if status["success"].as_bool().unwrap_or(false) {
+                        "done"
+                    } else {
+                        "failed"
+                    };
+                    let stats = rc.job_stats(id).await.unwrap_or_default();
+                    println!("   #{:<5} {:<8} {}", id, state, stats.progress_line());
+                }
+            }
+            _ => println!("⚠️  Usage: vega rc [jobs|stop <jobid>]"),
+        }
+        return;
+    }
+
+    // Storage: vega restore <snapshot> <dest>
+    if input == "restore" {
+        if args.len() < 4 {
+            println!("⚠️  Usage: vega restore <snapshot|id> <dest>");
+            return;
+        }
+        let dest = &args[3];
+        let non_empty = std::fs::read_dir(dest)
+            .map(|mut d| d.next().is_some())
+            .unwrap_or(false);
+        if non_empty
+            && !crate::safety::confirm_action(
+                crate::safety::RiskLevel::Warning,
+                &format!("restore {} into non-empty {}", args[2], dest),
+            )
+        {
+            println!("❌ Restore cancelled.");
+            return;
+        }
+        let db = match crate::storage::db::Database::new() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("❌ DB Error: {}", e);
+                return;
+            }
+        };
+        let manager = crate::system::backup::BackupManager::new(config.backup.as_ref());
+        match manager.restore(&db, &args[2], dest) {
+            Ok(report) => {
+                println!("✅ Restored {} into {} ({} layers).", args[2], dest, report.layers);
+                println!("ℹ️  Files first backed up after this snapshot are restored as well, and files already in {} are kept.", dest);
+            }
+            Err(e) => eprintln!("❌ Restore Failed: {}", e),
+        }
         return;
     }
 
@@ -242,13 +1293,182 @@ async fn main() {
     if input == "start" && args.len() >= 3 {
         let vm_name = &args[2];
         println!("🖥️  VM Controller: Starting '{}'...", vm_name);
-        match VmController::start(vm_name) {
+        match VmController::new().start(vm_name) {
             Ok(msg) => println!("{}", msg),
             Err(e) => eprintln!("❌ VM Error: {}", e),
         }
         return;
     }
 
+    // Containers: vega containers [--images]
+    if input == "containers" {
+        let inventory = crate::system::containers::ContainerScanner::scan();
+        if inventory.containers.is_empty() && inventory.images.is_empty() {
+            println!("ℹ️  No docker or podman containers found.");
+            return;
+        }
+        println!("📦 Containers");
+        for c in &inventory.containers {
+            let ports: Vec<String> = c.ports.iter().map(|p| p.label()).collect();
+            println!(
+                "   {:<24} {:<8} {:<10} {:<30} {}",
+                c.name,
+                c.runtime.binary(),
+                c.state,
+                c.image,
+                ports.join(", ")
+            );
+        }
+        if !inventory.compose_projects.is_empty() {
+            println!("🧩 Compose projects");
+            for p in &inventory.compose_projects {
+                println!(
+                    "   {:<24} {:<8} {}/{} running ({})",
+                    p.name,
+                    p.runtime.binary(),
+                    p.running,
+                    p.services.len(),
+                    p.services.join(", ")
+                );
+            }
+        }
+        if args.iter().any(|a| a == "--images") {
+            println!("🖼️  Images");
+            for i in &inventory.images {
+                println!("   {:<40} {:<14} {:<8} {}", format!("{}:{}", i.repository, i.tag), i.id, i.runtime.binary(), i.size);
+            }
+        } else {
+            println!("   ({} images, list them with --images)", inventory.images.len());
+        }
+        return;
+    }
+
+    // Virt: vega vm list | start|shutdown|reboot|stats <vm> | snapshot <vm> [list|create|revert|delete <name>]
+    //       | clone <vm> <new> | autostart <vm> on|off
+    if input == "vm" {
+        use crate::system::virt::ShutdownOutcome;
+
+        let controller = VmController::new();
+        let sub = args.get(2).map(|s| s.as_str()).unwrap_or("list");
+        let vm = args.get(3).map(|s| s.as_str());
+        let extra = |i: usize| args.get(i).map(|s| s.as_str());
+        let report = |result: Result<(), String>, done: String| match result {
+            Ok(_) => println!("✅ {}", done),
+            Err(e) => eprintln!("❌ VM Error: {}", e),
+        };
+
+        match (sub, vm) {
+            ("list", _) => {
+                let autostart = controller.autostart_enabled();
+                let vms = crate::system::virt::VmScanner::scan_with(&controller.virsh);
+                println!("🖥️  VMs on {}", controller.virsh.uri);
+                if vms.is_empty() {
+                    println!("   (none)");
+                }
+                for vm in vms {
+                    println!(
+                        "   {:<20} {:<12} {:<16} {:<10} {}",
+                        vm.name,
+                        vm.state,
+                        vm.ip.as_deref().unwrap_or("-"),
+                        vm.ip_source.map(|s| s.as_str()).unwrap_or(""),
+                        if autostart.contains(&vm.name) { "autostart" } else { "" }
+                    );
+                }
+            }
+            ("start", Some(name)) => match controller.start(name) {
+                Ok(msg) => println!("{}", msg),
+                Err(e) => eprintln!("❌ VM Error: {}", e),
+            },
+            ("shutdown", Some(name)) => {
+                let force = args.iter().any(|a| a == "--force");
+                if !force {
+                    println!(
+                        "⏳ Shutting down '{}' (forced after {}s)...",
+                        name,
+                        controller.shutdown_timeout.as_secs()
+                    );
+                }
+                match controller.shutdown(name, force) {
+                    Ok(ShutdownOutcome::AlreadyOff) => println!("ℹ️  VM '{}' is already off.", name),
+                    Ok(ShutdownOutcome::Graceful) => println!("✅ VM '{}' shut down.", name),
+                    Ok(ShutdownOutcome::Forced) => println!("⚠️  VM '{}' was forced off (destroy).", name),
+                    Err(e) => eprintln!("❌ VM Error: {}", e),
+                }
+            }
+            ("reboot", Some(name)) => report(controller.reboot(name), format!("Reboot requested for '{}'.", name)),
+            ("stats", Some(name)) => {
+                let first = controller.stats(name);
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                match (first, controller.stats(name)) {
+                    (Ok(a), Ok(b)) => {
+                        let mib = |v: u64| v as f64 / (1024.0 * 1024.0);
+                        println!("📊 {} ({})", name, b.state);
+                        println!("   CPU:    {:.1}% of {} vCPUs", b.cpu_percent(&a, std::time::Duration::from_secs(1)), b.vcpus);
+                        println!("   Memory: {:.0} / {:.0} MiB", b.memory_kib as f64 / 1024.0, b.memory_max_kib as f64 / 1024.0);
+                        println!("   Disk:   {:.1} MiB read, {:.1} MiB written", mib(b.disk_read_bytes), mib(b.disk_write_bytes));
+                        println!("   Net:    {:.1} MiB rx, {:.1} MiB tx", mib(b.net_rx_bytes), mib(b.net_tx_bytes));
+                    }
+                    (Err(e), _) | (_, Err(e)) => eprintln!("❌ VM Error: {}", e),
+                }
+            }
+            ("snapshot", Some(name)) => match (extra(4).unwrap_or("list"), extra(5)) {
+                ("list", _) => match controller.snapshot_list(name) {
+                    Ok(snaps) if snaps.is_empty() => println!("ℹ️  '{}' has no snapshots.", name),
+                    Ok(snaps) => {
+                        for s in snaps {
+                            let created = s
+                                .created
+                                .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                                .unwrap_or_default();
+                            println!(
+                                "   {} {:<20} {:<16} {:<10} {}",
+                                if s.current { "*" } else { " " },
+                                s.name,
+                                created,
+                                s.state,
+                                s.description.unwrap_or_default()
+                            );
+                        }
+                    }
+                    Err(e) => eprintln!("❌ VM Error: {}", e),
+                },
+                ("create", Some(snap)) => report(
+                    controller.snapshot_create(name, snap, extra(6)),
+                    format!("Snapshot '{}' of '{}' created.", snap, name),
+                ),
+                ("revert", Some(snap)) => {
+                    if Interactor::confirm(&format!("Revert '{}' to snapshot '{}'? Changes since then are lost.", name, snap)) {
+                        report(controller.snapshot_revert(name, snap), format!("'{}' reverted to '{}'.", name, snap));
+                    }
+                }
+                ("delete", Some(snap)) => report(
+                    controller.snapshot_delete(name, snap),
+                    format!("Snapshot '{}' deleted.", snap),
+                ),
+                _ => println!("⚠️  Usage: vega vm snapshot <vm> [list | create <name> [description] | revert <name> | delete <name>]"),
+            },
+            ("clone", Some(name)) => match extra(4) {
+                Some(new_name) => {
+                    println!("🧬 Cloning '{}' to '{}'...", name, new_name);
+                    report(controller.clone_vm(name, new_name), format!("'{}' cloned to '{}'.", name, new_name));
+                }
+                None => println!("⚠️  Usage: vega vm clone <vm> <new_name>"),
+            },
+            ("autostart", Some(name)) => match extra(4) {
+                Some("on") => report(controller.set_autostart(name, true), format!("Autostart enabled for '{}'.", name)),
+                Some("off") => report(controller.set_autostart(name, false), format!("Autostart disabled for '{}'.", name)),
+                _ => println!("⚠️  Usage: vega vm autostart <vm> on|off"),
+            },
+            _ => println!("⚠️  Usage: vega vm [list | start|shutdown [--force]|reboot|stats <vm> | snapshot <vm> ... | clone <vm> <new> | autostart <vm> on|off]"),
+        }
+        if !matches!(sub, "list" | "stats") {
+            SystemContext::invalidate(&["vms"]);
+        }
+        return;
+    }
+
     // Reporting: vega report [--session <id>]
     if input == "report" {
         let session_id = args
@@ -258,9 +1478,13 @@ async fn main() {
             .and_then(|s| s.parse::<i64>().ok());
 
         let sid = session_id.unwrap_or_else(|| {
-            // Default to current or latest session
+            // Default to the latest session that recorded any activity
             if let Ok(db) = crate::storage::db::Database::new() {
-                db.get_current_session_id().unwrap_or(0)
+                db.get_latest_active_session()
+                    .ok()
+                    .flatten()
+                    .or(db.get_current_session_id())
+                    .unwrap_or(0)
             } else {
                 0
             }
@@ -288,9 +1512,6 @@ async fn main() {
     // Healer: vega health
     if input == "health" {
         println!("❤️  System Healer: Analyzing Journal...");
-        // Auto-Maintenance: Rotate logs if too large
-        Healer::rotate_logs();
-
         let suggestions = Healer::analyze_journal();
         for suggestion in suggestions {
             println!("   {}", suggestion);
@@ -336,6 +1557,45 @@ async fn main() {
             target_name
         );
 
+        // 0. Hosts named in the current project's .vega.toml
+        if let Some(project) = crate::system::project::ProjectRoot::current() {
+            if let Some(spec) = project.profile.hosts.get(target_name.as_str()) {
+                let (user, host) = match spec.split_once('@') {
+                    Some((user, host)) => (Some(user), host),
+                    None => (None, spec.as_str()),
+                };
+                println!("📁 Project '{}': '{}' is {}", project.name, target_name, spec);
+                // A checked-out .vega.toml must not silently redirect a host the KB already knows
+                let mut use_project = true;
+                if let Some(entry) = kb.get(target_name) {
+                    let known = match &entry.user {
+                        Some(u) => format!("{}@{}", u, entry.ip),
+                        None => entry.ip.clone(),
+                    };
+                    if known != *spec && !args.contains(&"--yes".to_string()) {
+                        print!(
+                            "{} [y/N]: ",
+                            format!("Knowledge Base has '{}' as {}. Connect to the project's {} instead?", target_name, known, spec)
+                                .yellow()
+                        );
+                        use std::io::Write;
+                        let _ = std::io::stdout().flush();
+                        let mut answer = String::new();
+                        let _ = std::io::stdin().read_line(&mut answer);
+                        use_project = answer.trim().to_lowercase() == "y";
+                    }
+                }
+                if use_project {
+                    match SshConnection::check_connection(host, user) {
+                        Ok(_) => SshConnection::connect(host, user),
+                        Err((_, e)) => println!("❌ {} is unreachable: {}", spec, e.trim()),
+                    }
+                    return;
+                }
+                println!("📚 Using the Knowledge Base entry.");
+            }
+        }
+
         // 1. Resolve & Persist: Check Internal State (KB)
         let mut kb_hit = false;
         if let Some(entry) = kb.get(target_name).cloned() {
@@ -362,18 +1622,41 @@ async fn main() {
             }
         }
 
-        // 2. Silent Discovery: Scan VMs and Network
+        // 2. Silent Discovery: Scan VMs, Containers and Network
         if !kb_hit {
             println!("🔍 Silent Discovery: Scanning for '{}'...", target_name);
         }
 
+        let inventory = crate::system::containers::ContainerScanner::scan();
+        let connect_container = |container: &crate::system::containers::ContainerInfo| {
+            println!(
+                "📦 Discovery: Found container '{}' ({}, {})",
+                container.name,
+                container.runtime.binary(),
+                container.state
+            );
+            if let Err(e) = crate::system::containers::ContainerExec::connect(container) {
+                eprintln!("❌ Container Error: {}", e);
+            }
+        };
+        if let Some(container) = inventory.find_exact(target_name) {
+            connect_container(container);
+            return;
+        }
+
         let vms = VmScanner::scan();
-        let target_vm = vms.iter().find(|vm| vm.name.contains(target_name));
+        // A cached MAC finds the guest even when it was renamed
+        let cached_mac = kb.get(target_name).and_then(|e| e.mac.clone());
+        let target_vm = vms
+            .iter()
+            .find(|vm| cached_mac.as_ref().map(|m| vm.macs.contains(m)).unwrap_or(false))
+            .or_else(|| vms.iter().find(|vm| vm.name.contains(target_name)));
 
         if let Some(vm) = target_vm {
             println!("🎯 Discovery: Found VM '{}' (State: {})", vm.name, vm.state);
             if let Some(ip) = &vm.ip {
-                println!("   Resolved IP: {}", ip);
+                let source = vm.ip_source.map(|s| s.label()).unwrap_or("unknown");
+                println!("   Resolved IP: {} (via {})", ip, source);
 
                 // 3. Persist: Update State DB
                 print!("   Verifying new endpoint... ");
@@ -391,6 +1674,8 @@ async fn main() {
                             port: Some(22),
                             os_type: os_detected,
                             last_success: chrono::Local::now().to_rfc3339(),
+                            mac: vm.ip_mac.clone(),
+                            ip_source: vm.ip_source.map(|s| s.as_str().to_string()),
                         },
                     );
                     let _ = kb.save();
@@ -402,8 +1687,15 @@ async fn main() {
                 }
             } else {
                 println!("⚠️ Discovery Error: VM found but no IP address could be resolved.");
-                println!("   TIP: Ensure qemu-guest-agent is running or check DHCP leases.");
+                println!("   Tried: guest agent, DHCP leases, ARP, host neighbour table, dnsmasq lease files.");
+                if vm.macs.is_empty() {
+                    println!("   TIP: `virsh dumpxml {}` lists no <interface> with a MAC.", vm.name);
+                } else {
+                    println!("   TIP: Install qemu-guest-agent or ping the guest once so it shows up in ARP ({}).", vm.macs.join(", "));
+                }
             }
+        } else if let Some(container) = inventory.find(target_name) {
+            connect_container(container);
         } else {
             println!(
                 "❌ Discovery Failed: No target found matching '{}'.",
@@ -484,9 +1776,26 @@ async fn main() {
 
     match action {
         Action::SystemUpdate => {
+            use crate::executor::update::{self, Host, UpdateOptions};
             println!("🔧 [Hybrid] Detected System Update intent.");
-            println!("Context: {:?}", SystemContext::collect().load_avg);
-            println!("Executing: sudo apt update && sudo apt upgrade");
+            // Same path as `vega update --all`: pre-flight checks, the host's own package manager
+            let opts = UpdateOptions {
+                dry_run,
+                ..Default::default()
+            };
+            success = Interactor::confirm("Update all packages on this system?")
+                && match update::update_host(&Host::Local, &opts) {
+                    Ok(report) => {
+                        if !opts.dry_run {
+                            report.print();
+                        }
+                        report.success
+                    }
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        false
+                    }
+                };
         }
         Action::SshConnect(ref target) => {
             println!("🔌 [Hybrid] Detected SSH intent to '{}'", target);
@@ -506,9 +1815,25 @@ async fn main() {
         }
         Action::ShowLog => {
             println!("📜 [Hybrid] Showing logs...");
-            let _ = Command::new("tail")
-                .args(&["-n", "10", "logs/history.jsonl"])
-                .status();
+            if let Ok(db) = crate::storage::db::Database::new() {
+                if let Ok(events) = db.get_events(None, None, 10) {
+                    for e in events {
+                        println!("   [{}] {}", e.kind.as_str(), e.command.as_deref().unwrap_or(""));
+                    }
+                }
+            }
+        }
+        Action::Install(ref package) => {
+            println!("📦 [Hybrid] Detected install intent for '{}'", package);
+            success = Interactor::confirm(&format!("Install '{}'?", package)) && install_package(package, None, dry_run);
+        }
+        Action::LocateFile(ref query) => {
+            println!("🗂️  [Hybrid] Looking up the offline file index...");
+            if let Ok(db) = crate::storage::db::Database::new() {
+                if crate::system::file_index::show_matches(&db, query, 20) == 0 {
+                    success = false;
+                }
+            }
         }
         Action::Unknown => {
             // Intelligent Fallback: AI or fzf?
@@ -525,10 +1850,21 @@ async fn main() {
                 let ctx = SystemContext::collect();
                 let preferred_engine = config.ai.as_ref().map(|a| a.provider.clone());
 
+                // Storage aliases (구드, 나스, ...) reach the AI as masked remotes
+                let mut masker = crate::remote::RemoteMasker::from_rclone();
+                let project = crate::system::project::ProjectRoot::current();
+                if let Some(project) = &project {
+                    // Project remotes from .vega.toml work like the global aliases
+                    for (alias, spec) in &project.profile.remotes {
+                        masker.add_alias(alias, spec);
+                    }
+                }
+                let masked_input = masker.mask_text(full_input);
+
                 // Call async generate_with_fallback
                 match crate::ai::router::SmartRouter::generate_with_fallback(
                     &ctx,
-                    full_input,
+                    &masked_input,
                     preferred_engine,
                 )
                 .await
@@ -551,19 +1887,22 @@ async fn main() {
                                 if !ai_res.command.is_empty() {
                                     println!("   > Command: {}", ai_res.command.green().bold());
 
+                                    if let Some(project) = &project {
+                                        if let Err(step) = project.profile.check_command(&ai_res.command) {
+                                            println!(
+                                                "⛔ [Project] '{}' is not allowed by the allowed_commands of {} ({}).",
+                                                step,
+                                                project.name,
+                                                crate::system::project::PROFILE_FILE
+                                            );
+                                            return;
+                                        }
+                                    }
+
                                     if Interactor::confirm("Execute this command?") {
                                         println!("⚡ Executing...");
 
                                         // RE-RESOLVE REMOTE NAMES (Unmasking)
-                                        let mut masker = crate::remote::RemoteMasker::new();
-                                        // Discovery::run() returns real names. We need to mask them to get the same REMOTE_XX mapping.
-                                        if let Ok(discovery) =
-                                            crate::system::discovery::Discovery::run()
-                                        {
-                                            for remote in discovery.cloud_remotes {
-                                                let _ = masker.mask(&remote);
-                                            }
-                                        }
                                         let mut final_cmd = masker.resolve_command(&ai_res.command);
 
                                         if final_cmd != ai_res.command {
@@ -580,7 +1919,7 @@ async fn main() {
                                                 if parts.len() > 2 {
                                                     let path = parts[1];
                                                     let mut prune_rules = Vec::new();
-                                                    for b_path in crate::system::SRE_BLACKLIST {
+                                                    for b_path in crate::system::mounts::search_blacklist() {
                                                         prune_rules.push(format!(
                                                             "-path '{}' -prune",
                                                             b_path
@@ -624,7 +1963,29 @@ async fn main() {
                                                     );
                                                 }
                                             }
-                                            Err(e) => println!("❌ Failed to spawn shell: {}", e),
+                                            Err(ref e) => println!("❌ Failed to spawn shell: {}", e),
+                                        }
+
+                                        let exit_code = status.as_ref().ok().and_then(|s| s.code());
+                                        if matches!(exit_code, Some(126) | Some(127)) {
+                                            let result = crate::executor::ExecuteResult {
+                                                success: false,
+                                                stdout: String::new(),
+                                                stderr: String::new(),
+                                                exit_code,
+                                            };
+                                            if let Some(fix) = crate::executor::healer::Healer::diagnose(&result, &ctx, &final_cmd) {
+                                                println!("💡 [Healer] Try: {}", fix.cyan());
+                                            }
+                                        }
+                                        if let Ok(db) = crate::storage::db::Database::new() {
+                                            use crate::storage::events::{Event, EventKind};
+                                            let event = Event::new(EventKind::Execution, &final_cmd)
+                                                .with_result(exit_code == Some(0), exit_code)
+                                                .with_payload(serde_json::json!({
+                                                    "project_name": project.as_ref().map(|p| p.name.clone()),
+                                                }));
+                                            let _ = db.record_event(&event);
                                         }
                                     } else {
                                         println!("🚫 Aborted by user.");
@@ -670,3 +2031,97 @@ async fn main() {
         }
     }
 }
+
+/// Picks the manager to install `package` with: `--via` when given, otherwise the first
+/// one (the distro's before flatpak/snap/nix) whose repositories have it. `package` may
+/// also be a command; the returned name is what that manager calls it (`fd-find` for
+/// `fd` on apt, a flatpak app id for `gimp`).
+fn pkg_manager_for<'a>(
+    managers: &'a [Box<dyn pkg::PackageManager>],
+    via: Option<&str>,
+    package: &str,
+) -> Option<(&'a dyn pkg::PackageManager, String)> {
+    if let Some(via) = via {
+        let kind = pkg::PackageSystem::parse(via);
+        return match managers.iter().find(|m| Some(m.kind()) == kind) {
+            Some(pm) => {
+                let name = pkg::resolve(pm.as_ref(), package)
+                    .map(|i| i.name)
+                    .unwrap_or_else(|| package.to_string());
+                Some((pm.as_ref(), name))
+            }
+            None => {
+                println!("❌ '{}' is not an active package manager here.", via);
+                None
+            }
+        };
+    }
+    for pm in managers {
+        if let Some(info) = pkg::resolve(pm.as_ref(), package) {
+            println!(
+                "🔎 Found {} {} in {} ({})",
+                info.name,
+                info.version.as_deref().unwrap_or(""),
+                info.source.as_deref().unwrap_or("repositories"),
+                pm.name()
+            );
+            return Some((pm.as_ref(), info.name));
+        }
+    }
+    println!("⚠️  No repository lists '{}'; trying {} anyway.", package, managers[0].name());
+    Some((managers[0].as_ref(), package.to_string()))
+}
+
+/// `vega install`: skips packages that are already there, then installs through the
+/// first manager whose repositories have it (or `via`).
+fn install_package(pkg_name: &str, via: Option<&str>, dry_run: bool) -> bool {
+    if let Err(e) = pkg::check_package_name(pkg_name) {
+        println!("❌ {}", e);
+        return false;
+    }
+    let ctx = SystemContext::collect();
+    let managers = pkg::detect_all(&ctx);
+    if managers.is_empty() {
+        println!("❌ No supported package manager found (detected: {}).", ctx.pkg_manager);
+        return false;
+    }
+    let names: Vec<&str> = managers.iter().map(|m| m.name()).collect();
+    println!("📦 Package Managers Detected: {}", names.join(", "));
+
+    for pm in &managers {
+        if let Some((package, version)) = pkg::installed(pm.as_ref(), pkg_name) {
+            println!("✅ {} {} is already installed via {}.", package, version, pm.name());
+            return true;
+        }
+    }
+
+    let (pm, package) = match pkg_manager_for(&managers, via, pkg_name) {
+        Some(found) => found,
+        None => return false,
+    };
+    let cmd = match pm.install(&package) {
+        Ok(command) => command.to_shell(),
+        Err(e) => {
+            println!("❌ {}", e);
+            return false;
+        }
+    };
+    println!("🚀 Proposed Command: {}", cmd);
+
+    if dry_run {
+        println!("🛑 Dry-Run: Execution Skipped.");
+        return true;
+    }
+    println!("⚡ Executing...");
+    let _ = Command::new("sh").arg("-c").arg(&cmd).status();
+    match pm.installed_version(&package) {
+        Some(version) => {
+            println!("✅ Installed {} {} via {}.", package, version, pm.name());
+            true
+        }
+        None => {
+            println!("❌ {} is still not installed.", package);
+            false
+        }
+    }
+}
@@ -2,11 +2,13 @@ use crate::connection::ssh::SshConnection;
 use async_trait::async_trait;
 use std::collections::HashMap;
 
+pub mod rc;
 pub mod rclone;
 
 pub struct RemoteMasker {
     mapping: HashMap<String, String>,         // real -> masked
     reverse_mapping: HashMap<String, String>, // masked -> real
+    aliases: HashMap<String, String>,         // alias -> remote spec (e.g. 구드 -> gdrive:)
 }
 
 impl RemoteMasker {
@@ -14,10 +16,70 @@ impl RemoteMasker {
         Self {
             mapping: HashMap::new(),
             reverse_mapping: HashMap::new(),
+            aliases: HashMap::new(),
         }
     }
 
+    /// Masker over the configured rclone remotes (in `rclone listremotes` order, matching
+    /// the masks used in the AI context) that also understands the `[aliases]` from config.
+    pub fn from_rclone() -> Self {
+        let mut masker = Self::new();
+        for remote in rclone::RcloneProvider::list_remotes().unwrap_or_default() {
+            masker.mask(&remote);
+        }
+        masker.aliases = crate::system::storage::SmartStorage::new().aliases;
+        masker
+    }
+
+    pub fn add_alias(&mut self, alias: &str, remote_spec: &str) {
+        self.aliases.insert(alias.to_string(), remote_spec.to_string());
+    }
+
+    /// Masked form of an alias target: `gdrive:` -> `REMOTE_01`, `gdrive:backup` -> `REMOTE_01:backup`.
+    fn mask_spec(&mut self, spec: &str) -> String {
+        let (remote, path) = spec.split_once(':').unwrap_or((spec, ""));
+        let masked = self.mask(remote);
+        if path.is_empty() {
+            masked
+        } else {
+            format!("{}:{}", masked, path)
+        }
+    }
+
+    /// Replaces aliases in free text (natural-language requests) with masked remotes,
+    /// so the AI sees `REMOTE_01` where the user wrote `구드`.
+    pub fn mask_text(&mut self, text: &str) -> String {
+        let mut aliases: Vec<(String, String)> = self.aliases.clone().into_iter().collect();
+        // Longest first so that `구드백업` is not consumed by `구드`
+        aliases.sort_by_key(|(alias, _)| std::cmp::Reverse(alias.len()));
+
+        let mut masked_text = text.to_string();
+        for (alias, spec) in aliases {
+            // ASCII aliases need word boundaries; Hangul ones are followed by particles (구드에)
+            let pattern = if alias.is_ascii() {
+                format!(r"\b{}\b", regex::escape(&alias))
+            } else {
+                regex::escape(&alias)
+            };
+            let re = match regex::Regex::new(&pattern) {
+                Ok(re) => re,
+                Err(_) => continue,
+            };
+            if re.is_match(&masked_text) {
+                let replacement = self.mask_spec(&spec);
+                masked_text = re.replace_all(&masked_text, regex::NoExpand(&replacement)).to_string();
+            }
+        }
+        masked_text
+    }
+
     pub fn mask(&mut self, real_name: &str) -> String {
+        // Aliases share the mask of the remote they point to
+        let real_name = match self.aliases.get(real_name) {
+            Some(spec) => spec.split(':').next().unwrap_or(spec).to_string(),
+            None => real_name.to_string(),
+        };
+        let real_name = real_name.as_str();
         if let Some(masked) = self.mapping.get(real_name) {
             return masked.clone();
         }
@@ -76,6 +138,17 @@ impl RemoteProvider for SshProvider {
     }
 
     async fn search(&self, query: &str) -> Result<Vec<String>, String> {
+        // Hosts crawled by `vega index` are searched offline, at any depth
+        let origin = format!("{}:", self.ip);
+        let q = query.to_string();
+        if let Ok(Some(hits)) = crate::storage::db::with_db(move |db| {
+            Ok(crate::system::file_index::search_origin(db, &origin, &q))
+        })
+        .await
+        {
+            return Ok(hits);
+        }
+
         let cmd = format!("find . -name '*{}*' -maxdepth 2", query);
         let output = SshConnection::execute_remote_async(&self.ip, &cmd).await?;
         Ok(output.lines().map(|s| s.to_string()).collect())
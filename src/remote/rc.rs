@@ -0,0 +1,359 @@
+use crate::config::RcloneConfig;
+use log::info;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+/// How often a running job is polled for progress.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a freshly spawned `rclone rcd` gets to start answering.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Client for rclone's remote-control API (`rclone rcd`).
+///
+/// Attaches to `[rclone] rc_url` when configured, otherwise starts a private daemon on a
+/// random localhost port with a one-time password; that daemon is killed on drop.
+pub struct RcClient {
+    base_url: String,
+    user: Option<String>,
+    pass: Option<String>,
+    client: Client,
+    daemon: Option<Child>,
+}
+
+/// Snapshot of `core/stats` for one job.
+#[derive(Debug, Clone, Default)]
+pub struct TransferStats {
+    pub bytes: i64,
+    pub total_bytes: i64,
+    pub speed: f64,
+    pub eta: Option<i64>,
+    pub transfers: i64,
+    pub total_transfers: i64,
+    pub checks: i64,
+    pub errors: i64,
+    pub elapsed: f64,
+}
+
+impl TransferStats {
+    pub fn from_value(v: &Value) -> Self {
+        TransferStats {
+            bytes: v["bytes"].as_i64().unwrap_or(0),
+            total_bytes: v["totalBytes"].as_i64().unwrap_or(0),
+            speed: v["speed"].as_f64().unwrap_or(0.0),
+            eta: v["eta"].as_i64(),
+            transfers: v["transfers"].as_i64().unwrap_or(0),
+            total_transfers: v["totalTransfers"].as_i64().unwrap_or(0),
+            checks: v["checks"].as_i64().unwrap_or(0),
+            errors: v["errors"].as_i64().unwrap_or(0),
+            elapsed: v["elapsedTime"].as_f64().unwrap_or(0.0),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "bytes": self.bytes,
+            "total_bytes": self.total_bytes,
+            "speed": self.speed,
+            "transfers": self.transfers,
+            "total_transfers": self.total_transfers,
+            "checks": self.checks,
+            "errors": self.errors,
+            "elapsed_secs": self.elapsed,
+        })
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.total_bytes > 0 {
+            self.bytes as f64 * 100.0 / self.total_bytes as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// One-line progress, e.g. `42% 12.0/28.5 MiB @ 3.1 MiB/s, 5/12 files, ETA 6s`.
+    pub fn progress_line(&self) -> String {
+        let mib = |b: f64| b / (1024.0 * 1024.0);
+        format!(
+            "{:>3.0}% {:.1}/{:.1} MiB @ {:.1} MiB/s, {}/{} files, ETA {}",
+            self.percent(),
+            mib(self.bytes as f64),
+            mib(self.total_bytes as f64),
+            mib(self.speed),
+            self.transfers,
+            self.total_transfers,
+            self.eta.map(|e| format!("{}s", e)).unwrap_or_else(|| "-".to_string())
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JobOutcome {
+    pub job_id: i64,
+    pub success: bool,
+    pub cancelled: bool,
+    pub error: Option<String>,
+    pub stats: TransferStats,
+}
+
+impl JobOutcome {
+    /// The outcome described by a `job/status` reply, `None` while the job is running.
+    pub fn from_status(job_id: i64, status: &Value, stats: TransferStats, cancelled: bool) -> Option<Self> {
+        if !status["finished"].as_bool().unwrap_or(false) {
+            return None;
+        }
+        Some(JobOutcome {
+            job_id,
+            success: status["success"].as_bool().unwrap_or(false),
+            cancelled,
+            error: status["error"].as_str().filter(|e| !e.is_empty()).map(str::to_string),
+            stats,
+        })
+    }
+}
+
+impl RcClient {
+    /// Attaches to the configured daemon or starts a private one. Fails when rc is
+    /// disabled in config or rclone cannot be started, so callers can fall back to the CLI.
+    pub async fn connect() -> Result<Self, String> {
+        let config = crate::config::VegaConfig::load(&crate::init::get_config_path().to_string_lossy())
+            .ok()
+            .and_then(|c| c.rclone)
+            .unwrap_or_default();
+        Self::connect_with(&config).await
+    }
+
+    pub async fn connect_with(config: &RcloneConfig) -> Result<Self, String> {
+        if !config.use_rc.unwrap_or(true) {
+            return Err("rclone rc disabled in config".to_string());
+        }
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        if let Some(url) = &config.rc_url {
+            let rc = RcClient {
+                base_url: url.trim_end_matches('/').to_string(),
+                user: config.rc_user.clone(),
+                pass: config.rc_pass.clone(),
+                client,
+                daemon: None,
+            };
+            rc.call("rc/noop", json!({})).await?;
+            return Ok(rc);
+        }
+
+        // Private daemon: random port, random credentials, localhost only
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.local_addr())
+            .map(|a| a.port())
+            .map_err(|e| format!("No free port for rclone rcd: {}", e))?;
+        let pass = format!("{:032x}", rand::random::<u128>());
+        // The password goes through the environment: argv is readable by every local user
+        let daemon = Command::new("rclone")
+            .args(["rcd", "--rc-addr", &format!("127.0.0.1:{}", port)])
+            .env("RCLONE_RC_USER", "vega")
+            .env("RCLONE_RC_PASS", &pass)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start rclone rcd: {}", e))?;
+
+        let rc = RcClient {
+            base_url: format!("http://127.0.0.1:{}", port),
+            user: Some("vega".to_string()),
+            pass: Some(pass),
+            client,
+            daemon: Some(daemon),
+        };
+        let started = std::time::Instant::now();
+        loop {
+            if rc.call("rc/noop", json!({})).await.is_ok() {
+                info!("🔌 rclone rcd started on port {}", port);
+                return Ok(rc);
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                return Err("rclone rcd did not start in time".to_string());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// POSTs `params` to `/<method>`; rc errors come back as `{"error": ...}` with a non-2xx status.
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let mut req = self
+            .client
+            .post(format!("{}/{}", self.base_url, method))
+            .json(&params);
+        if let Some(user) = &self.user {
+            req = req.basic_auth(user, self.pass.as_ref());
+        }
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| format!("rc {} failed: {}", method, e))?;
+        let status = resp.status();
+        let body: Value = resp.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            Ok(body)
+        } else {
+            Err(body["error"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("rc {} returned {}", method, status)))
+        }
+    }
+
+    /// Starts `sync/sync` as a background job. `config` overrides global options
+    /// (e.g. `{"BackupDir": "...", "Transfers": 8}`).
+    pub async fn start_sync(&self, src: &str, dst: &str, config: Value) -> Result<i64, String> {
+        let mut params = json!({
+            "srcFs": src,
+            "dstFs": dst,
+            "_async": true,
+        });
+        if !config.is_null() {
+            params["_config"] = config;
+        }
+        let resp = self.call("sync/sync", params).await?;
+        resp["jobid"]
+            .as_i64()
+            .ok_or_else(|| "rc sync/sync returned no jobid".to_string())
+    }
+
+    pub async fn job_status(&self, job_id: i64) -> Result<Value, String> {
+        self.call("job/status", json!({ "jobid": job_id })).await
+    }
+
+    pub async fn job_stats(&self, job_id: i64) -> Result<TransferStats, String> {
+        let v = self
+            .call("core/stats", json!({ "group": format!("job/{}", job_id) }))
+            .await?;
+        Ok(TransferStats::from_value(&v))
+    }
+
+    pub async fn stop_job(&self, job_id: i64) -> Result<(), String> {
+        self.call("job/stop", json!({ "jobid": job_id })).await.map(|_| ())
+    }
+
+    pub async fn list_jobs(&self) -> Result<Vec<i64>, String> {
+        let v = self.call("job/list", json!({})).await?;
+        Ok(v["jobids"]
+            .as_array()
+            .map(|ids| ids.iter().filter_map(|i| i.as_i64()).collect())
+            .unwrap_or_default())
+    }
+
+    /// `operations/size`: (file count, bytes).
+    pub async fn size(&self, fs: &str) -> Result<(i64, i64), String> {
+        let v = self.call("operations/size", json!({ "fs": fs })).await?;
+        Ok((v["count"].as_i64().unwrap_or(0), v["bytes"].as_i64().unwrap_or(0)))
+    }
+
+    /// Polls a job until it finishes, reporting progress. Ctrl-C stops the job.
+    pub async fn wait_job(&self, job_id: i64, mut on_progress: impl FnMut(&TransferStats)) -> Result<JobOutcome, String> {
+        let mut cancelled = false;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = tokio::signal::ctrl_c(), if !cancelled => {
+                    eprintln!("\n🛑 Cancelling rclone job {}...", job_id);
+                    self.stop_job(job_id).await?;
+                    cancelled = true;
+                }
+            }
+
+            let status = self.job_status(job_id).await?;
+            let stats = self.job_stats(job_id).await.unwrap_or_default();
+            match JobOutcome::from_status(job_id, &status, stats.clone(), cancelled) {
+                Some(outcome) => return Ok(outcome),
+                None => on_progress(&stats),
+            }
+        }
+    }
+}
+
+impl Drop for RcClient {
+    fn drop(&mut self) {
+        if let Some(daemon) = &mut self.daemon {
+            let _ = daemon.kill();
+            let _ = daemon.wait();
+        }
+    }
+}
+
+/// Runs a sync as an rc job with a live progress line on stderr.
+/// A job that ran and failed is an `Ok` outcome; `Err` means rc itself failed.
+pub async fn sync_with_progress(rc: &RcClient, src: &str, dst: &str, config: Value) -> Result<JobOutcome, String> {
+    let job_id = rc.start_sync(src, dst, config).await?;
+    let outcome = rc
+        .wait_job(job_id, |stats| {
+            eprint!("\r   ⏳ {}   ", stats.progress_line());
+        })
+        .await?;
+    eprintln!("\r   📦 {}   ", outcome.stats.progress_line());
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_core_stats() {
+        let stats = TransferStats::from_value(&json!({
+            "bytes": 12582912, "totalBytes": 29884416, "speed": 3250585.6, "eta": 6,
+            "transfers": 5, "totalTransfers": 12, "checks": 3, "errors": 0, "elapsedTime": 4.2
+        }));
+        assert_eq!(stats.total_transfers, 12);
+        assert_eq!(stats.eta, Some(6));
+        assert_eq!(stats.progress_line(), " 42% 12.0/28.5 MiB @ 3.1 MiB/s, 5/12 files, ETA 6s");
+        assert_eq!(stats.to_json()["elapsed_secs"], 4.2);
+
+        // An idle group has no totals and no ETA
+        let idle = TransferStats::from_value(&json!({ "eta": null }));
+        assert_eq!(idle.percent(), 0.0);
+        assert!(idle.progress_line().ends_with("ETA -"));
+    }
+
+    #[test]
+    fn reads_job_status() {
+        let running = json!({ "finished": false, "success": false, "error": "" });
+        assert!(JobOutcome::from_status(7, &running, TransferStats::default(), false).is_none());
+
+        let done = json!({ "finished": true, "success": true, "error": "" });
+        let outcome = JobOutcome::from_status(7, &done, TransferStats::default(), false).unwrap();
+        assert!(outcome.success && outcome.error.is_none());
+
+        let failed = json!({ "finished": true, "success": false, "error": "directory not found" });
+        let outcome = JobOutcome::from_status(7, &failed, TransferStats::default(), true).unwrap();
+        assert!(!outcome.success && outcome.cancelled);
+        assert_eq!(outcome.error.as_deref(), Some("directory not found"));
+    }
+
+    /// Needs `rclone` on PATH: `cargo test -- --ignored rc_syncs_local_directories`.
+    #[tokio::test]
+    #[ignore]
+    async fn rc_syncs_local_directories() {
+        let root = std::env::temp_dir().join(format!("vega-rc-{}", std::process::id()));
+        let (src, dst) = (root.join("src"), root.join("dst"));
+        std::fs::create_dir_all(src.join("nested")).unwrap();
+        std::fs::write(src.join("a.txt"), "alpha").unwrap();
+        std::fs::write(src.join("nested/b.txt"), "beta").unwrap();
+
+        let rc = RcClient::connect_with(&RcloneConfig::default()).await.unwrap();
+        assert_eq!(rc.size(&src.to_string_lossy()).await.unwrap(), (2, 9));
+        let outcome = sync_with_progress(&rc, &src.to_string_lossy(), &dst.to_string_lossy(), Value::Null)
+            .await
+            .unwrap();
+        assert!(outcome.success, "{:?}", outcome.error);
+        assert_eq!(std::fs::read_to_string(dst.join("nested/b.txt")).unwrap(), "beta");
+        assert_eq!(outcome.stats.transfers, 2);
+
+        drop(rc);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,8 +1,19 @@
 use crate::remote::RemoteProvider;
+use crate::storage::db::with_db;
+use crate::system::file_index;
+use crate::system::probe::Probe;
 use async_trait::async_trait;
 use log::info;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::process::Command;
+use std::sync::OnceLock;
+
+/// Remote name -> backend type, read once per process from `rclone config dump`.
+static BACKEND_TYPES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Backends that wrap another remote; tuning follows the wrapped one.
+const WRAPPER_BACKENDS: &[&str] = &["crypt", "alias", "chunker", "compress", "hasher"];
 
 pub struct RcloneProvider {
     pub remote_name: String,
@@ -13,12 +24,26 @@ impl RcloneProvider {
         Self { remote_name }
     }
 
+    /// Runs rclone for real, also while probes are captured or replayed; for anything
+    /// that changes a remote (`sync`, `copy`, `purge`).
     pub fn execute_rclone(&self, args: Vec<&str>) -> Result<String, String> {
         let output = Command::new("rclone")
             .args(args)
             .output()
             .map_err(|e| format!("Failed to execute rclone: {}", e))?;
+        Self::stdout_or_stderr(output)
+    }
+
+    /// Read-only rclone call (`lsjson`, `lsf`, `size`), recorded and replayed like any probe.
+    pub fn query_rclone(&self, args: Vec<&str>) -> Result<String, String> {
+        let output = Probe::new("rclone")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to execute rclone: {}", e))?;
+        Self::stdout_or_stderr(output)
+    }
 
+    fn stdout_or_stderr(output: std::process::Output) -> Result<String, String> {
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stdout).to_string())
         } else {
@@ -26,8 +51,69 @@ impl RcloneProvider {
         }
     }
 
+    /// Prefixes a bare path with this provider's remote (`backup/x` -> `gdrive:backup/x`).
+    pub fn qualify(&self, path: &str) -> String {
+        if path.contains(':') || self.remote_name.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}:{}", self.remote_name, path)
+        }
+    }
+
+    /// Like `execute_rclone`, but returns the raw output (stats are printed to stderr).
+    pub fn execute_rclone_output(&self, args: Vec<&str>) -> Result<std::process::Output, String> {
+        Command::new("rclone")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to execute rclone: {}", e))
+    }
+
+    /// Backend type of every configured remote (`drive`, `s3`, `sftp`, ...).
+    /// Wrapper remotes such as `crypt` report the type of the remote they wrap.
+    /// Only `type` and `remote` are read from the dump; credentials are never kept.
+    pub fn backend_types() -> &'static HashMap<String, String> {
+        BACKEND_TYPES.get_or_init(|| {
+            let dump = Probe::new("rclone")
+                .args(["config", "dump"])
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .and_then(|o| serde_json::from_slice::<Value>(&o.stdout).ok())
+                .unwrap_or(Value::Null);
+            Self::parse_backend_types(&dump)
+        })
+    }
+
+    pub fn parse_backend_types(dump: &Value) -> HashMap<String, String> {
+        let mut types = HashMap::new();
+        let remotes = match dump.as_object() {
+            Some(map) => map,
+            None => return types,
+        };
+        for name in remotes.keys() {
+            let mut current = name.as_str();
+            // Bounded walk so a misconfigured alias loop cannot hang
+            for _ in 0..5 {
+                let entry = &remotes[current];
+                let kind = entry["type"].as_str().unwrap_or("unknown");
+                let wrapped = entry["remote"]
+                    .as_str()
+                    .and_then(|r| r.split(':').next())
+                    .filter(|r| remotes.contains_key(*r));
+                match wrapped {
+                    Some(inner) if WRAPPER_BACKENDS.contains(&kind) => current = inner,
+                    _ => {
+                        types.insert(name.clone(), kind.to_string());
+                        break;
+                    }
+                }
+            }
+        }
+        types
+    }
+
     pub fn list_remotes() -> Result<Vec<String>, String> {
-        let output = Command::new("rclone")
+        let output = Probe::new("rclone")
             .arg("listremotes")
             .output()
             .map_err(|e| format!("Failed to list rclone remotes: {}", e))?;
@@ -47,7 +133,7 @@ impl RcloneProvider {
 impl RemoteProvider for RcloneProvider {
     async fn list(&self, path: &str) -> Result<Vec<String>, String> {
         let full_path = format!("{}:{}", self.remote_name, path);
-        let output = self.execute_rclone(vec!["lsjson", &full_path])?;
+        let output = self.query_rclone(vec!["lsjson", &full_path])?;
 
         let json: Value = serde_json::from_str(&output)
             .map_err(|e| format!("Failed to parse rclone output: {}", e))?;
@@ -68,9 +154,15 @@ impl RemoteProvider for RcloneProvider {
     }
 
     async fn search(&self, query: &str) -> Result<Vec<String>, String> {
-        // Limited search to avoid excessive API calls
+        // Indexed remotes answer from the offline file index (`vega index`)
         let full_path = format!("{}:", self.remote_name);
-        let output = self.execute_rclone(vec![
+        let (origin, q) = (full_path.clone(), query.to_string());
+        if let Ok(Some(hits)) = with_db(move |db| Ok(file_index::search_origin(db, &origin, &q))).await {
+            return Ok(hits);
+        }
+
+        // Limited live search to avoid excessive API calls
+        let output = self.query_rclone(vec![
             "lsf",
             "-R",
             "--max-depth",
@@ -83,8 +175,9 @@ impl RemoteProvider for RcloneProvider {
     }
 
     async fn sync(&self, source: &str, destination: &str) -> Result<(), String> {
+        let destination = self.qualify(destination);
         info!("🔄 Syncing {} to {}", source, destination);
-        self.execute_rclone(vec!["sync", source, destination])?;
+        self.execute_rclone(vec!["sync", source, &destination])?;
         Ok(())
     }
 
@@ -92,8 +185,9 @@ impl RemoteProvider for RcloneProvider {
         let full_path = format!("{}:{}", self.remote_name, path);
         info!("🔌 Mounting {} to {}", full_path, mount_point);
 
-        // Use nohup or spawn in background to avoid blocking
-        Command::new("rclone")
+        // With --daemon rclone backgrounds itself once the mount is up, so waiting
+        // here reports mount errors instead of losing them
+        let output = Command::new("rclone")
             .args(&[
                 "mount",
                 &full_path,
@@ -102,9 +196,90 @@ impl RemoteProvider for RcloneProvider {
                 "full",
                 "--daemon",
             ])
-            .spawn()
+            .output()
             .map_err(|e| format!("Failed to mount rclone: {}", e))?;
 
-        Ok(())
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `rclone config dump` (rclone v1.66): obscured passwords, tokens as JSON strings
+    const CONFIG_DUMP: &str = r#"{
+    "archive": {
+        "remote": "secret:archive",
+        "type": "chunker"
+    },
+    "gdrive": {
+        "client_id": "",
+        "scope": "drive",
+        "token": "{\"access_token\":\"ya29.a0\",\"token_type\":\"Bearer\",\"expiry\":\"2024-03-01T10:00:00Z\"}",
+        "type": "drive"
+    },
+    "loop_a": {
+        "remote": "loop_b:",
+        "type": "alias"
+    },
+    "loop_b": {
+        "remote": "loop_a:",
+        "type": "alias"
+    },
+    "nas_sftp": {
+        "host": "192.168.0.10",
+        "key_file": "~/.ssh/id_ed25519",
+        "shell_type": "unix",
+        "type": "sftp",
+        "user": "vega"
+    },
+    "photos": {
+        "remote": "gdrive:Photos",
+        "type": "alias"
+    },
+    "pool": {
+        "type": "union",
+        "upstreams": "gdrive: nas_sftp:"
+    },
+    "s3": {
+        "env_auth": "true",
+        "provider": "AWS",
+        "region": "eu-central-1",
+        "type": "s3"
+    },
+    "secret": {
+        "password": "hPkp1Y7hVm4Z3xqkN1Jf0Q",
+        "remote": "s3:vega-backups",
+        "type": "crypt"
+    },
+    "local_crypt": {
+        "password": "hPkp1Y7hVm4Z3xqkN1Jf0Q",
+        "remote": "/mnt/usb/vault",
+        "type": "crypt"
+    }
+}"#;
+
+    #[test]
+    fn backend_types_follow_wrappers_to_the_storage_backend() {
+        let dump: Value = serde_json::from_str(CONFIG_DUMP).unwrap();
+        let types = RcloneProvider::parse_backend_types(&dump);
+        assert_eq!(types["gdrive"], "drive");
+        assert_eq!(types["nas_sftp"], "sftp");
+        assert_eq!(types["photos"], "drive");
+        // chunker -> crypt -> s3
+        assert_eq!(types["secret"], "s3");
+        assert_eq!(types["archive"], "s3");
+        // Not a wrapper of a single remote
+        assert_eq!(types["pool"], "union");
+        // A crypt over a local path has no remote to follow
+        assert_eq!(types["local_crypt"], "crypt");
+        // Alias loops give up instead of hanging
+        assert!(!types.contains_key("loop_a"));
+        assert!(RcloneProvider::parse_backend_types(&Value::Null).is_empty());
     }
 }
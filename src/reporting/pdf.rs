@@ -1,4 +1,4 @@
-use crate::storage::db::Database;
+use crate::storage::db::with_db;
 use log::info;
 use std::path::PathBuf;
 
@@ -8,9 +8,8 @@ impl PdfEngine {
     pub async fn generate_report(session_id: i64) -> Result<String, String> {
         info!("📄 Generating PDF Report for Session ID {}...", session_id);
 
-        let db = Database::new().map_err(|e| format!("DB Error: {}", e))?;
-        let tasks = db
-            .get_session_tasks(session_id)
+        let tasks = with_db(move |db| db.get_session_tasks(session_id))
+            .await
             .map_err(|e| format!("DB Query Error: {}", e))?;
         info!("   Found {} tasks to report.", tasks.len());
 
@@ -37,9 +36,8 @@ impl PdfEngine {
             "📝 Generating Markdown Report for Session ID {}...",
             session_id
         );
-        let db = Database::new().map_err(|e| format!("DB Error: {}", e))?;
-        let tasks = db
-            .get_session_tasks(session_id)
+        let tasks = with_db(move |db| db.get_session_tasks(session_id))
+            .await
             .map_err(|e| format!("DB Query Error: {}", e))?;
 
         // AI Summary Integration
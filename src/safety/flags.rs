@@ -0,0 +1,530 @@
+use crate::executor::ast::{Arg, CommandAst};
+use crate::safety::RiskLevel;
+use crate::storage::cache_file;
+use crate::system::probe::{self, Probe};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::sync::OnceLock;
+
+/// Flags that are valid but destroy or overwrite data, per program.
+const DESTRUCTIVE: &[(&str, &str, RiskLevel, &str)] = &[
+    ("rclone", "--delete-before", RiskLevel::Warning, "deletes files on the destination before transferring"),
+    ("rclone", "--delete-during", RiskLevel::Warning, "deletes files on the destination while transferring"),
+    ("rclone", "--delete-after", RiskLevel::Warning, "deletes files on the destination after transferring"),
+    ("rclone", "--delete-excluded", RiskLevel::Critical, "deletes excluded files on the destination"),
+    ("rclone", "--ignore-errors", RiskLevel::Warning, "keeps deleting even after I/O errors"),
+    ("rsync", "--delete", RiskLevel::Warning, "deletes files on the destination that are not in the source"),
+    ("rsync", "--delete-before", RiskLevel::Warning, "deletes files on the destination before transferring"),
+    ("rsync", "--delete-during", RiskLevel::Warning, "deletes files on the destination while transferring"),
+    ("rsync", "--delete-delay", RiskLevel::Warning, "deletes files on the destination after transferring"),
+    ("rsync", "--delete-after", RiskLevel::Warning, "deletes files on the destination after transferring"),
+    ("rsync", "--delete-excluded", RiskLevel::Critical, "deletes excluded files on the destination"),
+    ("rsync", "--remove-source-files", RiskLevel::Critical, "removes the source files once they are sent"),
+    ("rsync", "--force", RiskLevel::Warning, "deletes non-empty directories"),
+    ("tar", "--remove-files", RiskLevel::Critical, "removes the files after adding them to the archive"),
+    ("tar", "--recursive-unlink", RiskLevel::Critical, "empties existing directories before extracting"),
+    ("tar", "--unlink-first", RiskLevel::Warning, "removes existing files before extracting"),
+    ("tar", "-U", RiskLevel::Warning, "removes existing files before extracting"),
+    ("tar", "--overwrite", RiskLevel::Warning, "overwrites existing files when extracting"),
+    ("journalctl", "--vacuum-size", RiskLevel::Critical, "deletes archived journal files"),
+    ("journalctl", "--vacuum-time", RiskLevel::Critical, "deletes archived journal files"),
+    ("journalctl", "--vacuum-files", RiskLevel::Critical, "deletes archived journal files"),
+    ("journalctl", "--rotate", RiskLevel::Warning, "archives the active journal files"),
+    ("systemctl", "--force", RiskLevel::Warning, "skips the unit's normal shutdown"),
+    ("systemctl", "-f", RiskLevel::Warning, "skips the unit's normal shutdown"),
+    ("docker", "--force", RiskLevel::Warning, "kills or removes without asking"),
+    ("docker", "-f", RiskLevel::Warning, "kills or removes without asking"),
+    ("apt", "--allow-remove-essential", RiskLevel::Critical, "may remove packages the system needs to boot"),
+    ("apt", "--allow-downgrades", RiskLevel::Warning, "may downgrade installed packages"),
+    ("apt", "--purge", RiskLevel::Warning, "also deletes configuration files"),
+    ("dnf", "--allowerasing", RiskLevel::Warning, "may remove conflicting packages"),
+    ("pacman", "--overwrite", RiskLevel::Warning, "overwrites files owned by other packages"),
+    ("pacman", "--nodeps", RiskLevel::Critical, "skips dependency checks"),
+    ("zypper", "--force-resolution", RiskLevel::Warning, "may remove packages to resolve conflicts"),
+];
+
+/// Documented flags that make the tool run another program, so a generated
+/// option could carry a whole command. These are never accepted.
+const EXECUTES: &[(&str, &str, &str)] = &[
+    ("ssh", "-o", "options such as ProxyCommand and LocalCommand run commands"),
+    ("ssh", "-F", "a config file can set ProxyCommand"),
+    ("scp", "-o", "options such as ProxyCommand run commands"),
+    ("scp", "-F", "a config file can set ProxyCommand"),
+    ("scp", "-S", "runs the given program instead of ssh"),
+    ("rsync", "-e", "runs the given remote shell"),
+    ("rsync", "--rsh", "runs the given remote shell"),
+    ("rsync", "--rsync-path", "runs the given program on the remote side"),
+    ("rsync", "-M", "passes options to the remote rsync"),
+    ("rsync", "--remote-option", "passes options to the remote rsync"),
+    ("tar", "--to-command", "pipes extracted files into a command"),
+    ("tar", "--checkpoint-action", "can run a command at each checkpoint"),
+    ("tar", "-I", "runs the given compression program"),
+    ("tar", "--use-compress-program", "runs the given compression program"),
+    ("tar", "-F", "runs a script at the end of each volume"),
+    ("tar", "--info-script", "runs a script at the end of each volume"),
+    ("tar", "--new-volume-script", "runs a script at the end of each volume"),
+    ("tar", "--rsh-command", "runs the given remote shell"),
+    ("tar", "--rmt-command", "runs the given remote tape program"),
+    ("rclone", "--password-command", "runs a command to get the config password"),
+    ("rclone", "--metadata-mapper", "runs a program on every object's metadata"),
+    ("docker", "--entrypoint", "replaces the program the container runs"),
+    ("apt", "-o", "APT options such as DPkg::Pre-Invoke run commands"),
+    ("apt", "--option", "APT options such as DPkg::Pre-Invoke run commands"),
+    ("dnf", "--setopt", "can load plugins from any path"),
+    ("pacman", "--hookdir", "runs hooks from any directory"),
+];
+
+/// Extra help pages that document flags `<command> --help` leaves out.
+const EXTRA_HELP: &[(&str, &[&str])] = &[("rclone", &["help", "flags"])];
+
+/// A valid flag that destroys or overwrites data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlagTag {
+    pub flag: String,
+    pub risk: RiskLevel,
+    pub reason: &'static str,
+}
+
+/// Outcome of checking generated options against the tool's interface.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FlagReport {
+    /// What `--help`/`man` was read for (`rclone sync`).
+    pub command: String,
+    pub accepted: Vec<String>,
+    pub rejected: Vec<(String, String)>,
+    pub tagged: Vec<FlagTag>,
+}
+
+impl FlagReport {
+    pub fn print(&self) {
+        if self.accepted.is_empty() && self.rejected.is_empty() {
+            return;
+        }
+        println!("🔎 Options checked against `{} --help`:", self.command);
+        if !self.accepted.is_empty() {
+            println!("   ✅ Accepted: {}", self.accepted.join(" "));
+        }
+        for (option, reason) in &self.rejected {
+            println!("   ❌ Rejected: {} ({})", option, reason);
+        }
+        for tag in &self.tagged {
+            println!("   ⚠️  {:?}: {} {}", tag.risk, tag.flag, tag.reason);
+        }
+    }
+}
+
+/// Flags one command accepts, as read from its help output.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interface {
+    pub flags: BTreeSet<String>,
+    /// Flags documented with an argument (`--transfers int`, `-n NUM`, `--bwlimit=RATE`).
+    pub with_value: BTreeSet<String>,
+}
+
+impl Interface {
+    fn extend(&mut self, other: Interface) {
+        self.flags.extend(other.flags);
+        self.with_value.extend(other.with_value);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ToolFlags {
+    interface: Interface,
+    /// mtime of the binary the flags were read from; a newer binary is read again.
+    binary_mtime: Option<i64>,
+}
+
+/// Learned interfaces kept between runs in `<data dir>/vega/flag_cache.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FlagCache {
+    tools: HashMap<String, ToolFlags>,
+}
+
+impl FlagCache {
+    fn path() -> std::path::PathBuf {
+        cache_file::data_path("flag_cache.json")
+    }
+
+    fn load() -> Self {
+        cache_file::load(&Self::path())
+    }
+
+    fn save(&self) {
+        let _ = cache_file::save(&Self::path(), self);
+    }
+}
+
+/// The words `--help` is asked of: program and subcommand, without sudo or fixed flags.
+pub fn help_command(ast: &CommandAst) -> Vec<String> {
+    let mut words = Vec::new();
+    for arg in &ast.args {
+        match arg {
+            Arg::Word(word) if word == "sudo" && words.is_empty() => {}
+            Arg::Word(word) if word.starts_with('-') => {}
+            Arg::Word(word) => words.push(word.clone()),
+            Arg::Options => break,
+        }
+    }
+    if words.is_empty() {
+        words.extend(ast.argv().into_iter().find(|w| w != "sudo"));
+    }
+    words
+}
+
+/// Checks `options` against the flags `ast`'s program documents. Unknown flags and
+/// values that do not follow an accepted flag are rejected.
+pub fn validate(ast: &CommandAst, options: &[String]) -> FlagReport {
+    let command = help_command(ast);
+    match learn(&command) {
+        Some(known) => check(&command, &known, options),
+        None => FlagReport {
+            command: command.join(" "),
+            accepted: Vec::new(),
+            rejected: options
+                .iter()
+                .map(|o| (o.clone(), "tool interface could not be read".to_string()))
+                .collect(),
+            tagged: Vec::new(),
+        },
+    }
+}
+
+/// The flags `command` accepts, from the cache or its `--help` and man page.
+fn learn(command: &[String]) -> Option<Interface> {
+    let program = command.first()?;
+    let key = command.join(" ");
+    let use_cache = probe::is_live();
+    let mtime = binary_mtime(program);
+    let mut cache = if use_cache { FlagCache::load() } else { FlagCache::default() };
+    if let Some(entry) = cache.tools.get(&key) {
+        if entry.binary_mtime == mtime {
+            return Some(entry.interface.clone());
+        }
+    }
+
+    let mut interface = Interface::default();
+    if let Ok(output) = Probe::new(program).args(&command[1..]).arg("--help").output() {
+        interface.extend(parse_help(&String::from_utf8_lossy(&output.stdout)));
+        interface.extend(parse_help(&String::from_utf8_lossy(&output.stderr)));
+    }
+    // rclone's global flags (`--transfers`, `--bwlimit`) only appear in `rclone help flags`
+    for (_, args) in EXTRA_HELP.iter().filter(|(p, _)| p == program) {
+        if let Ok(output) = Probe::new(program).args(*args).output() {
+            if output.status.success() {
+                interface.extend(parse_help(&String::from_utf8_lossy(&output.stdout)));
+            }
+        }
+    }
+    if let Ok(output) = Probe::new("man").args(["-P", "cat", program.as_str()]).output() {
+        if output.status.success() {
+            interface.extend(parse_help(&String::from_utf8_lossy(&output.stdout)));
+        }
+    }
+    if interface.flags.is_empty() {
+        return None;
+    }
+
+    if use_cache {
+        cache.tools.insert(
+            key,
+            ToolFlags {
+                interface: interface.clone(),
+                binary_mtime: mtime,
+            },
+        );
+        cache.save();
+    }
+    Some(interface)
+}
+
+fn binary_mtime(program: &str) -> Option<i64> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find_map(|path| fs::metadata(path).ok()?.modified().ok())
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+}
+
+/// Flags mentioned in help or man page text: `--long`, `-s`, and grouped `[-abc]`.
+/// A flag followed by `=X`, `[=X]` or a lone word (`-n NUM`) takes a value.
+pub fn parse_help(text: &str) -> Interface {
+    static FLAG: OnceLock<Regex> = OnceLock::new();
+    static GROUP: OnceLock<Regex> = OnceLock::new();
+    let flag = FLAG.get_or_init(|| {
+        Regex::new(r"(?m)(?:^|[\s,\[(|/])(--?[A-Za-z0-9][A-Za-z0-9_-]*)(=|\[=| [^\s\-\],][^\s\],]*(?:\]|,|\s{2,}|$))?")
+            .unwrap()
+    });
+    let group = GROUP.get_or_init(|| Regex::new(r"\[-([A-Za-z0-9]{2,})\]").unwrap());
+
+    // man output may carry overstrike bold (`x\bx`) and typographic hyphens
+    let mut clean = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\u{8}' => {
+                clean.pop();
+            }
+            '\u{2010}' | '\u{2212}' => clean.push('-'),
+            _ => clean.push(c),
+        }
+    }
+
+    let mut interface = Interface::default();
+    for line in clean.lines() {
+        // In `-C, --directory=DIR` the short alias takes the value too
+        let mut aliases: Vec<String> = Vec::new();
+        for caps in flag.captures_iter(line) {
+            let name = caps[1].trim_end_matches('-').to_string();
+            if !name.starts_with("--") && name.len() != 2 {
+                aliases.clear();
+                continue;
+            }
+            interface.flags.insert(name.clone());
+            if caps.get(2).is_some() {
+                interface.with_value.extend(aliases.drain(..));
+                interface.with_value.insert(name);
+            } else if line[caps.get(1).map_or(0, |m| m.end())..].starts_with(',') {
+                aliases.push(name);
+            } else {
+                aliases.clear();
+            }
+        }
+    }
+    for caps in group.captures_iter(&clean) {
+        interface.flags.extend(caps[1].chars().map(|c| format!("-{}", c)));
+    }
+    interface
+}
+
+/// Flag names an option sets: `--bwlimit=1M` → `--bwlimit`, `-avz` → `-a -v -z`.
+fn flag_names(option: &str, known: &BTreeSet<String>) -> Option<Vec<String>> {
+    if let Some(long) = option.strip_prefix("--") {
+        let name = format!("--{}", long.split('=').next().unwrap_or(long));
+        return known.contains(&name).then(|| vec![name]);
+    }
+    let short = option.strip_prefix('-')?;
+    if short.chars().count() == 1 {
+        return known.contains(option).then(|| vec![option.to_string()]);
+    }
+    let names: Vec<String> = short.chars().map(|c| format!("-{}", c)).collect();
+    if names.iter().all(|n| known.contains(n)) {
+        return Some(names);
+    }
+    // `-n20`: a short flag with its value attached
+    let first = names.first()?;
+    let rest: String = short.chars().skip(1).collect();
+    (known.contains(first) && rest.chars().all(|c| c.is_ascii_digit())).then(|| vec![first.clone()])
+}
+
+pub fn check(command: &[String], known: &Interface, options: &[String]) -> FlagReport {
+    let program = command.first().map(String::as_str).unwrap_or_default();
+    let mut report = FlagReport {
+        command: command.join(" "),
+        ..Default::default()
+    };
+    // A bare value is only taken as the argument of the flag right before it
+    let mut takes_value = false;
+    for option in options {
+        if !option.starts_with('-') || option == "-" || option == "--" {
+            if takes_value {
+                report.accepted.push(option.clone());
+            } else {
+                report.rejected.push((option.clone(), "not a flag".to_string()));
+            }
+            takes_value = false;
+            continue;
+        }
+        if let Some((flag, reason)) = executes(program, option) {
+            report
+                .rejected
+                .push((option.clone(), format!("{} {}", flag, reason)));
+            takes_value = false;
+            continue;
+        }
+        match flag_names(option, &known.flags) {
+            Some(names) => {
+                for name in &names {
+                    report.tagged.extend(tag(program, name));
+                }
+                // `-n20` and `--bwlimit=1M` carry their value already
+                takes_value = !option.contains('=')
+                    && (option.starts_with("--") || names.len() == option.chars().count() - 1)
+                    && names.last().is_some_and(|n| known.with_value.contains(n));
+                report.accepted.push(option.clone());
+            }
+            None => {
+                report
+                    .rejected
+                    .push((option.clone(), format!("not in {}'s interface", report.command)));
+                takes_value = false;
+            }
+        }
+    }
+    report
+}
+
+fn tag(program: &str, flag: &str) -> Option<FlagTag> {
+    DESTRUCTIVE
+        .iter()
+        .find(|(p, f, _, _)| *p == program && *f == flag)
+        .map(|(_, f, risk, reason)| FlagTag {
+            flag: f.to_string(),
+            risk: *risk,
+            reason,
+        })
+}
+
+/// Every flag name an option could set, without consulting the interface:
+/// `--rsh=ssh` → `--rsh`, `-oProxyCommand=x` → `-o -P -r ...`.
+fn option_names(option: &str) -> Vec<String> {
+    if let Some(long) = option.strip_prefix("--") {
+        return vec![format!("--{}", long.split('=').next().unwrap_or(long))];
+    }
+    match option.strip_prefix('-') {
+        Some(short) => short.chars().map(|c| format!("-{}", c)).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// The command-running flag `option` sets for `program`, if any.
+fn executes(program: &str, option: &str) -> Option<(&'static str, &'static str)> {
+    option_names(option).iter().find_map(|name| {
+        EXECUTES
+            .iter()
+            .find(|(p, f, _)| *p == program && f == name)
+            .map(|(_, f, reason)| (*f, *reason))
+    })
+}
+
+/// Destructive flags among `ast`'s options, for the simulation step.
+pub fn destructive(ast: &CommandAst) -> Vec<FlagTag> {
+    let command = help_command(ast);
+    let program = command.first().map(String::as_str).unwrap_or_default();
+    ast.options
+        .iter()
+        .flat_map(|option| option_names(option))
+        .filter_map(|name| tag(program, &name))
+        .collect()
+}
+
+/// The first option of `ast` that makes its program run another command.
+pub fn command_running(ast: &CommandAst) -> Option<String> {
+    let command = help_command(ast);
+    let program = command.first().map(String::as_str).unwrap_or_default();
+    ast.options
+        .iter()
+        .find_map(|option| executes(program, option).map(|(flag, reason)| format!("{} {}", flag, reason)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RCLONE_HELP: &str = "\
+Usage:
+  rclone sync source:path dest:path [flags]
+
+Flags:
+      --create-empty-src-dirs   Create empty source dirs on destination after sync
+  -h, --help                    help for sync
+      --checksum                Check for changes with size & checksum
+      --delete-before           When synchronizing, delete files on destination before transferring
+      --transfers int           Number of file transfers to run in parallel (default 4)
+  -P, --progress                Show progress during transfer
+";
+
+    fn command(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_long_short_and_grouped_flags() {
+        let parsed = parse_help(RCLONE_HELP);
+        let flags = &parsed.flags;
+        for flag in ["--checksum", "--delete-before", "--transfers", "-P", "--progress", "-h"] {
+            assert!(flags.contains(flag), "{}", flag);
+        }
+        assert!(!flags.contains("--checksum-all"));
+        assert_eq!(parsed.with_value.iter().collect::<Vec<_>>(), ["--transfers"]);
+
+        let ssh = parse_help("usage: ssh [-46AaCfGgKkMNnqsTtVvXxYy] [-B bind_interface]\n");
+        assert!(ssh.flags.contains("-C") && ssh.flags.contains("-4"));
+        assert!(ssh.with_value.contains("-B") && !ssh.with_value.contains("-C"));
+        let man = parse_help("   -\u{8}--\u{8}-q\u{8}qu\u{8}ui\u{8}ie\u{8}et\u{8}t   Suppress output");
+        assert!(man.flags.contains("--quiet"));
+        assert!(parse_help("  -C, --directory=DIR        change to directory DIR\n").with_value.contains("-C"));
+    }
+
+    #[test]
+    fn rejects_unknown_flags_and_tags_destructive_ones() {
+        let known = parse_help(RCLONE_HELP);
+        let options = command(&["--checksum-all", "--transfers", "8", "-P", "--delete-before", "; rm -rf ~"]);
+        let report = check(&command(&["rclone", "sync"]), &known, &options);
+
+        assert_eq!(report.accepted, command(&["--transfers", "8", "-P", "--delete-before"]));
+        // `--delete-before` takes no value, so the word after it is not one
+        let rejected: Vec<&str> = report.rejected.iter().map(|(o, _)| o.as_str()).collect();
+        assert_eq!(rejected, ["--checksum-all", "; rm -rf ~"]);
+        assert_eq!(report.tagged.len(), 1);
+        assert_eq!(report.tagged[0].flag, "--delete-before");
+        assert_eq!(report.tagged[0].risk, RiskLevel::Warning);
+    }
+
+    #[test]
+    fn reads_help_for_the_subcommand_without_sudo() {
+        let mut ast = CommandAst::new("systemctl", "restart");
+        ast.args = ["sudo", "systemctl"]
+            .iter()
+            .map(|w| Arg::Word(w.to_string()))
+            .chain([Arg::Options, Arg::Word("restart".into()), Arg::Word("nginx".into())])
+            .collect();
+        assert_eq!(help_command(&ast), ["systemctl"]);
+
+        let bundle = probe::Bundle::new()
+            .with_command("systemctl", &["--help"], "  -f --force   When enabling unit files, override existing symlinks\n")
+            .with_command("man", &["-P", "cat", "systemctl"], "");
+        let report = probe::with_bundle(bundle, || validate(&ast, &command(&["--force", "--now-please"])));
+        assert_eq!(report.accepted, ["--force"]);
+        assert_eq!(report.rejected[0].0, "--now-please");
+        assert_eq!(report.tagged[0].risk, RiskLevel::Warning);
+    }
+
+    #[test]
+    fn never_accepts_flags_that_run_commands() {
+        let cases: &[(&str, &str, &[&str])] = &[
+            ("ssh", "  -o option   Can be used to give options\n  -C   Compression\n", &["-o", "ProxyCommand=sh -c id"]),
+            ("ssh", "  -o option   Can be used to give options\n  -C   Compression\n", &["-oLocalCommand=id"]),
+            ("scp", "  -S program   Name of program to use\n", &["-S", "/tmp/evil"]),
+            ("rsync", "  -e, --rsh=COMMAND   specify the remote shell\n", &["--rsh=sh -c id"]),
+            ("rsync", "      --rsync-path=PROGRAM   specify the rsync to run\n", &["--rsync-path=id"]),
+            ("tar", "      --to-command=COMMAND   pipe extracted files\n", &["--to-command=sh"]),
+            ("tar", "      --checkpoint-action=ACTION   run ACTION\n", &["--checkpoint-action=exec=id"]),
+            ("tar", "  -I, --use-compress-program=PROG   filter through PROG\n  -x   extract\n", &["-xI", "sh"]),
+        ];
+        for (program, help, options) in cases {
+            let report = check(&command(&[program]), &parse_help(help), &command(options));
+            assert!(report.accepted.is_empty(), "{} {:?}: {:?}", program, options, report.accepted);
+            assert_eq!(report.rejected.len(), options.len());
+        }
+
+        let mut ast = CommandAst::new("rsync", "sync");
+        ast.args = vec![Arg::Word("rsync".into()), Arg::Word("-a".into()), Arg::Options];
+        ast.options = vec!["-e".into(), "sh -c id".into()];
+        assert!(command_running(&ast).unwrap().starts_with("-e "));
+    }
+
+    #[test]
+    fn learns_rclone_global_flags_from_help_flags() {
+        let mut ast = CommandAst::new("rclone", "sync");
+        ast.args = vec![Arg::Word("rclone".into()), Arg::Word("sync".into()), Arg::Options];
+        let bundle = probe::Bundle::new()
+            .with_command("rclone", &["sync", "--help"], RCLONE_HELP)
+            .with_command("rclone", &["help", "flags"], "      --bwlimit BwTimetable   Bandwidth limit in KiB/s\n");
+        let report = probe::with_bundle(bundle, || validate(&ast, &command(&["--bwlimit", "10M", "--transfers", "8"])));
+        assert_eq!(report.accepted, ["--bwlimit", "10M", "--transfers", "8"]);
+        assert!(report.rejected.is_empty());
+    }
+}
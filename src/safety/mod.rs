@@ -1,5 +1,6 @@
 pub mod sanitizer;
 pub mod risk;
+pub mod flags;
 
 use colored::Colorize;
 use std::io::{self, Write};
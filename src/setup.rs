@@ -118,6 +118,7 @@ impl SetupWizard {
             shell_snapshot_path: None, // Use default logic in main.rs
             auto_sync: Some(true),
             primary_remote,
+            session_timeout_minutes: None, // Default: 30 minutes
         });
 
         // Save
@@ -1,4 +1,4 @@
-use std::process::Command;
+use crate::system::probe::Probe;
 use std::fs;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -38,7 +38,7 @@ impl ShellSnapshot {
     fn dump_aliases() -> HashMap<String, String> {
         let mut map = HashMap::new();
         // Use timeout to prevent hanging if .zshrc is interactive/broken
-        let output = Command::new("timeout")
+        let output = Probe::new("timeout")
             .args(&["3s", "zsh", "-i", "-c", "alias"])
             .output();
 
@@ -54,7 +54,7 @@ impl ShellSnapshot {
     }
 
     fn dump_zoxide() -> Vec<String> {
-        let output = Command::new("zoxide")
+        let output = Probe::new("zoxide")
             .args(&["query", "-l"])
             .output();
             
@@ -68,3 +68,18 @@ impl ShellSnapshot {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::probe::{with_bundle, Bundle};
+
+    #[test]
+    fn snapshot_from_capture() {
+        let bundle = Bundle::from_json(include_str!("../tests/fixtures/fedora-workstation.json")).unwrap();
+        let snapshot = with_bundle(bundle, ShellSnapshot::new);
+        assert_eq!(snapshot.aliases["ll"], "ls -l");
+        assert_eq!(snapshot.aliases["run-help"], "man");
+        assert_eq!(snapshot.zoxide_paths, vec!["/home/me/python_project2", "/mnt/HDD/Media"]);
+    }
+}
@@ -0,0 +1,80 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Numbers temp files within this process; the pid tells processes apart.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// `<data dir>/vega/<name>`, where the JSON caches between runs live.
+pub fn data_path(name: &str) -> PathBuf {
+    dirs::data_local_dir()
+        .map(|p| p.join("vega"))
+        .unwrap_or_else(|| PathBuf::from("logs"))
+        .join(name)
+}
+
+/// The cached value, or the default when the file is missing or unreadable.
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> T {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+/// Writes `value` to a temp file of its own, then renames it over `path`.
+/// Concurrent `vega` processes never share a temp file, so the last rename wins
+/// and readers only ever see a complete file.
+pub fn save<T: Serialize>(path: &Path, value: &T) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string(value)?;
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    if let Err(e) = fs::write(&tmp, json).and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn concurrent_saves_leave_one_complete_file() {
+        let dir = std::env::temp_dir().join(format!("vega-cache-file-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("flag_cache.json");
+
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let value: HashMap<String, String> = (0..200).map(|k| (format!("k{}", k), "x".repeat(i * 10))).collect();
+                    save(&path, &value).unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let loaded: HashMap<String, String> = load(&path);
+        assert_eq!(loaded.len(), 200);
+        let lengths: std::collections::HashSet<usize> = loaded.values().map(|v| v.len()).collect();
+        assert_eq!(lengths.len(), 1, "one writer's value, not a mix");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "no temp files left behind");
+        assert!(load::<HashMap<String, String>>(&dir.join("missing.json")).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,10 +1,36 @@
-use rusqlite::{params, Connection, Result};
+use crate::context::SyncEdge;
+use crate::storage::events::{Event, EventKind};
+use crate::storage::migrations;
+use crate::storage::retention::{self, ForgetFilter, RetentionPolicy, RetentionReport};
+use crate::storage::session::{self, SessionInfo};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde_json::json;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::os::unix::fs::PermissionsExt;
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
 
+/// Process-wide connection, opened lazily by the first `Database::new()`.
+static SHARED: Mutex<Option<SharedConn>> = Mutex::new(None);
+static LEGACY_CHECK: Once = Once::new();
+
+/// Reentrant so that a method holding the lock can call other `Database` methods.
+type SharedConn = Arc<ReentrantMutex<Connection>>;
+
+/// Milliseconds a writer waits for a concurrent `vega` process before "database is locked".
+const BUSY_TIMEOUT_MS: u64 = 5000;
+/// Smallest number of events the size cap trims per round; each round compacts the file.
+const TRIM_BATCH: i64 = 500;
+
+/// Cheap, clonable handle to the shared SQLite connection.
+#[derive(Clone)]
 pub struct Database {
-    conn: Connection,
+    conn: SharedConn,
+    path: PathBuf,
     current_session_id: Option<i64>,
 }
 
@@ -21,211 +47,334 @@ pub struct TaskEntry {
     pub timestamp: i64,
 }
 
+/// One `vega backup` run. `snapshot` names the dated `--backup-dir` version folder.
+#[derive(Debug, Clone, Default)]
+pub struct BackupRun {
+    pub id: i64,
+    pub source: String,
+    pub destination: String,
+    pub snapshot: String,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub success: bool,
+    pub verified: bool,
+    pub files_transferred: i64,
+    pub bytes_transferred: i64,
+    pub total_files: i64,
+    pub total_bytes: i64,
+    pub error: Option<String>,
+    pub pruned: bool,
+    /// Newer snapshot whose version folder took over this one's files when it was pruned.
+    /// `None` on a pruned run means the folder was deleted outright (before folding existed).
+    pub folded_into: Option<String>,
+}
+
+/// One crawled file or folder. `origin` is an rclone remote (`gdrive:`) or an
+/// scp-style host prefix (`user@10.0.0.5:`), so `origin + path` addresses the file.
+#[derive(Debug, Clone, Default)]
+pub struct IndexedFile {
+    pub origin: String,
+    pub path: String,
+    pub size: Option<i64>,
+    pub mtime: Option<i64>,
+    pub hash: Option<String>,
+    pub is_dir: bool,
+}
+
+/// A mount created by `vega mount`. `kind` is `rclone` or `sshfs`; mounts with a
+/// `systemd_unit` are kept alive by systemd instead of VEGA's start-up remount.
+#[derive(Debug, Clone, Default)]
+pub struct MountEntry {
+    pub id: i64,
+    pub kind: String,
+    pub source: String,
+    pub mount_point: String,
+    pub auto_remount: bool,
+    pub systemd_unit: Option<String>,
+    pub created_at: i64,
+    pub last_mounted: Option<i64>,
+    pub last_check: Option<i64>,
+    pub healthy: Option<bool>,
+    pub last_error: Option<String>,
+}
+
+/// Crawl state of one indexed remote or host.
+#[derive(Debug, Clone, Default)]
+pub struct IndexOrigin {
+    pub origin: String,
+    pub label: String,
+    pub kind: String, // rclone | host
+    pub last_crawl: Option<i64>,
+    pub entries: i64,
+    pub duration_ms: Option<i64>,
+    pub error: Option<String>,
+}
+
 impl Database {
     pub fn get_current_session_id(&self) -> Option<i64> {
         self.current_session_id
     }
 
     pub fn new() -> Result<Self> {
-        let config_dir = dirs::config_dir().unwrap_or_else(|| std::path::PathBuf::from("."));
-        let db_path = config_dir.join("vega").join("vega.db");
+        let db_path = Self::default_path();
+        let conn = {
+            let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+            match shared.as_ref() {
+                Some(conn) => conn.clone(),
+                None => {
+                    let conn = Arc::new(ReentrantMutex::new(Self::open_shared(&db_path)?));
+                    *shared = Some(conn.clone());
+                    conn
+                }
+            }
+        };
+
+        let db = Database {
+            conn,
+            path: db_path,
+            current_session_id: session::active(),
+        };
+
+        // One-shot import of the pre-event-log history stores
+        LEGACY_CHECK.call_once(|| {
+            if let Ok(None) = db.get_metadata(crate::storage::legacy::IMPORT_MARKER) {
+                if let Err(e) = crate::storage::legacy::import_all(&db) {
+                    eprintln!("⚠️ Legacy history import failed: {}", e);
+                }
+            }
+        });
+        Ok(db)
+    }
+
+    /// Opens the file once per process: permissions, pragmas and migrations.
+    fn open_shared(db_path: &Path) -> Result<Connection> {
         // Ensure directory exists
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent).ok();
         }
 
-        let conn = Connection::open(&db_path)?;
+        let conn = Connection::open(db_path)?;
         
         // Security: Enforce 600 permissions
-        if let Ok(metadata) = fs::metadata(&db_path) {
+        if let Ok(metadata) = fs::metadata(db_path) {
             let mut perms = metadata.permissions();
             perms.set_mode(0o600);
-            let _ = fs::set_permissions(&db_path, perms);
+            let _ = fs::set_permissions(db_path, perms);
         }
 
-        let mut db = Database {
-            conn,
+        // Concurrency: WAL lets readers run alongside a writer; busy_timeout queues writers
+        conn.busy_timeout(Duration::from_millis(BUSY_TIMEOUT_MS))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        // Privacy: overwrite deleted content so `vega forget` leaves nothing in free pages
+        conn.pragma_update(None, "secure_delete", "ON")?;
+        conn.set_prepared_statement_cache_capacity(64);
+
+        // Versioned schema: see storage::migrations
+        let applied = migrations::run(&conn, db_path)?;
+        if let Some(last) = applied.last() {
+            log::info!("🗄️  Database schema upgraded to v{}", last);
+        }
+        Ok(conn)
+    }
+
+    fn conn(&self) -> ReentrantMutexGuard<'_, Connection> {
+        self.conn.lock()
+    }
+
+    pub fn default_path() -> PathBuf {
+        let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        config_dir.join("vega").join("vega.db")
+    }
+
+    /// Opens a private connection without migrating or joining a session (`vega db status`).
+    pub fn open_raw() -> Result<Self> {
+        let path = Self::default_path();
+        let conn = Connection::open(&path)?;
+        conn.busy_timeout(Duration::from_millis(BUSY_TIMEOUT_MS))?;
+        Ok(Database {
+            conn: Arc::new(ReentrantMutex::new(conn)),
+            path,
             current_session_id: None,
-        };
-        db.migrate()?;
-        db.start_session()?;
-        Ok(db)
+        })
     }
 
-    fn migrate(&self) -> Result<()> {
-        self.conn.execute(
-            "PRAGMA foreign_keys = ON;",
-            [],
-        )?;
+    /// Opens a private connection to another database file (e.g. a peer snapshot)
+    /// and brings it up to the current schema.
+    pub fn open_file(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(Duration::from_millis(BUSY_TIMEOUT_MS))?;
+        migrations::run(&conn, path)?;
+        Ok(Database {
+            conn: Arc::new(ReentrantMutex::new(conn)),
+            path: path.to_path_buf(),
+            current_session_id: None,
+        })
+    }
 
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS sessions (
-                id INTEGER PRIMARY KEY,
-                start_time INTEGER NOT NULL,
-                end_time INTEGER,
-                total_weight INTEGER DEFAULT 0
-            )",
-            [],
-        )?;
+    /// Applies pending migrations and returns the versions that ran.
+    pub fn run_migrations(&self) -> Result<Vec<i32>> {
+        migrations::run(&self.conn(), &self.path)
+    }
 
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS commands (
-                id INTEGER PRIMARY KEY,
-                session_id INTEGER,
-                command TEXT NOT NULL,
-                ai_comment TEXT,
-                weight INTEGER,
-                timestamp INTEGER,
-                success BOOLEAN,
-                FOREIGN KEY(session_id) REFERENCES sessions(id)
-            )",
-            [],
-        )?;
+    pub fn schema_version(&self) -> Result<i32> {
+        migrations::current_version(&self.conn())
+    }
 
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS metadata (
-                key TEXT PRIMARY KEY,
-                value TEXT
-            )",
-            [],
-        )?;
+    pub fn backup(&self, tag: &str) -> Result<PathBuf> {
+        migrations::backup(&self.conn(), &self.path, tag)
+    }
 
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS chat_history (
-                id INTEGER PRIMARY KEY,
-                role TEXT NOT NULL,
-                content TEXT NOT NULL,
-                timestamp INTEGER
-            )",
-            [],
-        )?;
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS execution_logs (
-                id INTEGER PRIMARY KEY,
-                session_id INTEGER,
-                command TEXT NOT NULL,
-                success BOOLEAN,
-                exit_code INTEGER,
-                stdout TEXT,
-                stderr TEXT,
-                healer_intervention TEXT,
-                timestamp INTEGER,
-                FOREIGN KEY(session_id) REFERENCES sessions(id)
-            )",
-            [],
-        )?;
+    // --- Session Lifecycle (see storage::session) ---
 
-        // Advanced Schema (Phase 3-4-1)
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS task_history (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                session_id INTEGER,
-                project_name TEXT,
-                command TEXT NOT NULL,
-                exit_code INTEGER,
-                stdout TEXT,
-                stderr TEXT,
-                healer_used BOOLEAN,
-                healer_log TEXT,
-                token_usage INTEGER,
-                timestamp INTEGER,
-                FOREIGN KEY(session_id) REFERENCES sessions(id)
-            )",
-            [],
+    pub fn create_session(&self, tty: Option<&str>, shell_pid: i64) -> Result<i64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO sessions (start_time, last_activity, tty, shell_pid) VALUES (?, ?, ?, ?)",
+            params![now, now, tty, shell_pid],
         )?;
+        Ok(conn.last_insert_rowid())
+    }
 
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS decision_lineage (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                session_id INTEGER,
-                user_request TEXT NOT NULL,
-                intent TEXT,
-                generated_command TEXT,
-                simulation_log TEXT,
-                risk_score INTEGER,
-                execution_result TEXT,
-                timestamp INTEGER,
-                FOREIGN KEY(session_id) REFERENCES sessions(id)
-            )",
-            [],
-        )?;
+    /// Most recent session of the same TTY (or of the same shell when there is no TTY) that is
+    /// still open, or that a run closed on exit at or after `since`.
+    pub fn find_resumable_session(&self, tty: Option<&str>, shell_pid: i64, since: i64) -> Result<Option<i64>> {
+        let sql = if tty.is_some() {
+            "SELECT id FROM sessions WHERE (end_time IS NULL OR (resumable = 1 AND end_time >= ?3)) AND tty = ?1 ORDER BY id DESC LIMIT 1"
+        } else {
+            "SELECT id FROM sessions WHERE (end_time IS NULL OR (resumable = 1 AND end_time >= ?3)) AND tty IS NULL AND shell_pid = ?2 ORDER BY id DESC LIMIT 1"
+        };
+        self.conn()
+            .query_row(sql, params![tty, shell_pid, since], |row| row.get(0))
+            .optional()
+    }
 
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS error_solutions (
-                error_pattern TEXT PRIMARY KEY,
-                solution_cmd TEXT,
-                success_count INTEGER DEFAULT 1
-            )",
-            [],
+    /// Clears the end of a session that is being continued.
+    pub fn reopen_session(&self, session_id: i64) -> Result<()> {
+        self.conn().execute(
+            "UPDATE sessions SET end_time = NULL, summary = NULL, resumable = 0 WHERE id = ?",
+            params![session_id],
         )?;
+        Ok(())
+    }
 
-        // Phase 4: Advanced Metrics
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS system_stats (
-                date TEXT PRIMARY KEY,
-                total_input_tokens INTEGER DEFAULT 0,
-                total_output_tokens INTEGER DEFAULT 0,
-                error_count INTEGER DEFAULT 0
-            )",
-            [],
+    pub fn touch_session(&self, session_id: i64) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        self.conn().execute(
+            "UPDATE sessions SET last_activity = ? WHERE id = ?",
+            params![now, session_id],
         )?;
+        Ok(())
+    }
 
-        // Local RAG: FTS5 Virtual Table for semantic-like search
-        self.conn.execute(
-            "CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(content, origin_table UNINDEXED, timestamp UNINDEXED);",
-            [],
+    /// Closes open sessions whose last activity is older than `before`; end time is their last activity.
+    pub fn close_idle_sessions(&self, before: i64) -> Result<usize> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT id FROM sessions WHERE end_time IS NULL AND COALESCE(last_activity, start_time) < ?"
         )?;
+        let ids: Vec<i64> = stmt
+            .query_map(params![before], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+        for id in &ids {
+            let summary = self.summarize_session(*id)?;
+            self.conn().execute(
+                "UPDATE sessions SET end_time = COALESCE(last_activity, start_time), summary = ? WHERE id = ?",
+                params![summary, id],
+            )?;
+        }
+        Ok(ids.len())
+    }
 
-        // Sync triggers: chat_history -> search_index
-        self.conn.execute(
-            "CREATE TRIGGER IF NOT EXISTS sync_chat_search AFTER INSERT ON chat_history
-             WHEN NEW.role = 'user'
-             BEGIN
-                INSERT INTO search_index(content, origin_table, timestamp) VALUES (NEW.content, 'chat_history', NEW.timestamp);
-             END;",
-            [],
+    /// Ends a session with a summary. `resumable` sessions (closed because the run exited)
+    /// may still be continued from the same TTY within the session timeout.
+    pub fn close_session(&self, session_id: i64, resumable: bool) -> Result<bool> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let summary = self.summarize_session(session_id)?;
+        let closed = self.conn().execute(
+            "UPDATE sessions SET end_time = ?, last_activity = ?, summary = ?, resumable = ? WHERE id = ? AND end_time IS NULL",
+            params![now, now, summary, resumable, session_id],
         )?;
+        Ok(closed > 0)
+    }
 
-        // Sync triggers: commands -> search_index
-        self.conn.execute(
-            "CREATE TRIGGER IF NOT EXISTS sync_command_search AFTER INSERT ON commands
-             BEGIN
-                INSERT INTO search_index(content, origin_table, timestamp) VALUES (NEW.command, 'commands', NEW.timestamp);
-             END;",
-            [],
+    /// One-line digest of a session's events, stored on close.
+    pub fn summarize_session(&self, session_id: i64) -> Result<String> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT kind, COUNT(*), SUM(CASE WHEN success = 0 THEN 1 ELSE 0 END)
+             FROM events WHERE session_id = ? GROUP BY kind ORDER BY kind"
         )?;
+        let rows = stmt.query_map(params![session_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, Option<i64>>(2)?))
+        })?;
 
-    // Migration logic moved to migrate()
-    Ok(()) 
+        let mut parts = Vec::new();
+        for row in rows {
+            let (kind, count, failed) = row?;
+            match failed.unwrap_or(0) {
+                0 => parts.push(format!("{} {}", count, kind)),
+                f => parts.push(format!("{} {} ({} failed)", count, kind, f)),
+            }
+        }
+        if parts.is_empty() {
+            return Ok("No activity recorded.".to_string());
+        }
+        Ok(parts.join(", "))
     }
 
-    pub fn start_session(&mut self) -> Result<()> {
-        let start_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-        
-        self.conn.execute(
-            "INSERT INTO sessions (start_time) VALUES (?)",
-            params![start_time],
+    pub fn list_sessions(&self, limit: usize) -> Result<Vec<SessionInfo>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT s.id, s.start_time, s.end_time, s.last_activity, s.tty, s.shell_pid, s.total_weight, s.summary,
+                    (SELECT COUNT(*) FROM events e WHERE e.session_id = s.id)
+             FROM sessions s ORDER BY s.id DESC LIMIT ?"
         )?;
+        let rows = stmt.query_map(params![limit as i64], row_to_session)?;
+        rows.collect()
+    }
 
-        self.current_session_id = Some(self.conn.last_insert_rowid());
-        Ok(())
+    pub fn get_session(&self, session_id: i64) -> Result<Option<SessionInfo>> {
+        self.conn()
+            .query_row(
+                "SELECT s.id, s.start_time, s.end_time, s.last_activity, s.tty, s.shell_pid, s.total_weight, s.summary,
+                        (SELECT COUNT(*) FROM events e WHERE e.session_id = s.id)
+                 FROM sessions s WHERE s.id = ?",
+                params![session_id],
+                row_to_session,
+            )
+            .optional()
+    }
+
+    /// Latest session that actually recorded something (default for `vega report`).
+    pub fn get_latest_active_session(&self) -> Result<Option<i64>> {
+        self.conn()
+            .query_row(
+                "SELECT session_id FROM events WHERE session_id IS NOT NULL ORDER BY timestamp DESC, id DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
     }
 
     pub fn log_command(&self, command: &str, ai_comment: &str, success: bool) -> Result<()> {
         if let Some(session_id) = self.current_session_id {
-            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
             let weight = calculate_weight(command);
+            let event = Event::new(EventKind::Execution, command)
+                .with_result(success, None)
+                .with_payload(json!({ "ai_comment": ai_comment, "weight": weight }));
 
-            if let Err(e) = self.conn.execute(
-                "INSERT INTO commands (session_id, command, ai_comment, weight, timestamp, success)
-                 VALUES (?, ?, ?, ?, ?, ?)",
-                params![session_id, command, ai_comment, weight, timestamp, success],
-            ) {
+            if let Err(e) = self.record_event(&event) {
                 eprintln!("⚠️ Failed to log command: {}", e);
             } else {
                  // Update session total weight only if insert succeeded
-                if let Err(e) = self.conn.execute(
+                if let Err(e) = self.conn().execute(
                     "UPDATE sessions SET total_weight = total_weight + ? WHERE id = ?",
                     params![weight, session_id],
                 ) {
@@ -238,7 +387,7 @@ impl Database {
 
     pub fn save_chat_message(&self, role: &str, content: &str) -> Result<()> {
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-        self.conn.execute(
+        self.conn().execute(
             "INSERT INTO chat_history (role, content, timestamp) VALUES (?, ?, ?)",
             params![role, content, timestamp],
         )?;
@@ -246,17 +395,20 @@ impl Database {
     }
 
     pub fn add_execution_log(&self, session_id: i64, command: &str, success: bool, exit_code: i32, stdout: &str, stderr: &str, healer_intervention: Option<&str>) -> Result<()> {
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-        self.conn.execute(
-            "INSERT INTO execution_logs (session_id, command, success, exit_code, stdout, stderr, healer_intervention, timestamp)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
-            params![session_id, command, success, exit_code, stdout, stderr, healer_intervention, timestamp],
-        )?;
-        Ok(())
+        let mut event = Event::new(EventKind::Execution, command)
+            .with_result(success, Some(exit_code))
+            .with_payload(json!({
+                "stdout": stdout,
+                "stderr": stderr,
+                "healer_log": healer_intervention,
+            }));
+        event.session_id = Some(session_id);
+        self.record_event(&event)
     }
 
     pub fn get_recent_history(&self, limit: usize) -> Result<Vec<(String, String)>> {
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
             "SELECT role, content FROM chat_history ORDER BY id DESC LIMIT ?"
         )?;
         
@@ -273,8 +425,9 @@ impl Database {
     }
 
     pub fn get_failure_count(&self, command: &str) -> Result<i32> {
-        let mut stmt = self.conn.prepare(
-            "SELECT COUNT(*) FROM execution_logs WHERE command = ? AND success = 0"
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT COUNT(*) FROM events WHERE kind = 'execution' AND command = ? AND success = 0"
         )?;
         let count: i32 = stmt.query_row(params![command], |row| row.get(0))?;
         Ok(count)
@@ -294,29 +447,23 @@ impl Database {
         healer_log: Option<&str>, 
         token_usage: Option<i32>
     ) -> Result<()> {
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-        self.conn.execute(
-            "INSERT INTO task_history (session_id, project_name, command, exit_code, stdout, stderr, healer_used, healer_log, token_usage, timestamp)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            params![
-                session_id, 
-                project_name, 
-                command, 
-                exit_code, 
-                stdout, 
-                stderr, 
-                healer_used, 
-                healer_log, 
-                token_usage, 
-                timestamp
-            ],
-        )?;
-        Ok(())
+        let mut event = Event::new(EventKind::Execution, command)
+            .with_result(exit_code == 0, Some(exit_code))
+            .with_payload(json!({
+                "project_name": project_name,
+                "stdout": stdout,
+                "stderr": stderr,
+                "healer_used": healer_used,
+                "healer_log": healer_log,
+                "token_usage": token_usage,
+            }));
+        event.session_id = Some(session_id);
+        self.record_event(&event)
     }
 
     pub fn learn_solution(&self, error_pattern: &str, solution_cmd: &str) -> Result<()> {
         // Upsert logic: if exists, increment success_count
-        let _rows_affected = self.conn.execute(
+        let _rows_affected = self.conn().execute(
             "INSERT INTO error_solutions (error_pattern, solution_cmd, success_count)
              VALUES (?, ?, 1)
              ON CONFLICT(error_pattern) DO UPDATE SET success_count = success_count + 1",
@@ -326,7 +473,8 @@ impl Database {
     }
 
     pub fn get_solution(&self, error_pattern: &str) -> Result<Option<String>> {
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
             "SELECT solution_cmd FROM error_solutions WHERE error_pattern = ?"
         )?;
         
@@ -366,38 +514,669 @@ impl Database {
     }
 
     pub fn get_session_tasks(&self, session_id: i64) -> Result<Vec<TaskEntry>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT project_name, command, exit_code, stdout, stderr, healer_used, healer_log, token_usage, timestamp 
-             FROM task_history 
-             WHERE session_id = ? 
-             ORDER BY timestamp ASC"
+        let events = self.get_events(Some(EventKind::Execution), Some(session_id), usize::MAX)?;
+        Ok(events.iter().map(TaskEntry::from_event).collect())
+    }
+
+    // --- Event Log ---
+
+    /// Appends an event. Events without a session are attached to the current one.
+    /// Duplicate `uid`s are ignored so that imports and merges are idempotent.
+    pub fn record_event(&self, event: &Event) -> Result<()> {
+        let session_id = event.session_id.or(self.current_session_id);
+        if let Some(sid) = session_id {
+            self.touch_session(sid)?;
+        }
+        self.insert_event(event, session_id)?;
+        Ok(())
+    }
+
+    /// Inserts an event from another device as-is: its session ids are meaningless here,
+    /// so it is stored without one. Returns false when the `uid` already exists.
+    pub fn import_event(&self, event: &Event) -> Result<bool> {
+        Ok(self.insert_event(event, None)? > 0)
+    }
+
+    fn insert_event(&self, event: &Event, session_id: Option<i64>) -> Result<usize> {
+        // Outputs are redacted and truncated before they reach disk
+        let mut payload = event.payload.clone();
+        retention::policy().scrub_payload(&mut payload);
+        let payload = if payload.is_null() {
+            None
+        } else {
+            Some(payload.to_string())
+        };
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "INSERT OR IGNORE INTO events (uid, session_id, correlation_id, kind, command, success, exit_code, payload, timestamp, host, duration_ms)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )?;
-        
-        let task_iter = stmt.query_map(params![session_id], |row| {
-            Ok(TaskEntry {
-                project_name: row.get(0)?,
-                command: row.get(1)?,
-                exit_code: row.get(2)?,
-                stdout: row.get(3)?,
-                stderr: row.get(4)?,
-                healer_used: row.get(5)?,
-                healer_log: row.get(6)?,
-                token_usage: row.get(7)?,
-                timestamp: row.get(8)?,
+        stmt.execute(params![
+            event.uid,
+            session_id,
+            event.correlation_id,
+            event.kind.as_str(),
+            event.command,
+            event.success,
+            event.exit_code,
+            payload,
+            event.timestamp,
+            event.host,
+            event.duration_ms
+        ])
+    }
+
+    /// Appends many events in one transaction (a single fsync instead of one per row).
+    pub fn record_events(&self, events: &[Event]) -> Result<usize> {
+        self.in_transaction(|db| {
+            for event in events {
+                db.record_event(event)?;
+            }
+            Ok(events.len())
+        })
+    }
+
+    /// Returns up to `limit` most recent events in chronological order.
+    pub fn get_events(&self, kind: Option<EventKind>, session_id: Option<i64>, limit: usize) -> Result<Vec<Event>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT uid, session_id, correlation_id, kind, command, success, exit_code, payload, timestamp, host, duration_ms
+             FROM events
+             WHERE (?1 IS NULL OR kind = ?1) AND (?2 IS NULL OR session_id = ?2)
+             ORDER BY timestamp DESC, id DESC
+             LIMIT ?3"
+        )?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let rows = stmt.query_map(params![kind.map(|k| k.as_str()), session_id, limit], row_to_event)?;
+
+        let mut events = Vec::new();
+        for event in rows {
+            events.push(event?);
+        }
+        events.reverse();
+        Ok(events)
+    }
+
+    /// Returns all events with `from <= timestamp < to` in chronological order.
+    pub fn get_events_between(&self, from: i64, to: i64) -> Result<Vec<Event>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT uid, session_id, correlation_id, kind, command, success, exit_code, payload, timestamp, host, duration_ms
+             FROM events
+             WHERE timestamp >= ? AND timestamp < ?
+             ORDER BY timestamp ASC, id ASC"
+        )?;
+        let rows = stmt.query_map(params![from, to], row_to_event)?;
+
+        let mut events = Vec::new();
+        for event in rows {
+            events.push(event?);
+        }
+        Ok(events)
+    }
+
+    pub fn get_event(&self, uid: &str) -> Result<Option<Event>> {
+        self.conn()
+            .query_row(
+                "SELECT uid, session_id, correlation_id, kind, command, success, exit_code, payload, timestamp, host, duration_ms
+                 FROM events WHERE uid = ?",
+                params![uid],
+                row_to_event,
+            )
+            .optional()
+    }
+
+    /// Runs `f` inside a single write transaction; use it to batch many writes.
+    /// The connection stays locked for the duration, so other threads queue behind it.
+    pub fn in_transaction<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        let conn = self.conn();
+        conn.execute_batch("BEGIN IMMEDIATE")?;
+        match f(self) {
+            Ok(v) => {
+                conn.execute_batch("COMMIT")?;
+                Ok(v)
+            }
+            Err(e) => {
+                let _ = conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+
+    pub fn connection(&self) -> ReentrantMutexGuard<'_, Connection> {
+        self.conn()
+    }
+
+    // --- Backup Runs (see system::backup) ---
+
+    pub fn record_backup_run(&self, run: &BackupRun) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO backup_runs (source, destination, snapshot, started_at, finished_at, success, verified,
+                                      files_transferred, bytes_transferred, total_files, total_bytes, error)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                run.source,
+                run.destination,
+                run.snapshot,
+                run.started_at,
+                run.finished_at,
+                run.success,
+                run.verified,
+                run.files_transferred,
+                run.bytes_transferred,
+                run.total_files,
+                run.total_bytes,
+                run.error
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Most recent runs first; `destination` narrows to one backup target.
+    pub fn list_backup_runs(&self, destination: Option<&str>, limit: usize) -> Result<Vec<BackupRun>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, source, destination, snapshot, started_at, finished_at, success, verified,
+                    files_transferred, bytes_transferred, total_files, total_bytes, error, pruned, folded_into
+             FROM backup_runs
+             WHERE ?1 IS NULL OR destination = ?1
+             ORDER BY started_at DESC, id DESC
+             LIMIT ?2",
+        )?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let rows = stmt.query_map(params![destination, limit], row_to_backup_run)?;
+        rows.collect()
+    }
+
+    /// Looks a run up by numeric id or by snapshot name.
+    pub fn get_backup_run(&self, key: &str) -> Result<Option<BackupRun>> {
+        self.conn()
+            .query_row(
+                "SELECT id, source, destination, snapshot, started_at, finished_at, success, verified,
+                        files_transferred, bytes_transferred, total_files, total_bytes, error, pruned, folded_into
+                 FROM backup_runs WHERE CAST(id AS TEXT) = ?1 OR snapshot = ?1
+                 ORDER BY id DESC LIMIT 1",
+                params![key],
+                row_to_backup_run,
+            )
+            .optional()
+    }
+
+    pub fn mark_backup_pruned(&self, destination: &str, snapshot: &str, folded_into: &str) -> Result<()> {
+        self.conn().execute(
+            "UPDATE backup_runs SET pruned = 1, folded_into = ? WHERE destination = ? AND snapshot = ?",
+            params![folded_into, destination, snapshot],
+        )?;
+        Ok(())
+    }
+
+    // --- Sync Topology (see context::SyncEdge) ---
+
+    /// Upserts the `source -> destination` edge after a sync or backup run.
+    pub fn record_sync_edge(
+        &self,
+        source: &str,
+        destination: &str,
+        kind: &str,
+        success: bool,
+        bytes: i64,
+        error: Option<&str>,
+    ) -> Result<()> {
+        let now = crate::storage::events::now_secs();
+        self.conn().execute(
+            "INSERT INTO sync_edges (source, destination, kind, last_attempt, last_success, last_bytes, success, error, runs)
+             VALUES (?1, ?2, ?3, ?4, CASE WHEN ?5 THEN ?4 END, ?6, ?5, ?7, 1)
+             ON CONFLICT(source, destination) DO UPDATE SET
+                kind = excluded.kind,
+                last_attempt = excluded.last_attempt,
+                last_success = COALESCE(excluded.last_success, last_success),
+                last_bytes = excluded.last_bytes,
+                success = excluded.success,
+                error = excluded.error,
+                runs = runs + 1",
+            params![source, destination, kind, now, success, bytes, error],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_sync_edges(&self) -> Result<Vec<SyncEdge>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT source, destination, kind, last_attempt, last_success, last_bytes, success, error, runs
+             FROM sync_edges ORDER BY source, destination",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SyncEdge {
+                source: row.get(0)?,
+                destination: row.get(1)?,
+                kind: row.get(2)?,
+                last_sync: row.get(3)?,
+                last_success: row.get(4)?,
+                bytes: row.get::<_, Option<i64>>(5)?.unwrap_or(0),
+                success: row.get::<_, Option<bool>>(6)?.unwrap_or(false),
+                error: row.get(7)?,
+                runs: row.get::<_, Option<i64>>(8)?.unwrap_or(0),
+                stale: false,
+            })
+        })?;
+        rows.collect()
+    }
+
+    // --- File Index (see system::file_index) ---
+
+    /// Upserts one crawl batch in a single transaction and stamps every row with `seen`.
+    /// Returns how many rows were new or changed.
+    pub fn upsert_indexed_files(&self, files: &[IndexedFile], seen: i64) -> Result<usize> {
+        self.in_transaction(|db| {
+            let conn = db.conn();
+            let mut touch = conn.prepare_cached(
+                "UPDATE file_index SET seen = ?3
+                 WHERE origin = ?1 AND path = ?2 AND size IS ?4 AND mtime IS ?5 AND hash IS ?6 AND is_dir = ?7",
+            )?;
+            let mut upsert = conn.prepare_cached(
+                "INSERT INTO file_index (origin, path, size, mtime, hash, is_dir, seen)
+                 VALUES (?1, ?2, ?4, ?5, ?6, ?7, ?3)
+                 ON CONFLICT(origin, path) DO UPDATE SET
+                    size = excluded.size, mtime = excluded.mtime, hash = excluded.hash,
+                    is_dir = excluded.is_dir, seen = excluded.seen",
+            )?;
+            let mut changed = 0;
+            for f in files {
+                let args = params![f.origin, f.path, seen, f.size, f.mtime, f.hash, f.is_dir];
+                if touch.execute(args)? == 0 {
+                    upsert.execute(args)?;
+                    changed += 1;
+                }
+            }
+            Ok(changed)
+        })
+    }
+
+    /// Recorded modification times of the top-level folders of `origin`.
+    pub fn indexed_top_dirs(&self, origin: &str) -> Result<HashMap<String, Option<i64>>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT path, mtime FROM file_index WHERE origin = ? AND is_dir = 1 AND instr(path, '/') = 0",
+        )?;
+        let rows = stmt.query_map(params![origin], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Drops rows of `origin` not seen in the crawl `seen`, except below `keep_prefixes`
+    /// (folders whose listing failed or was skipped this time).
+    pub fn prune_file_index(&self, origin: &str, seen: i64, keep_prefixes: &[String]) -> Result<usize> {
+        self.in_transaction(|db| {
+            let conn = db.conn();
+            let stale: Vec<(i64, String)> = {
+                let mut stmt = conn.prepare_cached("SELECT id, path FROM file_index WHERE origin = ?1 AND seen < ?2")?;
+                let rows = stmt.query_map(params![origin, seen], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<Result<_>>()?
+            };
+            let mut removed = 0;
+            for (id, path) in stale {
+                let kept = keep_prefixes
+                    .iter()
+                    .any(|p| path == *p || path.starts_with(&format!("{}/", p)));
+                if !kept {
+                    removed += conn.execute("DELETE FROM file_index WHERE id = ?", params![id])?;
+                }
+            }
+            Ok(removed)
+        })
+    }
+
+    /// Full-text lookup over indexed paths; every term must match (as a word prefix).
+    /// Falls back to a substring scan for terms the tokenizer splits differently (e.g. Hangul).
+    /// `origin_like` narrows the search (SQL `LIKE` on the origin).
+    pub fn search_files(&self, terms: &[String], origin_like: Option<&str>, limit: usize) -> Result<Vec<IndexedFile>> {
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.conn();
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let fts_query = terms
+            .iter()
+            .map(|t| format!("\"{}\"*", t.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut stmt = conn.prepare_cached(
+            "SELECT f.origin, f.path, f.size, f.mtime, f.hash, f.is_dir
+             FROM file_index_fts JOIN file_index f ON f.id = file_index_fts.rowid
+             WHERE file_index_fts MATCH ?1 AND (?2 IS NULL OR f.origin LIKE ?2)
+             ORDER BY bm25(file_index_fts), length(f.path)
+             LIMIT ?3",
+        )?;
+        let found: Vec<IndexedFile> = stmt
+            .query_map(params![fts_query, origin_like, limit], row_to_indexed_file)?
+            .collect::<Result<_>>()?;
+        if !found.is_empty() {
+            return Ok(found);
+        }
+
+        let mut sql = String::from(
+            "SELECT origin, path, size, mtime, hash, is_dir FROM file_index WHERE (?1 IS NULL OR origin LIKE ?1)",
+        );
+        let mut args: Vec<String> = Vec::new();
+        for term in terms {
+            args.push(term.to_lowercase());
+            sql.push_str(&format!(" AND instr(lower(path), ?{}) > 0", args.len() + 1));
+        }
+        sql.push_str(&format!(" ORDER BY length(path) LIMIT {}", limit));
+        let mut stmt = conn.prepare(&sql)?;
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&origin_like];
+        values.extend(args.iter().map(|a| a as &dyn rusqlite::ToSql));
+        let rows = stmt.query_map(values.as_slice(), row_to_indexed_file)?;
+        rows.collect()
+    }
+
+    pub fn count_indexed_files(&self, origin: &str) -> Result<i64> {
+        self.conn().query_row(
+            "SELECT COUNT(*) FROM file_index WHERE origin = ?",
+            params![origin],
+            |row| row.get(0),
+        )
+    }
+
+    /// Crawl state of one origin. A failed crawl keeps the entry count of the last good one.
+    pub fn record_index_origin(&self, origin: &IndexOrigin) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO file_index_origins (origin, label, kind, last_crawl, entries, duration_ms, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(origin) DO UPDATE SET
+                label = excluded.label, kind = excluded.kind, last_crawl = excluded.last_crawl,
+                entries = CASE WHEN excluded.error IS NOT NULL AND excluded.entries = 0 THEN entries ELSE excluded.entries END,
+                duration_ms = excluded.duration_ms, error = excluded.error",
+            params![
+                origin.origin,
+                origin.label,
+                origin.kind,
+                origin.last_crawl,
+                origin.entries,
+                origin.duration_ms,
+                origin.error
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_index_origins(&self) -> Result<Vec<IndexOrigin>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT origin, label, kind, last_crawl, entries, duration_ms, error FROM file_index_origins ORDER BY label",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(IndexOrigin {
+                origin: row.get(0)?,
+                label: row.get(1)?,
+                kind: row.get(2)?,
+                last_crawl: row.get(3)?,
+                entries: row.get::<_, Option<i64>>(4)?.unwrap_or(0),
+                duration_ms: row.get(5)?,
+                error: row.get(6)?,
             })
         })?;
+        rows.collect()
+    }
+
+    // --- Mount Registry (see system::mounts) ---
+
+    /// Registers or replaces the mount at `entry.mount_point`.
+    pub fn record_mount(&self, entry: &MountEntry) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO mounts (kind, source, mount_point, auto_remount, systemd_unit, created_at, last_mounted, healthy, last_error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(mount_point) DO UPDATE SET
+                kind = excluded.kind, source = excluded.source, auto_remount = excluded.auto_remount,
+                systemd_unit = excluded.systemd_unit, last_mounted = excluded.last_mounted,
+                healthy = excluded.healthy, last_error = excluded.last_error",
+            params![
+                entry.kind,
+                entry.source,
+                entry.mount_point,
+                entry.auto_remount,
+                entry.systemd_unit,
+                entry.created_at,
+                entry.last_mounted,
+                entry.healthy,
+                entry.last_error
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_mounts(&self) -> Result<Vec<MountEntry>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, kind, source, mount_point, auto_remount, systemd_unit, created_at, last_mounted, last_check, healthy, last_error
+             FROM mounts ORDER BY mount_point",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(MountEntry {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                source: row.get(2)?,
+                mount_point: row.get(3)?,
+                auto_remount: row.get::<_, Option<bool>>(4)?.unwrap_or(true),
+                systemd_unit: row.get(5)?,
+                created_at: row.get(6)?,
+                last_mounted: row.get(7)?,
+                last_check: row.get(8)?,
+                healthy: row.get(9)?,
+                last_error: row.get(10)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn update_mount_health(&self, mount_point: &str, healthy: bool, error: Option<&str>) -> Result<()> {
+        self.conn().execute(
+            "UPDATE mounts SET last_check = ?2, healthy = ?3, last_error = ?4 WHERE mount_point = ?1",
+            params![mount_point, crate::storage::events::now_secs(), healthy, error],
+        )?;
+        Ok(())
+    }
+
+    pub fn mark_mounted(&self, mount_point: &str) -> Result<()> {
+        self.conn().execute(
+            "UPDATE mounts SET last_mounted = ?2, healthy = 1, last_error = NULL WHERE mount_point = ?1",
+            params![mount_point, crate::storage::events::now_secs()],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_mount(&self, mount_point: &str) -> Result<usize> {
+        self.conn()
+            .execute("DELETE FROM mounts WHERE mount_point = ?", params![mount_point])
+    }
+
+    // --- Retention & Forget ---
+
+    /// Rows in each history table matching `filter`, for the `vega forget` preview.
+    pub fn count_forgettable(&self, filter: &ForgetFilter) -> Result<Vec<(&'static str, usize)>> {
+        let conn = self.conn();
+        let like = filter.like_pattern();
+        let mut counts = Vec::new();
+        for table in HISTORY_TABLES {
+            if filter.session_id.is_some() && !table.has_session {
+                continue;
+            }
+            let sql = format!("SELECT COUNT(*) FROM {} WHERE {}", table.name, table.where_clause());
+            let count: i64 = conn.query_row(&sql, params![like, filter.session_id, filter.before], |row| row.get(0))?;
+            counts.push((table.name, count as usize));
+        }
+        Ok(counts)
+    }
+
+    /// Deletes matching history (events, chat and legacy tables) with their `search_index` rows,
+    /// then compacts the file so the content does not linger in free pages or the WAL.
+    pub fn forget(&self, filter: &ForgetFilter) -> Result<usize> {
+        let like = filter.like_pattern();
+        let removed = self.in_transaction(|db| {
+            let conn = db.conn();
+            let mut removed = 0;
+            for table in HISTORY_TABLES {
+                if filter.session_id.is_some() && !table.has_session {
+                    continue;
+                }
+                let sql = format!("DELETE FROM {} WHERE {}", table.name, table.where_clause());
+                removed += conn.execute(&sql, params![like, filter.session_id, filter.before])?;
+            }
+            Ok(removed)
+        })?;
+        if removed > 0 {
+            self.compact()?;
+        }
+        Ok(removed)
+    }
+
+    /// Expires history older than `retention_days`, then drops the oldest events
+    /// until the live data fits in `max_db_bytes`.
+    pub fn apply_retention(&self, policy: &RetentionPolicy) -> Result<RetentionReport> {
+        let mut report = RetentionReport {
+            size_before: self.live_size_bytes()?,
+            ..Default::default()
+        };
+
+        if let Some(days) = policy.retention_days {
+            let filter = ForgetFilter {
+                before: Some(crate::storage::events::now_secs() - days * 86400),
+                ..Default::default()
+            };
+            report.expired = self.forget(&filter)?;
+        }
 
-        let mut tasks = Vec::new();
-        for task in task_iter {
-            tasks.push(task?);
+        if let Some(max_bytes) = policy.max_db_bytes {
+            // Only the history counts: trimming events cannot shrink the file index or chat history
+            let mut history = self.history_size_bytes()?;
+            while history > max_bytes {
+                let remaining: i64 = self.conn().query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0))?;
+                if remaining == 0 {
+                    break;
+                }
+                // Oldest events in proportion to the overshoot; compacting settles the real size
+                let share = (remaining as u128 * (history - max_bytes) as u128).div_ceil(history as u128);
+                let batch = (share as i64).max(TRIM_BATCH);
+                let trimmed = self.conn().execute(
+                    "DELETE FROM events WHERE id IN (SELECT id FROM events ORDER BY timestamp ASC, id ASC LIMIT ?)",
+                    params![batch],
+                )?;
+                report.trimmed += trimmed;
+                self.compact()?;
+                history = self.history_size_bytes()?;
+            }
+            report.over_budget = history > max_bytes;
+        }
+
+        report.size_after = self.live_size_bytes()?;
+        Ok(report)
+    }
+
+    /// Runs `apply_retention` with the configured policy at most once a day.
+    pub fn maybe_apply_retention(&self) -> Result<Option<RetentionReport>> {
+        let now = crate::storage::events::now_secs();
+        let last_run = self
+            .get_metadata(retention::LAST_RUN_KEY)?
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(0);
+        if now - last_run < 86400 {
+            return Ok(None);
+        }
+        let report = self.apply_retention(retention::policy())?;
+        self.set_metadata(retention::LAST_RUN_KEY, &now.to_string())?;
+        Ok(Some(report))
+    }
+
+    /// Re-applies output redaction and truncation to everything already stored,
+    /// and clears raw outputs left in the legacy tables once they have been imported.
+    pub fn scrub_stored(&self) -> Result<usize> {
+        let policy = retention::policy();
+        let changed = self.in_transaction(|db| {
+            let conn = db.conn();
+            let rows: Vec<(i64, String)> = {
+                let mut stmt = conn.prepare("SELECT id, payload FROM events WHERE payload IS NOT NULL")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<Result<_>>()?
+            };
+
+            let mut changed = 0;
+            for (id, raw) in rows {
+                let mut payload: serde_json::Value = match serde_json::from_str(&raw) {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+                if policy.scrub_payload(&mut payload) {
+                    conn.execute(
+                        "UPDATE events SET payload = ? WHERE id = ?",
+                        params![payload.to_string(), id],
+                    )?;
+                    changed += 1;
+                }
+            }
+
+            if db.get_metadata(crate::storage::legacy::IMPORT_MARKER)?.is_some() {
+                changed += conn.execute(
+                    "UPDATE execution_logs SET stdout = NULL, stderr = NULL WHERE stdout IS NOT NULL OR stderr IS NOT NULL",
+                    [],
+                )?;
+                changed += conn.execute(
+                    "UPDATE task_history SET stdout = NULL, stderr = NULL WHERE stdout IS NOT NULL OR stderr IS NOT NULL",
+                    [],
+                )?;
+            }
+            Ok(changed)
+        })?;
+        if changed > 0 {
+            self.compact()?;
         }
-        Ok(tasks)
+        Ok(changed)
+    }
+
+    /// Bytes used by live pages (file size minus the freelist).
+    /// Bytes held by `events`, its indexes and the search index: the part of the
+    /// database the size cap trims.
+    pub fn history_size_bytes(&self) -> Result<u64> {
+        let conn = self.conn();
+        let measured = conn.query_row(
+            "SELECT COALESCE(SUM(pgsize), 0) FROM dbstat
+             WHERE name IN (SELECT name FROM sqlite_master
+                            WHERE tbl_name = 'events' OR tbl_name LIKE 'search\\_index%' ESCAPE '\\')",
+            [],
+            |row| row.get::<_, i64>(0),
+        );
+        let bytes = match measured {
+            Ok(bytes) => bytes,
+            // SQLite built without the dbstat table: estimate from the stored text
+            Err(_) => conn.query_row(
+                "SELECT COALESCE(SUM(64 + 2 * LENGTH(COALESCE(command, '')) + LENGTH(COALESCE(payload, ''))), 0) FROM events",
+                [],
+                |row| row.get(0),
+            )?,
+        };
+        Ok(bytes.max(0) as u64)
+    }
+
+    pub fn live_size_bytes(&self) -> Result<u64> {
+        let conn = self.conn();
+        let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        let page_count: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let free: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+        Ok(((page_count - free).max(0) * page_size) as u64)
+    }
+
+    /// Merges FTS segments (dropping deleted terms), rewrites the file and truncates the WAL.
+    fn compact(&self) -> Result<()> {
+        let conn = self.conn();
+        conn.execute("INSERT INTO search_index(search_index) VALUES('optimize')", [])?;
+        conn.execute_batch("VACUUM")?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        Ok(())
     }
 
     // --- Metadata Helper Methods ---
 
     pub fn set_metadata(&self, key: &str, value: &str) -> Result<()> {
-        self.conn.execute(
+        self.conn().execute(
             "INSERT INTO metadata (key, value) VALUES (?, ?)
              ON CONFLICT(key) DO UPDATE SET value = excluded.value",
             params![key, value],
@@ -406,7 +1185,8 @@ impl Database {
     }
 
     pub fn get_metadata(&self, key: &str) -> Result<Option<String>> {
-        let mut stmt = self.conn.prepare("SELECT value FROM metadata WHERE key = ?")?;
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached("SELECT value FROM metadata WHERE key = ?")?;
         let mut rows = stmt.query(params![key])?;
         
         if let Some(row) = rows.next()? {
@@ -448,7 +1228,9 @@ impl Database {
             
         if cleaned.is_empty() { return Ok(vec![]); }
 
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn();
+
+        let mut stmt = conn.prepare_cached(
             "SELECT content FROM search_index 
              WHERE content MATCH ? 
              ORDER BY rank 
@@ -472,7 +1254,8 @@ impl Database {
     }
 
     pub fn get_recent_sessions(&self, limit: usize) -> Result<Vec<(i64, i32)>> {
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
             "SELECT id, total_weight FROM sessions ORDER BY id DESC LIMIT ?"
         )?;
         let rows = stmt.query_map(params![limit], |row| {
@@ -486,8 +1269,13 @@ impl Database {
     }
 
     pub fn get_all_commands(&self) -> Result<Vec<String>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT DISTINCT command FROM commands ORDER BY timestamp DESC LIMIT 100"
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT command FROM events
+             WHERE kind IN ('request', 'execution') AND command IS NOT NULL
+             GROUP BY command
+             ORDER BY MAX(timestamp) DESC
+             LIMIT 100"
         )?;
         let rows = stmt.query_map([], |row| row.get(0))?;
         
@@ -499,37 +1287,126 @@ impl Database {
         }
         Ok(commands)
     }
+}
 
-    pub fn log_decision_lineage(
-        &self,
-        user_request: &str,
-        intent: &str,
-        generated_command: &str,
-        simulation_log: &str,
-        risk_score: i32,
-        execution_result: &str,
-    ) -> Result<()> {
-        if let Some(session_id) = self.current_session_id {
-            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-            self.conn.execute(
-                "INSERT INTO decision_lineage (session_id, user_request, intent, generated_command, simulation_log, risk_score, execution_result, timestamp)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
-                params![
-                    session_id,
-                    user_request,
-                    intent,
-                    generated_command,
-                    simulation_log,
-                    risk_score,
-                    execution_result,
-                    timestamp
-                ],
-            )?;
+/// A table holding user history, as seen by `vega forget` and retention.
+struct HistoryTable {
+    name: &'static str,
+    text_columns: &'static [&'static str],
+    has_session: bool,
+}
+
+const HISTORY_TABLES: &[HistoryTable] = &[
+    HistoryTable { name: "events", text_columns: &["command", "payload"], has_session: true },
+    HistoryTable { name: "chat_history", text_columns: &["content"], has_session: false },
+    HistoryTable { name: "commands", text_columns: &["command", "ai_comment"], has_session: true },
+    HistoryTable { name: "execution_logs", text_columns: &["command", "stdout", "stderr"], has_session: true },
+    HistoryTable { name: "task_history", text_columns: &["command", "stdout", "stderr"], has_session: true },
+    HistoryTable { name: "decision_lineage", text_columns: &["user_request", "generated_command", "execution_result"], has_session: true },
+];
+
+impl HistoryTable {
+    /// Bound as (?1 LIKE pattern, ?2 session id, ?3 before timestamp); NULL disables a condition.
+    fn where_clause(&self) -> String {
+        let text = self
+            .text_columns
+            .iter()
+            .map(|c| format!("{} LIKE ?1 ESCAPE '\\'", c))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let session = if self.has_session {
+            "(?2 IS NULL OR session_id = ?2)"
+        } else {
+            "?2 IS NULL"
+        };
+        format!("(?1 IS NULL OR {}) AND {} AND (?3 IS NULL OR timestamp < ?3)", text, session)
+    }
+}
+
+impl TaskEntry {
+    pub fn from_event(event: &Event) -> Self {
+        let p = &event.payload;
+        TaskEntry {
+            project_name: event.field("project_name").map(str::to_string),
+            command: event.command.clone().unwrap_or_default(),
+            exit_code: event
+                .exit_code
+                .unwrap_or(if event.success.unwrap_or(false) { 0 } else { -1 }),
+            stdout: event.field("stdout").unwrap_or_default().to_string(),
+            stderr: event.field("stderr").unwrap_or_default().to_string(),
+            healer_used: p["healer_used"].as_bool().unwrap_or(false),
+            healer_log: event.field("healer_log").map(str::to_string),
+            token_usage: p["token_usage"].as_i64().map(|t| t as i32),
+            timestamp: event.timestamp,
         }
-        Ok(())
     }
 }
 
+fn row_to_backup_run(row: &Row) -> Result<BackupRun> {
+    Ok(BackupRun {
+        id: row.get(0)?,
+        source: row.get(1)?,
+        destination: row.get(2)?,
+        snapshot: row.get(3)?,
+        started_at: row.get(4)?,
+        finished_at: row.get(5)?,
+        success: row.get::<_, Option<bool>>(6)?.unwrap_or(false),
+        verified: row.get::<_, Option<bool>>(7)?.unwrap_or(false),
+        files_transferred: row.get::<_, Option<i64>>(8)?.unwrap_or(0),
+        bytes_transferred: row.get::<_, Option<i64>>(9)?.unwrap_or(0),
+        total_files: row.get::<_, Option<i64>>(10)?.unwrap_or(0),
+        total_bytes: row.get::<_, Option<i64>>(11)?.unwrap_or(0),
+        error: row.get(12)?,
+        pruned: row.get::<_, Option<bool>>(13)?.unwrap_or(false),
+        folded_into: row.get(14)?,
+    })
+}
+
+fn row_to_indexed_file(row: &Row) -> Result<IndexedFile> {
+    Ok(IndexedFile {
+        origin: row.get(0)?,
+        path: row.get(1)?,
+        size: row.get(2)?,
+        mtime: row.get(3)?,
+        hash: row.get(4)?,
+        is_dir: row.get::<_, Option<bool>>(5)?.unwrap_or(false),
+    })
+}
+
+fn row_to_session(row: &Row) -> Result<SessionInfo> {
+    Ok(SessionInfo {
+        id: row.get(0)?,
+        start_time: row.get(1)?,
+        end_time: row.get(2)?,
+        last_activity: row.get(3)?,
+        tty: row.get(4)?,
+        shell_pid: row.get(5)?,
+        total_weight: row.get::<_, Option<i32>>(6)?.unwrap_or(0),
+        summary: row.get(7)?,
+        event_count: row.get(8)?,
+    })
+}
+
+fn row_to_event(row: &Row) -> Result<Event> {
+    let kind: String = row.get(3)?;
+    let payload: Option<String> = row.get(7)?;
+    Ok(Event {
+        uid: row.get(0)?,
+        session_id: row.get(1)?,
+        correlation_id: row.get(2)?,
+        kind: EventKind::parse(&kind).unwrap_or(EventKind::Execution),
+        command: row.get(4)?,
+        success: row.get(5)?,
+        exit_code: row.get(6)?,
+        payload: payload
+            .and_then(|p| serde_json::from_str(&p).ok())
+            .unwrap_or(serde_json::Value::Null),
+        timestamp: row.get(8)?,
+        host: row.get(9)?,
+        duration_ms: row.get(10)?,
+    })
+}
+
 fn calculate_weight(command: &str) -> i32 {
     let cmd = command.trim();
     
@@ -552,3 +1429,214 @@ fn calculate_weight(command: &str) -> i32 {
     // Default for other commands
     3
 }
+
+/// Async-friendly access: runs `f` on tokio's blocking pool so that async tasks
+/// never stall a runtime worker on SQLite disk I/O or lock waits.
+pub async fn with_db<T, F>(f: F) -> anyhow::Result<T>
+where
+    F: FnOnce(&Database) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let value = tokio::task::spawn_blocking(move || {
+        let db = Database::new()?;
+        f(&db)
+    })
+    .await??;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_db() -> Database {
+        Database::open_file(Path::new(":memory:")).unwrap()
+    }
+
+    fn count(db: &Database, sql: &str) -> i64 {
+        db.connection().query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    fn event_in(session_id: i64, command: &str, timestamp: i64) -> Event {
+        let mut event = Event::new(EventKind::Execution, command);
+        event.session_id = Some(session_id);
+        event.timestamp = timestamp;
+        event
+    }
+
+    #[test]
+    fn sessions_closed_on_exit_resume_but_explicit_closes_are_final() {
+        let db = memory_db();
+        let now = crate::storage::events::now_secs();
+        let id = db.create_session(Some("/dev/pts/1"), 10).unwrap();
+
+        assert!(db.close_session(id, true).unwrap());
+        let closed = db.get_session(id).unwrap().unwrap();
+        assert!(!closed.is_open());
+        assert!(closed.summary.is_some());
+
+        // Same TTY within the window continues it; another TTY or an expired window does not
+        assert_eq!(db.find_resumable_session(Some("/dev/pts/1"), 99, now - 60).unwrap(), Some(id));
+        assert_eq!(db.find_resumable_session(Some("/dev/pts/2"), 10, now - 60).unwrap(), None);
+        assert_eq!(db.find_resumable_session(Some("/dev/pts/1"), 10, now + 60).unwrap(), None);
+
+        db.reopen_session(id).unwrap();
+        assert!(db.get_session(id).unwrap().unwrap().is_open());
+
+        assert!(db.close_session(id, false).unwrap());
+        assert!(!db.close_session(id, false).unwrap());
+        assert_eq!(db.find_resumable_session(Some("/dev/pts/1"), 10, now - 60).unwrap(), None);
+
+        // Without a TTY the shell PID identifies the session
+        let shell = db.create_session(None, 42).unwrap();
+        assert_eq!(db.find_resumable_session(None, 42, now).unwrap(), Some(shell));
+        assert_eq!(db.find_resumable_session(None, 43, now).unwrap(), None);
+    }
+
+    #[test]
+    fn forget_only_unindexes_the_rows_it_deletes() {
+        let db = memory_db();
+        let first = db.create_session(Some("pts/1"), 1).unwrap();
+        let second = db.create_session(Some("pts/2"), 2).unwrap();
+        // Same second and same text in two sessions
+        db.record_event(&event_in(first, "cat secrets.txt", 1_700_000_000)).unwrap();
+        db.record_event(&event_in(second, "cat secrets.txt", 1_700_000_000)).unwrap();
+        db.record_event(&event_in(second, "ls -la", 1_700_000_000)).unwrap();
+        db.save_chat_message("user", "how do I cat secrets.txt").unwrap();
+
+        let filter = ForgetFilter {
+            pattern: Some("secrets".to_string()),
+            session_id: Some(first),
+            ..Default::default()
+        };
+        let preview = db.count_forgettable(&filter).unwrap();
+        assert!(preview.contains(&("events", 1)), "{:?}", preview);
+        // chat_history has no session, so a session filter skips it
+        assert!(!preview.iter().any(|(table, _)| *table == "chat_history"));
+
+        assert_eq!(db.forget(&filter).unwrap(), 1);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM events"), 2);
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM search_index WHERE content = 'cat secrets.txt'"),
+            1
+        );
+
+        let everywhere = ForgetFilter {
+            pattern: Some("secrets".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(db.forget(&everywhere).unwrap(), 2);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM search_index"), 1);
+        assert_eq!(db.search_relevant_context("secrets", 10).unwrap(), Vec::<String>::new());
+        assert_eq!(db.search_relevant_context("ls", 10).unwrap(), vec!["ls -la".to_string()]);
+    }
+
+    #[test]
+    fn forget_matches_wildcards_literally() {
+        let db = memory_db();
+        let session = db.create_session(None, 1).unwrap();
+        db.record_event(&event_in(session, "echo 100%", 1_700_000_000)).unwrap();
+        db.record_event(&event_in(session, "echo 1000", 1_700_000_000)).unwrap();
+
+        let filter = ForgetFilter {
+            pattern: Some("0%".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(db.forget(&filter).unwrap(), 1);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM events WHERE command = 'echo 1000'"), 1);
+    }
+
+    #[test]
+    fn retention_expires_old_history_then_trims_to_size() {
+        let db = memory_db();
+        let session = db.create_session(None, 1).unwrap();
+        let now = crate::storage::events::now_secs();
+        db.record_event(&event_in(session, "old", now - 40 * 86400)).unwrap();
+        db.record_event(&event_in(session, "recent", now - 86400)).unwrap();
+        db.record_event(&event_in(session, "today", now)).unwrap();
+
+        let policy = RetentionPolicy {
+            retention_days: Some(30),
+            max_db_bytes: None,
+            ..Default::default()
+        };
+        let report = db.apply_retention(&policy).unwrap();
+        assert_eq!(report.expired, 1);
+        assert_eq!(report.trimmed, 0);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM events"), 2);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM search_index WHERE content = 'old'"), 0);
+
+        // A limit below the empty schema drops every event, oldest first, then reports the miss
+        let policy = RetentionPolicy {
+            retention_days: None,
+            max_db_bytes: Some(1),
+            ..Default::default()
+        };
+        let report = db.apply_retention(&policy).unwrap();
+        assert_eq!(report.trimmed, 2);
+        assert!(report.over_budget);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM events"), 0);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM search_index"), 0);
+    }
+
+    #[test]
+    fn sync_edges_upsert_and_go_stale() {
+        let db = memory_db();
+        db.record_sync_edge("/home/me/docs", "gdrive:docs", "sync", true, 2048, None).unwrap();
+        db.record_sync_edge("/home/me/docs", "gdrive:docs", "backup", false, 0, Some("quota exceeded")).unwrap();
+
+        let edges = db.list_sync_edges().unwrap();
+        assert_eq!(edges.len(), 1);
+        let edge = &edges[0];
+        assert_eq!((edge.kind.as_str(), edge.runs, edge.success), ("backup", 2, false));
+        assert_eq!(edge.error.as_deref(), Some("quota exceeded"));
+        // A failed run keeps the last success
+        let last_success = edge.last_success.unwrap();
+        assert!(last_success <= edge.last_sync);
+        assert!(!edge.is_stale(last_success + 3600, 24));
+        assert!(edge.is_stale(last_success + 25 * 3600, 24));
+
+        db.connection()
+            .execute("UPDATE sync_edges SET last_success = last_success - 2 * 86400", [])
+            .unwrap();
+        let edge = &db.list_sync_edges().unwrap()[0];
+        assert!(edge.is_stale(crate::storage::events::now_secs(), 24));
+
+        db.record_sync_edge("/srv/www", "nas:www", "sync", false, 0, Some("timeout")).unwrap();
+        let never = db.list_sync_edges().unwrap().into_iter().find(|e| e.source == "/srv/www").unwrap();
+        assert_eq!(never.last_success, None);
+        assert!(never.is_stale(0, 24 * 365));
+    }
+
+    #[test]
+    fn size_cap_ignores_tables_retention_cannot_shrink() {
+        let db = memory_db();
+        let session = db.create_session(None, 1).unwrap();
+        let now = crate::storage::events::now_secs();
+        for i in 0..3 {
+            db.record_event(&event_in(session, &format!("echo {}", i), now - i)).unwrap();
+        }
+        db.in_transaction(|db| {
+            for i in 0..3000 {
+                db.connection().execute(
+                    "INSERT INTO file_index (origin, path, seen) VALUES ('gdrive:', ?, 0)",
+                    params![format!("photos/{:04}/{}", i, "x".repeat(200))],
+                )?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        let history = db.history_size_bytes().unwrap();
+        assert!(db.live_size_bytes().unwrap() > history + 512 * 1024);
+
+        let policy = RetentionPolicy {
+            retention_days: None,
+            max_db_bytes: Some(history + 4096),
+            ..Default::default()
+        };
+        let report = db.apply_retention(&policy).unwrap();
+        assert_eq!(report.trimmed, 0);
+        assert!(!report.over_budget);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM events"), 3);
+    }
+}
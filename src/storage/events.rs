@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Typed event categories stored in the canonical `events` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Request,
+    Resolution,
+    Simulation,
+    Execution,
+    Heal,
+    Sync,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 6] = [
+        EventKind::Request,
+        EventKind::Resolution,
+        EventKind::Simulation,
+        EventKind::Execution,
+        EventKind::Heal,
+        EventKind::Sync,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Request => "request",
+            EventKind::Resolution => "resolution",
+            EventKind::Simulation => "simulation",
+            EventKind::Execution => "execution",
+            EventKind::Heal => "heal",
+            EventKind::Sync => "sync",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|k| k.as_str() == s)
+    }
+}
+
+/// A single entry of the event log.
+///
+/// `uid` is globally unique so that logs from several machines can be merged.
+/// `correlation_id` groups the events produced by one user request
+/// (request -> resolution -> simulation -> execution).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub uid: String,
+    pub session_id: Option<i64>,
+    pub correlation_id: Option<String>,
+    pub kind: EventKind,
+    pub command: Option<String>,
+    pub success: Option<bool>,
+    pub exit_code: Option<i32>,
+    pub payload: Value,
+    pub timestamp: i64,
+    pub host: Option<String>,
+    pub duration_ms: Option<i64>,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, command: &str) -> Self {
+        Event {
+            uid: new_uid(),
+            session_id: None,
+            correlation_id: None,
+            kind,
+            command: Some(command.to_string()),
+            success: None,
+            exit_code: None,
+            payload: Value::Null,
+            timestamp: now_secs(),
+            host: local_hostname(),
+            duration_ms: None,
+        }
+    }
+
+    pub fn with_correlation(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = Some(correlation_id.to_string());
+        self
+    }
+
+    pub fn with_result(mut self, success: bool, exit_code: Option<i32>) -> Self {
+        self.success = Some(success);
+        self.exit_code = exit_code;
+        self
+    }
+
+    pub fn with_duration(mut self, duration: std::time::Duration) -> Self {
+        self.duration_ms = Some(duration.as_millis() as i64);
+        self
+    }
+
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Reads a string field out of the JSON payload.
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.payload.get(key).and_then(|v| v.as_str())
+    }
+}
+
+pub fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+pub fn local_hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+}
+
+/// Random 128-bit identifier, hex encoded.
+pub fn new_uid() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
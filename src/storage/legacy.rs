@@ -0,0 +1,289 @@
+// One-shot import of the history stores that predate the event log:
+// `history.jsonl` (ExecutionLogger), the Archivist NDJSON files and the
+// `commands` / `execution_logs` / `task_history` / `decision_lineage` tables.
+//
+// Imported events get a deterministic uid derived from (kind, timestamp, command),
+// so the same command recorded by several legacy stores collapses into one event
+// and re-running the import is harmless.
+
+use crate::storage::db::Database;
+use crate::storage::events::{Event, EventKind};
+use chrono::DateTime;
+use rusqlite::Result;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const IMPORT_MARKER: &str = "legacy_history_imported";
+
+fn legacy_uid(kind: EventKind, timestamp: i64, command: &str) -> String {
+    format!(
+        "legacy-{:x}",
+        md5::compute(format!("{}|{}|{}", kind.as_str(), timestamp, command))
+    )
+}
+
+fn legacy_event(kind: EventKind, timestamp: i64, command: &str) -> Event {
+    let mut event = Event::new(kind, command);
+    event.uid = legacy_uid(kind, timestamp, command);
+    event.timestamp = timestamp;
+    event
+}
+
+fn parse_rfc3339(ts: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(ts).ok().map(|d| d.timestamp())
+}
+
+/// Imports every legacy store and marks the database as migrated.
+/// Returns the number of events read from legacy sources.
+pub fn import_all(db: &Database) -> Result<usize> {
+    let imported = db.in_transaction(|db| {
+        let mut count = 0;
+        count += import_tables(db)?;
+
+        if let Some(path) = execution_log_path() {
+            count += import_execution_log(db, &path)?;
+        }
+        for path in archive_files() {
+            count += import_archive_ndjson(db, &path)?;
+        }
+
+        // Legacy `commands` rows are re-indexed through the events trigger
+        db.connection()
+            .execute("DELETE FROM search_index WHERE origin_table = 'commands'", [])?;
+        Ok(count)
+    })?;
+
+    db.set_metadata(IMPORT_MARKER, &chrono::Local::now().to_rfc3339())?;
+    if imported > 0 {
+        println!("📦 Imported {} legacy history entries into the event log.", imported);
+    }
+    Ok(imported)
+}
+
+fn import_tables(db: &Database) -> Result<usize> {
+    let conn = db.connection();
+    let mut count = 0;
+
+    let mut stmt = conn.prepare(
+        "SELECT session_id, command, ai_comment, weight, timestamp, success FROM commands",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, Option<i64>>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<i32>>(3)?,
+            row.get::<_, Option<i64>>(4)?,
+            row.get::<_, Option<bool>>(5)?,
+        ))
+    })?;
+    for row in rows {
+        let (sid, command, ai_comment, weight, ts, success) = row?;
+        let mut event = legacy_event(EventKind::Execution, ts.unwrap_or(0), &command)
+            .with_payload(json!({ "ai_comment": ai_comment, "weight": weight }));
+        event.success = success;
+        event.session_id = sid;
+        db.record_event(&event)?;
+        count += 1;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT session_id, command, success, exit_code, stdout, stderr, healer_intervention, timestamp
+         FROM execution_logs",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, Option<i64>>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<bool>>(2)?,
+            row.get::<_, Option<i32>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+            row.get::<_, Option<i64>>(7)?,
+        ))
+    })?;
+    for row in rows {
+        let (sid, command, success, exit_code, stdout, stderr, healer, ts) = row?;
+        let mut event = legacy_event(EventKind::Execution, ts.unwrap_or(0), &command)
+            .with_payload(json!({ "stdout": stdout, "stderr": stderr, "healer_log": healer }));
+        event.success = success;
+        event.exit_code = exit_code;
+        event.session_id = sid;
+        db.record_event(&event)?;
+        count += 1;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT session_id, project_name, command, exit_code, stdout, stderr, healer_used, healer_log, token_usage, timestamp
+         FROM task_history",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, Option<i64>>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<i32>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<bool>>(6)?,
+            row.get::<_, Option<String>>(7)?,
+            row.get::<_, Option<i32>>(8)?,
+            row.get::<_, Option<i64>>(9)?,
+        ))
+    })?;
+    for row in rows {
+        let (sid, project, command, exit_code, stdout, stderr, healer_used, healer_log, tokens, ts) =
+            row?;
+        let mut event = legacy_event(EventKind::Execution, ts.unwrap_or(0), &command)
+            .with_result(exit_code == Some(0), exit_code)
+            .with_payload(json!({
+                "project_name": project,
+                "stdout": stdout,
+                "stderr": stderr,
+                "healer_used": healer_used.unwrap_or(false),
+                "healer_log": healer_log,
+                "token_usage": tokens,
+            }));
+        event.session_id = sid;
+        db.record_event(&event)?;
+        count += 1;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT session_id, user_request, intent, generated_command, simulation_log, risk_score, execution_result, timestamp
+         FROM decision_lineage",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, Option<i64>>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<i32>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+            row.get::<_, Option<i64>>(7)?,
+        ))
+    })?;
+    for row in rows {
+        let (sid, request, intent, generated, sim_log, risk, result, ts) = row?;
+        let ts = ts.unwrap_or(0);
+        let correlation = legacy_uid(EventKind::Request, ts, &request);
+
+        let mut req = legacy_event(EventKind::Request, ts, &request).with_correlation(&correlation);
+        req.session_id = sid;
+        db.record_event(&req)?;
+
+        let generated = generated.unwrap_or_default();
+        let mut sim = legacy_event(EventKind::Simulation, ts, &generated)
+            .with_correlation(&correlation)
+            .with_payload(json!({
+                "intent": intent,
+                "simulation_log": sim_log,
+                "risk_score": risk,
+                "execution_result": result,
+            }));
+        sim.session_id = sid;
+        db.record_event(&sim)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn execution_log_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|p| p.join("vega").join("history.jsonl"))
+}
+
+/// `ExecutionLogger` lines: {timestamp, command, action_type, success}
+fn import_execution_log(db: &Database, path: &Path) -> Result<usize> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return Ok(0),
+    };
+    let mut count = 0;
+    for line in content.lines() {
+        let v = match serde_json::from_str::<Value>(line) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let (command, ts) = match (
+            v["command"].as_str(),
+            v["timestamp"].as_str().and_then(parse_rfc3339),
+        ) {
+            (Some(c), Some(t)) => (c, t),
+            _ => continue,
+        };
+        let success = v["success"].as_bool().unwrap_or(false);
+        let event = legacy_event(EventKind::Request, ts, command)
+            .with_result(success, None)
+            .with_payload(json!({ "action_type": v["action_type"] }));
+        db.record_event(&event)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn archive_files() -> Vec<PathBuf> {
+    let root = crate::init::get_config_path()
+        .parent()
+        .map(|p| p.join("history"))
+        .unwrap_or_else(|| PathBuf::from("history"));
+
+    let mut files = Vec::new();
+    let years = match fs::read_dir(&root) {
+        Ok(y) => y,
+        Err(_) => return files,
+    };
+    for year in years.flatten() {
+        let months = match fs::read_dir(year.path()) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        for month in months.flatten() {
+            let path = month.path().join("history.json");
+            if path.exists() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Archivist NDJSON lines (`ArchiveEntry`).
+fn import_archive_ndjson(db: &Database, path: &Path) -> Result<usize> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return Ok(0),
+    };
+    let mut count = 0;
+    for line in content.lines() {
+        let v = match serde_json::from_str::<Value>(line) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let (command, ts) = match (
+            v["command"].as_str(),
+            v["timestamp"].as_str().and_then(parse_rfc3339),
+        ) {
+            (Some(c), Some(t)) => (c, t),
+            _ => continue,
+        };
+        let exit_code = v["exit_code"].as_i64().map(|c| c as i32);
+        let healer = v["healer_action"].as_str();
+        let event = legacy_event(EventKind::Execution, ts, command)
+            .with_result(v["success"].as_bool().unwrap_or(false), exit_code)
+            .with_payload(json!({
+                "stdout": v["result"]["stdout"],
+                "stderr": v["result"]["stderr"],
+                "healer_used": healer.is_some(),
+                "healer_log": healer,
+            }));
+        db.record_event(&event)?;
+        count += 1;
+    }
+    Ok(count)
+}
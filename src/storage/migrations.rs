@@ -0,0 +1,566 @@
+use rusqlite::{params, Connection, DatabaseName, Result};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// A schema step. `version` is written to `PRAGMA user_version` once `sql` has run.
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Ordered schema history. Never edit an entry that has shipped; append a new one.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline schema (sessions, commands, metadata, chat, logs, lineage, FTS)",
+        sql: "
+            CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY,
+                start_time INTEGER NOT NULL,
+                end_time INTEGER,
+                total_weight INTEGER DEFAULT 0
+            );
+
+            CREATE TABLE IF NOT EXISTS commands (
+                id INTEGER PRIMARY KEY,
+                session_id INTEGER,
+                command TEXT NOT NULL,
+                ai_comment TEXT,
+                weight INTEGER,
+                timestamp INTEGER,
+                success BOOLEAN,
+                FOREIGN KEY(session_id) REFERENCES sessions(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS metadata (
+                key TEXT PRIMARY KEY,
+                value TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS chat_history (
+                id INTEGER PRIMARY KEY,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                timestamp INTEGER
+            );
+
+            CREATE TABLE IF NOT EXISTS execution_logs (
+                id INTEGER PRIMARY KEY,
+                session_id INTEGER,
+                command TEXT NOT NULL,
+                success BOOLEAN,
+                exit_code INTEGER,
+                stdout TEXT,
+                stderr TEXT,
+                healer_intervention TEXT,
+                timestamp INTEGER,
+                FOREIGN KEY(session_id) REFERENCES sessions(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS task_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id INTEGER,
+                project_name TEXT,
+                command TEXT NOT NULL,
+                exit_code INTEGER,
+                stdout TEXT,
+                stderr TEXT,
+                healer_used BOOLEAN,
+                healer_log TEXT,
+                token_usage INTEGER,
+                timestamp INTEGER,
+                FOREIGN KEY(session_id) REFERENCES sessions(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS decision_lineage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id INTEGER,
+                user_request TEXT NOT NULL,
+                intent TEXT,
+                generated_command TEXT,
+                simulation_log TEXT,
+                risk_score INTEGER,
+                execution_result TEXT,
+                timestamp INTEGER,
+                FOREIGN KEY(session_id) REFERENCES sessions(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS error_solutions (
+                error_pattern TEXT PRIMARY KEY,
+                solution_cmd TEXT,
+                success_count INTEGER DEFAULT 1
+            );
+
+            CREATE TABLE IF NOT EXISTS system_stats (
+                date TEXT PRIMARY KEY,
+                total_input_tokens INTEGER DEFAULT 0,
+                total_output_tokens INTEGER DEFAULT 0,
+                error_count INTEGER DEFAULT 0
+            );
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(content, origin_table UNINDEXED, timestamp UNINDEXED);
+
+            CREATE TRIGGER IF NOT EXISTS sync_chat_search AFTER INSERT ON chat_history
+            WHEN NEW.role = 'user'
+            BEGIN
+                INSERT INTO search_index(content, origin_table, timestamp) VALUES (NEW.content, 'chat_history', NEW.timestamp);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS sync_command_search AFTER INSERT ON commands
+            BEGIN
+                INSERT INTO search_index(content, origin_table, timestamp) VALUES (NEW.command, 'commands', NEW.timestamp);
+            END;
+        ",
+    },
+    Migration {
+        version: 2,
+        description: "canonical event log",
+        sql: "
+            CREATE TABLE IF NOT EXISTS events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                uid TEXT NOT NULL UNIQUE,
+                session_id INTEGER,
+                correlation_id TEXT,
+                kind TEXT NOT NULL,
+                command TEXT,
+                success BOOLEAN,
+                exit_code INTEGER,
+                payload TEXT,
+                timestamp INTEGER NOT NULL,
+                FOREIGN KEY(session_id) REFERENCES sessions(id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_events_kind_time ON events(kind, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_events_session ON events(session_id);
+
+            CREATE TRIGGER IF NOT EXISTS sync_event_search AFTER INSERT ON events
+            WHEN NEW.kind IN ('request', 'execution') AND NEW.command IS NOT NULL
+            BEGIN
+                INSERT INTO search_index(content, origin_table, timestamp) VALUES (NEW.command, 'events', NEW.timestamp);
+            END;
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "events.host and events.duration_ms",
+        sql: "
+            ALTER TABLE events ADD COLUMN host TEXT;
+            ALTER TABLE events ADD COLUMN duration_ms INTEGER;
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "session lifecycle (tty, shell pid, last activity, summary)",
+        sql: "
+            ALTER TABLE sessions ADD COLUMN tty TEXT;
+            ALTER TABLE sessions ADD COLUMN shell_pid INTEGER;
+            ALTER TABLE sessions ADD COLUMN last_activity INTEGER;
+            ALTER TABLE sessions ADD COLUMN summary TEXT;
+            UPDATE sessions SET last_activity = start_time;
+            CREATE INDEX IF NOT EXISTS idx_sessions_open ON sessions(end_time, tty);
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "drop search_index rows together with forgotten history",
+        sql: "
+            CREATE INDEX IF NOT EXISTS idx_events_time ON events(timestamp);
+
+            CREATE TRIGGER IF NOT EXISTS purge_event_search AFTER DELETE ON events
+            WHEN OLD.command IS NOT NULL
+            BEGIN
+                DELETE FROM search_index WHERE origin_table = 'events' AND timestamp = OLD.timestamp AND content = OLD.command;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS purge_chat_search AFTER DELETE ON chat_history
+            BEGIN
+                DELETE FROM search_index WHERE origin_table = 'chat_history' AND timestamp = OLD.timestamp AND content = OLD.content;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS purge_command_search AFTER DELETE ON commands
+            BEGIN
+                DELETE FROM search_index WHERE origin_table = 'commands' AND timestamp = OLD.timestamp AND content = OLD.command;
+            END;
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "backup runs (vega backup / restore)",
+        sql: "
+            CREATE TABLE IF NOT EXISTS backup_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source TEXT NOT NULL,
+                destination TEXT NOT NULL,
+                snapshot TEXT NOT NULL,
+                started_at INTEGER NOT NULL,
+                finished_at INTEGER,
+                success BOOLEAN,
+                verified BOOLEAN,
+                files_transferred INTEGER,
+                bytes_transferred INTEGER,
+                total_files INTEGER,
+                total_bytes INTEGER,
+                error TEXT,
+                pruned BOOLEAN DEFAULT 0
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_backup_runs_dest ON backup_runs(destination, started_at);
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "sync topology edges (source -> remote)",
+        sql: "
+            CREATE TABLE IF NOT EXISTS sync_edges (
+                source TEXT NOT NULL,
+                destination TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                last_attempt INTEGER NOT NULL,
+                last_success INTEGER,
+                last_bytes INTEGER DEFAULT 0,
+                success BOOLEAN,
+                error TEXT,
+                runs INTEGER DEFAULT 0,
+                PRIMARY KEY (source, destination)
+            );
+
+            -- Backfill from syncs and backups already in the event log
+            INSERT OR IGNORE INTO sync_edges (source, destination, kind, last_attempt, last_success, success, runs)
+            SELECT json_extract(payload, '$.source'),
+                   json_extract(payload, '$.destination'),
+                   COALESCE(json_extract(payload, '$.direction'), 'sync'),
+                   MAX(timestamp),
+                   MAX(CASE WHEN success THEN timestamp END),
+                   NULL,
+                   COUNT(*)
+            FROM events
+            WHERE kind = 'sync'
+              AND json_valid(payload)
+              AND COALESCE(json_extract(payload, '$.direction'), 'sync') IN ('sync', 'backup')
+              AND json_extract(payload, '$.source') IS NOT NULL
+              AND json_extract(payload, '$.destination') IS NOT NULL
+            GROUP BY 1, 2;
+
+            UPDATE sync_edges SET success = COALESCE(last_success = last_attempt, 0);
+        ",
+    },
+    Migration {
+        version: 8,
+        description: "offline file index of remotes and hosts",
+        sql: "
+            CREATE TABLE IF NOT EXISTS file_index (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                origin TEXT NOT NULL,
+                path TEXT NOT NULL,
+                size INTEGER,
+                mtime INTEGER,
+                hash TEXT,
+                is_dir BOOLEAN DEFAULT 0,
+                seen INTEGER NOT NULL,
+                UNIQUE (origin, path)
+            );
+
+            -- Paths never change for a row, so only inserts and deletes touch the FTS index
+            CREATE VIRTUAL TABLE IF NOT EXISTS file_index_fts USING fts5(path, content='file_index', content_rowid='id');
+
+            CREATE TRIGGER IF NOT EXISTS file_index_ai AFTER INSERT ON file_index BEGIN
+                INSERT INTO file_index_fts(rowid, path) VALUES (NEW.id, NEW.path);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS file_index_ad AFTER DELETE ON file_index BEGIN
+                INSERT INTO file_index_fts(file_index_fts, rowid, path) VALUES ('delete', OLD.id, OLD.path);
+            END;
+
+            CREATE TABLE IF NOT EXISTS file_index_origins (
+                origin TEXT PRIMARY KEY,
+                label TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                last_crawl INTEGER,
+                entries INTEGER DEFAULT 0,
+                duration_ms INTEGER,
+                error TEXT
+            );
+        ",
+    },
+    Migration {
+        version: 9,
+        description: "mount registry (vega mount / umount)",
+        sql: "
+            CREATE TABLE IF NOT EXISTS mounts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                source TEXT NOT NULL,
+                mount_point TEXT NOT NULL UNIQUE,
+                auto_remount BOOLEAN DEFAULT 1,
+                systemd_unit TEXT,
+                created_at INTEGER NOT NULL,
+                last_mounted INTEGER,
+                last_check INTEGER,
+                healthy BOOLEAN,
+                last_error TEXT
+            );
+        ",
+    },
+    Migration {
+        version: 10,
+        description: "key search_index rows on the row they index",
+        sql: "
+            DROP TRIGGER IF EXISTS sync_chat_search;
+            DROP TRIGGER IF EXISTS sync_command_search;
+            DROP TRIGGER IF EXISTS sync_event_search;
+            DROP TRIGGER IF EXISTS purge_event_search;
+            DROP TRIGGER IF EXISTS purge_chat_search;
+            DROP TRIGGER IF EXISTS purge_command_search;
+            DROP TABLE IF EXISTS search_index;
+
+            CREATE VIRTUAL TABLE search_index USING fts5(content, origin_table UNINDEXED, origin_uid UNINDEXED, timestamp UNINDEXED);
+
+            -- Rebuild from the source tables; imported legacy commands are already indexed through events
+            INSERT INTO search_index(content, origin_table, origin_uid, timestamp)
+            SELECT command, 'events', uid, timestamp FROM events
+            WHERE kind IN ('request', 'execution') AND command IS NOT NULL;
+
+            INSERT INTO search_index(content, origin_table, origin_uid, timestamp)
+            SELECT content, 'chat_history', CAST(id AS TEXT), timestamp FROM chat_history
+            WHERE role = 'user';
+
+            INSERT INTO search_index(content, origin_table, origin_uid, timestamp)
+            SELECT command, 'commands', CAST(id AS TEXT), timestamp FROM commands
+            WHERE NOT EXISTS (SELECT 1 FROM metadata WHERE key = 'legacy_history_imported');
+
+            CREATE TRIGGER sync_chat_search AFTER INSERT ON chat_history
+            WHEN NEW.role = 'user'
+            BEGIN
+                INSERT INTO search_index(content, origin_table, origin_uid, timestamp) VALUES (NEW.content, 'chat_history', CAST(NEW.id AS TEXT), NEW.timestamp);
+            END;
+
+            CREATE TRIGGER sync_command_search AFTER INSERT ON commands
+            BEGIN
+                INSERT INTO search_index(content, origin_table, origin_uid, timestamp) VALUES (NEW.command, 'commands', CAST(NEW.id AS TEXT), NEW.timestamp);
+            END;
+
+            CREATE TRIGGER sync_event_search AFTER INSERT ON events
+            WHEN NEW.kind IN ('request', 'execution') AND NEW.command IS NOT NULL
+            BEGIN
+                INSERT INTO search_index(content, origin_table, origin_uid, timestamp) VALUES (NEW.command, 'events', NEW.uid, NEW.timestamp);
+            END;
+
+            CREATE TRIGGER purge_event_search AFTER DELETE ON events
+            BEGIN
+                DELETE FROM search_index WHERE origin_table = 'events' AND origin_uid = OLD.uid;
+            END;
+
+            CREATE TRIGGER purge_chat_search AFTER DELETE ON chat_history
+            BEGIN
+                DELETE FROM search_index WHERE origin_table = 'chat_history' AND origin_uid = CAST(OLD.id AS TEXT);
+            END;
+
+            CREATE TRIGGER purge_command_search AFTER DELETE ON commands
+            BEGIN
+                DELETE FROM search_index WHERE origin_table = 'commands' AND origin_uid = CAST(OLD.id AS TEXT);
+            END;
+        ",
+    },
+    Migration {
+        version: 11,
+        description: "sessions closed on exit can be resumed",
+        sql: "
+            ALTER TABLE sessions ADD COLUMN resumable BOOLEAN DEFAULT 0;
+        ",
+    },
+    Migration {
+        version: 12,
+        description: "pruned backup versions record the snapshot they were folded into",
+        sql: "
+            ALTER TABLE backup_runs ADD COLUMN folded_into TEXT;
+        ",
+    },
+];
+
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> Result<i32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+pub fn pending(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    let current = current_version(conn)?;
+    Ok(MIGRATIONS.iter().filter(|m| m.version > current).collect())
+}
+
+/// True when the file holds any user table, i.e. an upgrade touches real data.
+fn has_data(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'sessions'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Applies every pending migration, each in its own transaction.
+/// A backup of the database file is taken first when existing data is upgraded;
+/// when it cannot be written nothing is migrated.
+/// Returns the versions that were applied.
+///
+/// Each step takes the write lock (`BEGIN IMMEDIATE`) and re-checks the version,
+/// so concurrent `vega` processes starting on an old schema upgrade it exactly once.
+pub fn run(conn: &Connection, db_path: &Path) -> Result<Vec<i32>> {
+    let todo = pending(conn)?;
+    if todo.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if has_data(conn)? {
+        // No upgrade without a way back
+        let from = current_version(conn)?;
+        match backup(conn, db_path, &format!("pre-v{}", from)) {
+            Ok(path) => log::info!("💾 Pre-migration backup: {:?}", path),
+            Err(e) => {
+                eprintln!("❌ Pre-migration backup failed, schema left at v{}: {}", from, e);
+                return Err(e);
+            }
+        }
+    }
+
+    let mut applied = Vec::new();
+    for m in todo {
+        conn.execute_batch("BEGIN IMMEDIATE")?;
+        if current_version(conn)? >= m.version {
+            conn.execute_batch("COMMIT")?;
+            continue;
+        }
+        let step = conn
+            .execute_batch(m.sql)
+            .and_then(|_| conn.pragma_update(None, "user_version", m.version));
+        match step {
+            Ok(_) => conn.execute_batch("COMMIT")?,
+            Err(e) => {
+                let _ = conn.execute_batch("ROLLBACK");
+                return Err(e);
+            }
+        }
+        applied.push(m.version);
+    }
+    Ok(applied)
+}
+
+pub fn backup_dir(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .map(|p| p.join("backups"))
+        .unwrap_or_else(|| PathBuf::from("backups"))
+}
+
+/// Online copy of the database via the SQLite backup API.
+pub fn backup(conn: &Connection, db_path: &Path, tag: &str) -> Result<PathBuf> {
+    let dir = backup_dir(db_path);
+    fs::create_dir_all(&dir).ok();
+    let name = format!(
+        "vega-{}-{}.db",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        tag
+    );
+    let path = dir.join(name);
+    backup_to(conn, &path)?;
+    Ok(path)
+}
+
+pub fn backup_to(conn: &Connection, path: &Path) -> Result<()> {
+    conn.backup(DatabaseName::Main, path, None)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        let mut perms = metadata.permissions();
+        perms.set_mode(0o600);
+        let _ = fs::set_permissions(path, perms);
+    }
+    Ok(())
+}
+
+/// Row counts for `vega db status`.
+pub fn table_counts(conn: &Connection) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'search_index_%' AND name NOT LIKE 'file_index_fts_%' ORDER BY name",
+    )?;
+    let names: Vec<String> = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+
+    let mut counts = Vec::new();
+    for name in names {
+        let count: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", name), params![], |row| {
+            row.get(0)
+        })?;
+        counts.push((name, count));
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database from before the migration system: baseline tables, one session, user_version 0.
+    fn legacy_db(name: &str) -> (PathBuf, Connection) {
+        let dir = std::env::temp_dir().join(format!("vega-migrate-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vega.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE sessions (id INTEGER PRIMARY KEY, start_time INTEGER NOT NULL, end_time INTEGER, total_weight INTEGER DEFAULT 0);
+             CREATE TABLE commands (id INTEGER PRIMARY KEY, session_id INTEGER, command TEXT NOT NULL, ai_comment TEXT,
+                                    weight INTEGER, timestamp INTEGER, success BOOLEAN);
+             INSERT INTO sessions (id, start_time) VALUES (1, 1700000000);
+             INSERT INTO commands (session_id, command, timestamp, success) VALUES (1, 'ls -la', 1700000000, 1);",
+        )
+        .unwrap();
+        (path, conn)
+    }
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table)).unwrap();
+        let names = stmt.query_map([], |row| row.get(1)).unwrap();
+        names.collect::<Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn legacy_database_is_backed_up_then_fully_migrated() {
+        let (path, conn) = legacy_db("full");
+        assert_eq!(current_version(&conn).unwrap(), 0);
+
+        let applied = run(&conn, &path).unwrap();
+        assert_eq!(applied, (1..=latest_version()).collect::<Vec<_>>());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert!(columns(&conn, "sessions").contains(&"resumable".to_string()));
+        assert!(columns(&conn, "backup_runs").contains(&"folded_into".to_string()));
+        assert!(columns(&conn, "search_index").contains(&"origin_uid".to_string()));
+        let kept: i64 = conn.query_row("SELECT COUNT(*) FROM commands", [], |row| row.get(0)).unwrap();
+        assert_eq!(kept, 1);
+        assert!(run(&conn, &path).unwrap().is_empty());
+
+        let backups: Vec<PathBuf> = fs::read_dir(backup_dir(&path)).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].to_string_lossy().ends_with("-pre-v0.db"));
+        let backup = Connection::open(&backups[0]).unwrap();
+        assert_eq!(current_version(&backup).unwrap(), 0);
+        let saved: String = backup.query_row("SELECT command FROM commands", [], |row| row.get(0)).unwrap();
+        assert_eq!(saved, "ls -la");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn failed_backup_leaves_the_schema_untouched() {
+        let (path, conn) = legacy_db("nobackup");
+        // A file where the backups folder should be
+        fs::write(backup_dir(&path), "").unwrap();
+
+        assert!(run(&conn, &path).is_err());
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        assert!(!columns(&conn, "sessions").contains(&"resumable".to_string()));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
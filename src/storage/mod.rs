@@ -1 +1,7 @@
+pub mod cache_file;
 pub mod db;
+pub mod events;
+pub mod legacy;
+pub mod migrations;
+pub mod retention;
+pub mod session;
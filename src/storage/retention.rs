@@ -0,0 +1,227 @@
+use crate::config::StorageConfig;
+use crate::safety::sanitizer::redact_output;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Policy configured from `[storage]` for this process; defaults apply until `configure` runs.
+static POLICY: OnceLock<RetentionPolicy> = OnceLock::new();
+
+/// Payload fields holding raw command output.
+pub const OUTPUT_FIELDS: [&str; 4] = ["stdout", "stderr", "healer_log", "error"];
+
+/// Metadata key holding the last time retention ran; it runs at most once a day.
+pub const LAST_RUN_KEY: &str = "retention_last_run";
+
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub retention_days: Option<i64>,
+    pub max_db_bytes: Option<u64>,
+    pub max_output_bytes: usize,
+    pub redact_outputs: bool,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            retention_days: Some(180),
+            max_db_bytes: Some(256 * 1024 * 1024),
+            max_output_bytes: 16 * 1024,
+            redact_outputs: true,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: Option<&StorageConfig>) -> Self {
+        let defaults = Self::default();
+        let config = match config {
+            Some(c) => c,
+            None => return defaults,
+        };
+        RetentionPolicy {
+            retention_days: match config.retention_days {
+                Some(0) => None,
+                Some(days) => Some(days),
+                None => defaults.retention_days,
+            },
+            max_db_bytes: match config.max_db_size_mb {
+                Some(0) => None,
+                Some(mb) => Some(mb * 1024 * 1024),
+                None => defaults.max_db_bytes,
+            },
+            max_output_bytes: config.max_output_bytes.unwrap_or(defaults.max_output_bytes),
+            redact_outputs: config.redact_outputs.unwrap_or(defaults.redact_outputs),
+        }
+    }
+
+    /// Redacts and truncates a single output before it is persisted.
+    pub fn scrub_text(&self, text: &str) -> String {
+        let text = if self.redact_outputs {
+            redact_output(text)
+        } else {
+            text.to_string()
+        };
+        truncate_output(&text, self.max_output_bytes)
+    }
+
+    /// Applies `scrub_text` to the output fields of an event payload.
+    /// Returns true when anything changed.
+    pub fn scrub_payload(&self, payload: &mut Value) -> bool {
+        let mut changed = false;
+        if let Some(map) = payload.as_object_mut() {
+            for field in OUTPUT_FIELDS {
+                if let Some(Value::String(text)) = map.get_mut(field) {
+                    let scrubbed = self.scrub_text(text);
+                    if scrubbed != *text {
+                        *text = scrubbed;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+}
+
+pub fn configure(config: Option<&StorageConfig>) {
+    let _ = POLICY.set(RetentionPolicy::from_config(config));
+}
+
+pub fn policy() -> &'static RetentionPolicy {
+    POLICY.get_or_init(RetentionPolicy::default)
+}
+
+/// Keeps the head and tail of `text` within `max_bytes`, marking the elided middle.
+/// The marker counts towards the limit, so truncating twice changes nothing.
+pub fn truncate_output(text: &str, max_bytes: usize) -> String {
+    if max_bytes == 0 || text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let marker_len = format!("\n... [truncated {} bytes] ...\n", text.len()).len();
+    let budget = max_bytes.saturating_sub(marker_len);
+    let head_end = floor_char_boundary(text, budget * 3 / 4);
+    let tail_start = ceil_char_boundary(text, text.len() - budget / 4);
+    format!(
+        "{}\n... [truncated {} bytes] ...\n{}",
+        &text[..head_end],
+        tail_start - head_end,
+        &text[tail_start..]
+    )
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+/// Selects history entries for `vega forget`. Unset fields match everything.
+#[derive(Debug, Default, Clone)]
+pub struct ForgetFilter {
+    pub pattern: Option<String>,
+    pub session_id: Option<i64>,
+    pub before: Option<i64>,
+}
+
+impl ForgetFilter {
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none() && self.session_id.is_none() && self.before.is_none()
+    }
+
+    /// `LIKE` argument for the pattern, with wildcards in the user input escaped.
+    pub fn like_pattern(&self) -> Option<String> {
+        self.pattern.as_ref().map(|p| {
+            let escaped = p.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            format!("%{}%", escaped)
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RetentionReport {
+    pub expired: usize,
+    pub trimmed: usize,
+    pub size_before: u64,
+    pub size_after: u64,
+    /// History is still above the size cap with every event trimmed.
+    pub over_budget: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn truncation_keeps_head_and_tail_on_char_boundaries() {
+        assert_eq!(truncate_output("short", 16), "short");
+        assert_eq!(truncate_output("unlimited", 0), "unlimited");
+
+        let text = "é".repeat(40); // 80 bytes, every char is 2 bytes
+        let truncated = truncate_output(&text, 45);
+        assert!(truncated.len() <= 45);
+        let (head, rest) = truncated.split_once("\n... [truncated ").unwrap();
+        let (elided, tail) = rest.split_once(" bytes] ...\n").unwrap();
+        // The 30-byte marker leaves 15: 15 * 3 / 4 = 11 falls inside a char, so the head stops at 10
+        assert_eq!(head, "é".repeat(5));
+        // len - 15 / 4 = 77 falls inside a char, so the tail starts at 78
+        assert_eq!(tail, "é");
+        assert_eq!(elided.parse::<usize>().unwrap(), 68);
+
+        let mixed = format!("{}{}", "a".repeat(30), "한글".repeat(10));
+        let truncated = truncate_output(&mixed, 50);
+        assert!(truncated.starts_with(&"a".repeat(15)));
+        assert!(truncated.ends_with("글"));
+        assert_eq!(truncate_output(&truncated, 50), truncated);
+    }
+
+    #[test]
+    fn scrubs_only_output_fields() {
+        let policy = RetentionPolicy {
+            max_output_bytes: 80,
+            ..Default::default()
+        };
+        let mut payload = json!({
+            "stdout": format!("token=abc\n{}", "x".repeat(100)),
+            "stderr": "clean",
+            "note": "password=kept because it is not an output field",
+        });
+        assert!(policy.scrub_payload(&mut payload));
+        let stdout = payload["stdout"].as_str().unwrap();
+        assert!(stdout.starts_with("token=[REDACTED]"), "{}", stdout);
+        assert!(stdout.contains("[truncated"), "{}", stdout);
+        assert_eq!(payload["stderr"], "clean");
+        assert_eq!(payload["note"], "password=kept because it is not an output field");
+
+        // A second pass finds nothing left to change
+        assert!(!policy.scrub_payload(&mut payload));
+
+        let raw = RetentionPolicy {
+            redact_outputs: false,
+            ..Default::default()
+        };
+        let mut payload = json!({ "stdout": "token=abc" });
+        assert!(!raw.scrub_payload(&mut payload));
+    }
+
+    #[test]
+    fn like_pattern_escapes_wildcards() {
+        let filter = |p: &str| ForgetFilter {
+            pattern: Some(p.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filter("rm -rf").like_pattern().unwrap(), "%rm -rf%");
+        assert_eq!(filter("100%_done").like_pattern().unwrap(), "%100\\%\\_done%");
+        assert_eq!(filter("C:\\tmp").like_pattern().unwrap(), "%C:\\\\tmp%");
+        assert_eq!(ForgetFilter::default().like_pattern(), None);
+        assert!(ForgetFilter::default().is_empty());
+    }
+}
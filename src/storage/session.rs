@@ -0,0 +1,92 @@
+use crate::storage::db::Database;
+use rusqlite::Result;
+use std::sync::OnceLock;
+
+/// Session opened by this process; every `Database` handle attaches its writes to it.
+static ACTIVE_SESSION: OnceLock<i64> = OnceLock::new();
+
+/// Default idle window in which a new invocation from the same TTY/shell continues a session.
+pub const DEFAULT_TIMEOUT_SECS: i64 = 30 * 60;
+
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub id: i64,
+    pub start_time: i64,
+    pub end_time: Option<i64>,
+    pub last_activity: Option<i64>,
+    pub tty: Option<String>,
+    pub shell_pid: Option<i64>,
+    pub total_weight: i32,
+    pub summary: Option<String>,
+    pub event_count: i64,
+}
+
+impl SessionInfo {
+    pub fn is_open(&self) -> bool {
+        self.end_time.is_none()
+    }
+}
+
+pub fn active() -> Option<i64> {
+    ACTIVE_SESSION.get().copied()
+}
+
+/// Controlling terminal of stdin (e.g. `/dev/pts/3`), if any.
+pub fn current_tty() -> Option<String> {
+    std::fs::read_link("/proc/self/fd/0")
+        .ok()
+        .map(|p| p.to_string_lossy().to_string())
+        .filter(|p| p.starts_with("/dev/pts/") || p.starts_with("/dev/tty"))
+}
+
+/// PID of the invoking shell.
+pub fn shell_pid() -> i64 {
+    std::os::unix::process::parent_id() as i64
+}
+
+/// Opens the session for this CLI run: closes sessions idle past `timeout_secs`,
+/// then continues the session of the same TTY (or shell PID) that is still open or was
+/// closed on exit within the timeout, or starts a new one.
+pub fn open(timeout_secs: i64) -> Result<i64> {
+    if let Some(id) = active() {
+        return Ok(id);
+    }
+
+    let db = Database::new()?;
+    let now = crate::storage::events::now_secs();
+    db.close_idle_sessions(now - timeout_secs)?;
+
+    let tty = current_tty();
+    let pid = shell_pid();
+    let id = match db.find_resumable_session(tty.as_deref(), pid, now - timeout_secs)? {
+        Some(id) => {
+            db.reopen_session(id)?;
+            id
+        }
+        None => db.create_session(tty.as_deref(), pid)?,
+    };
+    db.touch_session(id)?;
+
+    let _ = ACTIVE_SESSION.set(id);
+    Ok(id)
+}
+
+/// Closes the session of this run with its summary; `open` may still continue it
+/// from the same TTY within the timeout. Sessions closed by `vega session close` are final.
+pub fn close_on_exit() -> Result<()> {
+    if let Some(id) = active() {
+        Database::new()?.close_session(id, true)?;
+    }
+    Ok(())
+}
+
+/// Calls `close_on_exit` when the run (or chat) returns, whichever path it takes.
+pub struct ExitGuard;
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        if let Err(e) = close_on_exit() {
+            eprintln!("⚠️ Failed to close session: {}", e);
+        }
+    }
+}
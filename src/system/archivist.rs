@@ -1,10 +1,11 @@
 use crate::storage::db::Database;
 use crate::executor::ExecuteResult;
 
-use std::fs::{self, OpenOptions};
-use std::io::Write;
+use crate::storage::events::{Event, EventKind};
+
+use std::fs;
 use std::path::PathBuf;
-use chrono::Local;
+use chrono::{Local, NaiveDate, TimeZone};
 use serde::{Serialize, Deserialize};
 
 use serde_json::json;
@@ -33,12 +34,10 @@ impl Archivist {
         healer_action: Option<String>,
         token_usage: Option<i32>
     ) {
-        let timestamp = Local::now().to_rfc3339();
         let sanitized_cmd = sanitize_string(command);
 
-        // 1. Hot Data: SQLite
+        // Single source of truth: the event log. Markdown and NDJSON are derived views.
         if let (Some(database), Some(sid)) = (db, session_id) {
-            // Use advanced task logging
             let _ = database.log_task(
                 *sid,
                 project_name,
@@ -50,23 +49,14 @@ impl Archivist {
                 healer_action.as_deref(),
                 token_usage
             );
-        }
-
-        // 2. Cold Data: File Archive
-        let entry = ArchiveEntry {
-            timestamp: timestamp.clone(),
-            command: sanitized_cmd.clone(),
-            success: result.success,
-            exit_code: result.exit_code.unwrap_or(-1),
-            result: result.clone(),
-            healer_action: healer_action.clone(),
-        };
-
-        // Save JSON
-        Self::append_to_json_history(&entry);
 
-        // Save Markdown Report
-        Self::append_to_daily_report(&entry);
+            if let Some(action) = &healer_action {
+                let mut heal = Event::new(EventKind::Heal, &sanitized_cmd)
+                    .with_payload(json!({ "action": action }));
+                heal.session_id = Some(*sid);
+                let _ = database.record_event(&heal);
+            }
+        }
     }
 
     fn get_history_dir(year: &str, month: &str) -> PathBuf {
@@ -78,62 +68,103 @@ impl Archivist {
         history_dir
     }
 
-    fn append_to_json_history(entry: &ArchiveEntry) {
-        let now = Local::now();
-        let year = now.format("%Y").to_string();
-        let month = now.format("%m").to_string();
-        
-        let path = Self::get_history_dir(&year, &month).join("history.json");
-        // For simplicity, we are appending line-delimited JSON (NDJSON) or just appending to a list?
-        // True "history.json" usually implies a valid JSON array. But appending to an array is expensive (read-parse-write).
-        // Let's use NDJSON (Newlines) for efficiency and robustness.
-        
-        if let Ok(json_str) = serde_json::to_string(entry) {
-            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
-                if let Err(e) = writeln!(file, "{}", json_str) {
-                    eprintln!("Failed to write history.json: {}", e);
-                }
-            }
+    fn to_archive_entry(event: &Event) -> ArchiveEntry {
+        let exit_code = event.exit_code.unwrap_or(if event.success.unwrap_or(false) { 0 } else { -1 });
+        ArchiveEntry {
+            timestamp: Local
+                .timestamp_opt(event.timestamp, 0)
+                .single()
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+            command: event.command.clone().unwrap_or_default(),
+            success: event.success.unwrap_or(exit_code == 0),
+            exit_code,
+            result: ExecuteResult {
+                success: event.success.unwrap_or(exit_code == 0),
+                stdout: event.field("stdout").unwrap_or_default().to_string(),
+                stderr: event.field("stderr").unwrap_or_default().to_string(),
+                exit_code: event.exit_code,
+            },
+            healer_action: event.field("healer_log").map(str::to_string),
         }
     }
 
-    fn append_to_daily_report(entry: &ArchiveEntry) {
-        let now = Local::now();
-        let current_date = now.format("%Y-%m-%d").to_string();
-        let year = now.format("%Y").to_string();
-        let month = now.format("%m").to_string();
-        
-        let filename = format!("report_{}.md", current_date);
-        let path = Self::get_history_dir(&year, &month).join(filename);
-
-        let status_icon = if entry.success { "✅" } else { "❌" };
-        let fail_tag = if !entry.success { " #FAILED" } else { "" };
-        let healer_note = if let Some(action) = &entry.healer_action {
-             format!("\n- **🚑 Healer:** {}", action)
+    /// Local-time [start, end) bounds of a calendar month as unix seconds.
+    fn month_bounds(year: i32, month: u32) -> Option<(i64, i64)> {
+        let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let end = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)?
         } else {
-             String::new()
+            NaiveDate::from_ymd_opt(year, month + 1, 1)?
         };
+        Some((Self::local_midnight(start)?, Self::local_midnight(end)?))
+    }
 
-        // Construct Markdown Entry
-        let md_entry = format!(
-            "\n## {} Execution: `{}` {}\n- **Time:** {}\n- **Exit Code:** {}\n- **Result:** {}{}{}\n",
-            status_icon, 
-            entry.command, 
-            fail_tag,
-            now.format("%H:%M:%S"), 
-            entry.exit_code, 
-            status_icon,
-            healer_note,
-            if !entry.success { format!("\n- **Error:**\n```\n{}\n```", entry.result.stderr.trim()) } else { String::new() }
-        );
-
-        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
-            // Write header if new file
-            if file.metadata().map(|m| m.len() == 0).unwrap_or(false) {
-                let _ = writeln!(file, "# 🛡️ Daily Report: {}\n", current_date);
-            }
-            let _ = write!(file, "{}", md_entry);
+    fn local_midnight(date: NaiveDate) -> Option<i64> {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()
+            .map(|t| t.timestamp())
+    }
+
+    /// NDJSON view: one `ArchiveEntry` per execution event of the given month.
+    pub fn export_ndjson(db: &Database, year: i32, month: u32) -> Result<PathBuf, String> {
+        let (from, to) = Self::month_bounds(year, month).ok_or("Invalid month")?;
+        let events = db.get_events_between(from, to).map_err(|e| e.to_string())?;
+
+        let mut out = String::new();
+        for event in events.iter().filter(|e| e.kind == EventKind::Execution) {
+            let json_str = serde_json::to_string(&Self::to_archive_entry(event)).map_err(|e| e.to_string())?;
+            out.push_str(&json_str);
+            out.push('\n');
+        }
+
+        let path = Self::get_history_dir(&format!("{:04}", year), &format!("{:02}", month)).join("history.json");
+        fs::write(&path, out).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+
+    /// Markdown view: the daily report for `date`, rebuilt from the event log.
+    pub fn export_daily_report(db: &Database, date: NaiveDate) -> Result<PathBuf, String> {
+        let from = Self::local_midnight(date).ok_or("Invalid date")?;
+        let to = Self::local_midnight(date.succ_opt().ok_or("Invalid date")?).ok_or("Invalid date")?;
+        let events = db.get_events_between(from, to).map_err(|e| e.to_string())?;
+
+        let current_date = date.format("%Y-%m-%d").to_string();
+        let mut md = format!("# 🛡️ Daily Report: {}\n\n", current_date);
+
+        for event in events.iter().filter(|e| e.kind == EventKind::Execution) {
+            let entry = Self::to_archive_entry(event);
+            let status_icon = if entry.success { "✅" } else { "❌" };
+            let fail_tag = if !entry.success { " #FAILED" } else { "" };
+            let healer_note = if let Some(action) = &entry.healer_action {
+                 format!("\n- **🚑 Healer:** {}", action)
+            } else {
+                 String::new()
+            };
+            let time = Local
+                .timestamp_opt(event.timestamp, 0)
+                .single()
+                .map(|t| t.format("%H:%M:%S").to_string())
+                .unwrap_or_default();
+
+            md.push_str(&format!(
+                "\n## {} Execution: `{}` {}\n- **Time:** {}\n- **Exit Code:** {}\n- **Result:** {}{}{}\n",
+                status_icon,
+                entry.command,
+                fail_tag,
+                time,
+                entry.exit_code,
+                status_icon,
+                healer_note,
+                if !entry.success { format!("\n- **Error:**\n```\n{}\n```", entry.result.stderr.trim()) } else { String::new() }
+            ));
         }
+
+        let path = Self::get_history_dir(&date.format("%Y").to_string(), &date.format("%m").to_string())
+            .join(format!("report_{}.md", current_date));
+        fs::write(&path, md).map_err(|e| e.to_string())?;
+        Ok(path)
     }
 
     // internal sanitize_command removed in favor of crate::safety::sanitizer
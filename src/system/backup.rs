@@ -0,0 +1,537 @@
+use crate::remote::rc::{self, RcClient};
+use crate::remote::rclone::RcloneProvider;
+use crate::storage::db::{BackupRun, Database};
+use crate::storage::events::{now_secs, Event, EventKind};
+use crate::system::storage::SmartStorage;
+use chrono::{Datelike, NaiveDateTime};
+use log::info;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Folder created under a bare remote (`gdrive:`) to hold backups.
+const BACKUP_ROOT: &str = "vega_backups";
+/// Snapshot names double as `--backup-dir` folder names.
+const SNAPSHOT_FORMAT: &str = "%Y-%m-%d_%H%M%S";
+
+pub const DEFAULT_KEEP_DAILY: usize = 7;
+pub const DEFAULT_KEEP_WEEKLY: usize = 4;
+
+/// Versioned backups on rclone remotes.
+///
+/// Layout under `<remote>/<name>/`: `current/` mirrors the source, and each run moves the
+/// files it overwrites or deletes into `versions/<snapshot>/`.
+pub struct BackupManager {
+    provider: RcloneProvider,
+    storage: SmartStorage,
+    keep_daily: usize,
+    keep_weekly: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub layers: usize,
+}
+
+impl BackupManager {
+    pub fn new(config: Option<&crate::config::BackupConfig>) -> Self {
+        BackupManager {
+            provider: RcloneProvider::new(String::new()),
+            storage: SmartStorage::new(),
+            keep_daily: config.and_then(|c| c.keep_daily).unwrap_or(DEFAULT_KEEP_DAILY),
+            keep_weekly: config.and_then(|c| c.keep_weekly).unwrap_or(DEFAULT_KEEP_WEEKLY),
+        }
+    }
+
+    /// `<remote>vega_backups/<source name>` for a bare remote, `<remote path>/<source name>` otherwise.
+    pub fn destination(&self, source: &str, target_alias: &str) -> String {
+        let remote = self.storage.resolve(target_alias);
+        let source = canonical_source(source);
+        let name = Path::new(source.trim_end_matches('/'))
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "root".to_string());
+        if remote.ends_with(':') {
+            format!("{}{}/{}", remote, BACKUP_ROOT, name)
+        } else {
+            format!("{}/{}", remote.trim_end_matches('/'), name)
+        }
+    }
+
+    /// Syncs `source` into `current/` with a dated `--backup-dir`, verifies with
+    /// `rclone check`, records the run and prunes old versions.
+    pub async fn run(&self, db: &Database, source: &str, target_alias: &str) -> Result<BackupRun, String> {
+        let source = canonical_source(source);
+        let source = source.as_str();
+        let destination = self.destination(source, target_alias);
+        let current = format!("{}/current", destination);
+        let started = chrono::Local::now();
+        let snapshot = started.format(SNAPSHOT_FORMAT).to_string();
+        let backup_dir = format!("{}/versions/{}", destination, snapshot);
+
+        let mut run = BackupRun {
+            source: source.to_string(),
+            destination: destination.clone(),
+            snapshot: snapshot.clone(),
+            started_at: started.timestamp(),
+            ..Default::default()
+        };
+
+        // 1. Sync with versioning: rc job with live progress, or the CLI as fallback
+        info!("☁️ Backing up {} to {}", source, current);
+        let rc = match RcClient::connect().await {
+            Ok(rc) => Some(rc),
+            Err(e) => {
+                info!("rclone rc unavailable ({}), using the CLI", e);
+                None
+            }
+        };
+        let mut rc_config = self.storage.rc_config(&destination);
+        rc_config["BackupDir"] = json!(backup_dir);
+        let rc_outcome = match &rc {
+            Some(rc) => Some(rc::sync_with_progress(rc, source, &current, rc_config).await),
+            None => None,
+        };
+        match rc_outcome {
+            Some(Ok(outcome)) => {
+                run.files_transferred = outcome.stats.transfers;
+                run.bytes_transferred = outcome.stats.bytes;
+                run.success = outcome.success;
+                if outcome.cancelled {
+                    run.error = Some("cancelled".to_string());
+                } else if !outcome.success {
+                    run.error = outcome.error.or_else(|| Some("rclone job failed".to_string()));
+                }
+            }
+            Some(Err(e)) => run.error = Some(e),
+            None => self.sync_cli(source, &current, &backup_dir, &destination, &mut run),
+        }
+
+        // 2. Verify and measure
+        if run.success {
+            match self.provider.execute_rclone_output(vec!["check", source, &current]) {
+                Ok(output) if output.status.success() => run.verified = true,
+                Ok(output) => {
+                    run.error = Some(format!(
+                        "verification failed: {}",
+                        last_error(&String::from_utf8_lossy(&output.stderr))
+                    ))
+                }
+                Err(e) => run.error = Some(e),
+            }
+            let size = match &rc {
+                Some(rc) => rc.size(&current).await.ok(),
+                None => self
+                    .provider
+                    .query_rclone(vec!["size", "--json", &current])
+                    .ok()
+                    .and_then(|out| serde_json::from_str::<Value>(&out).ok())
+                    .map(|v| (v["count"].as_i64().unwrap_or(0), v["bytes"].as_i64().unwrap_or(0))),
+            };
+            if let Some((files, bytes)) = size {
+                run.total_files = files;
+                run.total_bytes = bytes;
+            }
+        }
+        run.finished_at = Some(now_secs());
+
+        run.id = db.record_backup_run(&run).map_err(|e| format!("DB Error: {}", e))?;
+        let event = Event::new(EventKind::Sync, &format!("vega backup {} {}", source, destination))
+            .with_result(run.success && run.verified, None)
+            .with_duration(std::time::Duration::from_secs(
+                (run.finished_at.unwrap_or(run.started_at) - run.started_at).max(0) as u64,
+            ))
+            .with_payload(json!({
+                "direction": "backup",
+                "source": source,
+                "destination": destination,
+                "snapshot": snapshot,
+                "files_transferred": run.files_transferred,
+                "bytes_transferred": run.bytes_transferred,
+                "verified": run.verified,
+                "error": run.error,
+            }));
+        let _ = db.record_event(&event);
+        let _ = db.record_sync_edge(
+            source,
+            &destination,
+            "backup",
+            run.success && run.verified,
+            run.bytes_transferred,
+            run.error.as_deref(),
+        );
+
+        if run.success {
+            if let Err(e) = self.prune(db, &destination) {
+                eprintln!("⚠️ Version pruning failed: {}", e);
+            }
+        }
+        Ok(run)
+    }
+
+    /// CLI sync; final stats are emitted as a JSON log line.
+    fn sync_cli(&self, source: &str, current: &str, backup_dir: &str, destination: &str, run: &mut BackupRun) {
+        let mut args = vec![
+            "sync",
+            source,
+            current,
+            "--backup-dir",
+            backup_dir,
+            "--use-json-log",
+            "--stats-log-level",
+            "NOTICE",
+            "--stats",
+            "24h",
+        ];
+        let tuning = self.storage.tuning_flags(destination);
+        args.extend(tuning.iter().map(|s| s.as_str()));
+
+        match self.provider.execute_rclone_output(args) {
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if let Some(stats) = final_stats(&stderr) {
+                    run.files_transferred = stats["transfers"].as_i64().unwrap_or(0);
+                    run.bytes_transferred = stats["bytes"].as_i64().unwrap_or(0);
+                }
+                run.success = output.status.success();
+                if !run.success {
+                    run.error = Some(last_error(&stderr));
+                }
+            }
+            Err(e) => run.error = Some(e),
+        }
+    }
+
+    /// Folds version folders outside the daily/weekly retention window into the next newer
+    /// snapshot's folder (older files win) and removes them. Returns their names.
+    ///
+    /// A version folder holds the only copy of the files its run overwrote, and restoring an
+    /// older snapshot layers it, so it is merged forward rather than deleted.
+    pub fn prune(&self, db: &Database, destination: &str) -> Result<Vec<String>, String> {
+        let versions_path = format!("{}/versions", destination);
+        let listing = match self.provider.query_rclone(vec!["lsf", "--dirs-only", &versions_path]) {
+            Ok(l) => l,
+            Err(_) => return Ok(Vec::new()), // nothing versioned yet
+        };
+        let mut snapshots: Vec<String> = listing
+            .lines()
+            .map(|l| l.trim_end_matches('/').to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        let doomed = select_prunable(&snapshots, self.keep_daily, self.keep_weekly);
+
+        // Runs that changed nothing have no folder but still need the pruned files
+        let runs = db
+            .list_backup_runs(Some(destination), usize::MAX)
+            .map_err(|e| format!("DB Error: {}", e))?;
+        snapshots.extend(runs.into_iter().filter(|r| !r.pruned).map(|r| r.snapshot));
+
+        let mut pruned = Vec::new();
+        for (snapshot, target) in fold_targets(&snapshots, &doomed) {
+            let from = format!("{}/{}", versions_path, snapshot);
+            let into = format!("{}/{}", versions_path, target);
+            self.provider
+                .execute_rclone(vec!["move", &from, &into, "--ignore-times", "--delete-empty-src-dirs"])?;
+            self.provider.execute_rclone(vec!["purge", &from])?;
+            let _ = db.mark_backup_pruned(destination, &snapshot, &target);
+            pruned.push(snapshot);
+        }
+        Ok(pruned)
+    }
+
+    /// Rebuilds the state right after backup run `key` (id or snapshot name) into `dest`:
+    /// `current/` is copied first, then the versions of every later run are layered
+    /// on top from newest to oldest.
+    ///
+    /// Versions only hold files that a later run overwrote or deleted, so files first
+    /// backed up after the snapshot are restored too, as are files already in `dest`.
+    pub fn restore(&self, db: &Database, key: &str, dest: &str) -> Result<RestoreReport, String> {
+        let run = db
+            .get_backup_run(key)
+            .map_err(|e| format!("DB Error: {}", e))?
+            .ok_or_else(|| format!("Unknown snapshot '{}'. See `vega backup list`.", key))?;
+
+        let later: Vec<BackupRun> = db
+            .list_backup_runs(Some(&run.destination), usize::MAX)
+            .map_err(|e| format!("DB Error: {}", e))?
+            .into_iter()
+            .filter(|r| r.started_at > run.started_at || (r.started_at == run.started_at && r.id > run.id))
+            .collect();
+        let layers = restore_layers(&run, &later)?;
+
+        let mut report = RestoreReport::default();
+        let current = format!("{}/current", run.destination);
+        self.provider.execute_rclone(vec!["copy", &current, dest])?;
+        report.layers += 1;
+
+        // `layers` is newest first, so the oldest layer (closest to the snapshot) wins
+        for snapshot in &layers {
+            let version = format!("{}/versions/{}", run.destination, snapshot);
+            let output = self.provider.execute_rclone_output(vec!["copy", &version, dest])?;
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if output.status.success() {
+                report.layers += 1;
+            } else if !directory_not_found(output.status.code(), &stderr) {
+                return Err(format!("Copying version {} failed: {}", snapshot, last_error(&stderr)));
+            }
+            // Runs that changed nothing have no version folder
+        }
+
+        let event = Event::new(EventKind::Sync, &format!("vega restore {} {}", run.snapshot, dest))
+            .with_result(true, None)
+            .with_payload(json!({
+                "direction": "restore",
+                "source": run.destination,
+                "destination": dest,
+                "snapshot": run.snapshot,
+                "layers": report.layers,
+            }));
+        let _ = db.record_event(&event);
+
+        Ok(report)
+    }
+}
+
+/// Absolute form of a local source, so `.` and `../x` name the directory they point
+/// at in destinations and records. Remote specs and missing paths are kept as given.
+pub fn canonical_source(source: &str) -> String {
+    let remote_spec = source
+        .split_once(':')
+        .is_some_and(|(head, _)| !head.is_empty() && !head.contains('/'));
+    if remote_spec {
+        return source.to_string();
+    }
+    std::fs::canonicalize(source)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| source.to_string())
+}
+
+/// Picks the snapshots to delete: the newest snapshot of each of the last `keep_daily`
+/// days and of each of the last `keep_weekly` ISO weeks survive. Unparsable names are kept.
+pub fn select_prunable(snapshots: &[String], keep_daily: usize, keep_weekly: usize) -> Vec<String> {
+    let mut dated: Vec<(NaiveDateTime, &String)> = snapshots
+        .iter()
+        .filter_map(|s| NaiveDateTime::parse_from_str(s, SNAPSHOT_FORMAT).ok().map(|t| (t, s)))
+        .collect();
+    dated.sort_by_key(|d| std::cmp::Reverse(d.0));
+
+    let mut keep = HashSet::new();
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    for (time, name) in &dated {
+        let day = time.date();
+        if !days.contains(&day) && days.len() < keep_daily {
+            days.insert(day);
+            keep.insert(*name);
+        }
+        let week = (time.iso_week().year(), time.iso_week().week());
+        if !weeks.contains(&week) && weeks.len() < keep_weekly {
+            weeks.insert(week);
+            keep.insert(*name);
+        }
+    }
+
+    dated
+        .into_iter()
+        .filter(|(_, name)| !keep.contains(name))
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+/// Pairs each prunable snapshot with the next newer snapshot that stays, the folder its
+/// files are merged into. Newest first, so when several fold into the same folder the
+/// oldest is moved last and its files win. Snapshots with nothing newer are left alone.
+pub fn fold_targets(snapshots: &[String], doomed: &[String]) -> Vec<(String, String)> {
+    let mut kept: Vec<&String> = snapshots.iter().filter(|s| !doomed.contains(s)).collect();
+    kept.sort();
+    let mut doomed: Vec<&String> = doomed.iter().collect();
+    doomed.sort_by(|a, b| b.cmp(a));
+    doomed
+        .into_iter()
+        .filter_map(|d| kept.iter().find(|k| k.as_str() > d.as_str()).map(|k| (d.clone(), (*k).clone())))
+        .collect()
+}
+
+/// Version folders to layer, newest first, when restoring `run`. `later` holds the runs
+/// after it. Pruned runs are skipped because their files now live in a kept folder, but a
+/// run pruned before folding existed took the only copy of its files with it.
+pub fn restore_layers(run: &BackupRun, later: &[BackupRun]) -> Result<Vec<String>, String> {
+    if run.pruned {
+        return Err(match &run.folded_into {
+            Some(into) => format!(
+                "Snapshot {} was pruned; its files were merged into {}. Restore a kept snapshot instead.",
+                run.snapshot, into
+            ),
+            None => format!("Snapshot {} was pruned.", run.snapshot),
+        });
+    }
+    let mut layers = Vec::new();
+    for newer in later {
+        if !newer.pruned {
+            layers.push(newer.snapshot.clone());
+        } else if newer.folded_into.is_none() {
+            return Err(format!(
+                "Version {} was deleted by an older prune, so snapshot {} can no longer be restored exactly.",
+                newer.snapshot, run.snapshot
+            ));
+        }
+    }
+    Ok(layers)
+}
+
+/// rclone exits with 3 when the source directory is missing.
+fn directory_not_found(code: Option<i32>, stderr: &str) -> bool {
+    code == Some(3) || stderr.to_lowercase().contains("directory not found")
+}
+
+/// Last `stats` object from rclone's JSON log output.
+fn final_stats(stderr: &str) -> Option<Value> {
+    stderr
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Value>(l).ok())
+        .filter_map(|v| v.get("stats").cloned())
+        .next_back()
+}
+
+fn last_error(stderr: &str) -> String {
+    stderr
+        .lines()
+        .rev()
+        .find_map(|l| {
+            let msg = serde_json::from_str::<Value>(l)
+                .ok()
+                .and_then(|v| v["msg"].as_str().map(str::to_string))
+                .unwrap_or_else(|| l.to_string());
+            let msg = msg.trim().to_string();
+            if msg.is_empty() { None } else { Some(msg) }
+        })
+        .unwrap_or_else(|| "rclone failed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_newest_snapshot_per_day_and_week() {
+        let snapshots = names(&[
+            "2024-03-04_090000", // Mon, week 10
+            "2024-03-04_180000",
+            "2024-03-06_120000", // Wed, week 10
+            "2024-03-11_080000", // Mon, week 11
+            "2024-03-12_080000", // Tue, week 11
+            "2024-03-12_200000",
+            "notes",
+        ]);
+        // Days: 03-12 20:00 and 03-11; weeks: 11 (03-12 20:00) and 10 (03-06)
+        let mut doomed = select_prunable(&snapshots, 2, 2);
+        doomed.sort();
+        assert_eq!(doomed, names(&["2024-03-04_090000", "2024-03-04_180000", "2024-03-12_080000"]));
+
+        // Plenty of slots still drop the older snapshot of a day
+        let mut doomed = select_prunable(&snapshots, 7, 4);
+        doomed.sort();
+        assert_eq!(doomed, names(&["2024-03-04_090000", "2024-03-12_080000"]));
+        assert_eq!(select_prunable(&snapshots, 0, 0).len(), 6);
+        assert!(select_prunable(&[], 1, 1).is_empty());
+    }
+
+    type Tree = std::collections::BTreeMap<String, u32>;
+
+    fn tree(files: &[(&str, u32)]) -> Tree {
+        files.iter().map(|(f, v)| (f.to_string(), *v)).collect()
+    }
+
+    fn run(snapshot: &str) -> BackupRun {
+        BackupRun { snapshot: snapshot.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn pruned_versions_fold_forward_and_older_snapshots_still_restore() {
+        let snaps = names(&["2024-03-01_120000", "2024-03-02_120000", "2024-03-03_120000", "2024-03-04_120000"]);
+        let states = [
+            tree(&[("a", 1), ("b", 1)]),
+            tree(&[("a", 2), ("b", 1), ("c", 2)]),
+            tree(&[("a", 3), ("b", 3), ("c", 2)]),
+            tree(&[("a", 3), ("b", 3)]),
+        ];
+
+        // `rclone sync --backup-dir`: overwritten and deleted files move into the run's version
+        let mut current = Tree::new();
+        let mut versions: std::collections::BTreeMap<String, Tree> = Default::default();
+        for (snapshot, state) in snaps.iter().zip(&states) {
+            let moved: Tree = current
+                .iter()
+                .filter(|(f, v)| state.get(*f) != Some(*v))
+                .map(|(f, v)| (f.clone(), *v))
+                .collect();
+            if !moved.is_empty() {
+                versions.insert(snapshot.clone(), moved);
+            }
+            current = state.clone();
+        }
+
+        // Prune the middle snapshot; `rclone move` overwrites what the target already holds
+        let mut runs: Vec<BackupRun> = snaps.iter().map(|s| run(s)).collect();
+        let doomed = vec![snaps[2].clone()];
+        for (snapshot, target) in fold_targets(&snaps, &doomed) {
+            let folded = versions.remove(&snapshot).unwrap();
+            versions.entry(target.clone()).or_default().extend(folded);
+            let r = runs.iter_mut().find(|r| r.snapshot == snapshot).unwrap();
+            r.pruned = true;
+            r.folded_into = Some(target);
+        }
+        assert_eq!(runs[2].folded_into.as_deref(), Some(snaps[3].as_str()));
+
+        for (i, expected) in states.iter().enumerate().take(2) {
+            let later: Vec<BackupRun> = runs[i + 1..].iter().rev().cloned().collect();
+            let mut restored = current.clone();
+            for layer in restore_layers(&runs[i], &later).unwrap() {
+                restored.extend(versions.get(&layer).cloned().unwrap_or_default());
+            }
+            for (file, version) in expected {
+                assert_eq!(restored.get(file), Some(version), "{} in {}", file, snaps[i]);
+            }
+        }
+
+        // The pruned snapshot itself, and anything behind a folder deleted by an old prune, refuse
+        assert!(restore_layers(&runs[2], &[]).is_err());
+        runs[2].folded_into = None;
+        let later: Vec<BackupRun> = runs[1..].iter().rev().cloned().collect();
+        assert!(restore_layers(&runs[0], &later).is_err());
+    }
+
+    #[test]
+    fn oldest_pruned_folder_is_folded_last() {
+        let snaps = names(&["s1", "s2", "s3", "s4"]);
+        assert_eq!(
+            fold_targets(&snaps, &names(&["s1", "s2"])),
+            vec![("s2".to_string(), "s3".to_string()), ("s1".to_string(), "s3".to_string())]
+        );
+        // Nothing newer to fold into: left in place
+        assert!(fold_targets(&snaps, &names(&["s4"])).is_empty());
+        assert!(directory_not_found(Some(3), ""));
+        assert!(directory_not_found(Some(1), "ERROR : directory not found"));
+        assert!(!directory_not_found(Some(1), "Failed to create file system: couldn't find token"));
+    }
+
+    #[test]
+    fn relative_sources_are_named_after_their_directory() {
+        let manager = BackupManager {
+            provider: RcloneProvider::new(String::new()),
+            storage: SmartStorage::with_aliases([("cloud".to_string(), "gdrive:".to_string())].into()),
+            keep_daily: 1,
+            keep_weekly: 1,
+        };
+        let cwd = std::env::current_dir().unwrap();
+        let name = cwd.file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(canonical_source("."), cwd.to_string_lossy());
+        assert_eq!(manager.destination(".", "cloud"), format!("gdrive:vega_backups/{}", name));
+        assert_eq!(manager.destination("./src/", "s3:bucket/"), "s3:bucket/src");
+        assert_eq!(canonical_source("gdrive:photos"), "gdrive:photos");
+    }
+}
@@ -0,0 +1,604 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use crate::system::probe::Probe;
+use std::process::{Command, Output};
+
+/// Compose labels; podman-compose sets the Docker one as well as its own.
+const COMPOSE_LABELS: &[&str] = &["com.docker.compose.project", "io.podman.compose.project"];
+/// Interactive shell that prefers bash and falls back to sh on minimal images.
+const SHELL_PROBE: &str = "command -v bash >/dev/null 2>&1 && exec bash || exec sh";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Runtime {
+    Docker,
+    Podman,
+}
+
+impl Runtime {
+    pub fn binary(&self) -> &'static str {
+        match self {
+            Runtime::Docker => "docker",
+            Runtime::Podman => "podman",
+        }
+    }
+
+    /// Runtimes whose daemon (or, for podman, local storage) answers.
+    pub fn detect() -> Vec<Runtime> {
+        [Runtime::Docker, Runtime::Podman]
+            .into_iter()
+            .filter(|rt| {
+                let probe = match rt {
+                    Runtime::Docker => ["version", "--format", "{{.Server.Version}}"],
+                    Runtime::Podman => ["info", "--format", "{{.Host.OS}}"],
+                };
+                Probe::new(rt.binary())
+                    .args(probe)
+                    .output()
+                    .map(|o| o.status.success())
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    pub fn run(&self, args: &[&str]) -> Result<String, String> {
+        let output = Probe::new(self.binary())
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to execute {}: {}", self.binary(), e))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortMapping {
+    pub host_ip: Option<String>,
+    pub host_port: Option<u16>,
+    pub container_port: u16,
+    pub protocol: String,
+}
+
+impl PortMapping {
+    /// `8080->80/tcp`, or `80/tcp` when the port is not published.
+    pub fn label(&self) -> String {
+        match self.host_port {
+            Some(host) => format!("{}->{}/{}", host, self.container_port, self.protocol),
+            None => format!("{}/{}", self.container_port, self.protocol),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerInfo {
+    pub runtime: Runtime,
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub state: String, // running, exited, created, paused
+    pub status: String,
+    pub ports: Vec<PortMapping>,
+    pub compose_project: Option<String>,
+}
+
+impl ContainerInfo {
+    pub fn is_running(&self) -> bool {
+        self.state == "running"
+    }
+
+    /// One-line summary for the AI prompt and `vega containers`.
+    pub fn describe(&self) -> String {
+        let mut line = format!("{} ({}, {}, {})", self.name, self.image, self.state, self.runtime.binary());
+        if !self.ports.is_empty() {
+            let ports: Vec<String> = self.ports.iter().map(|p| p.label()).collect();
+            line.push_str(&format!(" ports {}", ports.join(", ")));
+        }
+        if let Some(project) = &self.compose_project {
+            line.push_str(&format!(" compose:{}", project));
+        }
+        line
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub runtime: Runtime,
+    pub id: String,
+    pub repository: String,
+    pub tag: String,
+    pub size: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComposeProject {
+    pub name: String,
+    pub runtime: Runtime,
+    pub services: Vec<String>,
+    pub running: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContainerInventory {
+    pub containers: Vec<ContainerInfo>,
+    pub images: Vec<ImageInfo>,
+    pub compose_projects: Vec<ComposeProject>,
+}
+
+impl ContainerInventory {
+    /// Exact name or ID prefix. Running containers win over stopped ones with the same
+    /// name on another runtime.
+    pub fn find_exact(&self, query: &str) -> Option<&ContainerInfo> {
+        let mut ordered: Vec<&ContainerInfo> = self.containers.iter().collect();
+        ordered.sort_by_key(|c| !c.is_running());
+        ordered
+            .iter()
+            .find(|c| c.name == query)
+            .or_else(|| ordered.iter().find(|c| query.len() >= 4 && c.id.starts_with(query)))
+            .copied()
+    }
+
+    /// `find_exact`, then the first name containing `query`.
+    pub fn find(&self, query: &str) -> Option<&ContainerInfo> {
+        self.find_exact(query).or_else(|| {
+            let mut ordered: Vec<&ContainerInfo> = self.containers.iter().collect();
+            ordered.sort_by_key(|c| !c.is_running());
+            ordered.into_iter().find(|c| c.name.contains(query))
+        })
+    }
+
+    /// A running container a natural-language request explicitly runs in: the word after
+    /// `in` or `inside` ("restart nginx in web-1"). A name used as any other word
+    /// ("what is using the cache?") is not a target.
+    pub fn targeted_in(&self, input: &str) -> Option<&ContainerInfo> {
+        let words: Vec<&str> = input
+            .split(|c: char| c.is_whitespace() || c == ',' || c == '\'' || c == '"')
+            .map(|w| w.trim_end_matches(['.', '?', '!', ':', ';', ')']))
+            .filter(|w| !w.is_empty())
+            .collect();
+        words
+            .windows(2)
+            .filter(|pair| pair[0].eq_ignore_ascii_case("in") || pair[0].eq_ignore_ascii_case("inside"))
+            .find_map(|pair| self.containers.iter().find(|c| c.is_running() && c.name == pair[1]))
+    }
+}
+
+pub struct ContainerScanner;
+
+impl ContainerScanner {
+    pub fn scan() -> ContainerInventory {
+        let mut inventory = ContainerInventory::default();
+        for runtime in Runtime::detect() {
+            let containers = Self::containers(runtime);
+            inventory.compose_projects.extend(compose_projects(runtime, &containers));
+            inventory.containers.extend(containers);
+            inventory.images.extend(Self::images(runtime));
+        }
+        inventory
+    }
+
+    /// Running and stopped containers. Docker prints one JSON object per line,
+    /// podman a JSON array; both are accepted from either runtime.
+    pub fn containers(runtime: Runtime) -> Vec<ContainerInfo> {
+        let format = match runtime {
+            Runtime::Docker => "{{json .}}",
+            Runtime::Podman => "json",
+        };
+        runtime
+            .run(&["ps", "-a", "--no-trunc", "--format", format])
+            .map(|out| parse_ps(runtime, &out))
+            .unwrap_or_default()
+    }
+
+    pub fn images(runtime: Runtime) -> Vec<ImageInfo> {
+        let format = match runtime {
+            Runtime::Docker => "{{json .}}",
+            Runtime::Podman => "json",
+        };
+        runtime
+            .run(&["images", "--format", format])
+            .map(|out| parse_images(runtime, &out))
+            .unwrap_or_default()
+    }
+}
+
+/// Runs commands inside one container.
+pub struct ContainerExec {
+    pub runtime: Runtime,
+    pub container: String,
+}
+
+impl ContainerExec {
+    pub fn new(container: &ContainerInfo) -> Self {
+        ContainerExec {
+            runtime: container.runtime,
+            container: container.name.clone(),
+        }
+    }
+
+    /// Opens a shell in a running container; a stopped one is an error with a hint.
+    pub fn connect(container: &ContainerInfo) -> Result<i32, String> {
+        if !container.is_running() {
+            return Err(format!(
+                "Container '{}' is {}. Start it with `{} start {}`.",
+                container.name,
+                container.state,
+                container.runtime.binary(),
+                container.name
+            ));
+        }
+        Self::new(container).shell()
+    }
+
+    /// Interactive shell attached to the terminal; returns its exit code.
+    pub fn shell(&self) -> Result<i32, String> {
+        let status = Command::new(self.runtime.binary())
+            .args(["exec", "-it", &self.container, "sh", "-c", SHELL_PROBE])
+            .status()
+            .map_err(|e| format!("Failed to execute {}: {}", self.runtime.binary(), e))?;
+        Ok(status.code().unwrap_or(-1))
+    }
+
+    pub fn output(&self, command: &str) -> Result<Output, String> {
+        Command::new(self.runtime.binary())
+            .args(["exec", &self.container, "sh", "-c", command])
+            .output()
+            .map_err(|e| format!("Failed to execute {}: {}", self.runtime.binary(), e))
+    }
+
+    /// Like `output`, but runs `argv` directly with no shell inside the container.
+    pub fn output_argv(&self, argv: &[String]) -> Result<Output, String> {
+        Command::new(self.runtime.binary())
+            .args(["exec", &self.container])
+            .args(argv)
+            .output()
+            .map_err(|e| format!("Failed to execute {}: {}", self.runtime.binary(), e))
+    }
+}
+
+/// Top-level JSON values from either a JSON array or JSON lines.
+fn json_records(out: &str) -> Vec<Value> {
+    let trimmed = out.trim();
+    if trimmed.starts_with('[') {
+        return serde_json::from_str::<Vec<Value>>(trimmed).unwrap_or_default();
+    }
+    trimmed
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Value>(l.trim()).ok())
+        .collect()
+}
+
+/// First of `keys` present as a string.
+fn str_field(v: &Value, keys: &[&str]) -> String {
+    keys.iter()
+        .find_map(|k| v[*k].as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+pub fn parse_ps(runtime: Runtime, out: &str) -> Vec<ContainerInfo> {
+    json_records(out)
+        .iter()
+        .map(|v| {
+            // Docker: "Names": "web-1"; podman: "Names": ["web-1"]
+            let name = match &v["Names"] {
+                Value::Array(names) => names.first().and_then(|n| n.as_str()).unwrap_or_default().to_string(),
+                other => other.as_str().unwrap_or_default().split(',').next().unwrap_or_default().to_string(),
+            };
+            let ports = match &v["Ports"] {
+                Value::Array(ports) => ports.iter().flat_map(podman_ports).collect(),
+                other => parse_docker_ports(other.as_str().unwrap_or_default()),
+            };
+            let labels = match &v["Labels"] {
+                Value::Object(map) => map
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.as_str().unwrap_or_default().to_string()))
+                    .collect(),
+                other => parse_labels(other.as_str().unwrap_or_default()),
+            };
+            ContainerInfo {
+                runtime,
+                id: str_field(v, &["ID", "Id"]),
+                name,
+                image: str_field(v, &["Image"]),
+                state: str_field(v, &["State"]).to_lowercase(),
+                status: str_field(v, &["Status"]),
+                ports,
+                compose_project: COMPOSE_LABELS
+                    .iter()
+                    .find_map(|l| labels.get(*l))
+                    .filter(|p| !p.is_empty())
+                    .cloned(),
+            }
+        })
+        .filter(|c| !c.name.is_empty())
+        .collect()
+}
+
+/// Docker's `Ports` column: `0.0.0.0:8080->80/tcp, :::8080->80/tcp, 443/tcp`.
+/// The IPv6 duplicate of a published port is dropped.
+pub fn parse_docker_ports(ports: &str) -> Vec<PortMapping> {
+    let mut mappings: Vec<PortMapping> = Vec::new();
+    for entry in ports.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (binding, target) = match entry.split_once("->") {
+            Some((b, t)) => (Some(b), t),
+            None => (None, entry),
+        };
+        let (port, protocol) = target.split_once('/').unwrap_or((target, "tcp"));
+        // Ranges (8000-8010/tcp) keep their first port
+        let container_port = match port.split('-').next().and_then(|p| p.parse().ok()) {
+            Some(p) => p,
+            None => continue,
+        };
+        let (host_ip, host_port) = match binding.and_then(|b| b.rsplit_once(':')) {
+            Some((ip, port)) => (Some(ip.to_string()), port.split('-').next().and_then(|p| p.parse().ok())),
+            None => (None, None),
+        };
+        let mapping = PortMapping {
+            host_ip,
+            host_port,
+            container_port,
+            protocol: protocol.to_string(),
+        };
+        let duplicate = mappings.iter().any(|m| {
+            m.host_port == mapping.host_port && m.container_port == mapping.container_port && m.protocol == mapping.protocol
+        });
+        if !duplicate {
+            mappings.push(mapping);
+        }
+    }
+    mappings
+}
+
+/// Podman's `Ports` entries: `{"host_ip","container_port","host_port","range","protocol"}`.
+fn podman_ports(v: &Value) -> Vec<PortMapping> {
+    let container_port = match v["container_port"].as_u64() {
+        Some(p) => p as u16,
+        None => return Vec::new(),
+    };
+    vec![PortMapping {
+        host_ip: v["host_ip"].as_str().filter(|ip| !ip.is_empty()).map(str::to_string),
+        host_port: v["host_port"].as_u64().filter(|p| *p > 0).map(|p| p as u16),
+        container_port,
+        protocol: v["protocol"].as_str().unwrap_or("tcp").to_string(),
+    }]
+}
+
+/// Docker's `Labels` column: `key=value,key=value`.
+fn parse_labels(labels: &str) -> BTreeMap<String, String> {
+    labels
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+pub fn parse_images(runtime: Runtime, out: &str) -> Vec<ImageInfo> {
+    json_records(out)
+        .iter()
+        .flat_map(|v| {
+            let id = str_field(v, &["ID", "Id"]);
+            let id = id.trim_start_matches("sha256:").chars().take(12).collect::<String>();
+            // Podman: "Size": bytes and "Names": ["docker.io/library/nginx:latest"]
+            let size = match &v["Size"] {
+                Value::Number(n) => format!("{:.1}MB", n.as_f64().unwrap_or(0.0) / 1_000_000.0),
+                other => other.as_str().unwrap_or_default().to_string(),
+            };
+            let refs: Vec<(String, String)> = match &v["Names"] {
+                Value::Array(names) => names
+                    .iter()
+                    .filter_map(|n| n.as_str())
+                    .map(|n| match n.rsplit_once(':') {
+                        Some((repo, tag)) if !tag.contains('/') => (repo.to_string(), tag.to_string()),
+                        _ => (n.to_string(), "latest".to_string()),
+                    })
+                    .collect(),
+                _ => vec![(str_field(v, &["Repository"]), str_field(v, &["Tag"]))],
+            };
+            let refs = if refs.is_empty() {
+                vec![("<none>".to_string(), "<none>".to_string())]
+            } else {
+                refs
+            };
+            refs.into_iter()
+                .map(|(repository, tag)| ImageInfo {
+                    runtime,
+                    id: id.clone(),
+                    repository,
+                    tag,
+                    size: size.clone(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Groups containers by their compose project label.
+pub fn compose_projects(runtime: Runtime, containers: &[ContainerInfo]) -> Vec<ComposeProject> {
+    let mut projects: BTreeMap<String, ComposeProject> = BTreeMap::new();
+    for c in containers {
+        if let Some(name) = &c.compose_project {
+            let project = projects.entry(name.clone()).or_insert_with(|| ComposeProject {
+                name: name.clone(),
+                runtime,
+                services: Vec::new(),
+                running: 0,
+            });
+            project.services.push(c.name.clone());
+            if c.is_running() {
+                project.running += 1;
+            }
+        }
+    }
+    projects.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCKER_PS: &str = r#"{"Command":"\"/docker-entrypoint.…\"","ID":"a1b2c3d4e5f60718293a4b5c6d7e8f90","Image":"nginx:1.25","Labels":"com.docker.compose.project=shop,com.docker.compose.service=web","Names":"shop-web-1","Ports":"0.0.0.0:8080->80/tcp, :::8080->80/tcp, 443/tcp","State":"running","Status":"Up 2 hours"}
+{"Command":"\"docker-entrypoint.s…\"","ID":"0f1e2d3c4b5a69788796a5b4c3d2e1f0","Image":"postgres:16","Labels":"com.docker.compose.project=shop","Names":"shop-db-1","Ports":"","State":"exited","Status":"Exited (0) 3 days ago"}
+{"ID":"ffff0000ffff0000","Image":"redis","Labels":"","Names":"cache","Ports":"127.0.0.1:6379->6379/tcp","State":"running","Status":"Up 5 minutes"}
+"#;
+
+    const PODMAN_PS: &str = r#"[
+  {
+    "AutoRemove": false,
+    "Command": ["nginx", "-g", "daemon off;"],
+    "Exited": false,
+    "Id": "3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a",
+    "Image": "docker.io/library/nginx:latest",
+    "Labels": {"io.podman.compose.project": "blog", "com.docker.compose.project": "blog"},
+    "Names": ["blog_web_1"],
+    "Ports": [
+      {"host_ip": "", "container_port": 80, "host_port": 8081, "range": 1, "protocol": "tcp"},
+      {"host_ip": "127.0.0.1", "container_port": 9090, "host_port": 9090, "range": 1, "protocol": "udp"}
+    ],
+    "State": "running",
+    "Status": "Up 10 minutes"
+  },
+  {
+    "Id": "9a8b7c6d5e4f",
+    "Image": "docker.io/library/alpine:latest",
+    "Labels": null,
+    "Names": ["toolbox"],
+    "Ports": null,
+    "State": "Exited",
+    "Status": "Exited (1) 2 hours ago"
+  }
+]"#;
+
+    #[test]
+    fn parses_docker_json_lines() {
+        let containers = parse_ps(Runtime::Docker, DOCKER_PS);
+        assert_eq!(containers.len(), 3);
+
+        let web = &containers[0];
+        assert_eq!(web.name, "shop-web-1");
+        assert_eq!(web.image, "nginx:1.25");
+        assert!(web.is_running());
+        assert_eq!(web.compose_project.as_deref(), Some("shop"));
+        let ports: Vec<String> = web.ports.iter().map(|p| p.label()).collect();
+        assert_eq!(ports, vec!["8080->80/tcp", "443/tcp"]);
+
+        assert_eq!(containers[1].state, "exited");
+        assert!(containers[1].ports.is_empty());
+        assert_eq!(containers[2].compose_project, None);
+        assert_eq!(containers[2].ports[0].host_ip.as_deref(), Some("127.0.0.1"));
+    }
+
+    #[test]
+    fn parses_podman_json_array() {
+        let containers = parse_ps(Runtime::Podman, PODMAN_PS);
+        assert_eq!(containers.len(), 2);
+
+        let web = &containers[0];
+        assert_eq!(web.name, "blog_web_1");
+        assert!(web.id.starts_with("3f2a1b0c"));
+        assert_eq!(web.compose_project.as_deref(), Some("blog"));
+        assert_eq!(
+            web.ports,
+            vec![
+                PortMapping { host_ip: None, host_port: Some(8081), container_port: 80, protocol: "tcp".to_string() },
+                PortMapping {
+                    host_ip: Some("127.0.0.1".to_string()),
+                    host_port: Some(9090),
+                    container_port: 9090,
+                    protocol: "udp".to_string()
+                },
+            ]
+        );
+
+        // Null labels and ports, capitalized state
+        let toolbox = &containers[1];
+        assert_eq!(toolbox.state, "exited");
+        assert!(toolbox.ports.is_empty());
+        assert_eq!(toolbox.compose_project, None);
+
+        // Either output format is accepted from either runtime
+        assert_eq!(parse_ps(Runtime::Docker, PODMAN_PS).len(), 2);
+        assert!(parse_ps(Runtime::Docker, "").is_empty());
+        assert!(parse_ps(Runtime::Docker, "Error: cannot connect").is_empty());
+    }
+
+    #[test]
+    fn parses_docker_port_column() {
+        let ports = parse_docker_ports("0.0.0.0:8000-8002->8000-8002/tcp, [::]:8000-8002->8000-8002/tcp, 53/udp, junk/tcp");
+        assert_eq!(
+            ports,
+            vec![
+                PortMapping {
+                    host_ip: Some("0.0.0.0".to_string()),
+                    host_port: Some(8000),
+                    container_port: 8000,
+                    protocol: "tcp".to_string()
+                },
+                PortMapping { host_ip: None, host_port: None, container_port: 53, protocol: "udp".to_string() },
+            ]
+        );
+        assert!(parse_docker_ports("").is_empty());
+    }
+
+    #[test]
+    fn parses_images_from_both_runtimes() {
+        let docker = parse_images(
+            Runtime::Docker,
+            r#"{"ID":"a6bd71f48f68","Repository":"nginx","Tag":"1.25","Size":"187MB"}
+{"ID":"0123456789ab","Repository":"<none>","Tag":"<none>","Size":"5MB"}"#,
+        );
+        assert_eq!(docker.len(), 2);
+        assert_eq!((docker[0].repository.as_str(), docker[0].tag.as_str()), ("nginx", "1.25"));
+        assert_eq!(docker[0].size, "187MB");
+
+        let podman = parse_images(
+            Runtime::Podman,
+            r#"[{"Id":"sha256:a6bd71f48f6839d9faae1f29d3babef831e76bc213107682c5cc80f0cbb30866","Names":["docker.io/library/nginx:latest","localhost:5000/nginx:1.25","localhost:5000/base"],"Size":187000000},
+                {"Id":"sha256:0123456789abcdef","Names":[],"Size":5000000}]"#,
+        );
+        assert_eq!(podman.len(), 4);
+        assert!(podman.iter().all(|i| i.id.len() == 12));
+        assert_eq!(podman[0].repository, "docker.io/library/nginx");
+        assert_eq!(podman[1].repository, "localhost:5000/nginx");
+        assert_eq!(podman[1].tag, "1.25");
+        // A registry port is not a tag
+        assert_eq!((podman[2].repository.as_str(), podman[2].tag.as_str()), ("localhost:5000/base", "latest"));
+        assert_eq!(podman[0].size, "187.0MB");
+        assert_eq!(podman[3].repository, "<none>");
+    }
+
+    #[test]
+    fn groups_compose_projects() {
+        let containers = parse_ps(Runtime::Docker, DOCKER_PS);
+        let projects = compose_projects(Runtime::Docker, &containers);
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].name, "shop");
+        assert_eq!(projects[0].services, vec!["shop-web-1", "shop-db-1"]);
+        assert_eq!(projects[0].running, 1);
+    }
+
+    #[test]
+    fn targets_only_containers_named_after_in() {
+        let mut containers = parse_ps(Runtime::Docker, DOCKER_PS);
+        containers.extend(parse_ps(Runtime::Podman, PODMAN_PS));
+        let inventory = ContainerInventory { containers, ..Default::default() };
+
+        let name = |c: Option<&ContainerInfo>| c.map(|c| c.name.clone());
+        assert_eq!(name(inventory.targeted_in("restart nginx in shop-web-1")), Some("shop-web-1".to_string()));
+        assert_eq!(name(inventory.targeted_in("flush the queue inside 'cache'.")), Some("cache".to_string()));
+        assert_eq!(name(inventory.targeted_in("update packages In blog_web_1")), Some("blog_web_1".to_string()));
+        // Names used as ordinary words do not retarget the request
+        assert_eq!(name(inventory.targeted_in("what is using the cache?")), None);
+        assert_eq!(name(inventory.targeted_in("flush 'cache', then check blog_web_1")), None);
+        // A name inside a longer word is not a target
+        assert_eq!(name(inventory.targeted_in("clear files in the cachedir and in shop-web-10")), None);
+        // Stopped containers are only found by explicit lookups
+        assert_eq!(name(inventory.targeted_in("start postgres in shop-db-1")), None);
+        assert_eq!(name(inventory.find("shop-db")), Some("shop-db-1".to_string()));
+        assert_eq!(name(inventory.find_exact("ffff0000")), Some("cache".to_string()));
+        assert_eq!(name(inventory.find_exact("fff")), None);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::remote::rclone::RcloneProvider;
 use crate::remote::RemoteMasker;
-use std::fs;
+use crate::system::probe;
 use std::path::PathBuf;
 
 pub struct Discovery;
@@ -40,7 +40,7 @@ impl Discovery {
                     // But for discovery phase, simple is better.
                     // We'll just look for a few indicators in the root
                     if let Ok(output) = provider
-                        .execute_rclone(vec!["lsjson", &format!("{}:", provider.remote_name)])
+                        .query_rclone(vec!["lsjson", &format!("{}:", provider.remote_name)])
                     {
                         if let Ok(items) = serde_json::from_str::<serde_json::Value>(&output) {
                             if let Some(array) = items.as_array() {
@@ -81,8 +81,8 @@ impl Discovery {
     pub fn parse_ssh_config() -> Option<Vec<String>> {
         if let Ok(home) = std::env::var("HOME") {
             let config_path = PathBuf::from(&home).join(".ssh/config");
-            if config_path.exists() {
-                if let Ok(content) = fs::read_to_string(config_path) {
+            if probe::exists(&config_path) {
+                if let Ok(content) = probe::read_to_string(&config_path) {
                     let mut hosts = Vec::new();
                     for line in content.lines() {
                         let line: &str = line.trim();
@@ -103,10 +103,28 @@ impl Discovery {
     pub fn detect_plugin_manager() -> Option<String> {
         if let Ok(home) = std::env::var("HOME") {
             let lazy_lock = PathBuf::from(&home).join(".config/nvim/lazy-lock.json");
-            if lazy_lock.exists() {
+            if probe::exists(&lazy_lock) {
                 return Some("lazy.nvim".to_string());
             }
         }
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::probe::{with_bundle, Bundle};
+
+    #[test]
+    fn ssh_hosts_and_plugin_manager_from_capture() {
+        if std::env::var("HOME").map(|h| h.is_empty() || h == "/").unwrap_or(true) {
+            return;
+        }
+        let bundle = Bundle::from_json(include_str!("../../tests/fixtures/fedora-workstation.json")).unwrap();
+        with_bundle(bundle, || {
+            assert_eq!(Discovery::parse_ssh_config(), Some(vec!["nas".to_string(), "build-box".to_string()]));
+            assert_eq!(Discovery::detect_plugin_manager().as_deref(), Some("lazy.nvim"));
+        });
+    }
+}
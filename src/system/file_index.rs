@@ -0,0 +1,466 @@
+use crate::connection::ssh::SshConnection;
+use crate::knowledge::{KnowledgeBase, KnowledgeEntry};
+use crate::remote::rclone::RcloneProvider;
+use crate::storage::db::{Database, IndexOrigin, IndexedFile};
+use crate::storage::events::now_secs;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Backends that return hashes from their listing API; elsewhere `--hash` would read every file.
+const CHEAP_HASH_BACKENDS: &[&str] = &["drive", "s3", "b2", "dropbox", "onedrive", "box", "pcloud"];
+
+/// Metadata key holding when the last background crawl was started.
+const LAST_SPAWN_KEY: &str = "file_index_last_spawn";
+
+pub const DEFAULT_REFRESH_HOURS: i64 = 24;
+pub const DEFAULT_FULL_CRAWL_DAYS: i64 = 7;
+
+/// Metadata key prefix (`file_index_full_crawl:<origin>`) for the last crawl that listed every folder.
+const FULL_CRAWL_KEY: &str = "file_index_full_crawl";
+
+/// Words dropped from "where is my thesis pdf"-style questions before searching.
+const STOPWORDS: &[&str] = &[
+    "where", "is", "are", "my", "the", "a", "an", "find", "locate", "file", "files", "folder", "of", "in", "on",
+    "for", "me", "show", "search", "어디", "어디에", "어디있어", "어딨어", "어디야", "있어", "있지", "내", "파일", "찾아", "찾아줘",
+];
+
+/// Remote listing `find` runs over SSH: home directory, hidden entries pruned.
+/// Needs GNU `find`; a non-zero exit (unreadable folders, no `-printf`) fails the crawl.
+const HOST_LIST_CMD: &str = "cd && find . -mindepth 1 -maxdepth 8 -xdev \\( -name '.*' -prune \\) -o -printf '%P\\t%s\\t%T@\\t%y\\n'";
+
+#[derive(Debug, Default)]
+pub struct CrawlReport {
+    pub origin: String,
+    pub label: String,
+    pub entries: usize,
+    pub changed: usize,
+    pub removed: usize,
+    pub failed_dirs: Vec<String>,
+    /// Top-level folders not re-listed because their modification time is unchanged.
+    pub unchanged_dirs: Vec<String>,
+    pub duration_ms: i64,
+}
+
+/// Crawls rclone remotes and Knowledge Base hosts into the `file_index` table,
+/// so lookups work in milliseconds and offline.
+///
+/// Remotes are listed one top-level folder at a time with `lsjson -R`, each folder
+/// committed on its own; a folder that fails keeps its previous rows. Folders whose
+/// modification time has not changed since the last crawl are skipped, except on a full
+/// crawl every `full_crawl_days`. Only new or changed entries are rewritten, and entries
+/// missing from a complete listing are removed.
+pub struct FileIndexer {
+    quiet: bool,
+}
+
+impl FileIndexer {
+    pub fn new(quiet: bool) -> Self {
+        FileIndexer { quiet }
+    }
+
+    fn progress(&self, msg: &str) {
+        if !self.quiet {
+            println!("{}", msg);
+        }
+    }
+
+    /// Crawls every rclone remote and, unless disabled in `[index]`, every KB host.
+    pub async fn crawl_all(&self, db: &Database, kb: &KnowledgeBase) -> Vec<Result<CrawlReport, String>> {
+        let mut reports = Vec::new();
+        for remote in RcloneProvider::list_remotes().unwrap_or_default() {
+            reports.push(self.crawl_remote(db, &remote));
+        }
+        if index_config().hosts.unwrap_or(true) {
+            let mut names: Vec<&String> = kb.targets.keys().collect();
+            names.sort();
+            for name in names {
+                if let Some(entry) = kb.get(name) {
+                    if entry.protocol == "ssh" {
+                        reports.push(self.crawl_host(db, name, entry).await);
+                    }
+                }
+            }
+        }
+        let _ = db.set_metadata("file_index_last_crawl", &now_secs().to_string());
+        reports
+    }
+
+    pub fn crawl_remote(&self, db: &Database, remote: &str) -> Result<CrawlReport, String> {
+        let name = remote.trim_end_matches(':');
+        let origin = format!("{}:", name);
+        let provider = RcloneProvider::new(name.to_string());
+        let backend = RcloneProvider::backend_types().get(name).cloned().unwrap_or_default();
+        let hash = CHEAP_HASH_BACKENDS.contains(&backend.as_str());
+        let started = Instant::now();
+        let seen = now_secs();
+        self.progress(&format!("🔎 Indexing {} ({})...", origin, if backend.is_empty() { "?" } else { &backend }));
+
+        let mut report = CrawlReport {
+            origin: origin.clone(),
+            label: name.to_string(),
+            ..Default::default()
+        };
+
+        // 1. Top level, then each folder recursively in its own transaction
+        let top = match self.lsjson(&provider, &origin, "", false, hash) {
+            Ok(top) => top,
+            Err(e) => {
+                self.record_origin(db, &report, "rclone", Some(&e));
+                return Err(format!("{}: {}", origin, e));
+            }
+        };
+        // Folder times are read before the top level overwrites them
+        let full_key = format!("{}:{}", FULL_CRAWL_KEY, origin);
+        let last_full = db
+            .get_metadata(&full_key)
+            .ok()
+            .flatten()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(0);
+        let full_days = index_config().full_crawl_days.unwrap_or(DEFAULT_FULL_CRAWL_DAYS);
+        if seen - last_full < full_days * 86400 {
+            let previous = db.indexed_top_dirs(&origin).map_err(|e| e.to_string())?;
+            report.unchanged_dirs = unchanged_dirs(&top, &previous);
+        }
+        report.changed += db.upsert_indexed_files(&top, seen).map_err(|e| e.to_string())?;
+
+        for dir in top.iter().filter(|f| f.is_dir && !report.unchanged_dirs.contains(&f.path)) {
+            match self.lsjson(&provider, &origin, &dir.path, true, hash) {
+                Ok(files) => {
+                    report.changed += db.upsert_indexed_files(&files, seen).map_err(|e| e.to_string())?;
+                    self.progress(&format!("   📁 {} ({} entries)", dir.path, files.len()));
+                }
+                Err(e) => {
+                    self.progress(&format!("   ⚠️ {}: {}", dir.path, e.trim()));
+                    report.failed_dirs.push(dir.path.clone());
+                }
+            }
+        }
+
+        // 2. Whatever this listing no longer shows is gone
+        let keep: Vec<String> = report.failed_dirs.iter().chain(&report.unchanged_dirs).cloned().collect();
+        report.removed = db.prune_file_index(&origin, seen, &keep).map_err(|e| e.to_string())?;
+        report.entries = db.count_indexed_files(&origin).map_err(|e| e.to_string())? as usize;
+        if keep.is_empty() {
+            let _ = db.set_metadata(&full_key, &seen.to_string());
+        }
+        report.duration_ms = started.elapsed().as_millis() as i64;
+        let error = if report.failed_dirs.is_empty() {
+            None
+        } else {
+            Some(format!("{} folders failed", report.failed_dirs.len()))
+        };
+        self.record_origin(db, &report, "rclone", error.as_deref());
+        Ok(report)
+    }
+
+    fn lsjson(&self, provider: &RcloneProvider, origin: &str, dir: &str, recursive: bool, hash: bool) -> Result<Vec<IndexedFile>, String> {
+        let target = format!("{}{}", origin, dir);
+        let mut args = vec!["lsjson", "--no-mimetype", &target];
+        if recursive {
+            args.push("-R");
+        }
+        if hash {
+            args.push("--hash");
+        }
+        let output = provider.query_rclone(args)?;
+        parse_lsjson(origin, dir, &output)
+    }
+
+    pub async fn crawl_host(&self, db: &Database, name: &str, entry: &KnowledgeEntry) -> Result<CrawlReport, String> {
+        let target = match &entry.user {
+            Some(user) => format!("{}@{}", user, entry.ip),
+            None => entry.ip.clone(),
+        };
+        let origin = format!("{}:", target);
+        let started = Instant::now();
+        let seen = now_secs();
+        self.progress(&format!("🔎 Indexing host {} ({})...", name, target));
+
+        let mut report = CrawlReport {
+            origin: origin.clone(),
+            label: name.to_string(),
+            ..Default::default()
+        };
+        let output = match SshConnection::execute_remote_async(&target, HOST_LIST_CMD).await {
+            Ok(output) => output,
+            Err(e) => {
+                // `find` reports every unreadable folder; the last line is enough
+                let e = e.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("host unreachable").to_string();
+                self.record_origin(db, &report, "host", Some(&e));
+                return Err(format!("{}: {}", name, e));
+            }
+        };
+        let files = parse_find(&origin, &output);
+        if files.is_empty() {
+            // An empty home is far less likely than a listing that silently failed
+            let e = "empty listing; previous entries kept";
+            self.record_origin(db, &report, "host", Some(e));
+            return Err(format!("{}: {}", name, e));
+        }
+        report.entries = files.len();
+        report.changed = db.upsert_indexed_files(&files, seen).map_err(|e| e.to_string())?;
+        report.removed = db.prune_file_index(&origin, seen, &[]).map_err(|e| e.to_string())?;
+        report.duration_ms = started.elapsed().as_millis() as i64;
+        self.record_origin(db, &report, "host", None);
+        Ok(report)
+    }
+
+    fn record_origin(&self, db: &Database, report: &CrawlReport, kind: &str, error: Option<&str>) {
+        let _ = db.record_index_origin(&IndexOrigin {
+            origin: report.origin.clone(),
+            label: report.label.clone(),
+            kind: kind.to_string(),
+            last_crawl: Some(now_secs()),
+            entries: report.entries as i64,
+            duration_ms: Some(report.duration_ms),
+            error: error.map(str::to_string),
+        });
+    }
+}
+
+/// Parses `rclone lsjson` output listed from `origin + dir`; paths are made relative to the origin.
+pub fn parse_lsjson(origin: &str, dir: &str, json: &str) -> Result<Vec<IndexedFile>, String> {
+    let items: Vec<Value> = serde_json::from_str(json).map_err(|e| format!("Failed to parse lsjson: {}", e))?;
+    let prefix = dir.trim_end_matches('/');
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let path = item["Path"].as_str()?;
+            let path = if prefix.is_empty() {
+                path.to_string()
+            } else {
+                format!("{}/{}", prefix, path)
+            };
+            let is_dir = item["IsDir"].as_bool().unwrap_or(false);
+            let hash = item["Hashes"].as_object().and_then(|hashes| {
+                let mut kinds: Vec<&String> = hashes.keys().collect();
+                kinds.sort();
+                kinds
+                    .first()
+                    .and_then(|k| hashes[*k].as_str().map(|v| format!("{}:{}", k, v)))
+            });
+            Some(IndexedFile {
+                origin: origin.to_string(),
+                path,
+                size: if is_dir { None } else { item["Size"].as_i64() },
+                mtime: item["ModTime"]
+                    .as_str()
+                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                    .map(|t| t.timestamp()),
+                hash,
+                is_dir,
+            })
+        })
+        .collect())
+}
+
+/// Top-level folders of a listing whose modification time matches the one recorded by
+/// the last crawl. New folders and folders without a time are always listed.
+pub fn unchanged_dirs(top: &[IndexedFile], previous: &HashMap<String, Option<i64>>) -> Vec<String> {
+    top.iter()
+        .filter(|f| f.is_dir && f.mtime.is_some())
+        .filter(|f| previous.get(&f.path) == Some(&f.mtime))
+        .map(|f| f.path.clone())
+        .collect()
+}
+
+/// Parses `find -printf '%P\t%s\t%T@\t%y\n'` lines.
+pub fn parse_find(origin: &str, output: &str) -> Vec<IndexedFile> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let path = fields.next().filter(|p| !p.is_empty())?;
+            let size = fields.next().and_then(|s| s.parse::<i64>().ok());
+            let mtime = fields
+                .next()
+                .and_then(|t| t.split('.').next())
+                .and_then(|t| t.parse::<i64>().ok());
+            let is_dir = fields.next() == Some("d");
+            Some(IndexedFile {
+                origin: origin.to_string(),
+                path: path.to_string(),
+                size: if is_dir { None } else { size },
+                mtime,
+                hash: None,
+                is_dir,
+            })
+        })
+        .collect()
+}
+
+/// Search terms from a free-form question: lowercased, punctuation and stopwords removed.
+pub fn query_terms(input: &str) -> Vec<String> {
+    input
+        .split(|c: char| c.is_whitespace() || c == '?' || c == ',' || c == '\'' || c == '"')
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '.' && c != '_' && c != '-').to_lowercase())
+        .filter(|w| !w.is_empty() && !STOPWORDS.contains(&w.as_str()))
+        .collect()
+}
+
+/// Paths under `origin` matching `query`, or None when `origin` has never been indexed.
+pub fn search_origin(db: &Database, origin: &str, query: &str) -> Option<Vec<String>> {
+    if db.count_indexed_files(origin).ok()? == 0 {
+        return None;
+    }
+    let hits = db.search_files(&query_terms(query), Some(origin), 200).ok()?;
+    Some(hits.into_iter().map(|f| f.path).collect())
+}
+
+/// Prints index matches for `query` across all origins; returns how many were shown.
+pub fn show_matches(db: &Database, query: &str, limit: usize) -> usize {
+    let terms = query_terms(query);
+    if terms.is_empty() {
+        println!("⚠️  Nothing to search for.");
+        return 0;
+    }
+    let hits = db.search_files(&terms, None, limit).unwrap_or_default();
+    if hits.is_empty() {
+        let indexed = db.list_index_origins().map(|o| o.len()).unwrap_or(0);
+        if indexed == 0 {
+            println!("ℹ️  The file index is empty. Run `vega index` to crawl your remotes and hosts.");
+        } else {
+            println!("🔍 No indexed files match '{}' ({} sources indexed).", terms.join(" "), indexed);
+        }
+        return 0;
+    }
+    println!("🔍 {} matches for '{}':", hits.len(), terms.join(" "));
+    for f in &hits {
+        let icon = if f.is_dir { "📁" } else { "📄" };
+        let size = f
+            .size
+            .map(|b| format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)))
+            .unwrap_or_default();
+        let date = f
+            .mtime
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        println!("   {} {}{}  {}  {}", icon, f.origin, f.path, size, date);
+    }
+    hits.len()
+}
+
+fn index_config() -> crate::config::IndexConfig {
+    crate::config::VegaConfig::load(&crate::init::get_config_path().to_string_lossy())
+        .ok()
+        .and_then(|c| c.index)
+        .unwrap_or_default()
+}
+
+/// Starts `vega index --quiet` as a detached process when `[index] background` is on and
+/// the last crawl is older than `refresh_hours`. Returns true when a crawl was started.
+pub fn maybe_refresh_in_background(db: &Database) -> bool {
+    let config = index_config();
+    let refresh_hours = config.refresh_hours.unwrap_or(DEFAULT_REFRESH_HOURS);
+    if !config.background.unwrap_or(false) || refresh_hours <= 0 {
+        return false;
+    }
+    let last_spawn = db
+        .get_metadata(LAST_SPAWN_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(0);
+    let now = now_secs();
+    if now - last_spawn < refresh_hours * 3600 {
+        return false;
+    }
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(_) => return false,
+    };
+    // Own process group, so Ctrl-C in this terminal does not stop the crawl
+    use std::os::unix::process::CommandExt;
+    let spawned = std::process::Command::new(exe)
+        .args(["index", "--quiet"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .process_group(0)
+        .spawn()
+        .is_ok();
+    if spawned {
+        let _ = db.set_metadata(LAST_SPAWN_KEY, &now.to_string());
+    }
+    spawned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    // `rclone lsjson --no-mimetype --hash gdrive:Photos` (Drive reports md5, sha1 and sha256)
+    const LSJSON: &str = r#"[
+{"Path":"2024","Name":"2024","Size":-1,"ModTime":"2024-03-01T10:00:00.000Z","IsDir":true,"ID":"1AbC"},
+{"Path":"2024/beach.jpg","Name":"beach.jpg","Size":2483190,"ModTime":"2024-02-28T09:30:12.123Z","IsDir":false,"Hashes":{"sha256":"9f86","md5":"5d41","sha1":"aaf4"},"ID":"1XyZ"},
+{"Path":"notes.txt","Name":"notes.txt","Size":12,"ModTime":"2023-12-31T23:59:59Z","IsDir":false}
+]"#;
+
+    // `find -printf '%P\t%s\t%T@\t%y\n'` from GNU findutils
+    const FIND: &str = "Documents\t4096\t1709287200.1234567890\td\n\
+                        Documents/thesis-final.pdf\t1048576\t1709112612.0000000000\tf\n\
+                        \t0\t0\tf\n\
+                        bin/tool\t88\tgarbage\tf\n";
+
+    #[test]
+    fn lsjson_paths_are_relative_to_the_origin() {
+        let files = parse_lsjson("gdrive:", "Photos/", LSJSON).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].path, "Photos/2024");
+        assert!(files[0].is_dir && files[0].size.is_none());
+        assert_eq!(files[0].mtime, Some(1709287200));
+        // The alphabetically first hash kind is kept
+        assert_eq!(files[1].hash.as_deref(), Some("md5:5d41"));
+        assert_eq!(files[1].size, Some(2483190));
+        assert_eq!(parse_lsjson("gdrive:", "", LSJSON).unwrap()[2].path, "notes.txt");
+        assert!(parse_lsjson("gdrive:", "", "Failed to lsjson: directory not found").is_err());
+    }
+
+    #[test]
+    fn find_lines_parse_and_blank_paths_are_skipped() {
+        let files = parse_find("ci@10.0.0.12:", FIND);
+        assert_eq!(files.len(), 3);
+        assert!(files[0].is_dir && files[0].size.is_none());
+        assert_eq!(files[0].mtime, Some(1709287200));
+        assert_eq!(files[1].path, "Documents/thesis-final.pdf");
+        assert_eq!(files[1].size, Some(1048576));
+        assert_eq!(files[2].mtime, None);
+        assert!(parse_find("host:", "").is_empty());
+    }
+
+    #[test]
+    fn questions_reduce_to_search_terms() {
+        assert_eq!(query_terms("Where is my thesis PDF?"), vec!["thesis", "pdf"]);
+        assert_eq!(query_terms("find 'report_2024.xlsx', please"), vec!["report_2024.xlsx", "please"]);
+        assert_eq!(query_terms("내 논문 파일 어디있어"), vec!["논문"]);
+        assert!(query_terms("where is the file?").is_empty());
+    }
+
+    #[test]
+    fn origin_search_distinguishes_unindexed_from_no_match() {
+        let db = Database::open_file(Path::new(":memory:")).unwrap();
+        assert_eq!(search_origin(&db, "ci@10.0.0.12:", "thesis"), None);
+
+        db.upsert_indexed_files(&parse_find("ci@10.0.0.12:", FIND), 1).unwrap();
+        assert_eq!(
+            search_origin(&db, "ci@10.0.0.12:", "where is my thesis"),
+            Some(vec!["Documents/thesis-final.pdf".to_string()])
+        );
+        assert_eq!(search_origin(&db, "ci@10.0.0.12:", "vacation photos"), Some(Vec::new()));
+    }
+
+    #[test]
+    fn only_folders_with_an_unchanged_time_are_skipped() {
+        let top = parse_lsjson("gdrive:", "", LSJSON).unwrap();
+        let mut previous = HashMap::new();
+        assert!(unchanged_dirs(&top, &previous).is_empty());
+        previous.insert("2024".to_string(), Some(1709287200));
+        assert_eq!(unchanged_dirs(&top, &previous), vec!["2024".to_string()]);
+        previous.insert("2024".to_string(), Some(1709200000));
+        assert!(unchanged_dirs(&top, &previous).is_empty());
+    }
+}
@@ -4,11 +4,10 @@ use std::sync::OnceLock;
 static SYSTEM_CONTEXT: OnceLock<SystemContext> = OnceLock::new();
 
 pub fn initialize() {
-    SYSTEM_CONTEXT.get_or_init(|| SystemContext::collect());
+    get_context();
 }
 
+/// The process-wide context, collected on first use.
 pub fn get_context() -> &'static SystemContext {
-    SYSTEM_CONTEXT
-        .get()
-        .expect("SystemContext must be initialized before use!")
+    SYSTEM_CONTEXT.get_or_init(SystemContext::gather_blocking)
 }
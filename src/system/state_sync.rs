@@ -0,0 +1,567 @@
+use crate::knowledge::{KnowledgeBase, KnowledgeEntry};
+use crate::remote::rclone::RcloneProvider;
+use crate::storage::db::Database;
+use crate::storage::events::{local_hostname, now_secs, Event, EventKind};
+use crate::storage::migrations;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folder on the remote; every device pushes into its own `<hostname>/` subfolder.
+const REMOTE_DIR: &str = "vega_state";
+const DB_FILE: &str = "vega.db";
+const KB_FILE: &str = "knowledge.json";
+const CONFIG_FILE: &str = "config.toml";
+const MANIFEST_FILE: &str = "manifest.json";
+/// Config keys never pushed: exact names or `_<name>` suffixes (`rc_pass`, `api_key`, `client_secret`).
+const SECRET_CONFIG_KEYS: &[&str] = &["pass", "password", "token", "secret", "key", "credential", "sas_url"];
+
+/// Describes one pushed snapshot; checksums guard against partial uploads.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateManifest {
+    pub host: String,
+    pub created_at: i64,
+    pub schema_version: i32,
+    pub event_count: usize,
+    pub files: BTreeMap<String, String>, // file name -> md5
+}
+
+#[derive(Debug, Default)]
+pub struct PullReport {
+    pub peers: Vec<String>,
+    pub events_added: usize,
+    pub hosts_added: usize,
+    pub hosts_updated: usize,
+    pub conflicts: Vec<String>,
+}
+
+/// Cross-device synchronization of `vega.db`, the knowledge base and the config.
+pub struct StateSync {
+    provider: RcloneProvider,
+    host: String,
+}
+
+impl StateSync {
+    pub fn new(remote: &str) -> Self {
+        StateSync {
+            provider: RcloneProvider::new(remote.to_string()),
+            host: local_hostname().unwrap_or_else(|| "unknown-host".to_string()),
+        }
+    }
+
+    fn remote_path(&self, sub: &str) -> String {
+        if sub.is_empty() {
+            format!("{}:{}", self.provider.remote_name, REMOTE_DIR)
+        } else {
+            format!("{}:{}/{}", self.provider.remote_name, REMOTE_DIR, sub)
+        }
+    }
+
+    fn staging_dir(kind: &str) -> Result<PathBuf, String> {
+        let dir = dirs::cache_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("vega")
+            .join("state")
+            .join(kind);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).map_err(|e| format!("Failed to clear {:?}: {}", dir, e))?;
+        }
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        Ok(dir)
+    }
+
+    /// Snapshots the local state into a staging folder and uploads it.
+    pub fn push(&self) -> Result<StateManifest, String> {
+        let staging = Self::staging_dir("push")?;
+        let db = Database::new().map_err(|e| format!("DB Error: {}", e))?;
+
+        // 1. Consistent database copy via the backup API, minus device-local metadata
+        //    and the file index (rebuilt by each device's own crawls)
+        let db_copy = staging.join(DB_FILE);
+        migrations::backup_to(&db.connection(), &db_copy).map_err(|e| format!("Snapshot failed: {}", e))?;
+        let snapshot = Database::open_file(&db_copy).map_err(|e| format!("Snapshot failed: {}", e))?;
+        let event_count = {
+            let conn = snapshot.connection();
+            conn.execute_batch("DELETE FROM metadata; DELETE FROM file_index; DELETE FROM file_index_origins; VACUUM;")
+                .map_err(|e| format!("Snapshot cleanup failed: {}", e))?;
+            conn.query_row("SELECT COUNT(*) FROM events", [], |row| row.get::<_, i64>(0))
+                .unwrap_or(0) as usize
+        };
+        let schema_version = snapshot.schema_version().unwrap_or(0);
+        drop(snapshot);
+
+        // 2. Knowledge base and config, read right after the database snapshot
+        let kb_json = serde_json::to_string_pretty(&KnowledgeBase::load())
+            .map_err(|e| format!("Knowledge base serialization failed: {}", e))?;
+        fs::write(staging.join(KB_FILE), kb_json).map_err(|e| e.to_string())?;
+        let config_path = crate::init::get_config_path();
+        if let Ok(config) = fs::read_to_string(&config_path) {
+            // Passwords and keys stay on this device; an unparsable config is not pushed at all
+            match strip_secrets(&config) {
+                Some(config) => fs::write(staging.join(CONFIG_FILE), config).map_err(|e| e.to_string())?,
+                None => eprintln!("⚠️ config.toml could not be parsed and was not pushed."),
+            }
+        }
+
+        // 3. Manifest with checksums
+        let mut files = BTreeMap::new();
+        for name in [DB_FILE, KB_FILE, CONFIG_FILE] {
+            if let Ok(bytes) = fs::read(staging.join(name)) {
+                files.insert(name.to_string(), format!("{:x}", md5::compute(bytes)));
+            }
+        }
+        let manifest = StateManifest {
+            host: self.host.clone(),
+            created_at: now_secs(),
+            schema_version,
+            event_count,
+            files,
+        };
+        let manifest_json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+        fs::write(staging.join(MANIFEST_FILE), manifest_json).map_err(|e| e.to_string())?;
+
+        // 4. Upload into this device's folder
+        let staging_str = staging.to_string_lossy().to_string();
+        let dest = self.remote_path(&self.host);
+        let result = self.provider.execute_rclone(vec!["copy", &staging_str, &dest]);
+
+        let event = Event::new(EventKind::Sync, &format!("vega state push {}", dest))
+            .with_result(result.is_ok(), None)
+            .with_payload(json!({
+                "direction": "push",
+                "remote": self.provider.remote_name,
+                "destination": dest,
+                "events": event_count,
+                "error": result.as_ref().err(),
+            }));
+        let _ = db.record_event(&event);
+
+        result.map(|_| manifest)
+    }
+
+    /// Downloads every other device's snapshot and merges it into the local state.
+    pub fn pull(&self) -> Result<PullReport, String> {
+        let staging = Self::staging_dir("pull")?;
+        let staging_str = staging.to_string_lossy().to_string();
+        let source = self.remote_path("");
+        let exclude = format!("/{}/**", self.host);
+        self.provider
+            .execute_rclone(vec!["copy", &source, &staging_str, "--exclude", &exclude])?;
+
+        let db = Database::new().map_err(|e| format!("DB Error: {}", e))?;
+        let mut kb = KnowledgeBase::load();
+        let config_path = crate::init::get_config_path();
+        let mut report = PullReport::default();
+
+        let mut peers: Vec<PathBuf> = fs::read_dir(&staging)
+            .map_err(|e| e.to_string())?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect();
+        peers.sort();
+
+        for peer_dir in peers {
+            let peer = peer_dir
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            match merge_peer(&db, &mut kb, &peer, &peer_dir, &config_path, &mut report) {
+                Ok(_) => report.peers.push(peer),
+                Err(e) => report.conflicts.push(format!("{}: skipped ({})", peer, e)),
+            }
+        }
+
+        if report.hosts_added + report.hosts_updated > 0 {
+            kb.save().map_err(|e| format!("Failed to save knowledge base: {}", e))?;
+        }
+
+        let event = Event::new(EventKind::Sync, &format!("vega state pull {}", source))
+            .with_result(true, None)
+            .with_payload(json!({
+                "direction": "pull",
+                "remote": self.provider.remote_name,
+                "peers": report.peers,
+                "events_added": report.events_added,
+                "conflicts": report.conflicts,
+            }));
+        let _ = db.record_event(&event);
+
+        Ok(report)
+    }
+}
+
+/// Merges one peer snapshot: events by uid, hosts by newest `last_success`,
+/// and the config only when `config_path` does not exist yet.
+fn merge_peer(
+    db: &Database,
+    kb: &mut KnowledgeBase,
+    peer: &str,
+    dir: &Path,
+    config_path: &Path,
+    report: &mut PullReport,
+) -> Result<(), String> {
+    let manifest: StateManifest = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .ok_or("missing or unreadable manifest")?;
+    for (name, sum) in &manifest.files {
+        let bytes = fs::read(dir.join(name)).map_err(|e| format!("{}: {}", name, e))?;
+        if format!("{:x}", md5::compute(bytes)) != *sum {
+            return Err(format!("checksum mismatch on {}", name));
+        }
+    }
+    if manifest.schema_version > migrations::latest_version() {
+        return Err(format!(
+            "snapshot schema v{} is newer than this vega (v{}); upgrade first",
+            manifest.schema_version,
+            migrations::latest_version()
+        ));
+    }
+
+    // History: union by event uid
+    let peer_db = Database::open_file(&dir.join(DB_FILE)).map_err(|e| e.to_string())?;
+    let events = peer_db.get_events_between(0, i64::MAX).map_err(|e| e.to_string())?;
+    let (added, mismatched) = db
+        .in_transaction(|db| {
+            let mut added = 0;
+            let mut mismatched = 0;
+            for event in &events {
+                if db.import_event(event)? {
+                    added += 1;
+                } else if let Some(local) = db.get_event(&event.uid)? {
+                    if local.kind != event.kind
+                        || local.command != event.command
+                        || local.timestamp != event.timestamp
+                    {
+                        mismatched += 1;
+                    }
+                }
+            }
+            Ok((added, mismatched))
+        })
+        .map_err(|e| e.to_string())?;
+    report.events_added += added;
+    if mismatched > 0 {
+        report.conflicts.push(format!(
+            "{}: {} events share an id with different local content; local kept",
+            peer, mismatched
+        ));
+    }
+
+    // Hosts: last writer wins on `last_success`
+    if let Ok(content) = fs::read_to_string(dir.join(KB_FILE)) {
+        let peer_kb: KnowledgeBase = serde_json::from_str(&content)
+            .map_err(|e| format!("knowledge base: {}", e))?;
+        for (name, theirs) in peer_kb.targets {
+            let ours = match kb.get(&name) {
+                Some(entry) => entry.clone(),
+                None => {
+                    kb.add(&name, theirs);
+                    report.hosts_added += 1;
+                    continue;
+                }
+            };
+            let theirs_newer = parse_time(&theirs.last_success) > parse_time(&ours.last_success);
+            if !same_target(&ours, &theirs) {
+                report.conflicts.push(format!(
+                    "host '{}': local {} vs {} {} -> kept {}",
+                    name,
+                    ours.ip,
+                    peer,
+                    theirs.ip,
+                    if theirs_newer { peer } else { "local" }
+                ));
+            }
+            if theirs_newer {
+                kb.add(&name, theirs);
+                report.hosts_updated += 1;
+            }
+        }
+    }
+
+    // Config: never overwritten, only adopted when this device has none
+    if let Ok(theirs) = fs::read_to_string(dir.join(CONFIG_FILE)) {
+        match fs::read_to_string(config_path) {
+            Ok(ours) if ours != theirs => report.conflicts.push(format!(
+                "config.toml differs from {}; local kept",
+                peer
+            )),
+            Ok(_) => {}
+            Err(_) => {
+                use std::io::Write;
+                use std::os::unix::fs::OpenOptionsExt;
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(config_path)
+                    .and_then(|mut f| f.write_all(theirs.as_bytes()))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    let _ = db.set_metadata(&format!("state_last_pull:{}", peer), &manifest.created_at.to_string());
+    Ok(())
+}
+
+/// The config without secret values, or `None` when it is not valid TOML.
+fn strip_secrets(config: &str) -> Option<String> {
+    fn strip(value: &mut toml::Value) {
+        match value {
+            toml::Value::Table(table) => {
+                table.retain(|key, _| {
+                    let key = key.to_lowercase();
+                    !SECRET_CONFIG_KEYS
+                        .iter()
+                        .any(|part| key == *part || key.ends_with(&format!("_{}", part)))
+                });
+                table.iter_mut().for_each(|(_, v)| strip(v));
+            }
+            toml::Value::Array(items) => items.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let mut value: toml::Value = toml::from_str(config).ok()?;
+    strip(&mut value);
+    toml::to_string(&value).ok()
+}
+
+fn same_target(a: &KnowledgeEntry, b: &KnowledgeEntry) -> bool {
+    a.ip == b.ip && a.user == b.user && a.protocol == b.protocol && a.port == b.port
+}
+
+/// Unparsable timestamps sort oldest, so a valid entry always wins over them.
+fn parse_time(value: &str) -> i64 {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|t| t.timestamp())
+        .unwrap_or(i64::MIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Peer {
+        dir: PathBuf,
+    }
+
+    impl Peer {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("vega-state-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Peer { dir }
+        }
+
+        fn with_events(self, events: &[Event]) -> Self {
+            let db = Database::open_file(&self.dir.join(DB_FILE)).unwrap();
+            for event in events {
+                db.import_event(event).unwrap();
+            }
+            self
+        }
+
+        fn with_hosts(self, hosts: &[(&str, KnowledgeEntry)]) -> Self {
+            let mut kb = KnowledgeBase::default();
+            for (name, entry) in hosts {
+                kb.add(name, entry.clone());
+            }
+            fs::write(self.dir.join(KB_FILE), serde_json::to_string(&kb).unwrap()).unwrap();
+            self
+        }
+
+        fn with_config(self, config: &str) -> Self {
+            fs::write(self.dir.join(CONFIG_FILE), config).unwrap();
+            self
+        }
+
+        /// Writes a manifest that checksums whatever files are present.
+        fn sealed(self) -> Self {
+            let mut files = BTreeMap::new();
+            for name in [DB_FILE, KB_FILE, CONFIG_FILE] {
+                if let Ok(bytes) = fs::read(self.dir.join(name)) {
+                    files.insert(name.to_string(), format!("{:x}", md5::compute(bytes)));
+                }
+            }
+            let manifest = StateManifest {
+                host: "peer".to_string(),
+                created_at: 1_700_000_000,
+                schema_version: migrations::latest_version(),
+                event_count: 0,
+                files,
+            };
+            fs::write(self.dir.join(MANIFEST_FILE), serde_json::to_string(&manifest).unwrap()).unwrap();
+            self
+        }
+
+        fn merge(&self, db: &Database, kb: &mut KnowledgeBase, report: &mut PullReport) -> Result<(), String> {
+            merge_peer(db, kb, "peer", &self.dir, &self.dir.join("local-config.toml"), report)
+        }
+    }
+
+    impl Drop for Peer {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn event(uid: &str, command: &str) -> Event {
+        let mut event = Event::new(EventKind::Execution, command);
+        event.uid = uid.to_string();
+        event.timestamp = 1_700_000_000;
+        event
+    }
+
+    fn host(ip: &str, last_success: &str) -> KnowledgeEntry {
+        KnowledgeEntry {
+            ip: ip.to_string(),
+            user: Some("root".to_string()),
+            protocol: "ssh".to_string(),
+            port: Some(22),
+            os_type: None,
+            last_success: last_success.to_string(),
+            mac: None,
+            ip_source: None,
+        }
+    }
+
+    fn memory_db() -> Database {
+        Database::open_file(Path::new(":memory:")).unwrap()
+    }
+
+    #[test]
+    fn events_are_unioned_by_uid() {
+        let db = memory_db();
+        db.import_event(&event("shared", "uptime")).unwrap();
+        db.import_event(&event("clash", "ls /srv")).unwrap();
+        db.import_event(&event("local-only", "df -h")).unwrap();
+
+        let peer = Peer::new("events")
+            .with_events(&[
+                event("shared", "uptime"),
+                event("clash", "rm -rf /srv"),
+                event("peer-only", "free -m"),
+            ])
+            .sealed();
+
+        let mut kb = KnowledgeBase::default();
+        let mut report = PullReport::default();
+        peer.merge(&db, &mut kb, &mut report).unwrap();
+        assert_eq!(report.events_added, 1);
+        assert_eq!(db.get_events_between(0, i64::MAX).unwrap().len(), 4);
+        // Same uid with different content: the local copy stays
+        assert_eq!(db.get_event("clash").unwrap().unwrap().command.as_deref(), Some("ls /srv"));
+        assert_eq!(report.conflicts.len(), 1, "{:?}", report.conflicts);
+        assert!(report.conflicts[0].contains("1 events share an id"));
+
+        // Pulling the same snapshot again is a no-op
+        let mut again = PullReport::default();
+        peer.merge(&db, &mut kb, &mut again).unwrap();
+        assert_eq!(again.events_added, 0);
+        assert_eq!(db.get_events_between(0, i64::MAX).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn newest_host_entry_wins() {
+        let db = memory_db();
+        let mut kb = KnowledgeBase::default();
+        kb.add("stale", host("10.0.0.1", "2024-01-01T00:00:00+00:00"));
+        kb.add("fresh", host("10.0.0.2", "2024-06-01T00:00:00+00:00"));
+        kb.add("garbled", host("10.0.0.3", "2024-01-01T00:00:00+00:00"));
+        kb.add("same", host("10.0.0.4", "2024-01-01T00:00:00+00:00"));
+
+        let peer = Peer::new("hosts")
+            .with_hosts(&[
+                ("stale", host("10.0.1.1", "2024-03-01T00:00:00+00:00")),
+                ("fresh", host("10.0.1.2", "2024-03-01T00:00:00+00:00")),
+                ("garbled", host("10.0.1.3", "yesterday")),
+                ("same", host("10.0.0.4", "2024-01-01T00:00:00+00:00")),
+                ("new", host("10.0.1.5", "2024-03-01T00:00:00+00:00")),
+            ])
+            .sealed();
+
+        let mut report = PullReport::default();
+        peer.merge(&db, &mut kb, &mut report).unwrap();
+        assert_eq!(kb.get("stale").unwrap().ip, "10.0.1.1");
+        assert_eq!(kb.get("fresh").unwrap().ip, "10.0.0.2");
+        assert_eq!(kb.get("garbled").unwrap().ip, "10.0.0.3");
+        assert_eq!(kb.get("new").unwrap().ip, "10.0.1.5");
+        assert_eq!((report.hosts_added, report.hosts_updated), (1, 1));
+
+        // Every differing target is reported with the side that was kept; identical ones are not
+        let mut conflicts = report.conflicts.clone();
+        conflicts.sort();
+        assert_eq!(conflicts.len(), 3, "{:?}", conflicts);
+        assert!(conflicts[0].starts_with("host 'fresh'") && conflicts[0].ends_with("kept local"));
+        assert!(conflicts[1].starts_with("host 'garbled'") && conflicts[1].ends_with("kept local"));
+        assert!(conflicts[2].starts_with("host 'stale'") && conflicts[2].ends_with("kept peer"));
+    }
+
+    #[test]
+    fn config_is_adopted_only_when_missing() {
+        let db = memory_db();
+        let mut kb = KnowledgeBase::default();
+        let peer = Peer::new("config").with_config("[ai]\nprovider = \"peer\"\n").sealed();
+        let local = peer.dir.join("local-config.toml");
+
+        let mut report = PullReport::default();
+        peer.merge(&db, &mut kb, &mut report).unwrap();
+        assert_eq!(fs::read_to_string(&local).unwrap(), "[ai]\nprovider = \"peer\"\n");
+        assert!(report.conflicts.is_empty());
+
+        fs::write(&local, "[ai]\nprovider = \"local\"\n").unwrap();
+        let mut report = PullReport::default();
+        peer.merge(&db, &mut kb, &mut report).unwrap();
+        assert_eq!(fs::read_to_string(&local).unwrap(), "[ai]\nprovider = \"local\"\n");
+        assert_eq!(report.conflicts, vec!["config.toml differs from peer; local kept".to_string()]);
+    }
+
+    #[test]
+    fn pushed_config_drops_secrets_and_pulled_config_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let config = "[ai]\nprovider = \"gemini\"\napi_key_source = \"env_var\"\napi_key = \"AIza-local\"\n\n\
+                      [rclone]\nrc_user = \"vega\"\nrc_pass = \"hunter2\"\n";
+        let stripped = strip_secrets(config).unwrap();
+        assert!(!stripped.contains("hunter2") && !stripped.contains("AIza-local"), "{}", stripped);
+        assert!(stripped.contains("api_key_source") && stripped.contains("rc_user"));
+        let parsed: crate::config::VegaConfig = toml::from_str(&format!("{}\n[system]\n[execution]\n", stripped)).unwrap();
+        assert!(parsed.rclone.unwrap().rc_pass.is_none());
+        assert!(strip_secrets("not = [toml").is_none());
+
+        let db = memory_db();
+        let mut kb = KnowledgeBase::default();
+        let peer = Peer::new("private").with_config(&stripped).sealed();
+        let mut report = PullReport::default();
+        peer.merge(&db, &mut kb, &mut report).unwrap();
+        let mode = fs::metadata(peer.dir.join("local-config.toml")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn tampered_or_unsealed_snapshots_are_skipped() {
+        let db = memory_db();
+        let mut kb = KnowledgeBase::default();
+        let mut report = PullReport::default();
+
+        let unsealed = Peer::new("unsealed").with_config("a = 1\n");
+        assert_eq!(
+            unsealed.merge(&db, &mut kb, &mut report).unwrap_err(),
+            "missing or unreadable manifest"
+        );
+
+        let tampered = Peer::new("tampered")
+            .with_hosts(&[("new", host("10.0.1.5", "2024-03-01T00:00:00+00:00"))])
+            .sealed()
+            .with_hosts(&[("new", host("10.6.6.6", "2024-03-01T00:00:00+00:00"))]);
+        assert_eq!(
+            tampered.merge(&db, &mut kb, &mut report).unwrap_err(),
+            "checksum mismatch on knowledge.json"
+        );
+        assert!(kb.get("new").is_none());
+        assert_eq!(report.hosts_added, 0);
+    }
+}
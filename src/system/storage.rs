@@ -1,53 +1,130 @@
+use crate::remote::rclone::RcloneProvider;
 use std::process::Command;
 use std::collections::HashMap;
 
+/// Per-backend transfer defaults; chunk sizes and transfers are scaled by available memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningProfile {
+    pub backend: String,
+    pub transfers: u32,
+    pub checkers: u32,
+    pub chunk_flag: Option<&'static str>,
+    pub extra: Vec<&'static str>,
+}
+
+impl TuningProfile {
+    pub fn for_backend(backend: &str) -> Self {
+        let (transfers, checkers, chunk_flag, extra): (u32, u32, Option<&'static str>, Vec<&'static str>) = match backend {
+            // Drive rate-limits per user: few parallel uploads, big chunks
+            "drive" => (4, 8, Some("--drive-chunk-size"), vec!["--fast-list"]),
+            "s3" => (16, 32, Some("--s3-chunk-size"), vec!["--fast-list", "--s3-upload-concurrency", "4"]),
+            "b2" => (16, 32, Some("--b2-chunk-size"), vec!["--fast-list"]),
+            "dropbox" => (4, 8, Some("--dropbox-chunk-size"), vec![]),
+            "onedrive" => (4, 8, None, vec![]),
+            // Latency-bound: more parallel streams help
+            "sftp" | "ftp" | "smb" | "webdav" => (8, 16, None, vec![]),
+            _ => (4, 8, None, vec![]),
+        };
+        TuningProfile {
+            backend: backend.to_string(),
+            transfers,
+            checkers,
+            chunk_flag,
+            extra,
+        }
+    }
+
+    /// rclone flags for this profile given `MemAvailable` in KB (None when unknown).
+    pub fn flags(&self, mem_available_kb: Option<u64>) -> Vec<String> {
+        // Dynamic Memory Scaling: each transfer buffers one chunk
+        let (chunk_size, divisor) = match mem_available_kb {
+            Some(kb) if kb < 1_000_000 => ("16M", 2), // Less than 1GB available
+            Some(kb) if kb < 4_000_000 => ("32M", 1),
+            _ => ("64M", 1),
+        };
+        let transfers = (self.transfers / divisor).max(1);
+
+        let mut flags = vec![
+            "--transfers".to_string(),
+            transfers.to_string(),
+            "--checkers".to_string(),
+            self.checkers.to_string(),
+        ];
+        if let Some(flag) = self.chunk_flag {
+            flags.push(flag.to_string());
+            flags.push(chunk_size.to_string());
+        }
+        flags.extend(self.extra.iter().map(|s| s.to_string()));
+        flags
+    }
+
+    /// The same tuning as rc `_config` overrides. Backend chunk sizes are CLI-only.
+    pub fn rc_config(&self, mem_available_kb: Option<u64>) -> serde_json::Value {
+        let divisor = match mem_available_kb {
+            Some(kb) if kb < 1_000_000 => 2,
+            _ => 1,
+        };
+        serde_json::json!({
+            "Transfers": (self.transfers / divisor).max(1),
+            "Checkers": self.checkers,
+            "UseListR": self.extra.contains(&"--fast-list"),
+        })
+    }
+}
+
 pub struct SmartStorage {
     pub aliases: HashMap<String, String>,
 }
 
+/// Aliases VEGA always shipped with; written into a new `config.toml` and used when
+/// a config has no `[aliases]` table at all.
+pub fn default_aliases() -> HashMap<String, String> {
+    [("구드", "gdrive:"), ("나스", "nas_sftp:")]
+        .into_iter()
+        .map(|(alias, remote)| (alias.to_string(), remote.to_string()))
+        .collect()
+}
+
 impl SmartStorage {
+    /// Aliases come from the `[aliases]` table of `config.toml`, e.g. `"구드" = "gdrive:"`.
+    /// An empty table turns the defaults off.
     pub fn new() -> Self {
-        let mut aliases = HashMap::new();
-        // Default smart aliases - in future load from config
-        aliases.insert("구드".to_string(), "gdrive:".to_string());
-        aliases.insert("gdrive".to_string(), "gdrive:".to_string());
-        aliases.insert("나스".to_string(), "nas_sftp:".to_string());
-        
+        let aliases = crate::config::VegaConfig::load(&crate::init::get_config_path().to_string_lossy())
+            .ok()
+            .and_then(|c| c.aliases)
+            .unwrap_or_else(default_aliases);
         SmartStorage { aliases }
     }
 
-    pub fn backup_cmd(&self, source: &str, target_alias: &str) -> String {
-        let remote = self.aliases.get(target_alias).cloned().unwrap_or_else(|| target_alias.to_string());
-        
-        // Dynamic Memory Scaling
-        let mut chunk_size = "64M";
-        let mut transfers = 8;
-        
-        if let Ok(meminfo) = std::fs::read_to_string("/proc/meminfo") {
-            for line in meminfo.lines() {
-                if line.starts_with("MemAvailable:") {
-                    if let Some(kb_str) = line.split_whitespace().nth(1) {
-                        if let Ok(kb) = kb_str.parse::<u64>() {
-                            if kb < 1_000_000 { // Less than 1GB available
-                                chunk_size = "16M";
-                                transfers = 4;
-                            }
-                        }
-                    }
-                    break;
-                }
-            }
-        }
+    pub fn with_aliases(aliases: HashMap<String, String>) -> Self {
+        SmartStorage { aliases }
+    }
 
-        let mut flags = "--progress".to_string();
-        
-        if remote.contains("gdrive") {
-            flags.push_str(&format!(" --drive-chunk-size {} --transfers {} --vfs-cache-mode writes", chunk_size, transfers));
-        } else if remote.contains("sftp") || remote.contains("nas") {
-            flags.push_str(&format!(" --transfers {}", transfers * 2));
+    /// Maps an alias (e.g. `구드`) to its rclone remote; unknown names are used as-is.
+    pub fn resolve(&self, target_alias: &str) -> String {
+        match self.aliases.get(target_alias) {
+            Some(remote) => remote.clone(),
+            None if target_alias.contains(':') => target_alias.to_string(),
+            None => format!("{}:", target_alias),
         }
+    }
 
-        format!("rclone sync {} {} {}", source, remote, flags)
+    /// Backend type of the remote in a `name:path` spec, from `rclone config dump`.
+    pub fn backend_of(&self, remote: &str) -> String {
+        let name = remote.split(':').next().unwrap_or(remote);
+        RcloneProvider::backend_types()
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// Transfer tuning for the backend behind `remote`, scaled down on low memory.
+    pub fn tuning_flags(&self, remote: &str) -> Vec<String> {
+        TuningProfile::for_backend(&self.backend_of(remote)).flags(mem_available_kb())
+    }
+
+    pub fn rc_config(&self, remote: &str) -> serde_json::Value {
+        TuningProfile::for_backend(&self.backend_of(remote)).rc_config(mem_available_kb())
     }
 
     #[allow(dead_code)]
@@ -63,3 +140,18 @@ impl SmartStorage {
         }
     }
 }
+
+impl Default for SmartStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn mem_available_kb() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    meminfo
+        .lines()
+        .find(|l| l.starts_with("MemAvailable:"))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse().ok())
+}
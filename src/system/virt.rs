@@ -1,4 +1,18 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use crate::system::probe::{self, Probe};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Used when neither `[virt] uri` nor `LIBVIRT_DEFAULT_URI` is set.
+pub const DEFAULT_URI: &str = "qemu:///session";
+/// Seconds a guest gets to shut down by itself before it is destroyed.
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 60;
+/// libvirt's per-network dnsmasq state (`<bridge>.status` JSON, `<net>.leases`).
+const LIBVIRT_DNSMASQ_DIR: &str = "/var/lib/libvirt/dnsmasq";
+/// Lease file of a standalone dnsmasq.
+const DNSMASQ_LEASES: &str = "/var/lib/misc/dnsmasq.leases";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VmInfo {
@@ -6,95 +20,183 @@ pub struct VmInfo {
     pub name: String,
     pub state: String, // running, shut off
     pub ip: Option<String>,
+    #[serde(default)]
+    pub ip_source: Option<IpSource>,
+    #[serde(default)]
+    pub macs: Vec<String>,
+    /// The interface `ip` was found under, when the source reported one of `macs`.
+    #[serde(default)]
+    pub ip_mac: Option<String>,
+}
+
+/// Where a VM's address came from, in the order the resolver asks.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpSource {
+    Agent,
+    Lease,
+    Arp,
+    Neighbour,
+    Dnsmasq,
+}
+
+impl IpSource {
+    /// Short form stored in the knowledge base.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IpSource::Agent => "agent",
+            IpSource::Lease => "lease",
+            IpSource::Arp => "arp",
+            IpSource::Neighbour => "neighbour",
+            IpSource::Dnsmasq => "dnsmasq",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            IpSource::Agent => "guest agent",
+            IpSource::Lease => "DHCP lease",
+            IpSource::Arp => "ARP",
+            IpSource::Neighbour => "host neighbour table",
+            IpSource::Dnsmasq => "dnsmasq lease file",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub state: String,
+    pub created: Option<i64>,
+    pub parent: Option<String>,
+    pub current: bool,
+}
+
+/// One `virsh domstats --raw` sample. Byte and time counters are cumulative.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VmStats {
+    pub state: String,
+    pub cpu_time_ns: u64,
+    pub vcpus: u64,
+    pub memory_kib: u64,
+    pub memory_max_kib: u64,
+    pub disk_read_bytes: u64,
+    pub disk_write_bytes: u64,
+    pub net_rx_bytes: u64,
+    pub net_tx_bytes: u64,
+}
+
+impl VmStats {
+    /// Guest CPU usage between two samples taken `elapsed` apart, as a share of all vCPUs.
+    pub fn cpu_percent(&self, earlier: &VmStats, elapsed: Duration) -> f64 {
+        let used = self.cpu_time_ns.saturating_sub(earlier.cpu_time_ns) as f64;
+        let available = elapsed.as_nanos() as f64 * self.vcpus.max(1) as f64;
+        if available > 0.0 {
+            (used / available * 100.0).min(100.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ShutdownOutcome {
+    AlreadyOff,
+    Graceful,
+    Forced,
+}
+
+/// `virsh` bound to one libvirt connection URI.
+#[derive(Debug, Clone)]
+pub struct Virsh {
+    pub uri: String,
+}
+
+impl Virsh {
+    pub fn new(uri: &str) -> Self {
+        Virsh { uri: uri.to_string() }
+    }
+
+    /// `[virt] uri`, then `LIBVIRT_DEFAULT_URI`, then `qemu:///session`.
+    pub fn from_config() -> Self {
+        let uri = virt_config()
+            .uri
+            .or_else(|| std::env::var("LIBVIRT_DEFAULT_URI").ok())
+            .unwrap_or_else(|| DEFAULT_URI.to_string());
+        Self::new(&uri)
+    }
+
+    pub fn run(&self, args: &[&str]) -> Result<String, String> {
+        let output = Probe::new("virsh")
+            .arg("-c")
+            .arg(&self.uri)
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to execute virsh: {}", e))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+
+    pub fn is_available() -> bool {
+        Probe::new("virsh").arg("-v").output().is_ok()
+    }
+}
+
+fn virt_config() -> crate::config::VirtConfig {
+    crate::config::VegaConfig::load(&crate::init::get_config_path().to_string_lossy())
+        .ok()
+        .and_then(|c| c.virt)
+        .unwrap_or_default()
 }
 
 pub struct VmScanner;
 
 impl VmScanner {
     pub fn scan() -> Vec<VmInfo> {
-        let mut vms = Vec::new();
-
-        // 1. Libvirt (virsh)
-        // Check if virsh is installed
-        if Command::new("virsh").arg("-v").output().is_ok() {
-            // Get list of VMs
-            let output = Command::new("virsh")
-                .args(&["-c", "qemu:///session", "list", "--all"])
-                .output();
-
-            if let Ok(o) = output {
-                let stdout = String::from_utf8_lossy(&o.stdout);
-                // Parse virsh output
-                // Id   Name            State
-                // ------------------------------
-                // 1    ubuntu22.04     running
-                // -    fedora38        shut off
-
-                for line in stdout.lines().skip(2) {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 3 {
-                        let id = if parts[0] == "-" {
-                            None
-                        } else {
-                            Some(parts[0].to_string())
-                        };
-                        let name = parts[1].to_string();
-                        let state = parts[2..].join(" ");
-
-                        // Try to find IP if running
-                        let mut ip = None;
-                        if state.contains("running") {
-                            ip = Self::get_dom_ip(&name);
-                        }
-
-                        vms.push(VmInfo {
-                            id,
-                            name,
-                            state,
-                            ip,
-                        });
-                    }
-                }
-            }
-        }
-
-        vms
-    }
-
-    fn get_dom_ip(name: &str) -> Option<String> {
-        // Try virsh domifaddr
-        let output = Command::new("virsh")
-            .args(&[
-                "-c",
-                "qemu:///session",
-                "domifaddr",
-                name,
-                "--source",
-                "agent",
-            ])
-            .output();
-
-        if let Ok(o) = output {
-            // If agent works, great. If not, try lease?
-            let stdout = String::from_utf8_lossy(&o.stdout);
-            // Name       MAC address          Protocol     Address
-            // -------------------------------------------------------------------------------
-            // vnet0      52:54:00:1d:7b:3e    ipv4         192.168.122.185/24
-
-            for line in stdout.lines() {
-                if line.contains("ipv4") {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if let Some(addr_part) = parts.iter().find(|p| p.contains('/')) {
-                        let ip = addr_part.split('/').next().unwrap_or("").to_string();
-                        return Some(ip);
-                    }
-                }
-            }
+        if !Virsh::is_available() {
+            return Vec::new();
         }
+        Self::scan_with(&Virsh::from_config())
+    }
 
-        // Fallback: ARP table lookup (naive)?
-        // Or just return None for now as per KISS
-        None
+    /// All domains with their state from one `domstats --state` call.
+    pub fn scan_with(virsh: &Virsh) -> Vec<VmInfo> {
+        let stats = match virsh.run(&["domstats", "--state", "--raw"]) {
+            Ok(out) => parse_domstats(&out),
+            Err(_) => return Vec::new(),
+        };
+        stats
+            .into_iter()
+            .map(|(name, fields)| {
+                let state = state_name(fields.get("state.state").map(|s| s.as_str()).unwrap_or("0")).to_string();
+                let running = state == "running";
+                let id = if running {
+                    virsh
+                        .run(&["domid", &name])
+                        .ok()
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty() && s != "-")
+                } else {
+                    None
+                };
+                let (macs, resolved) = if running {
+                    let macs = virsh.run(&["dumpxml", &name]).map(|xml| parse_domain_macs(&xml)).unwrap_or_default();
+                    let resolved = resolve_ip(virsh, &name, &macs);
+                    (macs, resolved)
+                } else {
+                    (Vec::new(), None)
+                };
+                let (ip, ip_mac, ip_source) = match resolved {
+                    Some((ip, mac, source)) => (Some(ip), mac, Some(source)),
+                    None => (None, None, None),
+                };
+                VmInfo { id, name, state, ip, ip_source, macs, ip_mac }
+            })
+            .collect()
     }
 
     pub fn list_vms() -> Vec<VmInfo> {
@@ -102,35 +204,216 @@ impl VmScanner {
     }
 }
 
-use std::process::Command;
+/// Asks each source in turn until one has an IPv4 address for the domain: guest agent,
+/// libvirt DHCP leases, libvirt ARP, `ip neigh` on the host, then dnsmasq lease files.
+/// Host-wide sources only count when the MAC belongs to one of the domain's `macs`.
+/// Returns the address, the domain MAC it was found under (if any) and its source.
+pub fn resolve_ip(virsh: &Virsh, name: &str, macs: &[String]) -> Option<(String, Option<String>, IpSource)> {
+    let pick = |pairs: Vec<(String, String)>, require_mac: bool| -> Option<(String, Option<String>)> {
+        let matching = pairs
+            .iter()
+            .find_map(|(mac, ip)| macs.iter().find(|m| m.eq_ignore_ascii_case(mac)).map(|m| (ip.clone(), Some(m.clone()))));
+        match matching {
+            Some(found) => Some(found),
+            None if !require_mac || macs.is_empty() => pairs.into_iter().next().map(|(_, ip)| (ip, None)),
+            None => None,
+        }
+    };
+
+    let agent = virsh
+        .run(&["qemu-agent-command", name, r#"{"execute":"guest-network-get-interfaces"}"#])
+        .map(|reply| parse_agent_interfaces(&reply))
+        .unwrap_or_default();
+    if let Some((ip, mac)) = pick(agent, false) {
+        return Some((ip, mac, IpSource::Agent));
+    }
+    for (flag, source) in [("lease", IpSource::Lease), ("arp", IpSource::Arp)] {
+        let pairs = virsh
+            .run(&["domifaddr", name, "--source", flag, "--full"])
+            .map(|out| parse_domifaddr(&out))
+            .unwrap_or_default();
+        if let Some((ip, mac)) = pick(pairs, false) {
+            return Some((ip, mac, source));
+        }
+    }
+
+    // The rest is host-wide and only usable with the domain's MACs
+    if macs.is_empty() {
+        return None;
+    }
+    let neighbours = Probe::new("ip")
+        .args(["-j", "neigh", "show"])
+        .output()
+        .ok()
+        .map(|o| parse_ip_neigh(&String::from_utf8_lossy(&o.stdout)))
+        .unwrap_or_default();
+    if let Some((ip, mac)) = pick(neighbours, true) {
+        return Some((ip, mac, IpSource::Neighbour));
+    }
+    if let Some((ip, mac)) = pick(dnsmasq_leases(chrono::Utc::now().timestamp()), true) {
+        return Some((ip, mac, IpSource::Dnsmasq));
+    }
+    None
+}
+
+/// (MAC, IP) pairs from libvirt's dnsmasq state and the standalone dnsmasq lease file,
+/// skipping expired leases.
+fn dnsmasq_leases(now: i64) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    if let Ok(names) = probe::read_dir_names(LIBVIRT_DNSMASQ_DIR) {
+        for name in names {
+            let content = match probe::read_to_string(format!("{}/{}", LIBVIRT_DNSMASQ_DIR, name)) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            if name.ends_with(".status") {
+                pairs.extend(parse_dnsmasq_status(&content, now));
+            } else if name.ends_with(".leases") {
+                pairs.extend(parse_dnsmasq_leases(&content, now));
+            }
+        }
+    }
+    if let Ok(content) = probe::read_to_string(DNSMASQ_LEASES) {
+        pairs.extend(parse_dnsmasq_leases(&content, now));
+    }
+    pairs
+}
 
 // Aliases for compatibility with other modules (e.g. scanner.rs, context.rs)
 pub type VirtualMachine = VmInfo;
 pub type VirtManager = VmScanner;
 
-pub struct VmController;
+/// Lifecycle operations on one libvirt connection.
+pub struct VmController {
+    pub virsh: Virsh,
+    pub shutdown_timeout: Duration,
+}
 
 impl VmController {
-    pub fn start(name: &str) -> Result<String, String> {
-        // 1. Check if running
-        let vms = VmScanner::scan();
-        if let Some(vm) = vms.iter().find(|v| v.name == name) {
-            if vm.state == "running" {
-                return Ok(format!("VM '{}' is already running.", name));
+    pub fn new() -> Self {
+        let config = virt_config();
+        VmController {
+            virsh: Virsh::from_config(),
+            shutdown_timeout: Duration::from_secs(
+                config.shutdown_timeout_secs.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+            ),
+        }
+    }
+
+    pub fn state(&self, name: &str) -> Result<String, String> {
+        let out = self.virsh.run(&["domstats", "--state", "--raw", name])?;
+        parse_domstats(&out)
+            .get(name)
+            .and_then(|f| f.get("state.state"))
+            .map(|s| state_name(s).to_string())
+            .ok_or_else(|| format!("No state reported for '{}'", name))
+    }
+
+    pub fn start(&self, name: &str) -> Result<String, String> {
+        if self.state(name).map(|s| s == "running").unwrap_or(false) {
+            return Ok(format!("VM '{}' is already running.", name));
+        }
+        self.virsh.run(&["start", name])?;
+        Ok(format!("🚀 VM '{}' started successfully.", name))
+    }
+
+    /// ACPI shutdown, polling until the guest is off; destroys it after `shutdown_timeout`.
+    /// `force` skips straight to destroy.
+    pub fn shutdown(&self, name: &str, force: bool) -> Result<ShutdownOutcome, String> {
+        if self.state(name)? == "shut off" {
+            return Ok(ShutdownOutcome::AlreadyOff);
+        }
+        if !force {
+            self.virsh.run(&["shutdown", name])?;
+            let started = Instant::now();
+            while started.elapsed() < self.shutdown_timeout {
+                std::thread::sleep(Duration::from_secs(1));
+                if self.state(name)? == "shut off" {
+                    return Ok(ShutdownOutcome::Graceful);
+                }
             }
         }
+        self.virsh.run(&["destroy", name])?;
+        Ok(ShutdownOutcome::Forced)
+    }
 
-        // 2. Start
-        let output = Command::new("virsh")
-            .args(&["-c", "qemu:///session", "start", name])
-            .output()
-            .map_err(|e| e.to_string())?;
+    pub fn reboot(&self, name: &str) -> Result<(), String> {
+        self.virsh.run(&["reboot", name]).map(|_| ())
+    }
+
+    pub fn snapshot_create(&self, name: &str, snapshot: &str, description: Option<&str>) -> Result<(), String> {
+        let mut args = vec!["snapshot-create-as", name, "--name", snapshot];
+        if let Some(desc) = description {
+            args.push("--description");
+            args.push(desc);
+        }
+        self.virsh.run(&args).map(|_| ())
+    }
+
+    /// Snapshots oldest first, with details from `snapshot-dumpxml`.
+    pub fn snapshot_list(&self, name: &str) -> Result<Vec<SnapshotInfo>, String> {
+        let names = parse_name_list(&self.virsh.run(&["snapshot-list", name, "--name"])?);
+        let current = self
+            .virsh
+            .run(&["snapshot-current", name, "--name"])
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        let mut snapshots: Vec<SnapshotInfo> = names
+            .iter()
+            .filter_map(|snap| {
+                let xml = self.virsh.run(&["snapshot-dumpxml", name, snap]).ok()?;
+                let mut info = parse_snapshot_xml(&xml)?;
+                info.current = info.name == current;
+                Some(info)
+            })
+            .collect();
+        snapshots.sort_by_key(|s| s.created.unwrap_or(0));
+        Ok(snapshots)
+    }
+
+    pub fn snapshot_revert(&self, name: &str, snapshot: &str) -> Result<(), String> {
+        self.virsh.run(&["snapshot-revert", name, snapshot]).map(|_| ())
+    }
 
+    pub fn snapshot_delete(&self, name: &str, snapshot: &str) -> Result<(), String> {
+        self.virsh.run(&["snapshot-delete", name, snapshot]).map(|_| ())
+    }
+
+    /// Full clone with new disks and MACs via `virt-clone --auto-clone`; the source must be off.
+    pub fn clone_vm(&self, name: &str, new_name: &str) -> Result<(), String> {
+        let output = Command::new("virt-clone")
+            .args(["--connect", &self.virsh.uri, "--original", name, "--name", new_name, "--auto-clone"])
+            .output()
+            .map_err(|e| format!("Failed to execute virt-clone: {}", e))?;
         if output.status.success() {
-            Ok(format!("🚀 VM '{}' started successfully.", name))
+            Ok(())
         } else {
-            Err(String::from_utf8_lossy(&output.stderr).to_string())
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+
+    pub fn set_autostart(&self, name: &str, enabled: bool) -> Result<(), String> {
+        let mut args = vec!["autostart"];
+        if !enabled {
+            args.push("--disable");
         }
+        args.push(name);
+        self.virsh.run(&args).map(|_| ())
+    }
+
+    pub fn autostart_enabled(&self) -> Vec<String> {
+        self.virsh
+            .run(&["list", "--all", "--autostart", "--name"])
+            .map(|out| parse_name_list(&out))
+            .unwrap_or_default()
+    }
+
+    pub fn stats(&self, name: &str) -> Result<VmStats, String> {
+        let out = self.virsh.run(&["domstats", "--raw", name])?;
+        parse_domstats(&out)
+            .get(name)
+            .map(stats_from_fields)
+            .ok_or_else(|| format!("No stats reported for '{}'", name))
     }
 
     #[allow(dead_code)]
@@ -142,3 +425,435 @@ impl VmController {
         None
     }
 }
+
+impl Default for VmController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `virDomainState` codes as printed by `virsh domstate`.
+pub fn state_name(code: &str) -> &'static str {
+    match code {
+        "1" => "running",
+        "2" => "idle",
+        "3" => "paused",
+        "4" => "in shutdown",
+        "5" => "shut off",
+        "6" => "crashed",
+        "7" => "pmsuspended",
+        _ => "no state",
+    }
+}
+
+/// Output of `--name` listings: one name per line.
+pub fn parse_name_list(out: &str) -> Vec<String> {
+    out.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `virsh domstats --raw`: `Domain: 'name'` headers followed by `key=value` lines.
+pub fn parse_domstats(out: &str) -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut domains = BTreeMap::new();
+    let mut current: Option<String> = None;
+    for line in out.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("Domain:") {
+            let name = rest.trim().trim_matches('\'').to_string();
+            domains.entry(name.clone()).or_insert_with(BTreeMap::new);
+            current = Some(name);
+        } else if let (Some(name), Some((key, value))) = (&current, line.split_once('=')) {
+            if let Some(fields) = domains.get_mut(name) {
+                fields.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+    domains
+}
+
+fn stats_from_fields(fields: &BTreeMap<String, String>) -> VmStats {
+    let num = |key: &str| fields.get(key).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+    // Per-device counters are `block.<n>.rd.bytes`, `net.<n>.rx.bytes`
+    let sum = |prefix: &str, suffix: &str| -> u64 {
+        fields
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix) && k.ends_with(suffix))
+            .filter_map(|(_, v)| v.parse::<u64>().ok())
+            .sum()
+    };
+    VmStats {
+        state: state_name(fields.get("state.state").map(|s| s.as_str()).unwrap_or("0")).to_string(),
+        cpu_time_ns: num("cpu.time"),
+        vcpus: num("vcpu.current"),
+        memory_kib: num("balloon.current"),
+        memory_max_kib: num("balloon.maximum"),
+        disk_read_bytes: sum("block.", ".rd.bytes"),
+        disk_write_bytes: sum("block.", ".wr.bytes"),
+        net_rx_bytes: sum("net.", ".rx.bytes"),
+        net_tx_bytes: sum("net.", ".tx.bytes"),
+    }
+}
+
+/// (MAC, IPv4) pairs for the non-loopback interfaces in the guest agent's
+/// `guest-network-get-interfaces` reply.
+pub fn parse_agent_interfaces(json: &str) -> Vec<(String, String)> {
+    let reply: Value = match serde_json::from_str(json) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+    let mut pairs = Vec::new();
+    for iface in reply["return"].as_array().into_iter().flatten() {
+        if iface["name"].as_str() == Some("lo") {
+            continue;
+        }
+        let mac = iface["hardware-address"].as_str().unwrap_or("").to_lowercase();
+        for addr in iface["ip-addresses"].as_array().into_iter().flatten() {
+            if addr["ip-address-type"].as_str() != Some("ipv4") {
+                continue;
+            }
+            if let Some(ip) = addr["ip-address"].as_str().filter(|ip| !ip.starts_with("127.")) {
+                pairs.push((mac.clone(), ip.to_string()));
+            }
+        }
+    }
+    pairs
+}
+
+fn is_mac(token: &str) -> bool {
+    let parts: Vec<&str> = token.split(':').collect();
+    parts.len() == 6 && parts.iter().all(|p| p.len() == 2 && p.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// MAC addresses of the domain's NICs from `virsh dumpxml` (`<interface>` → `<mac address='…'/>`).
+pub fn parse_domain_macs(xml: &str) -> Vec<String> {
+    let mut macs = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<interface ") {
+        let block = &rest[start..];
+        let end = block.find("</interface>").unwrap_or(block.len());
+        if let Some(mac) = xml_attr(&block[..end], "mac", "address").filter(|m| is_mac(m)) {
+            macs.push(mac.to_lowercase());
+        }
+        rest = &block[end..];
+    }
+    macs
+}
+
+/// (MAC, IPv4) pairs from `virsh domifaddr --full`, where every row repeats the
+/// interface name and MAC instead of leaving `-` on extra addresses.
+pub fn parse_domifaddr(out: &str) -> Vec<(String, String)> {
+    out.lines()
+        .filter_map(|line| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.as_slice() {
+                [_, mac, "ipv4", addr] if is_mac(mac) => {
+                    let ip = addr.split('/').next().unwrap_or(addr);
+                    Some((mac.to_lowercase(), ip.to_string()))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// (MAC, IPv4) pairs from `ip -j neigh show`, without failed or incomplete entries.
+pub fn parse_ip_neigh(json: &str) -> Vec<(String, String)> {
+    let entries: Vec<Value> = serde_json::from_str(json).unwrap_or_default();
+    entries
+        .iter()
+        .filter(|e| {
+            !e["state"]
+                .as_array()
+                .map(|s| s.iter().any(|s| matches!(s.as_str(), Some("FAILED") | Some("INCOMPLETE"))))
+                .unwrap_or(false)
+        })
+        .filter_map(|e| {
+            let ip = e["dst"].as_str().filter(|ip| !ip.contains(':'))?;
+            let mac = e["lladdr"].as_str()?;
+            Some((mac.to_lowercase(), ip.to_string()))
+        })
+        .collect()
+}
+
+/// libvirt's `<bridge>.status`: a JSON array of `ip-address`/`mac-address`/`expiry-time`.
+pub fn parse_dnsmasq_status(json: &str, now: i64) -> Vec<(String, String)> {
+    let entries: Vec<Value> = serde_json::from_str(json).unwrap_or_default();
+    entries
+        .iter()
+        .filter(|e| e["expiry-time"].as_i64().map(|t| t == 0 || t > now).unwrap_or(true))
+        .filter_map(|e| {
+            let ip = e["ip-address"].as_str().filter(|ip| !ip.contains(':'))?;
+            let mac = e["mac-address"].as_str()?;
+            Some((mac.to_lowercase(), ip.to_string()))
+        })
+        .collect()
+}
+
+/// dnsmasq lease lines: `<expiry> <mac> <ip> <hostname> <client-id>`; expiry 0 never expires.
+pub fn parse_dnsmasq_leases(content: &str, now: i64) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|l| {
+            let fields: Vec<&str> = l.split_whitespace().collect();
+            let expiry: i64 = fields.first()?.parse().ok()?;
+            let (mac, ip) = (*fields.get(1)?, *fields.get(2)?);
+            if (expiry != 0 && expiry <= now) || !is_mac(mac) || ip.contains(':') {
+                return None;
+            }
+            Some((mac.to_lowercase(), ip.to_string()))
+        })
+        .collect()
+}
+
+/// `virsh snapshot-dumpxml`. Only the part before the embedded `<domain>` is read, so
+/// the domain's own `<name>` and `<description>` never leak into the snapshot's.
+pub fn parse_snapshot_xml(xml: &str) -> Option<SnapshotInfo> {
+    let head = match xml.find("<domain ") {
+        Some(i) => &xml[..i],
+        None => xml,
+    };
+    let parent = xml_tag(head, "parent").and_then(|p| xml_tag(&p, "name"));
+    // The parent block also has a <name>; drop it before reading the snapshot's own
+    let own = match (head.find("<parent>"), head.find("</parent>")) {
+        (Some(start), Some(end)) => format!("{}{}", &head[..start], &head[end..]),
+        _ => head.to_string(),
+    };
+    Some(SnapshotInfo {
+        name: xml_tag(&own, "name")?,
+        description: xml_tag(&own, "description"),
+        state: xml_tag(&own, "state").unwrap_or_default(),
+        created: xml_tag(&own, "creationTime").and_then(|t| t.parse().ok()),
+        parent,
+        current: false,
+    })
+}
+
+/// Value of `attr` on the first `<tag .../>` element, quoted with `'` or `"`.
+fn xml_attr(xml: &str, tag: &str, attr: &str) -> Option<String> {
+    let open = format!("<{} ", tag);
+    let element = &xml[xml.find(&open)?..];
+    let element = &element[..element.find('>')?];
+    let key = format!(" {}=", attr);
+    let value = &element[element.find(&key)? + key.len()..];
+    let quote = value.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let value = &value[1..];
+    Some(value[..value.find(quote)?].to_string())
+}
+
+/// Text of the first `<tag>...</tag>`, with the basic XML entities decoded.
+fn xml_tag(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(
+        xml[start..end]
+            .trim()
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Captured from libvirt 9.x (qemu:///system), trimmed
+    const DOMSTATS: &str = "Domain: 'fedora-server'
+  state.state=1
+  state.reason=1
+  cpu.time=125000000000
+  balloon.current=2097152
+  balloon.maximum=4194304
+  vcpu.current=2
+  vcpu.maximum=2
+  net.count=1
+  net.0.name=vnet0
+  net.0.rx.bytes=1048576
+  net.0.tx.bytes=524288
+  block.count=2
+  block.0.name=vda
+  block.0.rd.bytes=3000
+  block.0.wr.bytes=4000
+  block.1.name=sda
+  block.1.rd.bytes=1000
+  block.1.wr.bytes=0
+
+Domain: 'win 11'
+  state.state=5
+  state.reason=1
+
+";
+
+    const SNAPSHOT_XML: &str = "<domainsnapshot>
+  <name>pre-upgrade</name>
+  <description>before dnf upgrade &amp; reboot</description>
+  <state>running</state>
+  <parent>
+    <name>base</name>
+  </parent>
+  <creationTime>1700000000</creationTime>
+  <memory snapshot='internal'/>
+  <domain type='kvm'>
+    <name>fedora-server</name>
+    <description>build box</description>
+  </domain>
+</domainsnapshot>
+";
+
+    const AGENT_REPLY: &str = r#"{"return":[
+  {"name":"lo","hardware-address":"00:00:00:00:00:00","ip-addresses":[{"ip-address-type":"ipv4","ip-address":"127.0.0.1","prefix":8}]},
+  {"name":"enp1s0","hardware-address":"52:54:00:1d:7b:3e","ip-addresses":[
+    {"ip-address-type":"ipv4","ip-address":"192.168.122.185","prefix":24},
+    {"ip-address-type":"ipv6","ip-address":"fe80::5054:ff:fe1d:7b3e","prefix":64}]}
+]}"#;
+
+    #[test]
+    fn domstats_groups_fields_per_domain() {
+        let domains = parse_domstats(DOMSTATS);
+        assert_eq!(domains.len(), 2);
+        assert_eq!(state_name(&domains["fedora-server"]["state.state"]), "running");
+        assert_eq!(state_name(&domains["win 11"]["state.state"]), "shut off");
+    }
+
+    #[test]
+    fn stats_sum_devices() {
+        let domains = parse_domstats(DOMSTATS);
+        let stats = stats_from_fields(&domains["fedora-server"]);
+        assert_eq!(stats.vcpus, 2);
+        assert_eq!(stats.memory_kib, 2097152);
+        assert_eq!(stats.disk_read_bytes, 4000);
+        assert_eq!(stats.disk_write_bytes, 4000);
+        assert_eq!(stats.net_rx_bytes, 1048576);
+
+        let later = VmStats {
+            cpu_time_ns: stats.cpu_time_ns + 1_000_000_000,
+            ..stats.clone()
+        };
+        let pct = later.cpu_percent(&stats, Duration::from_secs(1));
+        assert!((pct - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn snapshot_xml_ignores_parent_and_domain() {
+        let snap = parse_snapshot_xml(SNAPSHOT_XML).unwrap();
+        assert_eq!(snap.name, "pre-upgrade");
+        assert_eq!(snap.description.as_deref(), Some("before dnf upgrade & reboot"));
+        assert_eq!(snap.parent.as_deref(), Some("base"));
+        assert_eq!(snap.created, Some(1700000000));
+        assert_eq!(snap.state, "running");
+    }
+
+    const DUMPXML: &str = r#"<domain type='kvm' id='2'>
+  <name>fedora-server</name>
+  <description>NIC 52:54:00:ff:ff:ff retired</description>
+  <devices>
+    <interface type='network'>
+      <mac address='52:54:00:1D:7B:3E'/>
+      <source network='lab net' portid='1b2c'/>
+      <target dev='vnet0'/>
+      <model type='virtio'/>
+    </interface>
+    <interface type="user">
+      <mac address="52:54:00:aa:bb:cc"/>
+      <model type="e1000"/>
+    </interface>
+    <interface type='bridge'>
+      <source bridge='br0'/>
+    </interface>
+    <hostdev mode='subsystem' type='pci'/>
+  </devices>
+</domain>
+"#;
+
+    const DOMIFADDR: &str = " Name       MAC address          Protocol     Address
+-------------------------------------------------------------------------------
+ vnet0      52:54:00:1d:7b:3e    ipv4         192.168.122.185/24
+ vnet0      52:54:00:1d:7b:3e    ipv4         192.168.122.186/24
+ vnet1      52:54:00:aa:bb:cc    ipv6         fd00::5/64
+";
+
+    #[test]
+    fn agent_reply_skips_loopback_and_ipv6() {
+        assert_eq!(
+            parse_agent_interfaces(AGENT_REPLY),
+            vec![("52:54:00:1d:7b:3e".to_string(), "192.168.122.185".to_string())]
+        );
+        assert!(parse_agent_interfaces("error: guest agent is not responding").is_empty());
+    }
+
+    #[test]
+    fn domain_macs_come_from_interface_elements() {
+        // Spaces in the source network and MACs outside <interface> do not matter
+        assert_eq!(parse_domain_macs(DUMPXML), vec!["52:54:00:1d:7b:3e", "52:54:00:aa:bb:cc"]);
+        assert!(parse_domain_macs("error: failed to get domain 'gone'").is_empty());
+    }
+
+    #[test]
+    fn domifaddr_reads_every_full_row() {
+        let pairs = parse_domifaddr(DOMIFADDR);
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[1], ("52:54:00:1d:7b:3e".to_string(), "192.168.122.186".to_string()));
+        // Rows without a MAC (non --full output) are not guessed
+        assert!(parse_domifaddr(" -          -                    ipv4         192.168.122.186/24").is_empty());
+    }
+
+    #[test]
+    fn neighbours_skip_failed_and_ipv6() {
+        let json = r#"[
+            {"dst":"192.168.122.185","dev":"virbr0","lladdr":"52:54:00:1D:7B:3E","state":["REACHABLE"]},
+            {"dst":"192.168.122.50","dev":"virbr0","state":["FAILED"]},
+            {"dst":"192.168.122.51","dev":"virbr0","lladdr":"52:54:00:00:00:01","state":["INCOMPLETE"]},
+            {"dst":"fe80::1","dev":"virbr0","lladdr":"52:54:00:1d:7b:3e","state":["STALE"]}
+        ]"#;
+        assert_eq!(
+            parse_ip_neigh(json),
+            vec![("52:54:00:1d:7b:3e".to_string(), "192.168.122.185".to_string())]
+        );
+    }
+
+    #[test]
+    fn dnsmasq_leases_drop_expired() {
+        let status = r#"[
+            {"ip-address":"192.168.122.185","mac-address":"52:54:00:1d:7b:3e","hostname":"fedora","expiry-time":2000},
+            {"ip-address":"192.168.122.20","mac-address":"52:54:00:00:00:02","expiry-time":500}
+        ]"#;
+        assert_eq!(parse_dnsmasq_status(status, 1000).len(), 1);
+
+        let leases = "2000 52:54:00:1d:7b:3e 192.168.122.185 fedora 01:52:54:00:1d:7b:3e
+500 52:54:00:00:00:02 192.168.122.20 old *
+0 52:54:00:00:00:03 192.168.122.30 static *
+";
+        let pairs = parse_dnsmasq_leases(leases, 1000);
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[1].1, "192.168.122.30");
+    }
+
+    #[test]
+    fn scan_falls_back_to_leases_without_agent() {
+        use crate::system::probe::{with_bundle, Bundle};
+        let bundle = Bundle::from_json(include_str!("../../tests/fixtures/fedora-workstation.json")).unwrap();
+        let vms = with_bundle(bundle, || VmScanner::scan_with(&Virsh::new("qemu:///session")));
+        assert_eq!(vms.len(), 2);
+        assert_eq!(vms[0].name, "fedora-server");
+        assert_eq!(vms[0].id.as_deref(), Some("2"));
+        assert_eq!(vms[0].ip.as_deref(), Some("192.168.122.185"));
+        assert_eq!(vms[0].ip_source, Some(IpSource::Lease));
+        // The lease is on the second interface; the first one is on an isolated network
+        assert_eq!(vms[0].macs, vec!["52:54:00:8a:02:11", "52:54:00:1d:7b:3e"]);
+        assert_eq!(vms[0].ip_mac.as_deref(), Some("52:54:00:1d:7b:3e"));
+        assert_eq!(vms[1].state, "shut off");
+        assert!(vms[1].ip.is_none());
+    }
+
+    #[test]
+    fn name_list_drops_blank_lines() {
+        assert_eq!(parse_name_list("fedora-server\nwin 11\n\n"), vec!["fedora-server", "win 11"]);
+    }
+}
@@ -2,14 +2,16 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 
 use serde::{Deserialize, Serialize};
-use regex::Regex;
-use serde_json::Value;
+
+use crate::ai::grammar::{IntentMatcher, Vocabulary};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Action {
     SystemUpdate,
     SshConnect(String), // target
     ShowLog,
+    LocateFile(String), // query, answered from the offline file index
+    Install(String), // package
     Unknown,
 }
 
@@ -22,7 +24,7 @@ pub struct TokenSaver {
 }
 
 impl TokenSaver {
-    pub fn new(cache_path: &str, history_path: &str, keywords: Vec<String>) -> Self {
+    pub fn new(cache_path: &str, keywords: Vec<String>) -> Self {
         let mut saver = TokenSaver {
             cache_path: cache_path.to_string(),
             cache: HashMap::new(),
@@ -30,7 +32,7 @@ impl TokenSaver {
             history: HashSet::new(),
         };
         saver.load_cache();
-        saver.load_history(history_path);
+        saver.load_history();
         saver
     }
 
@@ -42,41 +44,30 @@ impl TokenSaver {
         }
     }
 
-    fn load_history(&mut self, path: &str) {
-        if let Ok(content) = fs::read_to_string(path) {
-            for line in content.lines() {
-                if let Ok(v) = serde_json::from_str::<Value>(line) {
-                    if let Some(cmd) = v.get("command").and_then(|c| c.as_str()) {
-                        self.history.insert(cmd.to_string());
-                    }
-                }
+    // History is a view over the event log (requests + executions)
+    fn load_history(&mut self) {
+        if let Ok(db) = crate::storage::db::Database::new() {
+            if let Ok(commands) = db.get_all_commands() {
+                self.history.extend(commands);
             }
         }
     }
 
+    /// Routes `input` through the intent grammar shared with the pipeline
+    /// (`data/intents.toml`); only confident matches skip the AI.
     pub fn match_local_intent(&self, input: &str) -> Action {
-        let input_lower = input.to_lowercase();
-        
-        // Regex based routing (Hybrid Reasoning)
-        if input_lower.contains("update") || input_lower.contains("upgrade") {
-             if input_lower.contains("system") || input_lower.contains("vega") {
-                 return Action::SystemUpdate;
-             }
-        }
-        
-        if input_lower.starts_with("vega ssh") {
-             // Extract target
-             let re = Regex::new(r"vega ssh (?:to )?(\S+)").unwrap();
-             if let Some(caps) = re.captures(&input_lower) {
-                 return Action::SshConnect(caps[1].to_string());
-             }
-        }
-        
-        if input_lower.contains("log") && (input_lower.contains("show") || input_lower.contains("check")) {
-            return Action::ShowLog;
+        let found = match IntentMatcher::global().resolve(input, &Vocabulary::load()) {
+            Some(found) => found,
+            None => return Action::Unknown,
+        };
+        match (found.name.as_str(), found.target) {
+            ("update", _) => Action::SystemUpdate,
+            ("connect", Some(target)) => Action::SshConnect(target),
+            ("install", Some(package)) => Action::Install(package),
+            ("show_log", _) => Action::ShowLog,
+            ("locate", Some(query)) => Action::LocateFile(query),
+            _ => Action::Unknown,
         }
-
-        Action::Unknown
     }
 
     #[allow(dead_code)]